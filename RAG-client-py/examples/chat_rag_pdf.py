@@ -100,7 +100,7 @@ class RustKissVDBClient:
                 revision=int(it["revision"]),
                 expires_at_ms=it.get("expires_at_ms"),
             )
-            for it in data
+            for it in data["items"]
         ]
 
     def state_get(self, key: str) -> StateItem:
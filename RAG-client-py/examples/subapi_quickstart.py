@@ -17,8 +17,8 @@ def demo_state(client) -> None:
     client.state.put(key, {"messages": 1})
     item = client.state.get(key)
     print("GET ->", item)
-    items = client.state.list(prefix="sdk:demo", limit=10)
-    print("LIST ->", items)
+    page = client.state.list(prefix="sdk:demo", limit=10)
+    print("LIST ->", page["items"])
 
 
 def demo_doc(client) -> None:
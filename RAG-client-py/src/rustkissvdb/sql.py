@@ -10,18 +10,51 @@ class SqlAPI:
     def __init__(self, client: Client) -> None:
         self._client = client
 
-    def query(self, sql: str, params: List[Any] | None = None) -> List[Dict[str, Any]]:
+    def query(
+        self,
+        sql: str,
+        params: List[Any] | None = None,
+        named_params: Dict[str, Any] | None = None,
+    ) -> List[Dict[str, Any]]:
         data = self._client.request(
             "POST",
             "/v1/sql/query",
-            json={"sql": sql, "params": params or []},
+            json=_sql_body(sql, params, named_params),
         )
         return data.get("rows", [])
 
-    def execute(self, sql: str, params: List[Any] | None = None) -> int:
+    def execute(
+        self,
+        sql: str,
+        params: List[Any] | None = None,
+        named_params: Dict[str, Any] | None = None,
+    ) -> int:
         data = self._client.request(
             "POST",
             "/v1/sql/exec",
-            json={"sql": sql, "params": params or []},
+            json=_sql_body(sql, params, named_params),
         )
         return int(data.get("rows_affected", 0))
+
+    def transaction(self, statements: List[Dict[str, Any]]) -> List[int]:
+        """Ejecuta `[{"sql": ..., "params": [...]}, ...]` en una sola transacción.
+
+        Devuelve las filas afectadas por cada sentencia; si alguna falla no se
+        aplica ninguna.
+        """
+        data = self._client.request(
+            "POST",
+            "/v1/sql/tx",
+            json={"statements": statements},
+        )
+        return [int(n) for n in data.get("rows_affected", [])]
+
+
+def _sql_body(
+    sql: str, params: List[Any] | None, named_params: Dict[str, Any] | None
+) -> Dict[str, Any]:
+    """`named_params` enlaza placeholders `:nombre`; no se combina con `params`."""
+    body: Dict[str, Any] = {"sql": sql, "params": params or []}
+    if named_params is not None:
+        body["named_params"] = named_params
+    return body
@@ -10,12 +10,23 @@ class StateAPI:
     def __init__(self, client: Client) -> None:
         self._client = client
 
-    def list(self, prefix: str = "", limit: int = 100) -> List[Dict[str, Any]]:
-        return self._client.request(
-            "GET",
-            "/v1/state",
-            params={"prefix": prefix, "limit": int(limit)},
-        )
+    def list(
+        self, prefix: str = "", limit: int = 100, start_after: Optional[str] = None
+    ) -> Dict[str, Any]:
+        """Una página de claves en orden; para seguir, pasa ``next_cursor`` como ``start_after``."""
+        params: Dict[str, Any] = {"prefix": prefix, "limit": int(limit)}
+        if start_after is not None:
+            params["start_after"] = start_after
+        return self._client.request("GET", "/v1/state", params=params)
+
+    def range(
+        self, from_key: str, to_key: str, limit: int = 100, start_after: Optional[str] = None
+    ) -> Dict[str, Any]:
+        """Claves entre ``from_key`` y ``to_key`` (ambas incluidas), paginadas igual que ``list``."""
+        params: Dict[str, Any] = {"from": from_key, "to": to_key, "limit": int(limit)}
+        if start_after is not None:
+            params["start_after"] = start_after
+        return self._client.request("GET", "/v1/state", params=params)
 
     def get(self, key: str) -> Dict[str, Any]:
         return self._client.request("GET", f"/v1/state/{key}")
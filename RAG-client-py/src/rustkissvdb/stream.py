@@ -1,7 +1,7 @@
 from __future__ import annotations
 
 import json
-from typing import TYPE_CHECKING, Dict, Generator, Optional
+from typing import TYPE_CHECKING, Any, Dict, Generator, Optional
 
 if TYPE_CHECKING:
     from .client import Client, RustKissVDBError
@@ -49,3 +49,22 @@ class StreamAPI:
                         event["data_raw"] = payload
             if event:
                 yield event
+
+    def poll(
+        self,
+        *,
+        since: int = 0,
+        limit: int = 100,
+        types: Optional[str] = None,
+        key_prefix: Optional[str] = None,
+        collection: Optional[str] = None,
+    ) -> Dict[str, Any]:
+        """Eventos posteriores a ``since`` sin abrir un stream; ``last_offset`` es el siguiente ``since``."""
+        params: Dict[str, Any] = {"since": int(since), "limit": int(limit)}
+        if types:
+            params["types"] = types
+        if key_prefix:
+            params["key_prefix"] = key_prefix
+        if collection:
+            params["collection"] = collection
+        return self._client.request("GET", "/v1/events/poll", params=params)
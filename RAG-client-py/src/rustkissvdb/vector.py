@@ -31,10 +31,13 @@ class VectorAPI:
         vector_id: str,
         vector: List[float],
         meta: Optional[Dict[str, Any]] = None,
+        only_if_changed: bool = False,
     ) -> Dict[str, Any]:
-        payload = {"id": vector_id, "vector": vector}
+        payload: Dict[str, Any] = {"id": vector_id, "vector": vector}
         if meta is not None:
             payload["meta"] = meta
+        if only_if_changed:
+            payload["only_if_changed"] = True
         return self._client.request(
             "POST",
             f"/v1/vector/{collection}/upsert",
@@ -5,14 +5,33 @@ use axum::http::Request;
 use axum::middleware::Next;
 use axum::response::Response;
 
+/// The caller's privileges, attached to authenticated requests as an extension.
+#[derive(Clone, Copy, Debug)]
+pub struct AuthContext {
+    /// The static `API_KEY` or a stored key with role `admin`.
+    pub full_scope: bool,
+}
+
+/// Rejects callers that did not authenticate with a full-scope key.
+pub fn require_full_scope(ctx: Option<&AuthContext>) -> Result<(), ApiError> {
+    if ctx.is_some_and(|ctx| ctx.full_scope) {
+        return Ok(());
+    }
+    Err(ApiError::new(
+        axum::http::StatusCode::FORBIDDEN,
+        "forbidden",
+        "requires an admin api key",
+    ))
+}
+
 pub async fn auth_middleware(
     State(state): State<AppState>,
-    req: Request<axum::body::Body>,
+    mut req: Request<axum::body::Body>,
     next: Next,
 ) -> Result<Response, ApiError> {
     // Allow public assets
     let path = req.uri().path();
-    if path == "/" || path == "/index.html" || path.starts_with("/assets/") || path == "/v1/health" || path.starts_with("/docs") || path.ends_with("openapi.yaml") {
+    if path == "/" || path == "/index.html" || path.starts_with("/assets/") || path == "/v1/health" || path == "/v1/ready" || path.starts_with("/docs") || path.ends_with("openapi.yaml") {
         return Ok(next.run(req).await);
     }
 
@@ -58,7 +77,10 @@ pub async fn auth_middleware(
     // 1. Check AuthStore (DB)
     if let Some(store) = &state.auth_store {
         match store.validate_key(&token).await {
-            Ok(Some(_record)) => {
+            Ok(Some(record)) => {
+                req.extensions_mut().insert(AuthContext {
+                    full_scope: record.role == "admin",
+                });
                 return Ok(next.run(req).await);
             }
             Ok(None) => {
@@ -77,6 +99,8 @@ pub async fn auth_middleware(
 
     // 2. Check Static Config
     if token == state.config.api_key {
+        req.extensions_mut()
+            .insert(AuthContext { full_scope: true });
         return Ok(next.run(req).await);
     }
 
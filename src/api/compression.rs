@@ -0,0 +1,112 @@
+use crate::api::AppState;
+use axum::body::{Body, HttpBody};
+use axum::extract::State;
+use axum::http::header::{ACCEPT_ENCODING, CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_TYPE, VARY};
+use axum::http::{HeaderMap, HeaderValue, Request};
+use axum::middleware::Next;
+use axum::response::Response;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::io::Write;
+
+/// Gzips buffered responses of at least `RESPONSE_COMPRESS_MIN_BYTES` for
+/// clients that accept it and records how many bytes that saved. Streams
+/// such as SSE have no known size and pass through untouched.
+pub async fn compress_responses(
+    State(state): State<AppState>,
+    req: Request<Body>,
+    next: Next,
+) -> Response {
+    let min_bytes = state.config.response_compress_min_bytes;
+    let wants_gzip = min_bytes > 0 && accepts_gzip(req.headers());
+    let response = next.run(req).await;
+    if !wants_gzip
+        || response.headers().contains_key(CONTENT_ENCODING)
+        || is_event_stream(response.headers())
+    {
+        return response;
+    }
+    match response.body().size_hint().exact() {
+        Some(len) if len >= min_bytes as u64 => {}
+        _ => return response,
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let plain = match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(err) => {
+            tracing::warn!(error = %err, "failed to buffer response for compression");
+            return Response::from_parts(parts, Body::empty());
+        }
+    };
+    let packed = gzip(&plain).filter(|packed| packed.len() < plain.len());
+    let Some(packed) = packed else {
+        return Response::from_parts(parts, Body::from(plain));
+    };
+
+    state
+        .engine
+        .metrics()
+        .observe_compressed_response(plain.len() as u64, packed.len() as u64);
+    parts.headers.remove(CONTENT_LENGTH);
+    parts
+        .headers
+        .insert(CONTENT_ENCODING, HeaderValue::from_static("gzip"));
+    parts
+        .headers
+        .append(VARY, HeaderValue::from_static("accept-encoding"));
+    Response::from_parts(parts, Body::from(packed))
+}
+
+fn gzip(bytes: &[u8]) -> Option<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::fast());
+    encoder.write_all(bytes).ok()?;
+    encoder.finish().ok()
+}
+
+/// Whether `Accept-Encoding` lists `gzip` (or `*`) without `q=0`.
+fn accepts_gzip(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(ACCEPT_ENCODING)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|coding| {
+            let mut params = coding.split(';').map(str::trim);
+            let name = params.next().unwrap_or_default();
+            let refused = params.any(|p| {
+                p.strip_prefix("q=")
+                    .and_then(|q| q.parse::<f32>().ok())
+                    .is_some_and(|q| q <= 0.0)
+            });
+            (name.eq_ignore_ascii_case("gzip") || name == "*") && !refused
+        })
+}
+
+fn is_event_stream(headers: &HeaderMap) -> bool {
+    headers
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("text/event-stream"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(accept: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(ACCEPT_ENCODING, HeaderValue::from_str(accept).unwrap());
+        headers
+    }
+
+    #[test]
+    fn accept_encoding_is_parsed_with_quality_values() {
+        assert!(accepts_gzip(&headers("gzip")));
+        assert!(accepts_gzip(&headers("br, GZIP;q=0.5")));
+        assert!(accepts_gzip(&headers("*")));
+        assert!(!accepts_gzip(&headers("gzip;q=0, br")));
+        assert!(!accepts_gzip(&headers("deflate")));
+        assert!(!accepts_gzip(&HeaderMap::new()));
+    }
+}
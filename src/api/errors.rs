@@ -1,6 +1,7 @@
 use axum::http::StatusCode;
 use axum::response::{IntoResponse, Response};
 use serde::Serialize;
+use std::io::Write;
 
 #[derive(Debug, Serialize)]
 pub struct ErrorBody {
@@ -15,6 +16,11 @@ pub struct ApiError {
     message: String,
 }
 
+/// Response extension carrying the error code of a rejected request, read by
+/// the metrics middleware to label `requests_rejected_total`.
+#[derive(Clone, Copy, Debug)]
+pub struct RejectionReason(pub &'static str);
+
 impl ApiError {
     pub fn new(status: StatusCode, code: &'static str, message: impl Into<String>) -> Self {
         Self {
@@ -23,14 +29,94 @@ impl ApiError {
             message: message.into(),
         }
     }
+
+    /// Writes refused while the engine drains for shutdown.
+    pub fn shutting_down() -> Self {
+        Self::new(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "shutting_down",
+            "server is shutting down",
+        )
+    }
+
+    /// The error code when this error rejects the request as invalid.
+    pub fn rejection_reason(&self) -> Option<&'static str> {
+        is_rejection(self.status).then_some(self.code)
+    }
+}
+
+/// Client errors caused by the request content itself; auth failures,
+/// missing resources and conflicts are not counted as rejections.
+pub fn is_rejection(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::BAD_REQUEST | StatusCode::PAYLOAD_TOO_LARGE | StatusCode::UNPROCESSABLE_ENTITY
+    )
 }
 
 impl IntoResponse for ApiError {
     fn into_response(self) -> Response {
+        let reason = self.rejection_reason();
         let body = axum::Json(ErrorBody {
             error: self.code,
             message: self.message,
         });
-        (self.status, body).into_response()
+        let mut response = (self.status, body).into_response();
+        if let Some(reason) = reason {
+            response.extensions_mut().insert(RejectionReason(reason));
+        }
+        response
+    }
+}
+
+/// Rejects a response whose JSON encoding would exceed `max_bytes`, without buffering it.
+pub fn enforce_response_size<T: Serialize>(
+    body: &T,
+    max_bytes: usize,
+    hint: &str,
+) -> Result<(), ApiError> {
+    let mut counter = ByteCounter(0);
+    let _ = serde_json::to_writer(&mut counter, body);
+    if counter.0 > max_bytes {
+        return Err(ApiError::new(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            "response_too_large",
+            format!(
+                "response would be {} bytes (max {}); {}",
+                counter.0, max_bytes, hint
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// Resolves a listing endpoint's `limit`, rejecting values above `max_limit`
+/// instead of silently clamping them.
+pub fn list_limit(
+    requested: Option<usize>,
+    default: usize,
+    max_limit: usize,
+) -> Result<usize, ApiError> {
+    match requested {
+        Some(limit) if limit > max_limit => Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "limit_too_large",
+            format!("limit {limit} exceeds max {max_limit}"),
+        )),
+        Some(limit) => Ok(limit),
+        None => Ok(default.min(max_limit)),
+    }
+}
+
+struct ByteCounter(usize);
+
+impl Write for ByteCounter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0 += buf.len();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
     }
 }
@@ -0,0 +1,38 @@
+use crate::api::errors::{is_rejection, RejectionReason};
+use crate::api::AppState;
+use axum::extract::{MatchedPath, State};
+use axum::http::{Request, StatusCode};
+use axum::middleware::Next;
+use axum::response::Response;
+use std::time::Instant;
+
+pub async fn track_http_metrics(
+    State(state): State<AppState>,
+    req: Request<axum::body::Body>,
+    next: Next,
+) -> Response {
+    let route = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|p| p.as_str().to_string())
+        .unwrap_or_else(|| "unmatched".to_string());
+    let method = req.method().as_str().to_string();
+    let started = Instant::now();
+    let response = next.run(req).await;
+    let status = response.status();
+    state
+        .engine
+        .metrics()
+        .observe_http(&route, &method, status.as_u16(), started.elapsed());
+    if is_rejection(status) {
+        // Extractor and body-limit rejections don't go through ApiError.
+        let reason = match response.extensions().get::<RejectionReason>() {
+            Some(RejectionReason(reason)) => *reason,
+            None if status == StatusCode::PAYLOAD_TOO_LARGE => "payload_too_large",
+            None => "malformed_request",
+        };
+        state.engine.metrics().inc_rejected(reason);
+        tracing::info!(%route, %method, status = status.as_u16(), reason, "request rejected");
+    }
+    response
+}
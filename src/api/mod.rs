@@ -1,5 +1,8 @@
 pub mod auth;
+pub mod compression;
 pub mod errors;
+pub mod http_metrics;
+pub mod routes_admin;
 pub mod routes_doc;
 pub mod routes_docs;
 pub mod routes_events;
@@ -71,24 +74,64 @@ pub fn router(
         .route("/index.html", get(routes_ui::handler))
         .merge(routes_docs::routes_docs())
         .route("/v1/health", get(routes_state::health))
+        .route("/v1/ready", get(routes_state::ready))
         .route("/v1/metrics", get(routes_state::metrics))
+        .route("/v1/metrics.json", get(routes_state::metrics_json))
+        .route("/v1/ping", get(routes_state::ping))
+        .route("/v1/info", get(routes_state::info))
+        .route("/v1/admin/ttl", get(routes_admin::ttl))
+        .route("/v1/admin/config", get(routes_admin::config))
+        .route("/v1/admin/compact", post(routes_admin::compact))
+        .route("/v1/admin/status", get(routes_admin::status))
+        .route(
+            "/v1/admin/metrics/reset",
+            post(routes_admin::reset_metrics),
+        )
+        .route(
+            "/v1/admin/vector/:collection/reindex",
+            post(routes_vector::reindex),
+        )
         .route("/v1/auth/keys", get(routes_auth::list_keys).post(routes_auth::create_key))
         .route("/v1/auth/keys/:id", delete(routes_auth::revoke_key))
         .route("/v1/state", get(routes_state::list))
         .route("/v1/state/batch_put", post(routes_state::batch_put))
+        .route("/v1/state/batch_get", post(routes_state::batch_get))
+        .route("/v1/state/delete_prefix", post(routes_state::delete_prefix))
+        .route("/v1/state/txn", post(routes_state::txn))
         .route("/v1/state/:key", get(routes_state::get))
         .route("/v1/state/:key", put(routes_state::put))
         .route("/v1/state/:key", delete(routes_state::delete))
+        .route("/v1/state/:key/rename", post(routes_state::rename))
+        .route("/v1/state/:key/increment", post(routes_state::increment))
+        .route("/v1/state/:key/touch", post(routes_state::touch))
         .route("/v1/doc/:collection/:id", put(routes_doc::put))
         .route("/v1/doc/:collection/:id", get(routes_doc::get))
         .route("/v1/doc/:collection/:id", delete(routes_doc::delete))
         .route("/v1/doc/:collection/find", post(routes_doc::find))
+        .route("/v1/doc/:collection/match", post(routes_doc::match_filter))
+        .route("/v1/doc/:collection/schema", post(routes_doc::set_schema))
+        .route("/v1/doc/:collection/reindex", post(routes_doc::reindex))
         .route("/v1/events", get(routes_events::events))
         .route("/v1/stream", get(routes_events::stream))
-        .route("/v1/vector", get(routes_vector::list_collections))
+        .route("/v1/events/poll", get(routes_events::poll))
+        .route(
+            "/v1/vector",
+            get(routes_vector::list_collections).post(routes_vector::bulk_create_collections),
+        )
+        .route("/v1/vector/search_multi", post(routes_vector::search_multi))
         .route(
             "/v1/vector/:collection",
-            get(routes_vector::get_collection_detail).post(routes_vector::create_collection),
+            get(routes_vector::get_collection_detail)
+                .post(routes_vector::create_collection)
+                .delete(routes_vector::drop_collection),
+        )
+        .route(
+            "/v1/vector/:collection/stats",
+            get(routes_vector::collection_stats),
+        )
+        .route(
+            "/v1/vector/:collection/default_filter",
+            get(routes_vector::get_default_filter).put(routes_vector::set_default_filter),
         )
         .route("/v1/vector/:collection/add", post(routes_vector::add))
         .route("/v1/vector/:collection/upsert", post(routes_vector::upsert))
@@ -103,7 +146,28 @@ pub fn router(
             post(routes_vector::delete_batch),
         )
         .route("/v1/vector/:collection/get", get(routes_vector::get))
+        .route(
+            "/v1/vector/:collection/get_batch",
+            post(routes_vector::get_batch),
+        )
         .route("/v1/vector/:collection/search", post(routes_vector::search))
+        .route(
+            "/v1/vector/:collection/exists_similar",
+            post(routes_vector::exists_similar),
+        )
+        .route(
+            "/v1/vector/:collection/search_batch",
+            post(routes_vector::search_batch),
+        )
+        .route(
+            "/v1/vector/:collection/knn_graph",
+            post(routes_vector::knn_graph),
+        )
+        .route(
+            "/v1/vector/:collection/match",
+            post(routes_vector::match_filter),
+        )
+        .route("/v1/vector/:collection/vacuum", post(routes_vector::vacuum))
         .route(
             "/v1/vector/:collection/diskann/build",
             post(routes_vector::diskann_build),
@@ -118,18 +182,28 @@ pub fn router(
         )
         .route("/v1/sql/query", post(routes_sql::query))
         .route("/v1/sql/exec", post(routes_sql::exec))
+        .route("/v1/sql/tx", post(routes_sql::tx))
         .route("/search", post(routes_search::search))
         .route("/search/ingest", post(routes_search::ingest))
+        .route("/v1/search/:id", delete(routes_search::delete))
         .layer(DefaultBodyLimit::max(state.config.max_body_bytes))
         .layer(TimeoutLayer::with_status_code(
             StatusCode::REQUEST_TIMEOUT,
             Duration::from_secs(state.config.request_timeout_secs),
         ))
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            compression::compress_responses,
+        ))
         .layer(TraceLayer::new_for_http())
         .layer(cors)
         .layer(axum::middleware::from_fn_with_state(
             state.clone(),
             auth::auth_middleware,
         ))
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            http_metrics::track_http_metrics,
+        ))
         .with_state(state)
 }
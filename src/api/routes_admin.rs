@@ -0,0 +1,121 @@
+use crate::api::auth::{require_full_scope, AuthContext};
+use crate::api::errors::{list_limit, ApiError};
+use crate::api::AppState;
+use crate::engine::EngineError;
+use axum::extract::{Query, State};
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::Extension;
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+pub struct TtlQuery {
+    pub limit: Option<usize>,
+}
+
+pub async fn ttl(
+    State(state): State<AppState>,
+    Query(q): Query<TtlQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    let limit = list_limit(q.limit, 100, state.config.max_list_limit)?;
+    Ok(axum::Json(state.engine.list_ttl(limit)))
+}
+
+/// Zeroes the metric counters so a test can assert on what happens next;
+/// responds with the snapshot taken just before. Admin keys only.
+pub async fn reset_metrics(
+    State(state): State<AppState>,
+    ctx: Option<Extension<AuthContext>>,
+) -> Result<impl IntoResponse, ApiError> {
+    require_full_scope(ctx.as_deref())?;
+    Ok(axum::Json(state.engine.reset_metrics()))
+}
+
+/// Reports how far the WAL, the state db and the vector collections have
+/// got, plus the WAL segments and snapshot on disk. Admin keys only.
+pub async fn status(
+    State(state): State<AppState>,
+    ctx: Option<Extension<AuthContext>>,
+) -> Result<impl IntoResponse, ApiError> {
+    require_full_scope(ctx.as_deref())?;
+    let engine = state.engine.clone();
+    let status = tokio::task::spawn_blocking(move || engine.persistence_status())
+        .await
+        .map_err(|err| {
+            ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "internal",
+                err.to_string(),
+            )
+        })?
+        .map_err(map_admin_error)?;
+    Ok(axum::Json(status))
+}
+
+/// Snapshots the engine and deletes the WAL segments the snapshot covers,
+/// to reclaim disk on demand. Admin keys only.
+pub async fn compact(
+    State(state): State<AppState>,
+    ctx: Option<Extension<AuthContext>>,
+) -> Result<impl IntoResponse, ApiError> {
+    require_full_scope(ctx.as_deref())?;
+    let engine = state.engine.clone();
+    let compaction = tokio::task::spawn_blocking(move || engine.compact_wal())
+        .await
+        .map_err(|err| {
+            ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "internal",
+                err.to_string(),
+            )
+        })?
+        .map_err(map_admin_error)?;
+    let Some(compaction) = compaction else {
+        return Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            "not_enabled",
+            "persistence is disabled (no DATA_DIR)",
+        ));
+    };
+    Ok(axum::Json(compaction))
+}
+
+fn map_admin_error(err: EngineError) -> ApiError {
+    match err {
+        EngineError::Persistence(err) => ApiError::new(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "persistence_error",
+            err.to_string(),
+        ),
+        err => ApiError::new(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "internal",
+            err.to_string(),
+        ),
+    }
+}
+
+const REDACTED: &str = "***";
+
+/// The effective configuration with secrets redacted; admin keys only.
+pub async fn config(
+    State(state): State<AppState>,
+    ctx: Option<Extension<AuthContext>>,
+) -> Result<impl IntoResponse, ApiError> {
+    require_full_scope(ctx.as_deref())?;
+    let mut value = serde_json::to_value(&state.config).map_err(|err| {
+        ApiError::new(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "internal",
+            err.to_string(),
+        )
+    })?;
+    if let Some(obj) = value.as_object_mut() {
+        for key in ["api_key", "api_keys"] {
+            if let Some(v) = obj.get_mut(key) {
+                *v = serde_json::json!(REDACTED);
+            }
+        }
+    }
+    Ok(axum::Json(value))
+}
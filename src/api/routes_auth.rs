@@ -48,10 +48,8 @@ pub async fn create_key(
         ));
     };
     
-    // TODO: Verify current user is admin (requires pulling context from request extension)
-    // For now, assuming only admins have access or we trust the bearer token holder if they have admin role?
-    // We haven't implemented context injection in auth middleware yet.
-    // Let's assume for this MVP that any valid key can manage keys (or we lock it down later).
+    // TODO: gate key management with `auth::require_full_scope` (the middleware
+    // now attaches an `AuthContext`); for this MVP any valid key can manage keys.
     
     let plain_key = store.generate_api_key();
     let role = body.role.unwrap_or_else(|| "user".to_string());
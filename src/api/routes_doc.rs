@@ -1,6 +1,6 @@
-use crate::api::errors::ApiError;
+use crate::api::errors::{enforce_response_size, list_limit, ApiError};
 use crate::api::AppState;
-use crate::docstore::{self, DocRecord};
+use crate::docstore::{self, DocError, DocRecord};
 use crate::engine::EngineError;
 use axum::extract::{Path, State};
 use axum::http::StatusCode;
@@ -12,6 +12,10 @@ pub struct DocResponse {
     pub id: String,
     pub revision: u64,
     pub doc: serde_json::Value,
+    /// Set on a put whose document had more fields than
+    /// `MAX_DOC_INDEXED_FIELDS`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub warning: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -25,6 +29,17 @@ pub struct FindResponse {
     pub documents: Vec<DocRecord>,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct MatchBody {
+    pub id: String,
+    pub filter: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MatchResponse {
+    pub matched: bool,
+}
+
 pub async fn put(
     State(state): State<AppState>,
     Path((collection, id)): Path<(String, String)>,
@@ -32,12 +47,42 @@ pub async fn put(
 ) -> Result<impl IntoResponse, ApiError> {
     validate_collection_and_id(&state, &collection, &id)?;
     enforce_doc_size(&state, &body)?;
-    let record =
-        docstore::put_doc(&state.engine, &collection, &id, body).map_err(map_engine_error)?;
+    let cap = state.config.max_doc_indexed_fields;
+    let unindexed = docstore::unindexed_fields(&body, cap);
+    let record = docstore::put_doc(&state.engine, &collection, &id, body).map_err(map_doc_error)?;
+    let warning = (!unindexed.is_empty()).then(|| {
+        let fields: Vec<String> = unindexed.into_iter().collect();
+        format!(
+            "only the first {cap} fields are indexed; find on {} scans",
+            fields.join(", ")
+        )
+    });
     Ok(axum::Json(DocResponse {
         id: record.id,
         revision: record.revision,
         doc: record.doc,
+        warning,
+    }))
+}
+
+#[derive(Debug, Serialize)]
+pub struct SchemaResponse {
+    pub collection: String,
+    pub revision: u64,
+}
+
+pub async fn set_schema(
+    State(state): State<AppState>,
+    Path(collection): Path<String>,
+    axum::Json(schema): axum::Json<serde_json::Value>,
+) -> Result<impl IntoResponse, ApiError> {
+    validate_collection(&state, &collection)?;
+    enforce_doc_size(&state, &schema)?;
+    let revision =
+        docstore::set_schema(&state.engine, &collection, schema).map_err(map_doc_error)?;
+    Ok(axum::Json(SchemaResponse {
+        collection,
+        revision,
     }))
 }
 
@@ -59,6 +104,7 @@ pub async fn get(
         id: record.id,
         revision: record.revision,
         doc: record.doc,
+        warning: None,
     }))
 }
 
@@ -83,10 +129,45 @@ pub async fn find(
     axum::Json(body): axum::Json<FindBody>,
 ) -> Result<impl IntoResponse, ApiError> {
     validate_collection(&state, &collection)?;
-    let limit = body.limit.unwrap_or(20).min(state.config.max_doc_find);
+    let limit =
+        list_limit(body.limit, 20, state.config.max_list_limit)?.min(state.config.max_doc_find);
     let documents = docstore::find_docs(&state.engine, &collection, body.filter.as_ref(), limit)
         .map_err(map_engine_error)?;
-    Ok(axum::Json(FindResponse { documents }))
+    let response = FindResponse { documents };
+    enforce_response_size(
+        &response,
+        state.config.max_response_bytes,
+        "lower limit or narrow the filter",
+    )?;
+    Ok(axum::Json(response))
+}
+
+/// Evaluates a `find` filter against one document for filter debugging.
+pub async fn match_filter(
+    State(state): State<AppState>,
+    Path(collection): Path<String>,
+    axum::Json(body): axum::Json<MatchBody>,
+) -> Result<impl IntoResponse, ApiError> {
+    validate_collection_and_id(&state, &collection, &body.id)?;
+    let Some(matched) =
+        docstore::match_doc(&state.engine, &collection, &body.id, body.filter.as_ref())
+    else {
+        return Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            "not_found",
+            "document not found",
+        ));
+    };
+    Ok(axum::Json(MatchResponse { matched }))
+}
+
+pub async fn reindex(
+    State(state): State<AppState>,
+    Path(collection): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    validate_collection(&state, &collection)?;
+    let report = docstore::reindex(&state.engine, &collection).map_err(map_engine_error)?;
+    Ok(axum::Json(report))
 }
 
 fn validate_collection_and_id(
@@ -128,8 +209,23 @@ fn enforce_doc_size(state: &AppState, doc: &serde_json::Value) -> Result<(), Api
     Ok(())
 }
 
+fn map_doc_error(err: DocError) -> ApiError {
+    match err {
+        DocError::Engine(err) => map_engine_error(err),
+        DocError::InvalidSchema(msg) => {
+            ApiError::new(StatusCode::BAD_REQUEST, "invalid_argument", msg)
+        }
+        DocError::SchemaViolation(failures) => ApiError::new(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "schema_violation",
+            failures.join("; "),
+        ),
+    }
+}
+
 fn map_engine_error(err: EngineError) -> ApiError {
     match err {
+        EngineError::ShuttingDown => ApiError::shutting_down(),
         EngineError::Persistence(_) => ApiError::new(
             StatusCode::INTERNAL_SERVER_ERROR,
             "persistence_error",
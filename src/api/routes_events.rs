@@ -1,9 +1,12 @@
+use crate::api::errors::{list_limit, ApiError};
 use crate::api::AppState;
+use crate::engine::EventRecord;
 use axum::extract::{Query, State};
-use axum::http::HeaderMap;
+use axum::http::{HeaderMap, StatusCode};
 use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::Json;
 use futures_util::Stream;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::convert::Infallible;
 use std::time::Duration;
 use tokio::sync::mpsc;
@@ -26,6 +29,23 @@ pub struct EventsQuery {
     pub since: Option<u64>,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct PollQuery {
+    pub since: Option<u64>,
+    pub limit: Option<usize>,
+    pub types: Option<String>,
+    pub key_prefix: Option<String>,
+    pub collection: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PollResponse {
+    pub events: Vec<EventRecord>,
+    /// Highest offset scanned, matching or not, or `since` when there was
+    /// nothing to scan; pass it back as `since` on the next poll.
+    pub last_offset: u64,
+}
+
 pub async fn events(
     State(state): State<AppState>,
     headers: HeaderMap,
@@ -58,15 +78,14 @@ pub async fn stream(
 
     let key_prefix = q.key_prefix.clone();
     let collection = q.collection.clone();
-    let types: Option<Vec<String>> = q
-        .types
-        .as_deref()
-        .map(|s| s.split(',').map(|x| x.trim().to_string()).collect());
+    let types = parse_types(q.types.as_deref());
 
     let metrics = state.engine.metrics();
     metrics.inc_sse_clients();
     let bus = state.engine.events().clone();
     let persist = state.engine.persist();
+    let idle_timeout = (state.config.sse_idle_timeout_secs > 0)
+        .then(|| Duration::from_secs(state.config.sse_idle_timeout_secs));
 
     let stream = async_stream::stream! {
         struct Guard(std::sync::Arc<crate::engine::Metrics>);
@@ -79,6 +98,21 @@ pub async fn stream(
 
         let mut last_sent_offset = since;
 
+        let earliest = match &persist {
+            Some(persist) => {
+                let persist = persist.clone();
+                tokio::task::spawn_blocking(move || persist.earliest_available_offset())
+                    .await
+                    .ok()
+                    .and_then(|r| r.ok())
+                    .flatten()
+            }
+            None => bus.earliest_buffered_offset(),
+        };
+        if let Some(ev) = earliest.and_then(|earliest| truncated_event(since, earliest)) {
+            yield Ok(ev);
+        }
+
         if let Some(persist) = persist {
             let (tx, mut rx) = mpsc::unbounded_channel::<crate::engine::EventRecord>();
             let key_prefix2 = key_prefix.clone();
@@ -108,16 +142,30 @@ pub async fn stream(
         }
 
         let mut live = BroadcastStream::new(bus.subscribe());
+        // Keep-alives don't count as activity; only yielded events push this back.
+        let mut idle_deadline = idle_timeout.map(|d| tokio::time::Instant::now() + d);
         loop {
-            match live.next().await {
+            let next = match idle_deadline {
+                Some(deadline) => match tokio::time::timeout_at(deadline, live.next()).await {
+                    Ok(next) => next,
+                    Err(_) => {
+                        yield Ok(idle_timeout_event(last_sent_offset));
+                        break;
+                    }
+                },
+                None => live.next().await,
+            };
+            match next {
                 Some(Ok(ev)) => {
                     if matches_filters(&ev, types.as_ref(), key_prefix.as_deref(), collection.as_deref()) {
                         last_sent_offset = ev.offset;
+                        idle_deadline = idle_timeout.map(|d| tokio::time::Instant::now() + d);
                         yield Ok(to_sse(ev));
                     }
                 }
                 Some(Err(BroadcastStreamRecvError::Lagged(n))) => {
                     if let Some(ev) = gap_event(n, &mut last_sent_offset, bus.last_published_offset()) {
+                        idle_deadline = idle_timeout.map(|d| tokio::time::Instant::now() + d);
                         yield Ok(ev);
                     }
                 }
@@ -135,6 +183,85 @@ pub async fn stream(
     )
 }
 
+/// Returns the events after `since` that pass the same filters as
+/// `/v1/stream`, read from the WAL (or the replay buffer without
+/// persistence), for clients that poll instead of holding a stream open.
+pub async fn poll(
+    State(state): State<AppState>,
+    Query(q): Query<PollQuery>,
+) -> Result<Json<PollResponse>, ApiError> {
+    let since = q.since.unwrap_or(0);
+    let limit = list_limit(q.limit, 100, state.config.max_list_limit)?;
+    let types = parse_types(q.types.as_deref());
+    let key_prefix = q.key_prefix;
+    let collection = q.collection;
+
+    let (events, last_offset) = match state.engine.persist() {
+        Some(persist) => tokio::task::spawn_blocking(move || {
+            let mut out = Vec::new();
+            let mut scanned = since;
+            if limit == 0 {
+                return Ok((out, scanned));
+            }
+            persist.for_each_event_since(since, |ev| {
+                scanned = scanned.max(ev.offset);
+                if matches_filters(
+                    &ev,
+                    types.as_ref(),
+                    key_prefix.as_deref(),
+                    collection.as_deref(),
+                ) {
+                    out.push(ev);
+                }
+                out.len() < limit
+            })?;
+            Ok::<_, std::io::Error>((out, scanned))
+        })
+        .await
+        .map_err(|err| {
+            ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "internal",
+                err.to_string(),
+            )
+        })?
+        .map_err(|err| {
+            ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "persistence_error",
+                err.to_string(),
+            )
+        })?,
+        None => {
+            let mut out = Vec::new();
+            let mut scanned = since;
+            for ev in state.engine.events().replay_since(since) {
+                if out.len() >= limit {
+                    break;
+                }
+                scanned = scanned.max(ev.offset);
+                if matches_filters(
+                    &ev,
+                    types.as_ref(),
+                    key_prefix.as_deref(),
+                    collection.as_deref(),
+                ) {
+                    out.push(ev);
+                }
+            }
+            (out, scanned)
+        }
+    };
+    Ok(Json(PollResponse {
+        events,
+        last_offset,
+    }))
+}
+
+fn parse_types(types: Option<&str>) -> Option<Vec<String>> {
+    types.map(|s| s.split(',').map(|x| x.trim().to_string()).collect())
+}
+
 fn matches_filters(
     ev: &crate::engine::EventRecord,
     types: Option<&Vec<String>>,
@@ -192,6 +319,34 @@ fn gap_event(n: u64, last_sent_offset: &mut u64, last_published_offset: u64) ->
     )
 }
 
+/// Sent first when events right after `since` were already trimmed (WAL
+/// retention or a full replay buffer), so the client knows it missed some.
+fn truncated_event(since: u64, earliest_available: u64) -> Option<Event> {
+    if since.saturating_add(1) >= earliest_available {
+        return None;
+    }
+    Some(
+        Event::default().event("truncated").data(
+            serde_json::json!({
+                "requested_since": since,
+                "earliest_available": earliest_available,
+            })
+            .to_string(),
+        ),
+    )
+}
+
+/// Final event before closing a quiet stream; clients reconnect with
+/// `since=last_offset` to resume.
+fn idle_timeout_event(last_sent_offset: u64) -> Event {
+    Event::default().event("idle_timeout").data(
+        serde_json::json!({
+            "last_offset": last_sent_offset,
+        })
+        .to_string(),
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -207,6 +362,17 @@ mod tests {
         assert!(s.contains("gap"));
     }
 
+    #[test]
+    fn truncated_event_only_when_events_are_missing() {
+        assert!(truncated_event(0, 1).is_none());
+        assert!(truncated_event(9, 10).is_none());
+        assert!(truncated_event(12, 10).is_none());
+        let ev = truncated_event(3, 10).unwrap();
+        let s = format!("{:?}", ev);
+        assert!(s.contains("truncated"));
+        assert!(s.contains("earliest_available"));
+    }
+
     #[test]
     fn matches_filters_accepts_collection_match() {
         let ev = EventRecord {
@@ -1,7 +1,8 @@
+use crate::api::errors::ApiError;
 use crate::api::AppState;
 use crate::search::types::{IngestRequest, SearchRequest};
 use axum::{
-    extract::State,
+    extract::{Path, State},
     http::StatusCode,
     response::{IntoResponse, Json},
 };
@@ -10,7 +11,16 @@ pub async fn search(
     State(state): State<AppState>,
     Json(payload): Json<SearchRequest>,
 ) -> impl IntoResponse {
-    match state.search_engine.search(payload) {
+    if let Err(err) = payload.search_mode() {
+        return (StatusCode::BAD_REQUEST, err.to_string()).into_response();
+    }
+    // The embedder may call out over HTTP; keep it off the async workers.
+    let engine = state.search_engine.clone();
+    let result = tokio::task::spawn_blocking(move || engine.search(payload))
+        .await
+        .map_err(anyhow::Error::from)
+        .and_then(|res| res);
+    match result {
         Ok(res) => (StatusCode::OK, Json(res)).into_response(),
         Err(err) => {
             tracing::error!(%err, "search failed");
@@ -31,3 +41,19 @@ pub async fn ingest(
         }
     }
 }
+
+pub async fn delete(State(state): State<AppState>, Path(id): Path<u32>) -> impl IntoResponse {
+    match state.search_engine.delete(id) {
+        Ok(true) => (StatusCode::OK, Json(serde_json::json!({ "deleted": true }))).into_response(),
+        Ok(false) => ApiError::new(
+            StatusCode::NOT_FOUND,
+            "not_found",
+            "search document not found",
+        )
+        .into_response(),
+        Err(err) => {
+            tracing::error!(%err, id, "search delete failed");
+            (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()).into_response()
+        }
+    }
+}
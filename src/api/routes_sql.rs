@@ -1,5 +1,6 @@
 use crate::api::errors::ApiError;
 use crate::api::AppState;
+use crate::sqlite;
 use axum::extract::State;
 use axum::http::StatusCode;
 use axum::response::IntoResponse;
@@ -9,6 +10,29 @@ use serde::{Deserialize, Serialize};
 pub struct SqlBody {
     pub sql: String,
     pub params: Option<Vec<serde_json::Value>>,
+    /// Values for `:name` placeholders; can't be combined with `params`.
+    pub named_params: Option<serde_json::Map<String, serde_json::Value>>,
+}
+
+impl SqlBody {
+    fn into_parts(self) -> Result<(String, sqlite::SqlParams), ApiError> {
+        let params =
+            sqlite::SqlParams::from_request(self.params, self.named_params).map_err(|err| {
+                ApiError::new(StatusCode::BAD_REQUEST, "invalid_argument", err.to_string())
+            })?;
+        Ok((self.sql, params))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SqlTxBody {
+    pub statements: Vec<sqlite::SqlStatement>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SqlTxResponse {
+    /// Rows changed by each statement, in request order.
+    pub rows_affected: Vec<u64>,
 }
 
 #[derive(Debug, Serialize)]
@@ -39,10 +63,8 @@ pub async fn query(
             "sql query endpoint only accepts SELECT statements",
         ));
     }
-    let rows = service
-        .query(body.sql, body.params.unwrap_or_default())
-        .await
-        .map_err(|err| ApiError::new(StatusCode::BAD_REQUEST, "sqlite_error", err.to_string()))?;
+    let (sql, params) = body.into_parts()?;
+    let rows = service.query(sql, params).await.map_err(map_sqlite_error)?;
     Ok(axum::Json(SqlQueryResponse { rows }))
 }
 
@@ -57,11 +79,64 @@ pub async fn exec(
             "sqlite module is disabled",
         ));
     };
+    ensure_writable(&state)?;
+    let (sql, params) = body.into_parts()?;
     let affected = service
-        .execute(body.sql, body.params.unwrap_or_default())
+        .execute(sql, params)
         .await
-        .map_err(|err| ApiError::new(StatusCode::BAD_REQUEST, "sqlite_error", err.to_string()))?;
+        .map_err(map_sqlite_error)?;
     Ok(axum::Json(SqlExecResponse {
         rows_affected: affected,
     }))
 }
+
+pub async fn tx(
+    State(state): State<AppState>,
+    axum::Json(body): axum::Json<SqlTxBody>,
+) -> Result<impl IntoResponse, ApiError> {
+    let Some(service) = state.sqlite.as_ref() else {
+        return Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            "not_enabled",
+            "sqlite module is disabled",
+        ));
+    };
+    ensure_writable(&state)?;
+    if body.statements.is_empty() {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "invalid_argument",
+            "statements must not be empty",
+        ));
+    }
+    let rows_affected = service
+        .transaction(body.statements)
+        .await
+        .map_err(map_sqlite_error)?;
+    Ok(axum::Json(SqlTxResponse { rows_affected }))
+}
+
+fn ensure_writable(state: &AppState) -> Result<(), ApiError> {
+    if state.config.sqlite_readonly {
+        return Err(ApiError::new(
+            StatusCode::FORBIDDEN,
+            "forbidden",
+            "sqlite is read-only (SQLITE_READONLY)",
+        ));
+    }
+    Ok(())
+}
+
+/// Busy/locked errors are transient, so they get a 503 the client can retry;
+/// anything else is a problem with the statement itself.
+fn map_sqlite_error(err: anyhow::Error) -> ApiError {
+    if sqlite::is_busy(&err) {
+        return ApiError::new(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "sqlite_busy",
+            err.to_string(),
+        );
+    }
+    // `{:#}` keeps the context chain, e.g. which statement of a tx failed.
+    ApiError::new(StatusCode::BAD_REQUEST, "sqlite_error", format!("{err:#}"))
+}
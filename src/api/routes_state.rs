@@ -1,6 +1,6 @@
-use crate::api::errors::{ApiError, ErrorBody};
+use crate::api::errors::{list_limit, ApiError, ErrorBody};
 use crate::api::AppState;
-use crate::engine::{EngineError, StateError};
+use crate::engine::{Durability, EngineError, StateError, StateItem, StateOp};
 use axum::extract::{Path, Query, State};
 use axum::http::StatusCode;
 use axum::response::IntoResponse;
@@ -10,14 +10,89 @@ pub async fn health(State(state): State<AppState>) -> impl IntoResponse {
     state.engine.health()
 }
 
+const READY_SQLITE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(2);
+
+#[derive(Debug, Serialize)]
+pub struct ReadyResponse {
+    pub ready: bool,
+    /// Absent when SQLite is disabled.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sqlite_ok: Option<bool>,
+}
+
+pub async fn ready(State(state): State<AppState>) -> impl IntoResponse {
+    let sqlite_ok = match &state.sqlite {
+        Some(sqlite) => Some(match sqlite.ping(READY_SQLITE_TIMEOUT).await {
+            Ok(()) => true,
+            Err(err) => {
+                tracing::warn!(error = %err, "sqlite readiness probe failed");
+                false
+            }
+        }),
+        None => None,
+    };
+    let ready = sqlite_ok != Some(false) && !state.engine.is_draining();
+    let status = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, axum::Json(ReadyResponse { ready, sqlite_ok }))
+}
+
 pub async fn metrics(State(state): State<AppState>) -> impl IntoResponse {
-    (StatusCode::OK, state.engine.metrics_text())
+    let mut text = state.engine.metrics_text();
+    text.push_str(&state.search_engine.render_metrics());
+    (StatusCode::OK, text)
+}
+
+/// `/v1/metrics` as JSON for tooling that doesn't parse the Prometheus format.
+pub async fn metrics_json(State(state): State<AppState>) -> impl IntoResponse {
+    let mut metrics = state.engine.metrics_json();
+    metrics["embed_cache_hits_total"] = serde_json::json!(state.search_engine.embed_cache_hits());
+    axum::Json(metrics)
+}
+
+#[derive(Debug, Serialize)]
+pub struct PingResponse {
+    pub pong: bool,
+    pub now_ms: u64,
+    pub offset: u64,
+}
+
+pub async fn ping(State(state): State<AppState>) -> impl IntoResponse {
+    let now_ms = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64;
+    axum::Json(PingResponse {
+        pong: true,
+        now_ms,
+        offset: state.engine.events().next_offset().saturating_sub(1),
+    })
+}
+
+pub async fn info(State(state): State<AppState>) -> impl IntoResponse {
+    axum::Json(state.engine.summary())
 }
 
 #[derive(Debug, Deserialize)]
 pub struct ListQuery {
     pub prefix: Option<String>,
     pub limit: Option<usize>,
+    /// `next_cursor` of the previous page.
+    pub start_after: Option<String>,
+    /// Inclusive bounds of a key range; both or neither, not with a `prefix`.
+    pub from: Option<String>,
+    pub to: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ListResponse {
+    pub items: Vec<StateItem>,
+    /// Set when the page is full; pass it as `start_after` to continue.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
 }
 
 pub async fn list(
@@ -33,9 +108,65 @@ pub async fn list(
             ));
         }
     }
-    let limit = q.limit.unwrap_or(100).min(1000);
-    let items = state.engine.list_state(q.prefix.as_deref(), limit);
-    Ok(axum::Json(items))
+    if q.start_after
+        .as_ref()
+        .is_some_and(|cursor| cursor.len() > state.config.max_key_len)
+    {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "invalid_argument",
+            "start_after too long",
+        ));
+    }
+    let range = match (&q.from, &q.to) {
+        (None, None) => None,
+        (Some(from), Some(to)) => Some((from.as_str(), to.as_str())),
+        _ => {
+            return Err(ApiError::new(
+                StatusCode::BAD_REQUEST,
+                "invalid_argument",
+                "from and to must be given together",
+            ))
+        }
+    };
+    if let Some((from, to)) = range {
+        if q.prefix.as_deref().is_some_and(|p| !p.is_empty()) {
+            return Err(ApiError::new(
+                StatusCode::BAD_REQUEST,
+                "invalid_argument",
+                "prefix cannot be combined with from/to",
+            ));
+        }
+        if from.len() > state.config.max_key_len || to.len() > state.config.max_key_len {
+            return Err(ApiError::new(
+                StatusCode::BAD_REQUEST,
+                "invalid_argument",
+                "from/to too long",
+            ));
+        }
+        if from > to {
+            return Err(ApiError::new(
+                StatusCode::BAD_REQUEST,
+                "invalid_argument",
+                "from sorts after to",
+            ));
+        }
+    }
+    let limit = list_limit(q.limit, 100, state.config.max_list_limit)?;
+    let items = match range {
+        Some((from, to)) => {
+            state
+                .engine
+                .range_state_after(from, to, q.start_after.as_deref(), limit)
+        }
+        None => state
+            .engine
+            .list_state_after(q.prefix.as_deref(), q.start_after.as_deref(), limit),
+    };
+    let next_cursor = (limit > 0 && items.len() == limit)
+        .then(|| items.last().map(|item| item.key.clone()))
+        .flatten();
+    Ok(axum::Json(ListResponse { items, next_cursor }))
 }
 
 pub async fn get(
@@ -49,7 +180,10 @@ pub async fn get(
             "key too long",
         ));
     }
-    let Some(item) = state.engine.get_state(&key) else {
+    let started = std::time::Instant::now();
+    let item = state.engine.get_state(&key);
+    state.engine.metrics().observe_state_get(started.elapsed());
+    let Some(item) = item else {
         return Err(ApiError::new(
             StatusCode::NOT_FOUND,
             "not_found",
@@ -64,6 +198,7 @@ pub struct PutBody {
     pub value: serde_json::Value,
     pub ttl_ms: Option<u64>,
     pub if_revision: Option<u64>,
+    pub durability: Option<Durability>,
 }
 
 #[derive(Debug, Serialize)]
@@ -71,6 +206,7 @@ pub struct PutResponse {
     pub key: String,
     pub revision: u64,
     pub expires_at_ms: Option<u64>,
+    pub durable: bool,
 }
 
 #[derive(Debug, Deserialize)]
@@ -127,20 +263,31 @@ pub async fn put(
             "value too large",
         ));
     }
-    match state
-        .engine
-        .put_state(key.clone(), body.value, body.ttl_ms, body.if_revision)
-    {
+    let durability = body
+        .durability
+        .unwrap_or_else(|| state.engine.default_durability());
+    let started = std::time::Instant::now();
+    let result = state.engine.put_state_with_durability(
+        key.clone(),
+        body.value,
+        body.ttl_ms,
+        body.if_revision,
+        durability,
+    );
+    state.engine.metrics().observe_state_put(started.elapsed());
+    match result {
         Ok(item) => Ok(axum::Json(PutResponse {
             key,
             revision: item.revision,
             expires_at_ms: item.expires_at_ms,
+            durable: durability == Durability::Sync && state.config.data_dir.is_some(),
         })),
         Err(EngineError::State(StateError::RevisionMismatch)) => Err(ApiError::new(
             StatusCode::CONFLICT,
             "revision_mismatch",
             "if_revision mismatch",
         )),
+        Err(EngineError::ShuttingDown) => Err(ApiError::shutting_down()),
         Err(EngineError::Persistence(_)) => Err(ApiError::new(
             StatusCode::INTERNAL_SERVER_ERROR,
             "persistence_error",
@@ -214,6 +361,7 @@ pub async fn batch_put(
                     },
                 })
             }
+            Err(EngineError::ShuttingDown) => return Err(ApiError::shutting_down()),
             Err(EngineError::Persistence(_)) => {
                 return Err(ApiError::new(
                     StatusCode::INTERNAL_SERVER_ERROR,
@@ -239,9 +387,15 @@ pub struct DeleteResponse {
     pub deleted: bool,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct DeleteQuery {
+    pub if_revision: Option<u64>,
+}
+
 pub async fn delete(
     State(state): State<AppState>,
     Path(key): Path<String>,
+    Query(q): Query<DeleteQuery>,
 ) -> Result<impl IntoResponse, ApiError> {
     if key.len() > state.config.max_key_len {
         return Err(ApiError::new(
@@ -250,17 +404,313 @@ pub async fn delete(
             "key too long",
         ));
     }
-    let deleted = state.engine.delete_state(&key).map_err(|err| match err {
-        EngineError::Persistence(_) => ApiError::new(
-            StatusCode::INTERNAL_SERVER_ERROR,
-            "persistence_error",
-            "failed to persist event",
-        ),
-        _ => ApiError::new(
-            StatusCode::INTERNAL_SERVER_ERROR,
-            "internal",
-            "internal error",
-        ),
-    })?;
+    let deleted = state
+        .engine
+        .delete_state_with_reason(&key, "explicit", q.if_revision)
+        .map_err(|err| match err {
+            EngineError::State(StateError::RevisionMismatch) => ApiError::new(
+                StatusCode::CONFLICT,
+                "revision_mismatch",
+                "if_revision mismatch",
+            ),
+            EngineError::ShuttingDown => ApiError::shutting_down(),
+            EngineError::Persistence(_) => ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "persistence_error",
+                "failed to persist event",
+            ),
+            _ => ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "internal",
+                "internal error",
+            ),
+        })?;
     Ok(axum::Json(DeleteResponse { deleted }))
 }
+
+#[derive(Debug, Deserialize)]
+pub struct DeletePrefixBody {
+    pub prefix: String,
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DeletePrefixResponse {
+    pub deleted: usize,
+    /// More keys match the prefix; call again to continue.
+    pub more: bool,
+}
+
+/// Deletes up to `limit` (at most `MAX_STATE_BATCH`) keys under `prefix`.
+pub async fn delete_prefix(
+    State(state): State<AppState>,
+    axum::Json(body): axum::Json<DeletePrefixBody>,
+) -> Result<impl IntoResponse, ApiError> {
+    if body.prefix.is_empty() {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "invalid_argument",
+            "prefix required",
+        ));
+    }
+    if body.prefix.len() > state.config.max_key_len {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "invalid_argument",
+            "prefix too long",
+        ));
+    }
+    let limit = list_limit(
+        body.limit,
+        state.config.max_state_batch,
+        state.config.max_state_batch,
+    )?;
+    let (deleted, more) =
+        state
+            .engine
+            .delete_prefix(&body.prefix, limit)
+            .map_err(|err| match err {
+                EngineError::ShuttingDown => ApiError::shutting_down(),
+                EngineError::Persistence(_) => ApiError::new(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "persistence_error",
+                    "failed to persist event",
+                ),
+                _ => ApiError::new(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "internal",
+                    "internal error",
+                ),
+            })?;
+    Ok(axum::Json(DeletePrefixResponse { deleted, more }))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BatchGetBody {
+    pub keys: Vec<String>,
+}
+
+/// Items returned by `batch_get` and `txn`.
+#[derive(Debug, Serialize)]
+pub struct ItemsResponse {
+    pub items: Vec<StateItem>,
+}
+
+pub async fn batch_get(
+    State(state): State<AppState>,
+    axum::Json(body): axum::Json<BatchGetBody>,
+) -> Result<impl IntoResponse, ApiError> {
+    if body.keys.len() > state.config.max_state_batch {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "invalid_argument",
+            "too many keys",
+        ));
+    }
+    if body.keys.iter().any(|k| k.len() > state.config.max_key_len) {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "invalid_argument",
+            "key too long",
+        ));
+    }
+    let items = state.engine.get_state_many(&body.keys);
+    Ok(axum::Json(ItemsResponse { items }))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TxnBody {
+    pub ops: Vec<StateOp>,
+}
+
+pub async fn txn(
+    State(state): State<AppState>,
+    axum::Json(body): axum::Json<TxnBody>,
+) -> Result<impl IntoResponse, ApiError> {
+    if body.ops.is_empty() {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "invalid_argument",
+            "ops required",
+        ));
+    }
+    if body.ops.len() > state.config.max_state_batch {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "invalid_argument",
+            "too many operations",
+        ));
+    }
+    for op in &body.ops {
+        if op.key().len() > state.config.max_key_len {
+            return Err(ApiError::new(
+                StatusCode::BAD_REQUEST,
+                "invalid_argument",
+                "key too long",
+            ));
+        }
+        if let StateOp::Put { value, .. } = op {
+            let estimated = serde_json::to_vec(value).map(|v| v.len()).unwrap_or(0);
+            if estimated > state.config.max_json_bytes {
+                return Err(ApiError::new(
+                    StatusCode::PAYLOAD_TOO_LARGE,
+                    "payload_too_large",
+                    "value too large",
+                ));
+            }
+        }
+    }
+    let items = state
+        .engine
+        .transact_state(body.ops)
+        .map_err(|err| match err {
+            EngineError::State(StateError::RevisionMismatch) => ApiError::new(
+                StatusCode::CONFLICT,
+                "revision_mismatch",
+                "if_revision mismatch",
+            ),
+            EngineError::State(StateError::AlreadyExists) => ApiError::new(
+                StatusCode::CONFLICT,
+                "already_exists",
+                "key exists (if_not_exists)",
+            ),
+            EngineError::ShuttingDown => ApiError::shutting_down(),
+            EngineError::Persistence(_) => ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "persistence_error",
+                "failed to persist event",
+            ),
+            _ => ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "internal",
+                "internal error",
+            ),
+        })?;
+    Ok(axum::Json(ItemsResponse { items }))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RenameBody {
+    pub to: String,
+    #[serde(default)]
+    pub overwrite: bool,
+}
+
+pub async fn rename(
+    State(state): State<AppState>,
+    Path(key): Path<String>,
+    axum::Json(body): axum::Json<RenameBody>,
+) -> Result<impl IntoResponse, ApiError> {
+    if key.len() > state.config.max_key_len || body.to.len() > state.config.max_key_len {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "invalid_argument",
+            "key too long",
+        ));
+    }
+    let item = state
+        .engine
+        .rename_state(&key, &body.to, body.overwrite)
+        .map_err(|err| match err {
+            EngineError::State(StateError::NotFound) => {
+                ApiError::new(StatusCode::NOT_FOUND, "not_found", "key not found")
+            }
+            EngineError::State(StateError::AlreadyExists) => ApiError::new(
+                StatusCode::CONFLICT,
+                "already_exists",
+                "target key exists (set overwrite=true)",
+            ),
+            EngineError::ShuttingDown => ApiError::shutting_down(),
+            EngineError::Persistence(_) => ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "persistence_error",
+                "failed to persist event",
+            ),
+            _ => ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "internal",
+                "internal error",
+            ),
+        })?;
+    Ok(axum::Json(item))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct IncrementBody {
+    pub delta: i64,
+    pub ttl_ms: Option<u64>,
+}
+
+pub async fn increment(
+    State(state): State<AppState>,
+    Path(key): Path<String>,
+    axum::Json(body): axum::Json<IncrementBody>,
+) -> Result<impl IntoResponse, ApiError> {
+    if key.len() > state.config.max_key_len {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "invalid_argument",
+            "key too long",
+        ));
+    }
+    let item = state
+        .engine
+        .increment_state(&key, body.delta, body.ttl_ms)
+        .map_err(|err| match err {
+            EngineError::ShuttingDown => ApiError::shutting_down(),
+            EngineError::Persistence(_) => ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "persistence_error",
+                "failed to persist event",
+            ),
+            _ => ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "internal",
+                "internal error",
+            ),
+        })?;
+    Ok(axum::Json(item))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TouchBody {
+    pub ttl_ms: u64,
+}
+
+pub async fn touch(
+    State(state): State<AppState>,
+    Path(key): Path<String>,
+    axum::Json(body): axum::Json<TouchBody>,
+) -> Result<impl IntoResponse, ApiError> {
+    if key.len() > state.config.max_key_len {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "invalid_argument",
+            "key too long",
+        ));
+    }
+    let item = state
+        .engine
+        .touch_state(&key, body.ttl_ms)
+        .map_err(|err| match err {
+            EngineError::ShuttingDown => ApiError::shutting_down(),
+            EngineError::Persistence(_) => ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "persistence_error",
+                "failed to persist event",
+            ),
+            _ => ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "internal",
+                "internal error",
+            ),
+        })?;
+    let Some(item) = item else {
+        return Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            "not_found",
+            "key not found",
+        ));
+    };
+    Ok(axum::Json(item))
+}
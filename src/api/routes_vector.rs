@@ -1,17 +1,25 @@
-use crate::api::errors::{ApiError, ErrorBody};
+use crate::api::auth::{require_full_scope, AuthContext};
+use crate::api::errors::{enforce_response_size, ApiError, ErrorBody};
 use crate::api::AppState;
-use crate::engine::EngineError;
+use crate::engine::{EngineError, VectorUpsertOutcome};
 use crate::vector::index::{DiskAnnBuildParams, DiskIndexStatus};
-use crate::vector::{Metric, SearchRequest, VectorCollectionInfo, VectorError, VectorItem, SearchHit};
+use crate::vector::{
+    validate_filters, CollectionHit, HnswParams, KnnEdge, Metric, NormStats, SearchHit,
+    SearchRequest, SearchStats, VectorCollectionInfo, VectorError, VectorItem,
+};
 use axum::extract::{Path, Query, State};
 use axum::http::StatusCode;
 use axum::response::IntoResponse;
+use axum::Extension;
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
 
 #[derive(Debug, Deserialize)]
 pub struct CreateCollectionBody {
     pub dim: usize,
     pub metric: Metric,
+    #[serde(default)]
+    pub hnsw: Option<HnswParams>,
 }
 
 #[derive(Debug, Serialize)]
@@ -19,6 +27,8 @@ pub struct CreateCollectionResponse {
     pub collection: String,
     pub dim: usize,
     pub metric: Metric,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hnsw: Option<HnswParams>,
 }
 
 #[derive(Debug, Serialize)]
@@ -38,6 +48,7 @@ pub struct VectorCollectionDetailResponse {
     pub notes: Option<String>,
     pub segments: Option<usize>,
     pub deleted: Option<u64>,
+    pub norm_stats: Option<NormStats>,
 }
 
 pub async fn list_collections(
@@ -57,6 +68,56 @@ pub async fn list_collections(
     Ok(axum::Json(ListCollectionsResponse { collections }))
 }
 
+pub async fn collection_stats(
+    State(state): State<AppState>,
+    Path(collection): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    ensure_collection_len(&collection, &state)?;
+    let Some(stats) = state.engine.vector_collection_stats(&collection) else {
+        return Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            "not_found",
+            "collection not found",
+        ));
+    };
+    Ok(axum::Json(stats))
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct DefaultFilterBody {
+    #[serde(default)]
+    pub filter: Option<serde_json::Value>,
+}
+
+pub async fn get_default_filter(
+    State(state): State<AppState>,
+    Path(collection): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    ensure_collection_len(&collection, &state)?;
+    let filter = state
+        .engine
+        .vector_default_filter(&collection)
+        .map_err(map_vector_error)?;
+    Ok(axum::Json(DefaultFilterBody { filter }))
+}
+
+/// A `null` filter clears the default.
+pub async fn set_default_filter(
+    State(state): State<AppState>,
+    Path(collection): Path<String>,
+    axum::Json(body): axum::Json<DefaultFilterBody>,
+) -> Result<impl IntoResponse, ApiError> {
+    ensure_collection_len(&collection, &state)?;
+    if let Some(filter) = &body.filter {
+        ensure_valid_filters(filter)?;
+    }
+    state
+        .engine
+        .set_vector_default_filter(&collection, body.filter.clone())
+        .map_err(map_engine_error)?;
+    Ok(axum::Json(body))
+}
+
 pub async fn get_collection_detail(
     State(state): State<AppState>,
     Path(collection): Path<String>,
@@ -96,16 +157,17 @@ pub async fn get_collection_detail(
         .and_then(|v| v.get("updated_at_ms"))
         .and_then(|v| v.as_u64());
 
-    let (dim, metric, count, segments, deleted) = if let Some(info) = stats.as_ref() {
+    let (dim, metric, count, segments, deleted, norm_stats) = if let Some(info) = stats.as_ref() {
         (
             Some(info.dim),
             Some(info.metric),
             Some(info.live_count),
             info.segments,
             info.deleted_count,
+            info.norm_stats,
         )
     } else {
-        (None, None, None, None, None)
+        (None, None, None, None, None, None)
     };
 
     let mut notes = None;
@@ -124,6 +186,7 @@ pub async fn get_collection_detail(
         notes,
         segments,
         deleted,
+        norm_stats,
     };
     Ok(axum::Json(response))
 }
@@ -147,22 +210,181 @@ pub async fn create_collection(
             "invalid dim",
         ));
     }
+    if let Some(hnsw) = &body.hnsw {
+        hnsw.validate()
+            .map_err(|msg| ApiError::new(StatusCode::BAD_REQUEST, "invalid_argument", msg))?;
+    }
     state
         .engine
-        .create_vector_collection(&collection, body.dim, body.metric)
+        .create_vector_collection_with_hnsw(&collection, body.dim, body.metric, body.hnsw)
         .map_err(map_engine_error)?;
     Ok(axum::Json(CreateCollectionResponse {
         collection,
         dim: body.dim,
         metric: body.metric,
+        hnsw: body.hnsw,
     }))
 }
 
+#[derive(Debug, Serialize)]
+pub struct DropCollectionResponse {
+    pub collection: String,
+    pub dropped: bool,
+}
+
+pub async fn drop_collection(
+    State(state): State<AppState>,
+    Path(collection): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    ensure_collection_len(&collection, &state)?;
+    state
+        .engine
+        .drop_vector_collection(&collection)
+        .map_err(map_engine_error)?;
+    Ok(axum::Json(DropCollectionResponse {
+        collection,
+        dropped: true,
+    }))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BulkCreateBody {
+    pub collections: Vec<BulkCreateItem>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BulkCreateItem {
+    pub name: String,
+    pub dim: usize,
+    pub metric: Metric,
+    #[serde(default)]
+    pub hnsw: Option<HnswParams>,
+    #[serde(default)]
+    pub if_not_exists: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BulkCreateResponse {
+    pub results: Vec<BulkCreateResult>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum BulkCreateResult {
+    Created {
+        collection: String,
+    },
+    Exists {
+        collection: String,
+    },
+    Error {
+        collection: String,
+        error: ErrorBody,
+    },
+}
+
+fn bulk_create_error(collection: String, error: &'static str, message: &str) -> BulkCreateResult {
+    BulkCreateResult::Error {
+        collection,
+        error: ErrorBody {
+            error,
+            message: message.into(),
+        },
+    }
+}
+
+pub async fn bulk_create_collections(
+    State(state): State<AppState>,
+    axum::Json(body): axum::Json<BulkCreateBody>,
+) -> Result<impl IntoResponse, ApiError> {
+    if body.collections.is_empty() {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "invalid_argument",
+            "collections required",
+        ));
+    }
+    if body.collections.len() > state.config.max_vector_batch {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "invalid_argument",
+            "too many collections",
+        ));
+    }
+    let mut results = Vec::with_capacity(body.collections.len());
+    for item in body.collections {
+        let BulkCreateItem {
+            name,
+            dim,
+            metric,
+            hnsw,
+            if_not_exists,
+        } = item;
+        if name.is_empty() || name.len() > state.config.max_collection_len {
+            results.push(bulk_create_error(
+                name,
+                "invalid_argument",
+                "invalid collection name",
+            ));
+            continue;
+        }
+        if dim == 0 || dim > state.config.max_vector_dim {
+            results.push(bulk_create_error(name, "invalid_argument", "invalid dim"));
+            continue;
+        }
+        if let Some(Err(msg)) = hnsw.as_ref().map(HnswParams::validate) {
+            results.push(bulk_create_error(name, "invalid_argument", &msg));
+            continue;
+        }
+        match state
+            .engine
+            .create_vector_collection_with_hnsw(&name, dim, metric, hnsw)
+        {
+            Ok(()) => results.push(BulkCreateResult::Created { collection: name }),
+            Err(EngineError::Vector(VectorError::CollectionExists)) => {
+                let same_shape = state
+                    .engine
+                    .vector_collection_info(&name)
+                    .is_some_and(|info| info.dim == dim && info.metric == metric);
+                if if_not_exists && same_shape {
+                    results.push(BulkCreateResult::Exists { collection: name });
+                } else if if_not_exists {
+                    results.push(bulk_create_error(
+                        name,
+                        "already_exists",
+                        "collection exists with a different dim or metric",
+                    ));
+                } else {
+                    results.push(bulk_create_error(
+                        name,
+                        "already_exists",
+                        "collection already exists",
+                    ));
+                }
+            }
+            Err(err) => return Err(map_engine_error(err)),
+        }
+    }
+    Ok(axum::Json(BulkCreateResponse { results }))
+}
+
 #[derive(Debug, Clone, Deserialize)]
 pub struct AddBody {
     pub id: String,
     pub vector: Vec<f32>,
     pub meta: Option<serde_json::Value>,
+    pub if_revision: Option<u64>,
+    /// Deletes the vector this many ms after the write.
+    #[serde(default)]
+    pub ttl_ms: Option<u64>,
+    /// Output size of the client's embedding model; checked against the
+    /// collection dim before anything is written.
+    #[serde(default)]
+    pub expected_dim: Option<usize>,
+    /// Upserts only: skip the write when the stored vector, meta and expiry
+    /// are already the same.
+    #[serde(default)]
+    pub only_if_changed: bool,
 }
 
 #[derive(Debug, Serialize)]
@@ -170,9 +392,20 @@ pub struct OkResponse {
     pub ok: bool,
 }
 
+#[derive(Debug, Serialize)]
+pub struct UpsertResponse {
+    pub ok: bool,
+    #[serde(flatten)]
+    pub outcome: VectorUpsertOutcome,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct UpsertBatchBody {
     pub items: Vec<AddBody>,
+    /// Like `AddBody::expected_dim`, for every item: a mismatch rejects the
+    /// whole batch up front instead of failing item by item.
+    #[serde(default)]
+    pub expected_dim: Option<usize>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -210,9 +443,20 @@ pub struct DiskAnnMutationResponse {
 #[derive(Debug, Serialize)]
 #[serde(tag = "status", rename_all = "snake_case")]
 pub enum VectorBatchResult {
-    Upserted { id: String },
-    Deleted { id: String },
-    Error { id: String, error: ErrorBody },
+    Upserted {
+        id: String,
+    },
+    /// Skipped by `only_if_changed`.
+    Unchanged {
+        id: String,
+    },
+    Deleted {
+        id: String,
+    },
+    Error {
+        id: String,
+        error: ErrorBody,
+    },
 }
 
 pub async fn add(
@@ -227,6 +471,14 @@ pub async fn add(
             "collection too long",
         ));
     }
+    // `add` only creates; conditional writes go through `upsert`.
+    if body.if_revision.is_some() || body.only_if_changed {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "invalid_argument",
+            "if_revision and only_if_changed are only supported by upsert",
+        ));
+    }
     if body.id.len() > state.config.max_id_len {
         return Err(ApiError::new(
             StatusCode::BAD_REQUEST,
@@ -251,6 +503,12 @@ pub async fn add(
             ));
         }
     }
+    check_expected_dim(
+        &state,
+        &collection,
+        body.expected_dim,
+        [(body.id.as_str(), body.vector.len())],
+    )?;
     state
         .engine
         .vector_add(
@@ -259,6 +517,8 @@ pub async fn add(
             VectorItem {
                 vector: body.vector,
                 meta: body.meta.unwrap_or(serde_json::Value::Null),
+                revision: 0,
+                expires_at_ms: expires_at(body.ttl_ms),
             },
         )
         .map_err(map_engine_error)?;
@@ -301,18 +561,28 @@ pub async fn upsert(
             ));
         }
     }
-    state
+    check_expected_dim(
+        &state,
+        &collection,
+        body.expected_dim,
+        [(body.id.as_str(), body.vector.len())],
+    )?;
+    let outcome = state
         .engine
-        .vector_upsert(
+        .vector_upsert_with_options(
             &collection,
             &body.id,
             VectorItem {
                 vector: body.vector,
                 meta: body.meta.unwrap_or(serde_json::Value::Null),
+                revision: 0,
+                expires_at_ms: expires_at(body.ttl_ms),
             },
+            body.if_revision,
+            body.only_if_changed,
         )
         .map_err(map_engine_error)?;
-    Ok(axum::Json(OkResponse { ok: true }))
+    Ok(axum::Json(UpsertResponse { ok: true, outcome }))
 }
 
 pub async fn upsert_batch(
@@ -341,9 +611,33 @@ pub async fn upsert_batch(
             "too many items",
         ));
     }
+    let expected_dims: BTreeSet<usize> = body
+        .items
+        .iter()
+        .filter_map(|item| item.expected_dim)
+        .chain(body.expected_dim)
+        .collect();
+    for expected_dim in expected_dims {
+        check_expected_dim(
+            &state,
+            &collection,
+            Some(expected_dim),
+            body.items
+                .iter()
+                .map(|item| (item.id.as_str(), item.vector.len())),
+        )?;
+    }
     let mut results = Vec::with_capacity(body.items.len());
     for op in body.items {
-        let AddBody { id, vector, meta } = op;
+        let AddBody {
+            id,
+            vector,
+            meta,
+            if_revision,
+            ttl_ms,
+            only_if_changed,
+            ..
+        } = op;
         if id.len() > state.config.max_id_len {
             results.push(VectorBatchResult::Error {
                 id,
@@ -377,14 +671,19 @@ pub async fn upsert_batch(
                 continue;
             }
         }
-        match state.engine.vector_upsert(
+        match state.engine.vector_upsert_with_options(
             &collection,
             &id,
             VectorItem {
                 vector,
                 meta: meta.unwrap_or(serde_json::Value::Null),
+                revision: 0,
+                expires_at_ms: expires_at(ttl_ms),
             },
+            if_revision,
+            only_if_changed,
         ) {
+            Ok(outcome) if !outcome.changed => results.push(VectorBatchResult::Unchanged { id }),
             Ok(_) => results.push(VectorBatchResult::Upserted { id }),
             Err(EngineError::Vector(VectorError::DimMismatch)) => {
                 results.push(VectorBatchResult::Error {
@@ -413,6 +712,25 @@ pub async fn upsert_batch(
                     },
                 });
             }
+            Err(EngineError::Vector(VectorError::RevisionMismatch)) => {
+                results.push(VectorBatchResult::Error {
+                    id,
+                    error: ErrorBody {
+                        error: "revision_mismatch",
+                        message: "if_revision mismatch".into(),
+                    },
+                });
+            }
+            Err(EngineError::Vector(VectorError::CollectionFull)) => {
+                results.push(VectorBatchResult::Error {
+                    id,
+                    error: ErrorBody {
+                        error: "collection_full",
+                        message: "collection reached max_vectors_per_collection".into(),
+                    },
+                });
+            }
+            Err(EngineError::ShuttingDown) => return Err(ApiError::shutting_down()),
             Err(EngineError::Persistence(_)) => {
                 return Err(ApiError::new(
                     StatusCode::INTERNAL_SERVER_ERROR,
@@ -440,6 +758,7 @@ pub struct UpdateBody {
     pub id: String,
     pub vector: Option<Vec<f32>>,
     pub meta: Option<serde_json::Value>,
+    pub if_revision: Option<u64>,
 }
 
 pub async fn update(
@@ -482,7 +801,13 @@ pub async fn update(
     }
     state
         .engine
-        .vector_update(&collection, &body.id, body.vector, body.meta)
+        .vector_update(
+            &collection,
+            &body.id,
+            body.vector,
+            body.meta,
+            body.if_revision,
+        )
         .map_err(map_engine_error)?;
     Ok(axum::Json(OkResponse { ok: true }))
 }
@@ -581,6 +906,7 @@ pub async fn delete_batch(
             Err(EngineError::Vector(VectorError::Persistence)) => {
                 return Err(map_vector_error(VectorError::Persistence));
             }
+            Err(EngineError::ShuttingDown) => return Err(ApiError::shutting_down()),
             Err(EngineError::Persistence(_)) => {
                 return Err(ApiError::new(
                     StatusCode::INTERNAL_SERVER_ERROR,
@@ -610,6 +936,57 @@ pub struct GetResponse {
     pub id: String,
     pub vector: Vec<f32>,
     pub meta: serde_json::Value,
+    pub revision: u64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GetBatchBody {
+    pub ids: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GetBatchResponse {
+    pub items: HashMap<String, VectorItem>,
+}
+
+pub async fn get_batch(
+    State(state): State<AppState>,
+    Path(collection): Path<String>,
+    axum::Json(body): axum::Json<GetBatchBody>,
+) -> Result<impl IntoResponse, ApiError> {
+    ensure_collection_len(&collection, &state)?;
+    if body.ids.is_empty() {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "invalid_argument",
+            "ids required",
+        ));
+    }
+    if body.ids.len() > state.config.max_vector_batch {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "invalid_argument",
+            "too many ids",
+        ));
+    }
+    if body.ids.iter().any(|id| id.len() > state.config.max_id_len) {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "invalid_argument",
+            "id too long",
+        ));
+    }
+    let items = state
+        .engine
+        .vector_get_many(&collection, &body.ids)
+        .map_err(map_vector_error)?;
+    let response = GetBatchResponse { items };
+    enforce_response_size(
+        &response,
+        state.config.max_response_bytes,
+        "request fewer ids",
+    )?;
+    Ok(axum::Json(response))
 }
 
 pub async fn get(
@@ -646,24 +1023,207 @@ pub async fn get(
         id: q.id,
         vector: item.vector,
         meta: item.meta,
+        revision: item.revision,
     }))
 }
 
+#[derive(Debug, Deserialize)]
+pub struct MatchBody {
+    pub id: String,
+    pub filter: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MatchResponse {
+    pub matched: bool,
+}
+
+/// Runs a search filter against a single item so clients can debug filters
+/// without issuing a search.
+pub async fn match_filter(
+    State(state): State<AppState>,
+    Path(collection): Path<String>,
+    axum::Json(body): axum::Json<MatchBody>,
+) -> Result<impl IntoResponse, ApiError> {
+    ensure_collection_len(&collection, &state)?;
+    if body.id.len() > state.config.max_id_len {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "invalid_argument",
+            "id too long",
+        ));
+    }
+    if let Some(filter) = &body.filter {
+        ensure_valid_filters(filter)?;
+    }
+    let matched = state
+        .engine
+        .vector_matches_filter(&collection, &body.id, body.filter.as_ref())
+        .map_err(map_vector_error)?;
+    let Some(matched) = matched else {
+        return Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            "not_found",
+            "vector id not found",
+        ));
+    };
+    Ok(axum::Json(MatchResponse { matched }))
+}
+
 #[derive(Debug, Serialize)]
 pub struct SearchResponse {
     pub hits: Vec<SearchHit>,
+    /// Whether the hits come from exact brute-force scoring rather than an
+    /// approximate index (HNSW, IVF, DiskANN).
+    pub exact: bool,
+    /// Only present with `with_total: true`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total: Option<usize>,
+    /// Only present with `?debug=true`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stats: Option<SearchStats>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SearchQuery {
+    #[serde(default)]
+    pub debug: bool,
 }
 
 pub async fn search(
     State(state): State<AppState>,
     Path(collection): Path<String>,
+    Query(q): Query<SearchQuery>,
     axum::Json(body): axum::Json<SearchRequest>,
 ) -> Result<impl IntoResponse, ApiError> {
-    if collection.len() > state.config.max_collection_len {
+    ensure_collection_len(&collection, &state)?;
+    validate_search_request(&state, &body)?;
+    let outcome = state
+        .engine
+        .vector_search_detailed(&collection, body)
+        .map_err(map_vector_error)?;
+    let response = SearchResponse {
+        hits: outcome.hits,
+        exact: outcome.exact,
+        total: outcome.total,
+        stats: q.debug.then_some(outcome.stats),
+    };
+    enforce_response_size(
+        &response,
+        state.config.max_response_bytes,
+        "lower k or set include_meta=false",
+    )?;
+    Ok(axum::Json(response))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SearchMultiBody {
+    pub collections: Vec<String>,
+    #[serde(flatten)]
+    pub search: SearchRequest,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SearchMultiResponse {
+    pub hits: Vec<CollectionHit>,
+}
+
+pub async fn search_multi(
+    State(state): State<AppState>,
+    axum::Json(body): axum::Json<SearchMultiBody>,
+) -> Result<impl IntoResponse, ApiError> {
+    if body.collections.is_empty() || body.collections.len() > state.config.max_vector_batch {
         return Err(ApiError::new(
             StatusCode::BAD_REQUEST,
             "invalid_argument",
-            "collection too long",
+            "collections must list 1..=MAX_VECTOR_BATCH names",
+        ));
+    }
+    for collection in &body.collections {
+        ensure_collection_len(collection, &state)?;
+    }
+    validate_search_request(&state, &body.search)?;
+    let hits = state
+        .engine
+        .vector_search_multi(&body.collections, body.search)
+        .map_err(map_vector_error)?;
+    let response = SearchMultiResponse { hits };
+    enforce_response_size(
+        &response,
+        state.config.max_response_bytes,
+        "lower k or set include_meta=false",
+    )?;
+    Ok(axum::Json(response))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ExistsSimilarBody {
+    pub vector: Vec<f32>,
+    /// Minimum score, on the same scale as search hits.
+    pub threshold: f32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ExistsSimilarResponse {
+    pub exists: bool,
+    pub id: Option<String>,
+    pub score: Option<f32>,
+}
+
+pub async fn exists_similar(
+    State(state): State<AppState>,
+    Path(collection): Path<String>,
+    axum::Json(body): axum::Json<ExistsSimilarBody>,
+) -> Result<impl IntoResponse, ApiError> {
+    ensure_collection_len(&collection, &state)?;
+    if body.vector.len() > state.config.max_vector_dim {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "invalid_argument",
+            "vector too large",
+        ));
+    }
+    if !body.threshold.is_finite() {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "invalid_argument",
+            "threshold must be a finite number",
+        ));
+    }
+    let found = state
+        .engine
+        .vector_exists_similar(&collection, body.vector, body.threshold)
+        .map_err(map_vector_error)?;
+    let (id, score) = found.map_or((None, None), |(id, score)| (Some(id), Some(score)));
+    Ok(axum::Json(ExistsSimilarResponse {
+        exists: id.is_some(),
+        id,
+        score,
+    }))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct KnnGraphBody {
+    pub ids: Vec<String>,
+    pub k: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct KnnGraphResponse {
+    pub edges: Vec<KnnEdge>,
+}
+
+pub async fn knn_graph(
+    State(state): State<AppState>,
+    Path(collection): Path<String>,
+    axum::Json(body): axum::Json<KnnGraphBody>,
+) -> Result<impl IntoResponse, ApiError> {
+    ensure_collection_len(&collection, &state)?;
+    if body.ids.len() > state.config.max_vector_batch {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "invalid_argument",
+            "too many ids",
         ));
     }
     if body.k == 0 || body.k > state.config.max_k {
@@ -673,14 +1233,74 @@ pub async fn search(
             "invalid k",
         ));
     }
-    if body.vector.len() > state.config.max_vector_dim {
+    let edges = state
+        .engine
+        .vector_knn_graph(&collection, &body.ids, body.k)
+        .map_err(map_vector_error)?;
+    let response = KnnGraphResponse { edges };
+    enforce_response_size(
+        &response,
+        state.config.max_response_bytes,
+        "send fewer ids or lower k",
+    )?;
+    Ok(axum::Json(response))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SearchBatchBody {
+    pub queries: Vec<SearchRequest>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SearchBatchResponse {
+    pub results: Vec<Vec<SearchHit>>,
+}
+
+pub async fn search_batch(
+    State(state): State<AppState>,
+    Path(collection): Path<String>,
+    axum::Json(body): axum::Json<SearchBatchBody>,
+) -> Result<impl IntoResponse, ApiError> {
+    ensure_collection_len(&collection, &state)?;
+    if body.queries.len() > state.config.max_vector_batch {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "invalid_argument",
+            "too many queries",
+        ));
+    }
+    for query in &body.queries {
+        validate_search_request(&state, query)?;
+    }
+    let results = state
+        .engine
+        .vector_search_batch(&collection, body.queries)
+        .map_err(map_vector_error)?;
+    let response = SearchBatchResponse { results };
+    enforce_response_size(
+        &response,
+        state.config.max_response_bytes,
+        "send fewer queries, lower k or set include_meta=false",
+    )?;
+    Ok(axum::Json(response))
+}
+
+fn validate_search_request(state: &AppState, req: &SearchRequest) -> Result<(), ApiError> {
+    if req.k == 0 || req.k > state.config.max_k {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "invalid_argument",
+            "invalid k",
+        ));
+    }
+    if req.vector.len() > state.config.max_vector_dim {
         return Err(ApiError::new(
             StatusCode::BAD_REQUEST,
             "invalid_argument",
             "vector too large",
         ));
     }
-    if let Some(filters) = &body.filters {
+    if let Some(filters) = &req.filters {
         let estimated = serde_json::to_vec(filters).map(|v| v.len()).unwrap_or(0);
         if estimated > state.config.max_json_bytes {
             return Err(ApiError::new(
@@ -689,12 +1309,23 @@ pub async fn search(
                 "filters too large",
             ));
         }
+        ensure_valid_filters(filters)?;
     }
-    let hits = state
-        .engine
-        .vector_search(&collection, body)
-        .map_err(map_vector_error)?;
-    Ok(axum::Json(SearchResponse { hits }))
+    Ok(())
+}
+
+/// Absolute expiry for a write carrying `ttl_ms`.
+fn expires_at(ttl_ms: Option<u64>) -> Option<u64> {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64;
+    ttl_ms.map(|ttl| now.saturating_add(ttl))
+}
+
+fn ensure_valid_filters(filters: &serde_json::Value) -> Result<(), ApiError> {
+    validate_filters(filters)
+        .map_err(|msg| ApiError::new(StatusCode::BAD_REQUEST, "invalid_argument", msg))
 }
 
 fn map_vector_error(err: VectorError) -> ApiError {
@@ -730,11 +1361,22 @@ fn map_vector_error(err: VectorError) -> ApiError {
             "not_supported",
             "vector operation not supported",
         ),
+        VectorError::RevisionMismatch => ApiError::new(
+            StatusCode::CONFLICT,
+            "revision_mismatch",
+            "if_revision mismatch",
+        ),
+        VectorError::CollectionFull => ApiError::new(
+            StatusCode::CONFLICT,
+            "collection_full",
+            "collection reached max_vectors_per_collection",
+        ),
     }
 }
 
 fn map_engine_error(err: EngineError) -> ApiError {
     match err {
+        EngineError::ShuttingDown => ApiError::shutting_down(),
         EngineError::Persistence(_) => ApiError::new(
             StatusCode::INTERNAL_SERVER_ERROR,
             "persistence_error",
@@ -753,6 +1395,46 @@ fn map_engine_error(err: EngineError) -> ApiError {
         ),
     }
 }
+
+#[derive(Debug, Serialize)]
+pub struct VacuumResponse {
+    pub collection: String,
+    pub file_len: u64,
+    pub live_count: usize,
+}
+
+pub async fn vacuum(
+    State(state): State<AppState>,
+    Path(collection): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    ensure_collection_len(&collection, &state)?;
+    let info = state
+        .engine
+        .vector_vacuum_collection(&collection)
+        .map_err(map_engine_error)?;
+    Ok(axum::Json(VacuumResponse {
+        collection: info.collection,
+        file_len: info.file_len,
+        live_count: info.live_count,
+    }))
+}
+
+/// Rebuilds the collection's in-memory index from its stored vectors, e.g.
+/// after a suspected corrupt HNSW dump. Admin keys only.
+pub async fn reindex(
+    State(state): State<AppState>,
+    ctx: Option<Extension<AuthContext>>,
+    Path(collection): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    require_full_scope(ctx.as_deref())?;
+    ensure_collection_len(&collection, &state)?;
+    let stats = state
+        .engine
+        .vector_reindex_collection(&collection)
+        .map_err(map_engine_error)?;
+    Ok(axum::Json(stats))
+}
+
 pub async fn diskann_build(
     State(state): State<AppState>,
     Path(collection): Path<String>,
@@ -809,6 +1491,41 @@ pub async fn diskann_status(
     Ok(axum::Json(DiskAnnStatusResponse::from(status)))
 }
 
+/// Fails with one `dim_mismatch` when the client's `expected_dim` disagrees
+/// with the collection or with any of `vectors` (`(id, len)` pairs), so a
+/// swapped embedding model is reported before a single write.
+fn check_expected_dim<'a>(
+    state: &AppState,
+    collection: &str,
+    expected_dim: Option<usize>,
+    vectors: impl IntoIterator<Item = (&'a str, usize)>,
+) -> Result<(), ApiError> {
+    let Some(expected) = expected_dim else {
+        return Ok(());
+    };
+    let Some(info) = state.engine.vector_collection_info(collection) else {
+        return Err(map_vector_error(VectorError::CollectionNotFound));
+    };
+    if info.dim != expected {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "dim_mismatch",
+            format!(
+                "expected_dim {expected} does not match collection dim {}",
+                info.dim
+            ),
+        ));
+    }
+    if let Some((id, len)) = vectors.into_iter().find(|(_, len)| *len != expected) {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "dim_mismatch",
+            format!("vector `{id}` has {len} dimensions, expected_dim is {expected}"),
+        ));
+    }
+    Ok(())
+}
+
 fn ensure_collection_len(collection: &str, state: &AppState) -> Result<(), ApiError> {
     if collection.len() > state.config.max_collection_len {
         return Err(ApiError::new(
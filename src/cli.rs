@@ -1,5 +1,5 @@
 use rust_kiss_vdb::config::Config;
-use rust_kiss_vdb::vector::VectorStore;
+use rust_kiss_vdb::vector::{ExportQuantize, VectorStore};
 
 #[derive(Debug)]
 pub enum Command {
@@ -8,6 +8,31 @@ pub enum Command {
     DiskAnnBuild(crate::diskann::DiskAnnCli),
     DiskAnnTune(crate::diskann::DiskAnnCli),
     DiskAnnStatus { collection: String },
+    DumpEvents(DumpEventsCli),
+    Export(ExportCli),
+    Import(ImportCli),
+}
+
+#[derive(Debug)]
+pub struct DumpEventsCli {
+    pub data_dir: Option<String>,
+    pub out: String,
+    pub since: u64,
+}
+
+#[derive(Debug)]
+pub struct ExportCli {
+    pub data_dir: Option<String>,
+    pub collection: String,
+    pub out: String,
+    pub quantize: ExportQuantize,
+}
+
+#[derive(Debug)]
+pub struct ImportCli {
+    pub data_dir: Option<String>,
+    pub input: String,
+    pub collection: Option<String>,
 }
 
 pub fn parse_command() -> anyhow::Result<Command> {
@@ -21,6 +46,9 @@ pub fn parse_command() -> anyhow::Result<Command> {
         "serve" => Ok(Command::Serve),
         "vacuum" => parse_vacuum(&args[2..]),
         "diskann" => crate::diskann::parse_diskann(&args[2..]),
+        "dump-events" => parse_dump_events(&args[2..]),
+        "export" => parse_export(&args[2..]),
+        "import" => parse_import(&args[2..]),
         _ => Ok(Command::Serve),
     }
 }
@@ -40,6 +68,136 @@ fn parse_vacuum(args: &[String]) -> anyhow::Result<Command> {
     anyhow::bail!("vacuum requiere --collection")
 }
 
+fn parse_dump_events(args: &[String]) -> anyhow::Result<Command> {
+    let mut opts = DumpEventsCli {
+        data_dir: None,
+        out: String::new(),
+        since: 0,
+    };
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let mut value = || {
+            iter.next()
+                .ok_or_else(|| anyhow::anyhow!("{arg} requiere un valor"))
+        };
+        match arg.as_str() {
+            "--data-dir" => opts.data_dir = Some(value()?.to_string()),
+            "--out" => opts.out = value()?.to_string(),
+            "--since" => {
+                opts.since = value()?
+                    .parse()
+                    .map_err(|_| anyhow::anyhow!("--since debe ser un offset numérico"))?
+            }
+            other => anyhow::bail!("argumento desconocido para dump-events: {other}"),
+        }
+    }
+    if opts.out.is_empty() {
+        anyhow::bail!("dump-events requiere --out");
+    }
+    Ok(Command::DumpEvents(opts))
+}
+
+fn parse_export(args: &[String]) -> anyhow::Result<Command> {
+    let mut opts = ExportCli {
+        data_dir: None,
+        collection: String::new(),
+        out: String::new(),
+        quantize: ExportQuantize::F32,
+    };
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let mut value = || {
+            iter.next()
+                .ok_or_else(|| anyhow::anyhow!("{arg} requiere un valor"))
+        };
+        match arg.as_str() {
+            "--data-dir" => opts.data_dir = Some(value()?.to_string()),
+            "--collection" => opts.collection = value()?.to_string(),
+            "--out" => opts.out = value()?.to_string(),
+            "--quantize" => {
+                opts.quantize = ExportQuantize::parse(value()?)
+                    .ok_or_else(|| anyhow::anyhow!("--quantize debe ser `f32` o `int8`"))?
+            }
+            other => anyhow::bail!("argumento desconocido para export: {other}"),
+        }
+    }
+    if opts.collection.is_empty() || opts.out.is_empty() {
+        anyhow::bail!("export requiere --collection y --out");
+    }
+    Ok(Command::Export(opts))
+}
+
+fn parse_import(args: &[String]) -> anyhow::Result<Command> {
+    let mut opts = ImportCli {
+        data_dir: None,
+        input: String::new(),
+        collection: None,
+    };
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let mut value = || {
+            iter.next()
+                .ok_or_else(|| anyhow::anyhow!("{arg} requiere un valor"))
+        };
+        match arg.as_str() {
+            "--data-dir" => opts.data_dir = Some(value()?.to_string()),
+            "--in" => opts.input = value()?.to_string(),
+            "--collection" => opts.collection = Some(value()?.to_string()),
+            other => anyhow::bail!("argumento desconocido para import: {other}"),
+        }
+    }
+    if opts.input.is_empty() {
+        anyhow::bail!("import requiere --in");
+    }
+    Ok(Command::Import(opts))
+}
+
+pub fn run_dump_events(config: &Config, opts: DumpEventsCli) -> anyhow::Result<()> {
+    let dir = opts
+        .data_dir
+        .or_else(|| config.data_dir.clone())
+        .ok_or_else(|| anyhow::anyhow!("--data-dir o DATA_DIR requerido para dump-events"))?;
+
+    let file = std::fs::File::create(&opts.out)?;
+    let mut out = std::io::BufWriter::new(file);
+    let written = rust_kiss_vdb::engine::dump_events(&dir, opts.since, &mut out)?;
+
+    println!("{written} eventos escritos en `{}`.", opts.out);
+    Ok(())
+}
+
+pub fn run_export(config: &Config, opts: ExportCli) -> anyhow::Result<()> {
+    let dir = opts
+        .data_dir
+        .or_else(|| config.data_dir.clone())
+        .ok_or_else(|| anyhow::anyhow!("--data-dir o DATA_DIR requerido para export"))?;
+
+    let store = VectorStore::open(dir)?;
+    let file = std::fs::File::create(&opts.out)?;
+    let mut out = std::io::BufWriter::new(file);
+    let written = store.export_collection(&opts.collection, opts.quantize, &mut out)?;
+
+    println!(
+        "{written} vectores de `{}` exportados a `{}`.",
+        opts.collection, opts.out
+    );
+    Ok(())
+}
+
+pub fn run_import(config: &Config, opts: ImportCli) -> anyhow::Result<()> {
+    let dir = opts
+        .data_dir
+        .or_else(|| config.data_dir.clone())
+        .ok_or_else(|| anyhow::anyhow!("--data-dir o DATA_DIR requerido para import"))?;
+
+    let store = VectorStore::open(dir)?;
+    let input = std::io::BufReader::new(std::fs::File::open(&opts.input)?);
+    let (collection, imported) = store.import_collection(input, opts.collection.as_deref())?;
+
+    println!("{imported} vectores importados en `{collection}`.");
+    Ok(())
+}
+
 pub fn run_vacuum(config: &Config, collection: String) -> anyhow::Result<()> {
     let dir = config
         .data_dir
@@ -12,23 +12,68 @@ pub struct Config {
     pub live_broadcast_capacity: usize,
     pub wal_segment_max_bytes: u64,
     pub wal_retention_segments: usize,
+    pub wal_truncate_after_snapshot: bool,
+    /// How WAL and snapshot writes reach the disk: `data` (fdatasync),
+    /// `full` (fsync plus the directory) or `none`.
+    pub wal_fsync_policy: String,
     pub request_timeout_secs: u64,
     pub max_body_bytes: usize,
     pub max_key_len: usize,
     pub max_collection_len: usize,
     pub max_id_len: usize,
     pub max_vector_dim: usize,
+    pub strict_query_dim: bool,
     pub max_k: usize,
     pub max_json_bytes: usize,
     pub max_state_batch: usize,
     pub max_vector_batch: usize,
+    pub max_vectors_per_collection: usize,
     pub max_doc_find: usize,
+    /// String fields per document written to the `docidx:` keys, taken in
+    /// name order; `0` means unlimited.
+    pub max_doc_indexed_fields: usize,
+    pub max_list_limit: usize,
+    pub max_response_bytes: usize,
+    pub embed_cache_size: usize,
+    /// Embedding service used by `/v1/search`; unset means the built-in hash
+    /// embedder.
+    pub embed_url: Option<String>,
+    /// Sent as `model` to `embed_url` and part of the embedding cache key.
+    pub embed_model: String,
+    pub embed_timeout_ms: u64,
+    pub sse_idle_timeout_secs: u64,
+    pub value_compress_min_bytes: usize,
+    /// Smallest response body gzipped for clients sending
+    /// `Accept-Encoding: gzip`; `0` disables response compression.
+    pub response_compress_min_bytes: usize,
+    /// Reads keep serving a state key this long past its TTL; the expiry
+    /// sweep still deletes it on time.
+    pub ttl_grace_ms: u64,
+    /// A search waiting longer than this for the collections read lock logs
+    /// a warning; `0` turns the warning off.
+    pub lock_wait_warn_ms: u64,
     pub cors_allowed_origins: Option<String>,
     pub sqlite_enabled: bool,
     pub sqlite_path: Option<String>,
+    /// Opens the SQL endpoints' connection read-only and refuses
+    /// `/v1/sql/exec` and `/v1/sql/tx` with `403`.
+    pub sqlite_readonly: bool,
+    /// Extra attempts, with exponential backoff, when SQLite reports the database busy or locked.
+    pub sqlite_busy_retries: u32,
+    /// Directory of `.sql` files applied in name order when SQLite starts.
+    pub sqlite_migrations_dir: Option<String>,
     pub search_threads: usize,
     pub parallel_probe: bool,
     pub parallel_probe_min_segments: usize,
+    /// Points per HNSW segment for new collections; existing ones keep the
+    /// value stored in their manifest.
+    pub vector_segment_max_items: usize,
+    /// Tombstone share across a collection's segments that triggers an
+    /// in-memory merge of the sparse ones; `0` disables it.
+    pub vector_merge_tombstone_ratio: f32,
+    /// Distinct values per metadata field kept in the vector keyword index;
+    /// `0` means unlimited.
+    pub max_index_values_per_field: usize,
     pub simd_enabled: bool,
     pub index_kind: String,
     pub ivf_clusters: usize,
@@ -44,6 +89,8 @@ pub struct Config {
     pub run_retention: usize,
     pub compaction_trigger_tombstone_ratio: f32,
     pub compaction_max_bytes_per_pass: u64,
+    pub verify_on_start: bool,
+    pub default_durability: String,
 }
 
 impl Config {
@@ -80,6 +127,9 @@ impl Config {
             .ok()
             .and_then(|v| v.parse().ok())
             .unwrap_or(8);
+        let wal_truncate_after_snapshot = parse_env_bool("WAL_TRUNCATE_AFTER_SNAPSHOT", false);
+        let wal_fsync_policy =
+            std::env::var("WAL_FSYNC_POLICY").unwrap_or_else(|_| "data".to_string());
 
         let request_timeout_secs = std::env::var("REQUEST_TIMEOUT_SECS")
             .ok()
@@ -111,6 +161,8 @@ impl Config {
             .and_then(|v| v.parse().ok())
             .unwrap_or(4096);
 
+        let strict_query_dim = parse_env_bool("STRICT_QUERY_DIM", true);
+
         let max_k = std::env::var("MAX_K")
             .ok()
             .and_then(|v| v.parse().ok())
@@ -131,11 +183,67 @@ impl Config {
             .and_then(|v| v.parse().ok())
             .unwrap_or(256);
 
+        let max_vectors_per_collection = std::env::var("MAX_VECTORS_PER_COLLECTION")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(0);
+
+        let embed_cache_size = std::env::var("EMBED_CACHE_SIZE")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(crate::search::engine::DEFAULT_EMBED_CACHE_SIZE);
+        let embed_url = std::env::var("EMBED_URL").ok().filter(|v| !v.is_empty());
+        let embed_model = std::env::var("EMBED_MODEL").unwrap_or_else(|_| "default".to_string());
+        let embed_timeout_ms = std::env::var("EMBED_TIMEOUT_MS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(10_000);
+
+        let sse_idle_timeout_secs = std::env::var("SSE_IDLE_TIMEOUT_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(0);
+
+        let value_compress_min_bytes = std::env::var("VALUE_COMPRESS_MIN_BYTES")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(0);
+
+        let response_compress_min_bytes = std::env::var("RESPONSE_COMPRESS_MIN_BYTES")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(0);
+
+        let ttl_grace_ms = std::env::var("TTL_GRACE_MS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(0);
+
+        let lock_wait_warn_ms = std::env::var("LOCK_WAIT_WARN_MS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(100);
+
         let max_doc_find = std::env::var("MAX_DOC_FIND")
             .ok()
             .and_then(|v| v.parse().ok())
             .unwrap_or(100);
 
+        let max_doc_indexed_fields = std::env::var("MAX_DOC_INDEXED_FIELDS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(0);
+
+        let max_list_limit = std::env::var("MAX_LIST_LIMIT")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(1000);
+
+        let max_response_bytes = std::env::var("MAX_RESPONSE_BYTES")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(8 * 1024 * 1024);
+
         let search_threads = std::env::var("SEARCH_THREADS")
             .ok()
             .and_then(|v| v.parse().ok())
@@ -148,6 +256,23 @@ impl Config {
             .and_then(|v| v.parse().ok())
             .unwrap_or(4);
 
+        let vector_segment_max_items = std::env::var("VECTOR_SEGMENT_MAX_ITEMS")
+            .ok()
+            .and_then(|v| v.parse::<usize>().ok())
+            .filter(|v| *v > 0)
+            .unwrap_or(8192);
+
+        let vector_merge_tombstone_ratio = std::env::var("VECTOR_MERGE_TOMBSTONE_RATIO")
+            .ok()
+            .and_then(|v| v.parse::<f32>().ok())
+            .filter(|v| v.is_finite())
+            .unwrap_or(0.3);
+
+        let max_index_values_per_field = std::env::var("MAX_INDEX_VALUES_PER_FIELD")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(0);
+
         let simd_enabled = parse_env_bool("SIMD_ENABLED", true);
 
         let index_kind = std::env::var("INDEX_KIND").unwrap_or_else(|_| "IVF_FLAT_Q8".to_string());
@@ -216,9 +341,19 @@ impl Config {
             .and_then(|v| v.parse().ok())
             .unwrap_or(1_073_741_824);
 
+        let verify_on_start = parse_env_bool("VERIFY_ON_START", false);
+        let default_durability =
+            std::env::var("DEFAULT_DURABILITY").unwrap_or_else(|_| "sync".to_string());
+
         let cors_allowed_origins = std::env::var("CORS_ALLOWED_ORIGINS").ok();
         let sqlite_enabled = resolve_sqlite_enabled();
         let sqlite_path = std::env::var("SQLITE_DB_PATH").ok();
+        let sqlite_readonly = parse_env_bool("SQLITE_READONLY", false);
+        let sqlite_busy_retries = std::env::var("SQLITE_BUSY_RETRIES")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(3);
+        let sqlite_migrations_dir = std::env::var("SQLITE_MIGRATIONS_DIR").ok();
 
         Ok(Self {
             port,
@@ -230,23 +365,45 @@ impl Config {
             live_broadcast_capacity,
             wal_segment_max_bytes,
             wal_retention_segments,
+            wal_truncate_after_snapshot,
+            wal_fsync_policy,
             request_timeout_secs,
             max_body_bytes,
             max_key_len,
             max_collection_len,
             max_id_len,
             max_vector_dim,
+            strict_query_dim,
             max_k,
             max_json_bytes,
             max_state_batch,
             max_vector_batch,
+            max_vectors_per_collection,
             max_doc_find,
+            max_doc_indexed_fields,
+            max_list_limit,
+            max_response_bytes,
+            embed_cache_size,
+            embed_url,
+            embed_model,
+            embed_timeout_ms,
+            sse_idle_timeout_secs,
+            value_compress_min_bytes,
+            response_compress_min_bytes,
+            ttl_grace_ms,
+            lock_wait_warn_ms,
             cors_allowed_origins,
             sqlite_enabled,
             sqlite_path,
+            sqlite_readonly,
+            sqlite_busy_retries,
+            sqlite_migrations_dir,
             search_threads,
             parallel_probe,
             parallel_probe_min_segments,
+            vector_segment_max_items,
+            vector_merge_tombstone_ratio,
+            max_index_values_per_field,
             simd_enabled,
             index_kind,
             ivf_clusters,
@@ -262,6 +419,8 @@ impl Config {
             run_retention,
             compaction_trigger_tombstone_ratio,
             compaction_max_bytes_per_pass,
+            verify_on_start,
+            default_durability,
         })
     }
 }
@@ -1,6 +1,19 @@
-use crate::engine::{Engine, EngineError};
+use crate::engine::{Engine, EngineError, IndexChanges, StateOp};
+use jsonschema::JSONSchema;
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum DocError {
+    #[error(transparent)]
+    Engine(#[from] EngineError),
+    #[error("invalid schema: {0}")]
+    InvalidSchema(String),
+    /// One `path: message` entry per failed constraint.
+    #[error("document violates schema")]
+    SchemaViolation(Vec<String>),
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DocRecord {
@@ -14,14 +27,12 @@ pub fn put_doc(
     collection: &str,
     id: &str,
     doc: serde_json::Value,
-) -> Result<DocRecord, EngineError> {
+) -> Result<DocRecord, DocError> {
+    validate_against_schema(engine, collection, &doc)?;
     let key = doc_key(collection, id);
-    let previous = engine.get_state(&key);
-    let stored = engine.put_state(key, doc.clone(), None, None)?;
-    if let Some(prev) = previous {
-        update_indexes_remove(engine, collection, id, &prev.value)?;
-    }
-    update_indexes_add(engine, collection, id, &doc)?;
+    let stored = engine.commit_doc_upsert(collection, id, key, doc.clone(), |previous| {
+        index_changes(engine, collection, id, previous.map(|p| &p.value), &doc)
+    })?;
     Ok(DocRecord {
         id: id.to_string(),
         doc,
@@ -54,6 +65,18 @@ pub fn delete_doc(engine: &Engine, collection: &str, id: &str) -> Result<bool, E
     Ok(deleted)
 }
 
+/// Evaluates a `find` filter against one document; `None` when the id is absent.
+pub fn match_doc(
+    engine: &Engine,
+    collection: &str,
+    id: &str,
+    filter: Option<&serde_json::Value>,
+) -> Option<bool> {
+    engine
+        .get_state(&doc_key(collection, id))
+        .map(|item| doc_matches(&item.value, filter))
+}
+
 pub fn find_docs(
     engine: &Engine,
     collection: &str,
@@ -85,50 +108,270 @@ pub fn find_docs(
         }
     }
 
+    // Pages through the whole collection: matches may sit past any fixed window.
     let prefix = format!("doc:{collection}:");
+    let page_size = limit.saturating_mul(4);
     let mut docs = Vec::new();
-    for item in engine.list_state(Some(&prefix), limit * 4) {
-        if !item.key.starts_with(&prefix) {
+    let mut start_after: Option<String> = None;
+    loop {
+        let page = engine.list_state_after(Some(&prefix), start_after.as_deref(), page_size);
+        let exhausted = page.len() < page_size;
+        start_after = page.last().map(|item| item.key.clone());
+        for item in page {
+            if !item.key.starts_with(&prefix) {
+                continue;
+            }
+            let id = item.key[prefix.len()..].to_string();
+            if !doc_matches(&item.value, filter) {
+                continue;
+            }
+            docs.push(DocRecord {
+                id,
+                doc: item.value,
+                revision: item.revision,
+            });
+            if docs.len() >= limit {
+                return Ok(docs);
+            }
+        }
+        if exhausted || start_after.is_none() {
+            return Ok(docs);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ReindexReport {
+    pub documents: usize,
+    pub index_keys: usize,
+    /// Index keys rewritten or deleted because they had drifted.
+    pub repaired: usize,
+}
+
+/// Rebuilds every `docidx:` key of `collection` from the stored documents,
+/// dropping keys no document supports any more, along with the list of
+/// fields left out by `MAX_DOC_INDEXED_FIELDS`. Also backfills the tag
+/// postings of documents stored before tags were indexed. The documents are
+/// read and the fixes written as one transaction under the commit lock, so
+/// concurrent writes can't interleave and a crash leaves no half-built index.
+pub fn reindex(engine: &Engine, collection: &str) -> Result<ReindexReport, EngineError> {
+    let mut report = ReindexReport::default();
+    engine.transact_state_with(|| {
+        let (ops, planned) = plan_reindex(engine, collection);
+        report = planned;
+        ops
+    })?;
+    Ok(report)
+}
+
+fn plan_reindex(engine: &Engine, collection: &str) -> (Vec<StateOp>, ReindexReport) {
+    let cap = engine.max_doc_indexed_fields();
+    let doc_prefix = format!("doc:{collection}:");
+    let mut rebuilt: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    let mut unindexed = BTreeSet::new();
+    let mut documents = 0;
+    for item in engine.list_state(Some(&doc_prefix), usize::MAX) {
+        let Some(id) = item.key.strip_prefix(&doc_prefix) else {
             continue;
+        };
+        documents += 1;
+        unindexed.extend(unindexed_fields(&item.value, cap));
+        for (field, value) in indexed_fields(&item.value, cap) {
+            rebuilt
+                .entry(index_key(collection, &field, &value))
+                .or_default()
+                .push(id.to_string());
         }
-        let id = item.key[prefix.len()..].to_string();
-        if !doc_matches(&item.value, filter) {
+    }
+
+    let delete = |key: String| StateOp::Delete {
+        key,
+        if_revision: None,
+    };
+    let put = |key: String, value: serde_json::Value| StateOp::Put {
+        key,
+        value,
+        ttl_ms: None,
+        if_revision: None,
+        if_not_exists: false,
+    };
+    let idx_prefix = format!("docidx:{collection}:");
+    let mut ops = Vec::new();
+    let mut repaired = 0;
+    for item in engine.list_state(Some(&idx_prefix), usize::MAX) {
+        if !rebuilt.contains_key(&item.key) {
+            ops.push(delete(item.key));
+            repaired += 1;
+        }
+    }
+    for (key, ids) in rebuilt.iter_mut() {
+        ids.sort();
+        ids.dedup();
+        let current = engine.get_state(key).map(|item| parse_ids(&item.value));
+        if current.as_ref() == Some(ids) {
             continue;
         }
-        docs.push(DocRecord {
-            id,
-            doc: item.value,
-            revision: item.revision,
-        });
-        if docs.len() >= limit {
-            break;
+        ops.push(put(key.clone(), serde_json::json!({ "ids": ids })));
+        repaired += 1;
+    }
+    if unindexed != unindexed_field_set(engine, collection) {
+        let key = unindexed_key(collection);
+        if unindexed.is_empty() {
+            ops.push(delete(key));
+        } else {
+            ops.push(put(key, serde_json::json!({ "fields": unindexed })));
         }
+        repaired += 1;
+    }
+    if !tags_indexed(engine, collection) {
+        ops.push(put(tags_indexed_key(collection), serde_json::json!(true)));
+    }
+    let report = ReindexReport {
+        documents,
+        index_keys: rebuilt.len(),
+        repaired,
+    };
+    (ops, report)
+}
+
+/// Registers (or replaces) the JSON Schema enforced on future writes to
+/// `collection`. Existing documents are not re-validated.
+pub fn set_schema(
+    engine: &Engine,
+    collection: &str,
+    schema: serde_json::Value,
+) -> Result<u64, DocError> {
+    JSONSchema::compile(&schema).map_err(|err| DocError::InvalidSchema(err.to_string()))?;
+    let stored = engine.put_state(schema_key(collection), schema, None, None)?;
+    Ok(stored.revision)
+}
+
+fn validate_against_schema(
+    engine: &Engine,
+    collection: &str,
+    doc: &serde_json::Value,
+) -> Result<(), DocError> {
+    let Some(item) = engine.get_state(&schema_key(collection)) else {
+        return Ok(());
+    };
+    let schema =
+        JSONSchema::compile(&item.value).map_err(|err| DocError::InvalidSchema(err.to_string()))?;
+    if let Err(errors) = schema.validate(doc) {
+        let failures = errors
+            .map(|err| {
+                let path = err.instance_path.to_string();
+                let path = if path.is_empty() { "/" } else { path.as_str() };
+                format!("{path}: {err}")
+            })
+            .collect();
+        return Err(DocError::SchemaViolation(failures));
     }
-    Ok(docs)
+    Ok(())
 }
 
+/// Array field whose string elements are indexed one by one and matched with
+/// `{"tags": {"all": [...], "any": [...]}}`.
+const TAGS_FIELD: &str = "tags";
+
 fn doc_key(collection: &str, id: &str) -> String {
     format!("doc:{collection}:{id}")
 }
 
+fn schema_key(collection: &str) -> String {
+    format!("docschema:{collection}")
+}
+
 fn index_key(collection: &str, field: &str, value: &str) -> String {
     format!("docidx:{collection}:{field}:{value}")
 }
 
-fn update_indexes_add(
+fn unindexed_key(collection: &str) -> String {
+    format!("docunindexed:{collection}")
+}
+
+/// Present once every document of `collection` has its tags in `docidx:`;
+/// documents stored before tags were indexed have none until `reindex`.
+fn tags_indexed_key(collection: &str) -> String {
+    format!("doctagsindexed:{collection}")
+}
+
+fn tags_indexed(engine: &Engine, collection: &str) -> bool {
+    engine.get_state(&tags_indexed_key(collection)).is_some()
+}
+
+/// Index keys whose id lists change when `id` goes from `previous` to `doc`.
+fn index_changes(
     engine: &Engine,
     collection: &str,
     id: &str,
+    previous: Option<&serde_json::Value>,
     doc: &serde_json::Value,
-) -> Result<(), EngineError> {
-    for (field, value) in string_fields(doc) {
-        modify_index(engine, &index_key(collection, &field, &value), |ids| {
-            if !ids.iter().any(|existing| existing == id) {
-                ids.push(id.to_string());
+) -> IndexChanges {
+    let cap = engine.max_doc_indexed_fields();
+    let old: HashSet<(String, String)> = previous
+        .map(|previous| indexed_fields(previous, cap))
+        .unwrap_or_default()
+        .into_iter()
+        .collect();
+    let new: HashSet<(String, String)> = indexed_fields(doc, cap).into_iter().collect();
+    let mut touched: BTreeMap<String, bool> = BTreeMap::new();
+    for (field, value) in old.difference(&new) {
+        touched.insert(index_key(collection, field, value), false);
+    }
+    for (field, value) in new.difference(&old) {
+        touched.insert(index_key(collection, field, value), true);
+    }
+
+    let mut changes = IndexChanges::default();
+    for (key, add) in touched {
+        let state = engine.get_state(&key);
+        let mut ids = state
+            .as_ref()
+            .map(|item| parse_ids(&item.value))
+            .unwrap_or_default();
+        if add {
+            ids.push(id.to_string());
+        } else {
+            ids.retain(|existing| existing != id);
+        }
+        ids.sort();
+        ids.dedup();
+        if ids.is_empty() {
+            if state.is_some() {
+                changes.deletes.push(key);
             }
-        })?;
+        } else {
+            changes.puts.push((key, serde_json::json!({ "ids": ids })));
+        }
     }
-    Ok(())
+
+    // The first document of a collection starts its tag postings complete.
+    if previous.is_none() && !tags_indexed(engine, collection) {
+        let doc_prefix = format!("doc:{collection}:");
+        let empty = !engine
+            .list_state(Some(&doc_prefix), 1)
+            .iter()
+            .any(|item| item.key.starts_with(&doc_prefix));
+        if empty {
+            changes
+                .puts
+                .push((tags_indexed_key(collection), serde_json::json!(true)));
+        }
+    }
+
+    let skipped = unindexed_fields(doc, cap);
+    if !skipped.is_empty() {
+        let mut known = unindexed_field_set(engine, collection);
+        let before = known.len();
+        known.extend(skipped);
+        if known.len() > before {
+            changes.puts.push((
+                unindexed_key(collection),
+                serde_json::json!({ "fields": known }),
+            ));
+        }
+    }
+    changes
 }
 
 fn update_indexes_remove(
@@ -173,6 +416,60 @@ where
     Ok(())
 }
 
+/// `string_fields` restricted to the first `cap` field names in sorted
+/// order; `0` keeps them all.
+fn indexed_fields(doc: &serde_json::Value, cap: usize) -> Vec<(String, String)> {
+    let fields = string_fields(doc);
+    if cap == 0 {
+        return fields;
+    }
+    let kept: BTreeSet<&str> = fields
+        .iter()
+        .map(|(field, _)| field.as_str())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .take(cap)
+        .collect();
+    fields
+        .iter()
+        .filter(|(field, _)| kept.contains(field.as_str()))
+        .cloned()
+        .collect()
+}
+
+/// Indexable fields of `doc` left out by `MAX_DOC_INDEXED_FIELDS`; `find`
+/// only matches them by scanning.
+pub fn unindexed_fields(doc: &serde_json::Value, cap: usize) -> BTreeSet<String> {
+    if cap == 0 {
+        return BTreeSet::new();
+    }
+    string_fields(doc)
+        .into_iter()
+        .map(|(field, _)| field)
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .skip(cap)
+        .collect()
+}
+
+/// Fields some document of `collection` had left out of the index, so their
+/// postings can't be trusted to be complete.
+fn unindexed_field_set(engine: &Engine, collection: &str) -> BTreeSet<String> {
+    engine
+        .get_state(&unindexed_key(collection))
+        .and_then(|item| {
+            item.value
+                .get("fields")
+                .and_then(|v| v.as_array())
+                .map(|arr| {
+                    arr.iter()
+                        .filter_map(|v| v.as_str().map(str::to_string))
+                        .collect()
+                })
+        })
+        .unwrap_or_default()
+}
+
 fn string_fields(doc: &serde_json::Value) -> Vec<(String, String)> {
     let mut out = Vec::new();
     let Some(obj) = doc.as_object() else {
@@ -181,11 +478,52 @@ fn string_fields(doc: &serde_json::Value) -> Vec<(String, String)> {
     for (k, v) in obj {
         if let Some(value) = v.as_str() {
             out.push((k.clone(), value.to_string()));
+        } else if k == TAGS_FIELD {
+            for tag in v
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|t| t.as_str())
+            {
+                out.push((k.clone(), tag.to_string()));
+            }
         }
     }
     out
 }
 
+/// The `all`/`any` lists of a tags filter, or `None` when `expected` is a
+/// plain value to compare for equality.
+fn tags_filter(expected: &serde_json::Value) -> Option<(Vec<&str>, Option<Vec<&str>>)> {
+    let obj = expected.as_object()?;
+    if obj.is_empty() || !obj.keys().all(|k| k == "all" || k == "any") {
+        return None;
+    }
+    let list = |key: &str| -> Option<Option<Vec<&str>>> {
+        match obj.get(key) {
+            None => Some(None),
+            Some(v) => v
+                .as_array()?
+                .iter()
+                .map(|t| t.as_str())
+                .collect::<Option<Vec<_>>>()
+                .map(Some),
+        }
+    };
+    Some((list("all")?.unwrap_or_default(), list("any")?))
+}
+
+fn tags_match(actual: Option<&serde_json::Value>, all: &[&str], any: Option<&[&str]>) -> bool {
+    let tags: HashSet<&str> = actual
+        .and_then(|v| v.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|t| t.as_str())
+        .collect();
+    all.iter().all(|t| tags.contains(t))
+        && any.is_none_or(|any| any.iter().any(|t| tags.contains(t)))
+}
+
 fn parse_ids(value: &serde_json::Value) -> Vec<String> {
     value
         .get("ids")
@@ -207,17 +545,44 @@ fn indexed_candidates(
         Some(o) => o,
         None => return Ok(None),
     };
+    let postings = |field: &str, value: &str| -> HashSet<String> {
+        engine
+            .get_state(&index_key(collection, field, value))
+            .map(|item| parse_ids(&item.value).into_iter().collect())
+            .unwrap_or_default()
+    };
+    let unindexed = unindexed_field_set(engine, collection);
+    let tags_indexed = tags_indexed(engine, collection);
     let mut result: Option<HashSet<String>> = None;
     for (field, value) in obj {
-        let Some(value_str) = value.as_str() else {
-            return Ok(None);
-        };
-        let key = index_key(collection, field, value_str);
-        let Some(item) = engine.get_state(&key) else {
-            return Ok(Some(Vec::new()));
+        // Left to `doc_matches`; the other fields narrow the candidates.
+        if unindexed.contains(field) || (field == TAGS_FIELD && !tags_indexed) {
+            continue;
+        }
+        let set = match tags_filter(value).filter(|_| field == TAGS_FIELD) {
+            Some((all, any)) => {
+                // `all` intersects the per-tag postings, `any` unions them.
+                let mut sets: Vec<HashSet<String>> =
+                    all.iter().map(|tag| postings(field, tag)).collect();
+                if let Some(any) = any {
+                    sets.push(any.iter().flat_map(|tag| postings(field, tag)).collect());
+                }
+                let Some(set) = sets
+                    .into_iter()
+                    .reduce(|acc, set| acc.into_iter().filter(|id| set.contains(id)).collect())
+                else {
+                    // `{"all": []}` alone constrains nothing.
+                    continue;
+                };
+                set
+            }
+            None => {
+                let Some(value_str) = value.as_str() else {
+                    return Ok(None);
+                };
+                postings(field, value_str)
+            }
         };
-        let ids = parse_ids(&item.value);
-        let set: HashSet<String> = ids.into_iter().collect();
         result = Some(match result {
             None => set,
             Some(current) => current.into_iter().filter(|id| set.contains(id)).collect(),
@@ -239,6 +604,14 @@ fn doc_matches(doc: &serde_json::Value, filter: Option<&serde_json::Value>) -> b
         return false;
     };
     for (field, expected) in filter_obj {
+        if field == TAGS_FIELD {
+            if let Some((all, any)) = tags_filter(expected) {
+                if !tags_match(doc_obj.get(field), &all, any.as_deref()) {
+                    return false;
+                }
+                continue;
+            }
+        }
         match doc_obj.get(field) {
             Some(actual) if actual == expected => {}
             _ => return false,
@@ -77,10 +77,19 @@ impl EventBus {
             .collect()
     }
 
+    /// Offset of the oldest event still held in the replay buffer.
+    pub fn earliest_buffered_offset(&self) -> Option<u64> {
+        self.0.buffer.lock().front().map(|e| e.offset)
+    }
+
     pub fn last_published_offset(&self) -> u64 {
         self.0.last_published_offset.load(Ordering::Relaxed)
     }
 
+    pub fn next_offset(&self) -> u64 {
+        self.0.next_offset.load(Ordering::Relaxed)
+    }
+
     pub fn set_next_offset(&self, next: u64) {
         self.0.next_offset.store(next.max(1), Ordering::Relaxed);
     }
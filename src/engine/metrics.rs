@@ -1,12 +1,182 @@
+use parking_lot::{Mutex, RwLock};
+use serde::Serialize;
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::fmt::Write;
 use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+const HTTP_DURATION_BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// Upper bounds, in microseconds, of the engine operation latency buckets.
+const OP_LATENCY_BUCKETS_US: [u64; 14] = [
+    10, 25, 50, 100, 250, 500, 1_000, 2_500, 5_000, 10_000, 25_000, 50_000, 100_000, 1_000_000,
+];
 
 #[derive(Default)]
 pub struct Metrics {
     state_put_total: AtomicU64,
     state_delete_total: AtomicU64,
     vector_ops_total: AtomicU64,
+    vector_tombstone_skips_total: AtomicU64,
     events_total: AtomicU64,
     sse_clients: AtomicU64,
+    responses_compressed_total: AtomicU64,
+    response_bytes_saved_total: AtomicU64,
+    /// Keyed by collection; an entry goes when its collection is dropped.
+    vector_search_latency: RwLock<BTreeMap<String, LatencyHistogram>>,
+    state_put_latency: LatencyHistogram,
+    state_get_latency: LatencyHistogram,
+    vector_search_lock_wait: LatencyHistogram,
+    http: Mutex<HttpStats>,
+    process_start: ProcessStart,
+}
+
+/// When the metrics (and so the engine) were created; `uptime_seconds` is
+/// measured on the monotonic clock from here.
+struct ProcessStart {
+    at: Instant,
+    unix_ms: u64,
+}
+
+impl Default for ProcessStart {
+    fn default() -> Self {
+        Self {
+            at: Instant::now(),
+            unix_ms: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis() as u64,
+        }
+    }
+}
+
+/// Lock-free histogram for hot engine paths; buckets are cumulative like
+/// Prometheus `le` buckets.
+#[derive(Default)]
+struct LatencyHistogram {
+    buckets: [AtomicU64; OP_LATENCY_BUCKETS_US.len()],
+    sum_us: AtomicU64,
+    count: AtomicU64,
+}
+
+impl LatencyHistogram {
+    fn observe(&self, elapsed: Duration) {
+        let us = u64::try_from(elapsed.as_micros()).unwrap_or(u64::MAX);
+        for (bucket, le) in self.buckets.iter().zip(OP_LATENCY_BUCKETS_US) {
+            if us <= le {
+                bucket.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        self.sum_us.fetch_add(us, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Microsecond buckets as `{le, count}` pairs plus `count`, `sum_us` and
+    /// the derived `mean_us` (`0` before the first observation).
+    fn to_json(&self) -> serde_json::Value {
+        let count = self.count.load(Ordering::Relaxed);
+        let sum_us = self.sum_us.load(Ordering::Relaxed);
+        let buckets: Vec<_> = self
+            .buckets
+            .iter()
+            .zip(OP_LATENCY_BUCKETS_US)
+            .map(|(bucket, le)| json!({"le": le, "count": bucket.load(Ordering::Relaxed)}))
+            .collect();
+        json!({
+            "count": count,
+            "sum_us": sum_us,
+            "mean_us": ratio(sum_us as f64, count),
+            "buckets": buckets,
+        })
+    }
+
+    fn reset(&self) {
+        for bucket in &self.buckets {
+            bucket.store(0, Ordering::Relaxed);
+        }
+        self.sum_us.store(0, Ordering::Relaxed);
+        self.count.store(0, Ordering::Relaxed);
+    }
+
+    /// Writes the series with `le` and `_sum` divided by `us_per_unit`, so
+    /// the same buckets can be exposed in microseconds or seconds. `labels`
+    /// (`key="value",...`) go on every line; the `# TYPE` line is the
+    /// caller's.
+    fn render(&self, name: &str, labels: &str, us_per_unit: f64, out: &mut String) {
+        let (le_prefix, braced) = if labels.is_empty() {
+            (String::new(), String::new())
+        } else {
+            (format!("{labels},"), format!("{{{labels}}}"))
+        };
+        for (bucket, le) in self.buckets.iter().zip(OP_LATENCY_BUCKETS_US) {
+            let _ = writeln!(
+                out,
+                "{name}_bucket{{{le_prefix}le=\"{}\"}} {}",
+                le as f64 / us_per_unit,
+                bucket.load(Ordering::Relaxed)
+            );
+        }
+        let count = self.count.load(Ordering::Relaxed);
+        let sum = self.sum_us.load(Ordering::Relaxed) as f64 / us_per_unit;
+        let _ = writeln!(out, "{name}_bucket{{{le_prefix}le=\"+Inf\"}} {count}");
+        let _ = writeln!(out, "{name}_sum{braced} {sum}");
+        let _ = writeln!(out, "{name}_count{braced} {count}");
+    }
+}
+
+/// Keyed by matched route pattern, never the raw path, to bound label cardinality.
+#[derive(Default)]
+struct HttpStats {
+    requests: BTreeMap<(String, String, u16), u64>,
+    durations: BTreeMap<String, Histogram>,
+    rejected: BTreeMap<&'static str, u64>,
+}
+
+#[derive(Default)]
+struct Histogram {
+    buckets: [u64; HTTP_DURATION_BUCKETS.len()],
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, secs: f64) {
+        for (bucket, le) in self.buckets.iter_mut().zip(HTTP_DURATION_BUCKETS) {
+            if secs <= le {
+                *bucket += 1;
+            }
+        }
+        self.sum += secs;
+        self.count += 1;
+    }
+}
+
+/// Point-in-time copy of every counter and gauge, for library users.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct MetricsSnapshot {
+    pub state_put_total: u64,
+    pub state_delete_total: u64,
+    pub vector_ops_total: u64,
+    pub vector_tombstone_skips_total: u64,
+    pub events_total: u64,
+    pub sse_clients: u64,
+    pub responses_compressed_total: u64,
+    /// Body bytes before compression minus bytes sent, summed.
+    pub response_bytes_saved_total: u64,
+}
+
+/// Disk use read by the engine right before rendering, so the gauges are a
+/// point-in-time, best-effort view: files can change while they are summed.
+/// Both stay `0` without a data dir.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct DiskUsage {
+    /// WAL segments on disk.
+    pub wal_bytes_total: u64,
+    /// `file_len` summed over the vector collection manifests.
+    pub vector_data_bytes_total: u64,
 }
 
 impl Metrics {
@@ -19,6 +189,13 @@ impl Metrics {
     pub fn inc_vector_op(&self) {
         self.vector_ops_total.fetch_add(1, Ordering::Relaxed);
     }
+    pub fn add_vector_ops(&self, n: u64) {
+        self.vector_ops_total.fetch_add(n, Ordering::Relaxed);
+    }
+    pub fn add_tombstone_skips(&self, n: u64) {
+        self.vector_tombstone_skips_total
+            .fetch_add(n, Ordering::Relaxed);
+    }
     pub fn inc_events(&self) {
         self.events_total.fetch_add(1, Ordering::Relaxed);
     }
@@ -29,14 +206,110 @@ impl Metrics {
         self.sse_clients.fetch_sub(1, Ordering::Relaxed);
     }
 
-    pub fn render(&self) -> String {
-        let state_put = self.state_put_total.load(Ordering::Relaxed);
-        let state_delete = self.state_delete_total.load(Ordering::Relaxed);
-        let vector_ops = self.vector_ops_total.load(Ordering::Relaxed);
-        let events = self.events_total.load(Ordering::Relaxed);
-        let sse_clients = self.sse_clients.load(Ordering::Relaxed);
+    pub fn observe_compressed_response(&self, before: u64, after: u64) {
+        self.responses_compressed_total
+            .fetch_add(1, Ordering::Relaxed);
+        self.response_bytes_saved_total
+            .fetch_add(before.saturating_sub(after), Ordering::Relaxed);
+    }
+
+    pub fn observe_http(&self, route: &str, method: &str, status: u16, elapsed: Duration) {
+        let mut http = self.http.lock();
+        *http
+            .requests
+            .entry((route.to_string(), method.to_string(), status))
+            .or_default() += 1;
+        http.durations
+            .entry(route.to_string())
+            .or_default()
+            .observe(elapsed.as_secs_f64());
+    }
 
-        format!(
+    pub fn observe_vector_search(&self, collection: &str, elapsed: Duration) {
+        if let Some(hist) = self.vector_search_latency.read().get(collection) {
+            hist.observe(elapsed);
+            return;
+        }
+        self.vector_search_latency
+            .write()
+            .entry(collection.to_string())
+            .or_default()
+            .observe(elapsed);
+    }
+
+    /// Drops the search latency series of a dropped collection.
+    pub fn forget_vector_collection(&self, collection: &str) {
+        self.vector_search_latency.write().remove(collection);
+    }
+
+    pub fn observe_state_put(&self, elapsed: Duration) {
+        self.state_put_latency.observe(elapsed);
+    }
+
+    pub fn observe_state_get(&self, elapsed: Duration) {
+        self.state_get_latency.observe(elapsed);
+    }
+
+    pub fn observe_search_lock_wait(&self, waited: Duration) {
+        self.vector_search_lock_wait.observe(waited);
+    }
+
+    /// Unix time in milliseconds at which this process started serving.
+    pub fn process_start_ms(&self) -> u64 {
+        self.process_start.unix_ms
+    }
+
+    pub fn uptime(&self) -> Duration {
+        self.process_start.at.elapsed()
+    }
+
+    /// Counts a request refused by validation; `reason` is the error code.
+    pub fn inc_rejected(&self, reason: &'static str) {
+        *self.http.lock().rejected.entry(reason).or_default() += 1;
+    }
+
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        MetricsSnapshot {
+            state_put_total: self.state_put_total.load(Ordering::Relaxed),
+            state_delete_total: self.state_delete_total.load(Ordering::Relaxed),
+            vector_ops_total: self.vector_ops_total.load(Ordering::Relaxed),
+            vector_tombstone_skips_total: self.vector_tombstone_skips_total.load(Ordering::Relaxed),
+            events_total: self.events_total.load(Ordering::Relaxed),
+            sse_clients: self.sse_clients.load(Ordering::Relaxed),
+            responses_compressed_total: self.responses_compressed_total.load(Ordering::Relaxed),
+            response_bytes_saved_total: self.response_bytes_saved_total.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Zeroes every counter, including the HTTP and latency series, and
+    /// returns what they held. The `sse_clients` gauge tracks live
+    /// connections and is kept.
+    pub fn reset(&self) -> MetricsSnapshot {
+        let mut http = self.http.lock();
+        let snap = MetricsSnapshot {
+            state_put_total: self.state_put_total.swap(0, Ordering::Relaxed),
+            state_delete_total: self.state_delete_total.swap(0, Ordering::Relaxed),
+            vector_ops_total: self.vector_ops_total.swap(0, Ordering::Relaxed),
+            vector_tombstone_skips_total: self
+                .vector_tombstone_skips_total
+                .swap(0, Ordering::Relaxed),
+            events_total: self.events_total.swap(0, Ordering::Relaxed),
+            sse_clients: self.sse_clients.load(Ordering::Relaxed),
+            responses_compressed_total: self.responses_compressed_total.swap(0, Ordering::Relaxed),
+            response_bytes_saved_total: self.response_bytes_saved_total.swap(0, Ordering::Relaxed),
+        };
+        *http = HttpStats::default();
+        self.vector_search_latency.write().clear();
+        self.state_put_latency.reset();
+        self.state_get_latency.reset();
+        self.vector_search_lock_wait.reset();
+        snap
+    }
+
+    pub fn render(&self, disk: DiskUsage) -> String {
+        let snap = self.snapshot();
+
+        let mut out = format!(
             concat!(
                 "# TYPE state_put_total counter\n",
                 "state_put_total {}\n",
@@ -44,12 +317,200 @@ impl Metrics {
                 "state_delete_total {}\n",
                 "# TYPE vector_ops_total counter\n",
                 "vector_ops_total {}\n",
+                "# TYPE vector_tombstone_skips_total counter\n",
+                "vector_tombstone_skips_total {}\n",
                 "# TYPE events_total counter\n",
                 "events_total {}\n",
                 "# TYPE sse_clients gauge\n",
                 "sse_clients {}\n",
+                "# TYPE responses_compressed_total counter\n",
+                "responses_compressed_total {}\n",
+                "# TYPE response_bytes_saved_total counter\n",
+                "response_bytes_saved_total {}\n",
+            ),
+            snap.state_put_total,
+            snap.state_delete_total,
+            snap.vector_ops_total,
+            snap.vector_tombstone_skips_total,
+            snap.events_total,
+            snap.sse_clients,
+            snap.responses_compressed_total,
+            snap.response_bytes_saved_total
+        );
+        let _ = write!(
+            out,
+            concat!(
+                "# TYPE wal_bytes_total gauge\n",
+                "wal_bytes_total {}\n",
+                "# TYPE vector_data_bytes_total gauge\n",
+                "vector_data_bytes_total {}\n",
+                "# TYPE uptime_seconds gauge\n",
+                "uptime_seconds {}\n",
+                "# TYPE build_info gauge\n",
+                "build_info{{version=\"{}\"}} 1\n",
+            ),
+            disk.wal_bytes_total,
+            disk.vector_data_bytes_total,
+            self.uptime().as_secs_f64(),
+            env!("CARGO_PKG_VERSION")
+        );
+        out.push_str("# TYPE vector_search_duration_microseconds histogram\n");
+        for (collection, hist) in self.vector_search_latency.read().iter() {
+            hist.render(
+                "vector_search_duration_microseconds",
+                &format!("collection=\"{}\"", escape_label(collection)),
+                1.0,
+                &mut out,
+            );
+        }
+        for (name, hist) in [
+            ("state_put_duration_microseconds", &self.state_put_latency),
+            ("state_get_duration_microseconds", &self.state_get_latency),
+        ] {
+            let _ = writeln!(out, "# TYPE {name} histogram");
+            hist.render(name, "", 1.0, &mut out);
+        }
+        out.push_str("# TYPE vector_search_lock_wait_seconds histogram\n");
+        self.vector_search_lock_wait
+            .render("vector_search_lock_wait_seconds", "", 1e6, &mut out);
+        self.render_http(&mut out);
+        out
+    }
+
+    /// The counters of `render` as one JSON object, with histograms
+    /// summarised and a few ratios that the text format leaves to the
+    /// scraper. Field names match the Prometheus series; the histogram
+    /// summaries are in microseconds whatever the series unit.
+    pub fn render_json(&self, disk: DiskUsage) -> serde_json::Value {
+        let snap = self.snapshot();
+        let mut out = serde_json::to_value(snap).unwrap_or_else(|_| json!({}));
+        out["wal_bytes_total"] = json!(disk.wal_bytes_total);
+        out["vector_data_bytes_total"] = json!(disk.vector_data_bytes_total);
+        out["uptime_seconds"] = json!(self.uptime().as_secs_f64());
+        out["process_start_ms"] = json!(self.process_start_ms());
+        out["build_info"] = json!({"version": env!("CARGO_PKG_VERSION")});
+
+        let http = self.http.lock();
+        let requests: Vec<_> = http
+            .requests
+            .iter()
+            .map(|((route, method, status), count)| {
+                json!({"route": route, "method": method, "status": status, "count": count})
+            })
+            .collect();
+        let total_requests: u64 = http.requests.values().sum();
+        let server_errors: u64 = http
+            .requests
+            .iter()
+            .filter(|((_, _, status), _)| *status >= 500)
+            .map(|(_, count)| count)
+            .sum();
+        let durations: serde_json::Map<_, _> = http
+            .durations
+            .iter()
+            .map(|(route, hist)| {
+                let value = json!({
+                    "count": hist.count,
+                    "sum_seconds": hist.sum,
+                    "mean_seconds": ratio(hist.sum, hist.count),
+                });
+                (route.clone(), value)
+            })
+            .collect();
+        let rejected: serde_json::Map<_, _> = http
+            .rejected
+            .iter()
+            .map(|(reason, count)| (reason.to_string(), json!(count)))
+            .collect();
+        drop(http);
+
+        out["latency"] = json!({
+            "vector_search_duration_microseconds": self
+                .vector_search_latency
+                .read()
+                .iter()
+                .map(|(collection, hist)| (collection.clone(), hist.to_json()))
+                .collect::<serde_json::Map<_, _>>(),
+            "state_put_duration_microseconds": self.state_put_latency.to_json(),
+            "state_get_duration_microseconds": self.state_get_latency.to_json(),
+            "vector_search_lock_wait_seconds": self.vector_search_lock_wait.to_json(),
+        });
+        out["http"] = json!({
+            "http_requests_total": requests,
+            "http_request_duration_seconds": durations,
+            "requests_rejected_total": rejected,
+        });
+        out["derived"] = json!({
+            "http_requests": total_requests,
+            "http_server_error_ratio": ratio(server_errors as f64, total_requests),
+            "tombstone_skips_per_vector_op": ratio(
+                snap.vector_tombstone_skips_total as f64,
+                snap.vector_ops_total
+            ),
+            "bytes_saved_per_compressed_response": ratio(
+                snap.response_bytes_saved_total as f64,
+                snap.responses_compressed_total
             ),
-            state_put, state_delete, vector_ops, events, sse_clients
-        )
+        });
+        out
+    }
+
+    fn render_http(&self, out: &mut String) {
+        let http = self.http.lock();
+        out.push_str("# TYPE http_requests_total counter\n");
+        for ((route, method, status), count) in http.requests.iter() {
+            let _ = writeln!(
+                out,
+                "http_requests_total{{route=\"{route}\",method=\"{method}\",status=\"{status}\"}} {count}"
+            );
+        }
+        out.push_str("# TYPE http_request_duration_seconds histogram\n");
+        for (route, hist) in http.durations.iter() {
+            for (le, count) in HTTP_DURATION_BUCKETS.iter().zip(hist.buckets) {
+                let _ = writeln!(
+                    out,
+                    "http_request_duration_seconds_bucket{{route=\"{route}\",le=\"{le}\"}} {count}"
+                );
+            }
+            let _ = writeln!(
+                out,
+                "http_request_duration_seconds_bucket{{route=\"{route}\",le=\"+Inf\"}} {}",
+                hist.count
+            );
+            let _ = writeln!(
+                out,
+                "http_request_duration_seconds_sum{{route=\"{route}\"}} {}",
+                hist.sum
+            );
+            let _ = writeln!(
+                out,
+                "http_request_duration_seconds_count{{route=\"{route}\"}} {}",
+                hist.count
+            );
+        }
+        out.push_str("# TYPE requests_rejected_total counter\n");
+        for (reason, count) in http.rejected.iter() {
+            let _ = writeln!(
+                out,
+                "requests_rejected_total{{reason=\"{reason}\"}} {count}"
+            );
+        }
+    }
+}
+
+/// Collection names come from the URL, so quote them the Prometheus way.
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// `num / den`, or `0` when nothing was counted yet.
+fn ratio(num: f64, den: u64) -> f64 {
+    if den == 0 {
+        0.0
+    } else {
+        num / den as f64
     }
 }
@@ -7,12 +7,14 @@ mod state_db;
 use crate::config::Config;
 use crate::vector::index::{DiskAnnBuildParams, DiskIndexStatus};
 use crate::vector::{
-    Metric, SearchHit, SearchRequest, VectorCollectionInfo, VectorError, VectorItem,
-    VectorSettings, VectorStore,
+    CollectionHit, CollectionStats, HnswParams, KnnEdge, Metric, SearchHit, SearchOutcome,
+    SearchRequest, VectorCollectionInfo, VectorError, VectorItem, VectorSettings, VectorStore,
 };
 use anyhow::Context;
-use parking_lot::Mutex;
+use parking_lot::{Mutex, MutexGuard};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
 use tokio_util::sync::CancellationToken;
@@ -30,8 +32,73 @@ pub enum EngineError {
     State(#[from] state::StateError),
     #[error(transparent)]
     Vector(#[from] VectorError),
+    #[error("engine is shutting down")]
+    ShuttingDown,
 }
 
+/// How far a write must get before the call returns.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Durability {
+    /// The WAL line is fsynced before returning.
+    Sync,
+    /// The WAL line is queued and fsynced by the background group commit.
+    Async,
+}
+
+impl Durability {
+    pub fn from_config(value: &str) -> anyhow::Result<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "sync" => Ok(Durability::Sync),
+            "async" => Ok(Durability::Async),
+            other => anyhow::bail!("invalid DEFAULT_DURABILITY {other:?}: expected sync or async"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct EngineSummary {
+    pub vector_collections: usize,
+    pub live_vectors: u64,
+    pub state_keys: u64,
+    /// Offset of the last event handed out.
+    pub offset: u64,
+}
+
+/// What `Engine::vector_upsert_with_options` did with the item.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct VectorUpsertOutcome {
+    /// The id was not stored before.
+    pub created: bool,
+    /// False only when `only_if_changed` skipped an identical item.
+    pub changed: bool,
+}
+
+/// How far persistence has progressed; served by `/v1/admin/status`.
+#[derive(Clone, Debug, Serialize)]
+pub struct PersistenceStatus {
+    /// False without a data dir; the disk fields are then empty.
+    pub persistence: bool,
+    pub last_published_offset: u64,
+    pub state_applied_offset: Option<u64>,
+    /// Highest offset applied by any vector collection.
+    pub vector_applied_offset: u64,
+    pub wal_segments: Vec<persist::WalSegment>,
+    pub wal_bytes: u64,
+    pub snapshot_exists: bool,
+    pub snapshot_offset: Option<u64>,
+}
+
+/// Result of `Engine::compact_wal`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct WalCompaction {
+    /// Offset of the snapshot the WAL now starts after.
+    pub last_offset: u64,
+    pub removed_segments: usize,
+}
+
+const ASYNC_WAL_FLUSH_INTERVAL_MS: u64 = 5;
+
 struct Inner {
     config: Config,
     state: state::StateStore,
@@ -41,7 +108,26 @@ struct Inner {
     metrics: Arc<metrics::Metrics>,
     persist: Option<persist::Persist>,
     commit_lock: Mutex<()>,
+    /// Set by `drain`; writes that take the commit lock afterwards are refused.
+    draining: AtomicBool,
     shutdown: CancellationToken,
+    default_durability: Durability,
+}
+
+impl Drop for Inner {
+    fn drop(&mut self) {
+        if let Some(persist) = &self.persist {
+            if let Err(err) = persist.flush_pending() {
+                tracing::error!(error = %err, "failed to flush buffered wal events");
+                return;
+            }
+        }
+        if let Some(db) = &self.state_db {
+            if let Err(err) = db.sync() {
+                tracing::error!(error = %err, "failed to sync deferred state db commits");
+            }
+        }
+    }
 }
 
 const VECTOR_MANIFEST_PREFIX: &str = "vector:";
@@ -50,27 +136,36 @@ const VECTOR_MANIFEST_SCAN_LIMIT: usize = 4096;
 
 impl Engine {
     pub fn new(config: Config, shutdown: CancellationToken) -> anyhow::Result<Self> {
+        let default_durability = Durability::from_config(&config.default_durability)?;
         let events =
             events::EventBus::new(config.event_buffer_size, config.live_broadcast_capacity);
         let metrics = Arc::new(metrics::Metrics::default());
 
         let persist = match &config.data_dir {
-            Some(dir) => Some(
-                persist::Persist::new(
+            Some(dir) => {
+                let persist = persist::Persist::new(
                     dir,
                     config.wal_segment_max_bytes,
                     config.wal_retention_segments,
+                    persist::WalFsyncPolicy::from_config(&config.wal_fsync_policy),
                 )
-                .context("init persistence")?,
-            ),
+                .context("init persistence")?;
+                persist.trim_torn_tail().context("trim torn wal tail")?;
+                Some(persist)
+            }
             None => None,
         };
 
         let state_db = match &config.data_dir {
-            Some(dir) => Some(state_db::StateDb::open(dir).context("open state db")?),
+            Some(dir) => Some(
+                state_db::StateDb::open(dir)
+                    .context("open state db")?
+                    .with_value_compression(config.value_compress_min_bytes)
+                    .with_ttl_grace_ms(config.ttl_grace_ms),
+            ),
             None => None,
         };
-        let state = state::StateStore::new();
+        let state = state::StateStore::with_ttl_grace_ms(config.ttl_grace_ms);
         let vector_settings = VectorSettings::from_config(&config);
         let vectors = match &config.data_dir {
             Some(dir) => VectorStore::open_with_settings(dir, vector_settings.clone())
@@ -87,12 +182,26 @@ impl Engine {
             metrics,
             persist,
             commit_lock: Mutex::new(()),
+            draining: AtomicBool::new(false),
             shutdown,
+            default_durability,
         }));
 
         if engine.0.persist.is_some() {
             engine.load_from_disk().context("load from disk")?;
+            let summary = engine.summary();
+            tracing::info!(
+                vector_collections = summary.vector_collections,
+                live_vectors = summary.live_vectors,
+                state_keys = summary.state_keys,
+                offset = summary.offset,
+                "loaded data dir"
+            );
+            if config.verify_on_start {
+                engine.verify_replay().context("verify replay")?;
+            }
             engine.start_snapshot_task_if_runtime();
+            engine.start_wal_flush_task_if_runtime();
         }
         if let Err(err) = engine.expire_due_keys(10_000) {
             tracing::warn!(error = %err, "startup ttl expire failed");
@@ -106,14 +215,102 @@ impl Engine {
         self.0.shutdown.cancel();
     }
 
+    /// Stops accepting writes, waits for the commit in flight, fsyncs queued
+    /// WAL events, writes a final snapshot and dumps the HNSW graphs, then
+    /// cancels background tasks. Once it returns every acknowledged write is on disk.
+    /// Background tasks are cancelled even when a step fails.
+    pub fn drain(&self) -> Result<(), EngineError> {
+        self.0.draining.store(true, Ordering::SeqCst);
+        // Commits check `draining` under the lock, so once we hold it no new
+        // write can start and the one that was running has finished.
+        drop(self.0.commit_lock.lock());
+        let result = self.flush_wal().and_then(|_| self.snapshot_once());
+        match &result {
+            Ok(()) => {
+                // Only saves the rebuild on the next start, so a failure isn't fatal.
+                if let Err(err) = self.0.vectors.dump_hnsw_graphs() {
+                    tracing::warn!(error = %err, "hnsw graph dump on drain failed");
+                }
+            }
+            Err(err) => tracing::error!(error = %err, "drain failed to persist pending writes"),
+        }
+        self.0.shutdown.cancel();
+        result
+    }
+
+    pub fn is_draining(&self) -> bool {
+        self.0.draining.load(Ordering::SeqCst)
+    }
+
+    fn lock_commit(&self) -> Result<MutexGuard<'_, ()>, EngineError> {
+        let guard = self.0.commit_lock.lock();
+        if self.0.draining.load(Ordering::SeqCst) {
+            return Err(EngineError::ShuttingDown);
+        }
+        Ok(guard)
+    }
+
     pub fn metrics_text(&self) -> String {
-        self.0.metrics.render()
+        self.0.metrics.render(self.disk_usage())
+    }
+
+    pub fn metrics_json(&self) -> serde_json::Value {
+        self.0.metrics.render_json(self.disk_usage())
+    }
+
+    /// Sizes for the disk gauges. A segment removed while listing (rotation,
+    /// compaction) is skipped rather than failing the scrape.
+    pub fn disk_usage(&self) -> DiskUsage {
+        let Some(persist) = &self.0.persist else {
+            return DiskUsage::default();
+        };
+        let wal_bytes_total = persist
+            .list_segments()
+            .iter()
+            .filter_map(|path| std::fs::metadata(path).ok())
+            .map(|meta| meta.len())
+            .sum();
+        let vector_data_bytes_total = self
+            .0
+            .vectors
+            .list_collections()
+            .iter()
+            .map(|info| info.file_len)
+            .sum();
+        DiskUsage {
+            wal_bytes_total,
+            vector_data_bytes_total,
+        }
+    }
+
+    pub fn metrics_snapshot(&self) -> MetricsSnapshot {
+        self.0.metrics.snapshot()
+    }
+
+    /// Zeroes the metric counters and returns their values from just before.
+    pub fn reset_metrics(&self) -> MetricsSnapshot {
+        self.0.metrics.reset()
     }
 
     pub fn health(&self) -> &'static str {
         "ok"
     }
 
+    /// Counts of what the engine currently holds; logged at startup and served by `/v1/info`.
+    pub fn summary(&self) -> EngineSummary {
+        let collections = self.0.vectors.list_collections();
+        let state_keys = match &self.0.state_db {
+            Some(db) => db.key_count().unwrap_or(0),
+            None => self.0.state.key_count() as u64,
+        };
+        EngineSummary {
+            vector_collections: collections.len(),
+            live_vectors: collections.iter().map(|c| c.live_count as u64).sum(),
+            state_keys,
+            offset: self.0.events.next_offset().saturating_sub(1),
+        }
+    }
+
     pub fn events(&self) -> &events::EventBus {
         &self.0.events
     }
@@ -126,6 +323,30 @@ impl Engine {
         self.0.persist.clone()
     }
 
+    pub fn persistence_status(&self) -> Result<PersistenceStatus, EngineError> {
+        let state_applied_offset = match &self.0.state_db {
+            Some(db) => Some(db.applied_offset()?),
+            None => None,
+        };
+        let (wal_segments, snapshot_offset) = match &self.0.persist {
+            Some(persist) => (
+                persist.segment_sizes()?,
+                persist.load_snapshot()?.map(|snap| snap.last_offset),
+            ),
+            None => (Vec::new(), None),
+        };
+        Ok(PersistenceStatus {
+            persistence: self.0.persist.is_some(),
+            last_published_offset: self.0.events.last_published_offset(),
+            state_applied_offset,
+            vector_applied_offset: self.0.vectors.applied_offset(),
+            wal_bytes: wal_segments.iter().map(|seg| seg.bytes).sum(),
+            wal_segments,
+            snapshot_exists: snapshot_offset.is_some(),
+            snapshot_offset,
+        })
+    }
+
     fn load_from_disk(&self) -> anyhow::Result<()> {
         let Some(persist) = &self.0.persist else {
             return Ok(());
@@ -135,17 +356,34 @@ impl Engine {
         if let Some(db) = &self.0.state_db {
             since_offset = since_offset.max(db.applied_offset().unwrap_or(0));
         }
+        let mut checkpoint = None;
         if let Some(snapshot) = persist.load_snapshot().context("read snapshot")? {
-            self.0.events.set_next_offset(snapshot.last_offset + 1);
             since_offset = snapshot.last_offset;
+            checkpoint = snapshot.checkpoint;
         }
+        // Checked against what is on disk before replay can move the offsets.
+        let (mut gaps, mut missing) = match &checkpoint {
+            Some(checkpoint) => self.checkpoint_gaps(checkpoint)?,
+            None => (Vec::new(), Vec::new()),
+        };
+        // Nothing newer than the applied offsets may be handed out again, even when
+        // the WAL tail holds no event past them.
+        self.0.events.set_next_offset(
+            since_offset
+                .max(self.0.vectors.applied_offset())
+                .saturating_add(1),
+        );
 
         let mut applied = 0usize;
         if let Some(db) = &self.0.state_db {
             let vectors = self.0.vectors.clone();
-            let events = self.0.events.clone();
-            persist
-                .for_each_event_since(since_offset, |ev| {
+            let head = persist
+                .for_each_committed_event_since(since_offset, |ev| {
+                    if ev.event_type == "vector_collection_deleted" {
+                        if let Some(name) = ev.data.get("collection").and_then(|v| v.as_str()) {
+                            missing.retain(|m| m != name);
+                        }
+                    }
                     match ev.event_type.as_str() {
                         "state_updated" => {
                             let _ = db.apply_state_updated(&ev);
@@ -153,20 +391,41 @@ impl Engine {
                         "state_deleted" => {
                             let _ = db.apply_state_deleted(&ev);
                         }
+                        "doc_upserted" => {
+                            if let Err(err) = db.apply_doc_upserted(&ev) {
+                                tracing::error!(
+                                    error = %err,
+                                    offset = ev.offset,
+                                    "failed to replay doc_upserted"
+                                );
+                            }
+                        }
                         "vector_collection_created"
+                        | "vector_collection_deleted"
+                        | "vector_default_filter_set"
                         | "vector_added"
                         | "vector_upserted"
                         | "vector_updated"
                         | "vector_deleted" => {
-                            let _ = vectors.apply_event(&ev);
+                            if let Err(err) = vectors.apply_event(&ev) {
+                                tracing::error!(
+                                    error = %err,
+                                    offset = ev.offset,
+                                    event_type = %ev.event_type,
+                                    "failed to replay vector event"
+                                );
+                            }
                         }
                         _ => {}
                     }
-                    events.set_next_offset(ev.offset.saturating_add(1));
                     applied += 1;
                     true
                 })
                 .context("replay wal (db)")?;
+            // Past dropped events too, so their offsets are never reused.
+            if let Some(head) = head {
+                self.0.events.set_next_offset(head.saturating_add(1));
+            }
         } else {
             applied = persist
                 .replay_wal_since(since_offset, &self.0.state, &self.0.vectors, &self.0.events)
@@ -174,9 +433,146 @@ impl Engine {
         }
         tracing::info!(applied, "replayed wal events");
 
+        gaps.extend(missing.into_iter().map(|collection| {
+            format!("vector collection {collection} missing, checkpoint at offset {since_offset}")
+        }));
+        if !gaps.is_empty() {
+            for gap in &gaps {
+                tracing::error!(gap = %gap, "data behind snapshot checkpoint");
+            }
+            anyhow::bail!("data behind snapshot checkpoint: {}", gaps.join("; "));
+        }
         Ok(())
     }
 
+    /// Stores that hold fewer writes than `checkpoint` says they had, and the
+    /// checkpointed collections that are gone (fine only if the WAL tail
+    /// drops them).
+    fn checkpoint_gaps(
+        &self,
+        checkpoint: &persist::Checkpoint,
+    ) -> anyhow::Result<(Vec<String>, Vec<String>)> {
+        let mut gaps = Vec::new();
+        if let Some(db) = &self.0.state_db {
+            let applied = db.applied_offset().context("read state applied offset")?;
+            if applied < checkpoint.state_applied_offset {
+                gaps.push(format!(
+                    "state at {applied}, checkpoint has {}",
+                    checkpoint.state_applied_offset
+                ));
+            }
+        }
+        let mut missing = Vec::new();
+        for cp in &checkpoint.vectors {
+            match self.0.vectors.get_collection_info(&cp.collection) {
+                Some(info) if info.applied_offset < cp.applied_offset => gaps.push(format!(
+                    "vector collection {} at {}, checkpoint has {}",
+                    cp.collection, info.applied_offset, cp.applied_offset
+                )),
+                Some(_) => {}
+                None => missing.push(cp.collection.clone()),
+            }
+        }
+        Ok((gaps, missing))
+    }
+
+    /// Cross-checks every subsystem's applied offset against the WAL after replay.
+    /// A subsystem behind the newest event it owns, or ahead of the log head, means
+    /// replay silently dropped (or invented) events.
+    fn verify_replay(&self) -> anyhow::Result<()> {
+        let Some(persist) = &self.0.persist else {
+            return Ok(());
+        };
+        let snapshot_offset = persist
+            .load_snapshot()
+            .context("read snapshot")?
+            .map(|snap| snap.last_offset)
+            .unwrap_or(0);
+
+        let mut wal_state = 0u64;
+        let mut wal_vectors: HashMap<String, u64> = HashMap::new();
+        let wal_head = persist
+            .for_each_committed_event_since(0, |ev| {
+                match ev.event_type.as_str() {
+                    "state_updated" | "state_deleted" | "doc_upserted" => {
+                        wal_state = wal_state.max(ev.offset)
+                    }
+                    "vector_collection_deleted" => {
+                        if let Some(name) = ev.data.get("collection").and_then(|v| v.as_str()) {
+                            wal_vectors.remove(name);
+                        }
+                    }
+                    "vector_collection_created"
+                    | "vector_default_filter_set"
+                    | "vector_added"
+                    | "vector_upserted"
+                    | "vector_updated"
+                    | "vector_deleted" => {
+                        if let Some(name) = ev.data.get("collection").and_then(|v| v.as_str()) {
+                            let entry = wal_vectors.entry(name.to_string()).or_default();
+                            *entry = (*entry).max(ev.offset);
+                        }
+                    }
+                    _ => {}
+                }
+                true
+            })
+            .context("scan wal")?
+            .unwrap_or(0);
+        let log_head = wal_head.max(snapshot_offset);
+
+        let mut gaps = Vec::new();
+        let next_offset = self.0.events.next_offset();
+        if next_offset <= log_head {
+            gaps.push(format!(
+                "next event offset {next_offset} would reuse log head {log_head}"
+            ));
+        }
+        if let Some(db) = &self.0.state_db {
+            let applied = db.applied_offset().context("read state applied offset")?;
+            if applied < wal_state {
+                gaps.push(format!("state at {applied}, wal has {wal_state}"));
+            }
+            if applied > log_head {
+                gaps.push(format!("state at {applied}, ahead of log head {log_head}"));
+            }
+        }
+        let infos: HashMap<String, u64> = self
+            .0
+            .vectors
+            .list_collections()
+            .into_iter()
+            .map(|info| (info.collection, info.applied_offset))
+            .collect();
+        for (collection, wal_offset) in &wal_vectors {
+            match infos.get(collection) {
+                Some(applied) if applied < wal_offset => gaps.push(format!(
+                    "vector collection {collection} at {applied}, wal has {wal_offset}"
+                )),
+                Some(_) => {}
+                None => gaps.push(format!(
+                    "vector collection {collection} missing, wal has {wal_offset}"
+                )),
+            }
+        }
+        for (collection, applied) in &infos {
+            if *applied > log_head {
+                gaps.push(format!(
+                    "vector collection {collection} at {applied}, ahead of log head {log_head}"
+                ));
+            }
+        }
+
+        if gaps.is_empty() {
+            tracing::info!(log_head, "replay consistency check passed");
+            return Ok(());
+        }
+        for gap in &gaps {
+            tracing::error!(gap = %gap, "replay consistency check failed");
+        }
+        anyhow::bail!("replay consistency check failed: {}", gaps.join("; "))
+    }
+
     fn start_snapshot_task_if_runtime(&self) {
         if tokio::runtime::Handle::try_current().is_err() {
             return;
@@ -208,9 +604,17 @@ impl Engine {
         });
     }
 
-    fn snapshot_once(&self) -> std::io::Result<()> {
+    fn snapshot_once(&self) -> Result<(), EngineError> {
+        self.write_snapshot(self.0.config.wal_truncate_after_snapshot)
+            .map(|_| ())
+    }
+
+    /// Writes a snapshot with a checkpoint of the state db and vector
+    /// collections, then, when `truncate` is set, deletes the WAL segments
+    /// it covers. `None` without a data dir.
+    fn write_snapshot(&self, truncate: bool) -> Result<Option<WalCompaction>, EngineError> {
         let Some(persist) = &self.0.persist else {
-            return Ok(());
+            return Ok(None);
         };
         let _g = self.0.commit_lock.lock();
         loop {
@@ -223,10 +627,55 @@ impl Engine {
                 }
             }
         }
+        // Truncation below may drop WAL lines of deferred commits, so make
+        // those durable before the snapshot claims to cover them.
+        persist.flush_pending()?;
+        if let Some(db) = &self.0.state_db {
+            db.sync()?;
+        }
         let snapshot = persist::Snapshot {
             last_offset: self.0.events.last_published_offset(),
+            checkpoint: Some(self.checkpoint()?),
         };
-        persist.write_snapshot_and_rotate(&snapshot)
+        persist.write_snapshot_and_rotate(&snapshot)?;
+        let mut removed_segments = 0;
+        if truncate {
+            removed_segments = persist.truncate_below(snapshot.last_offset.saturating_add(1))?;
+            if removed_segments > 0 {
+                tracing::info!(
+                    removed = removed_segments,
+                    offset = snapshot.last_offset,
+                    "truncated wal below snapshot"
+                );
+            }
+        }
+        Ok(Some(WalCompaction {
+            last_offset: snapshot.last_offset,
+            removed_segments,
+        }))
+    }
+
+    fn checkpoint(&self) -> anyhow::Result<persist::Checkpoint> {
+        let (state_applied_offset, state_keys) = match &self.0.state_db {
+            Some(db) => (db.applied_offset()?, db.key_count()?),
+            None => (0, self.0.state.key_count() as u64),
+        };
+        let vectors = self
+            .0
+            .vectors
+            .list_collections()
+            .into_iter()
+            .map(|info| persist::VectorCheckpoint {
+                collection: info.collection,
+                applied_offset: info.applied_offset,
+                live_count: info.live_count,
+            })
+            .collect();
+        Ok(persist::Checkpoint {
+            state_applied_offset,
+            state_keys,
+            vectors,
+        })
     }
 
     pub fn force_snapshot(&self) -> Result<(), EngineError> {
@@ -234,6 +683,71 @@ impl Engine {
         Ok(())
     }
 
+    /// Reclaims WAL disk on demand: writes a checkpointed snapshot and
+    /// deletes every segment below it, whatever `WAL_TRUNCATE_AFTER_SNAPSHOT`
+    /// says. The state db and vector runs keep the data. `None` without a
+    /// data dir.
+    pub fn compact_wal(&self) -> Result<Option<WalCompaction>, EngineError> {
+        self.write_snapshot(true)
+    }
+
+    fn start_wal_flush_task_if_runtime(&self) {
+        if tokio::runtime::Handle::try_current().is_err() {
+            return;
+        }
+        let weak = Arc::downgrade(&self.0);
+        let shutdown = self.0.shutdown.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(std::time::Duration::from_millis(
+                ASYNC_WAL_FLUSH_INTERVAL_MS,
+            ));
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => {
+                        let Some(inner) = weak.upgrade() else { break };
+                        let engine = Engine(inner);
+                        let res = tokio::task::spawn_blocking(move || engine.flush_wal()).await;
+                        match res {
+                            Ok(Ok(_)) => {}
+                            Ok(Err(err)) => tracing::warn!(error = %err, "wal flush failed"),
+                            Err(err) => tracing::warn!(error = %err, "wal flush task join failed"),
+                        }
+                    }
+                    _ = shutdown.cancelled() => {
+                        let Some(inner) = weak.upgrade() else { break };
+                        if let Err(err) = Engine(inner).flush_wal() {
+                            tracing::warn!(error = %err, "final wal flush failed");
+                        }
+                        tracing::info!("wal flush task stopping");
+                        break;
+                    }
+                }
+            }
+        });
+    }
+
+    /// Fsyncs every event queued by `Durability::Async` writes, then their
+    /// state db commits; returns how many events were written.
+    pub fn flush_wal(&self) -> Result<usize, EngineError> {
+        let Some(persist) = &self.0.persist else {
+            return Ok(0);
+        };
+        let flushed = persist.flush_pending()?;
+        // Only after the WAL, so the db never holds an offset the WAL lost.
+        if let Some(db) = &self.0.state_db {
+            db.sync()?;
+        }
+        Ok(flushed)
+    }
+
+    pub fn default_durability(&self) -> Durability {
+        self.0.default_durability
+    }
+
+    pub fn max_doc_indexed_fields(&self) -> usize {
+        self.0.config.max_doc_indexed_fields
+    }
+
     fn start_ttl_task_if_runtime(&self) {
         if tokio::runtime::Handle::try_current().is_err() {
             return;
@@ -265,17 +779,66 @@ impl Engine {
     }
 
     pub fn list_state(&self, prefix: Option<&str>, limit: usize) -> Vec<state::StateItem> {
+        self.list_state_after(prefix, None, limit)
+    }
+
+    /// Keys in order, resuming after `start_after`; pass the last key of the
+    /// previous page to continue a listing.
+    pub fn list_state_after(
+        &self,
+        prefix: Option<&str>,
+        start_after: Option<&str>,
+        limit: usize,
+    ) -> Vec<state::StateItem> {
         if let Some(db) = &self.0.state_db {
-            return db.list(prefix, limit).unwrap_or_default();
+            return db.list(prefix, start_after, limit).unwrap_or_default();
         }
-        self.0.state.list(prefix, limit)
+        self.0.state.list(prefix, start_after, limit)
+    }
+
+    /// Live keys from `from` to `to`, both inclusive, in key order.
+    pub fn range_state(&self, from: &str, to: &str, limit: usize) -> Vec<state::StateItem> {
+        self.range_state_after(from, to, None, limit)
+    }
+
+    /// `range_state` resuming after `start_after`, the last key of the
+    /// previous page.
+    pub fn range_state_after(
+        &self,
+        from: &str,
+        to: &str,
+        start_after: Option<&str>,
+        limit: usize,
+    ) -> Vec<state::StateItem> {
+        if let Some(db) = &self.0.state_db {
+            return db.range(from, to, start_after, limit).unwrap_or_default();
+        }
+        self.0.state.range(from, to, start_after, limit)
+    }
+
+    pub fn list_ttl(&self, limit: usize) -> Vec<state::TtlEntry> {
+        let now = now_ms();
+        if let Some(db) = &self.0.state_db {
+            return db.ttl_entries(now, limit).unwrap_or_default();
+        }
+        self.0.state.ttl_entries(now, limit)
     }
 
     pub fn get_state(&self, key: &str) -> Option<state::StateItem> {
         if let Some(db) = &self.0.state_db {
-            return db.get_state(key).ok().flatten();
+            db.get_state(key).ok().flatten()
+        } else {
+            self.0.state.get(key)
         }
-        self.0.state.get(key)
+    }
+
+    /// Found items for `keys`, in request order; missing keys are skipped.
+    /// Reads a single consistent view of the store.
+    pub fn get_state_many(&self, keys: &[String]) -> Vec<state::StateItem> {
+        if let Some(db) = &self.0.state_db {
+            return db.get_many(keys).unwrap_or_default();
+        }
+        self.0.state.get_many(keys)
     }
 
     pub fn put_state(
@@ -285,7 +848,18 @@ impl Engine {
         ttl_ms: Option<u64>,
         if_revision: Option<u64>,
     ) -> Result<state::StateItem, EngineError> {
-        let _g = self.0.commit_lock.lock();
+        self.put_state_with_durability(key, value, ttl_ms, if_revision, self.0.default_durability)
+    }
+
+    pub fn put_state_with_durability(
+        &self,
+        key: String,
+        value: serde_json::Value,
+        ttl_ms: Option<u64>,
+        if_revision: Option<u64>,
+        durability: Durability,
+    ) -> Result<state::StateItem, EngineError> {
+        let _g = self.lock_commit()?;
 
         let now = now_ms();
         let expires_at_ms = ttl_ms.map(|ttl| now.saturating_add(ttl));
@@ -305,10 +879,19 @@ impl Engine {
         let key = event_data["key"].as_str().unwrap_or_default().to_string();
         let event = self.0.events.next_record("state_updated", event_data);
         if let Some(persist) = &self.0.persist {
-            persist.append_event(&event)?;
+            match durability {
+                Durability::Sync => persist.append_event(&event)?,
+                Durability::Async => persist.buffer_event(&event)?,
+            }
         }
         if let Some(db) = &self.0.state_db {
-            db.apply_state_updated(&event)?;
+            // A later durable commit also persists a deferred one, but every
+            // durable path appends to the WAL first, which writes the
+            // buffered lines ahead of it.
+            match durability {
+                Durability::Sync => db.apply_state_updated(&event)?,
+                Durability::Async => db.apply_state_updated_deferred(&event)?,
+            }
         }
         self.0.events.publish_record(event.clone());
         self.metrics().inc_events();
@@ -325,17 +908,163 @@ impl Engine {
         Ok(item)
     }
 
+    /// Adds `delta` to the integer stored at `key` under the commit lock, so
+    /// concurrent increments never lose an update. A missing key or a value
+    /// that isn't an integer counts as 0. `ttl_ms` sets a new expiry; without
+    /// it the key keeps the one it had.
+    pub fn increment_state(
+        &self,
+        key: &str,
+        delta: i64,
+        ttl_ms: Option<u64>,
+    ) -> Result<state::StateItem, EngineError> {
+        let _g = self.lock_commit()?;
+
+        let revision = if let Some(db) = &self.0.state_db {
+            db.prepare_put_revision(key, None)?
+        } else {
+            self.0.state.prepare_put_revision(key, None)?
+        };
+        // Revision 1 means there is no live entry; a key still readable
+        // through the TTL grace starts over like a missing one.
+        let current = if revision > 1 {
+            if let Some(db) = &self.0.state_db {
+                db.get_state(key)?
+            } else {
+                self.0.state.get(key)
+            }
+        } else {
+            None
+        };
+        let (base, current_expiry) = current
+            .map(|item| (item.value.as_i64().unwrap_or(0), item.expires_at_ms))
+            .unwrap_or((0, None));
+        let value = serde_json::json!(base.saturating_add(delta));
+        let expires_at_ms = match ttl_ms {
+            Some(ttl) => Some(now_ms().saturating_add(ttl)),
+            None => current_expiry,
+        };
+        self.commit_state_put(key, value, revision, expires_at_ms)
+    }
+
+    /// Pushes the expiry of a live key to `ttl_ms` from now, keeping its
+    /// value and bumping its revision. `None` when the key is missing or has
+    /// already expired.
+    pub fn touch_state(
+        &self,
+        key: &str,
+        ttl_ms: u64,
+    ) -> Result<Option<state::StateItem>, EngineError> {
+        let _g = self.lock_commit()?;
+
+        let now = now_ms();
+        let current = if let Some(db) = &self.0.state_db {
+            db.get_state(key)?
+        } else {
+            self.0.state.get(key)
+        };
+        let Some(current) = current.filter(|item| item.expires_at_ms.is_none_or(|at| at > now))
+        else {
+            return Ok(None);
+        };
+        let revision = if let Some(db) = &self.0.state_db {
+            db.prepare_put_revision(key, None)?
+        } else {
+            self.0.state.prepare_put_revision(key, None)?
+        };
+        let expires_at_ms = Some(now.saturating_add(ttl_ms));
+        self.commit_state_put(key, current.value, revision, expires_at_ms)
+            .map(Some)
+    }
+
+    /// Logs, applies and publishes a `state_updated` event for a revision
+    /// already reserved by the caller, who holds the commit lock.
+    fn commit_state_put(
+        &self,
+        key: &str,
+        value: serde_json::Value,
+        revision: u64,
+        expires_at_ms: Option<u64>,
+    ) -> Result<state::StateItem, EngineError> {
+        let event = self.0.events.next_record(
+            "state_updated",
+            serde_json::json!({
+                "key": key,
+                "revision": revision,
+                "value": value,
+                "expires_at_ms": expires_at_ms,
+            }),
+        );
+        if let Some(persist) = &self.0.persist {
+            persist.append_event(&event)?;
+        }
+        if let Some(db) = &self.0.state_db {
+            db.apply_state_updated(&event)?;
+        }
+        self.0.events.publish_record(event);
+        self.metrics().inc_events();
+        self.metrics().inc_state_put();
+
+        let item = if let Some(db) = &self.0.state_db {
+            db.get_state(key)?
+                .ok_or_else(|| anyhow::anyhow!("state missing after write"))?
+        } else {
+            self.0
+                .state
+                .apply_put_with_revision(key.to_string(), value, revision, expires_at_ms)
+        };
+        Ok(item)
+    }
+
     pub fn delete_state(&self, key: &str) -> Result<bool, EngineError> {
-        self.delete_state_with_reason(key, "explicit")
+        self.delete_state_with_reason(key, "explicit", None)
     }
 
+    /// Deletes `key`, recording `reason` in the event. With `if_revision` the
+    /// delete only happens while the key is live at that revision; otherwise
+    /// it fails with `RevisionMismatch`.
     pub fn delete_state_with_reason(
         &self,
         key: &str,
         reason: &'static str,
+        if_revision: Option<u64>,
     ) -> Result<bool, EngineError> {
-        let _g = self.0.commit_lock.lock();
+        let _g = self.lock_commit()?;
+        self.delete_state_locked(key, reason, if_revision)
+    }
+
+    /// Deletes up to `limit` keys starting with `prefix` in key order, one
+    /// `state_deleted` event each with reason `prefix`, under a single hold
+    /// of the commit lock. Returns how many were deleted and whether matching
+    /// keys remain for another call.
+    pub fn delete_prefix(&self, prefix: &str, limit: usize) -> Result<(usize, bool), EngineError> {
+        let _g = self.lock_commit()?;
+        let mut keys = self.list_state(Some(prefix), limit.saturating_add(1));
+        let more = keys.len() > limit;
+        keys.truncate(limit);
+        let mut deleted = 0;
+        for item in keys {
+            if self.delete_state_locked(&item.key, "prefix", None)? {
+                deleted += 1;
+            }
+        }
+        Ok((deleted, more))
+    }
 
+    /// Body of `delete_state_with_reason`; the caller holds the commit lock.
+    fn delete_state_locked(
+        &self,
+        key: &str,
+        reason: &'static str,
+        if_revision: Option<u64>,
+    ) -> Result<bool, EngineError> {
+        if let Some(expected) = if_revision {
+            if let Some(db) = &self.0.state_db {
+                db.check_revision(key, expected)?;
+            } else {
+                self.0.state.check_revision(key, expected)?;
+            }
+        }
         let exists = if let Some(db) = &self.0.state_db {
             db.exists_live(key)?
         } else {
@@ -370,6 +1099,289 @@ impl Engine {
         Ok(deleted)
     }
 
+    /// Applies `ops` all-or-nothing. Every precondition (`if_revision`,
+    /// `if_not_exists`) is checked under the commit lock, against the store as
+    /// changed by the earlier ops, before any event is created; a failing one
+    /// returns its error with nothing written or published. Returns the new
+    /// item of each put, in op order. Deletes of missing keys are no-ops.
+    pub fn transact_state(
+        &self,
+        ops: Vec<state::StateOp>,
+    ) -> Result<Vec<state::StateItem>, EngineError> {
+        self.transact_state_with(|| ops)
+    }
+
+    /// `transact_state` with the ops built by `plan`, which runs under the
+    /// commit lock and must only read, so no other write lands between what
+    /// it reads and what the transaction writes.
+    pub fn transact_state_with<F>(&self, plan: F) -> Result<Vec<state::StateItem>, EngineError>
+    where
+        F: FnOnce() -> Vec<state::StateOp>,
+    {
+        let _g = self.lock_commit()?;
+        let ops = plan();
+
+        let now = now_ms();
+        let mut staged: HashMap<String, Option<u64>> = HashMap::new();
+        let mut planned: Vec<(&'static str, serde_json::Value, Option<state::StateItem>)> =
+            Vec::with_capacity(ops.len());
+        for op in ops {
+            let key = op.key().to_string();
+            let current = match staged.get(&key) {
+                Some(revision) => *revision,
+                None => self.live_state_revision(&key)?,
+            };
+            match op {
+                state::StateOp::Put {
+                    value,
+                    ttl_ms,
+                    if_revision,
+                    if_not_exists,
+                    ..
+                } => {
+                    if if_revision.is_some_and(|expected| current.unwrap_or(0) != expected) {
+                        return Err(state::StateError::RevisionMismatch.into());
+                    }
+                    if if_not_exists && current.is_some() {
+                        return Err(state::StateError::AlreadyExists.into());
+                    }
+                    let revision = current.unwrap_or(0).saturating_add(1);
+                    let expires_at_ms = ttl_ms.map(|ttl| now.saturating_add(ttl));
+                    staged.insert(key.clone(), Some(revision));
+                    let data = serde_json::json!({
+                        "key": key,
+                        "revision": revision,
+                        "value": value,
+                        "expires_at_ms": expires_at_ms,
+                    });
+                    let item = state::StateItem {
+                        key,
+                        value,
+                        revision,
+                        expires_at_ms,
+                    };
+                    planned.push(("state_updated", data, Some(item)));
+                }
+                state::StateOp::Delete { if_revision, .. } => {
+                    if if_revision.is_some_and(|expected| current.unwrap_or(0) != expected) {
+                        return Err(state::StateError::RevisionMismatch.into());
+                    }
+                    if current.is_none() {
+                        continue;
+                    }
+                    staged.insert(key.clone(), None);
+                    let data = serde_json::json!({
+                        "key": key,
+                        "reason": "txn",
+                    });
+                    planned.push(("state_deleted", data, None));
+                }
+            }
+        }
+
+        // Replay applies the events only once it has read all `txn_len`.
+        let txn_len = planned.len();
+        let events: Vec<(EventRecord, Option<state::StateItem>)> = planned
+            .into_iter()
+            .enumerate()
+            .map(|(seq, (event_type, mut data, item))| {
+                data["txn_seq"] = serde_json::json!(seq);
+                data["txn_len"] = serde_json::json!(txn_len);
+                (self.0.events.next_record(event_type, data), item)
+            })
+            .collect();
+        if let Some(persist) = &self.0.persist {
+            let records: Vec<EventRecord> = events.iter().map(|(ev, _)| ev.clone()).collect();
+            persist.append_events(&records)?;
+        }
+        let mut items = Vec::new();
+        for (event, item) in events {
+            match item {
+                Some(item) => {
+                    if let Some(db) = &self.0.state_db {
+                        db.apply_state_updated(&event)?;
+                    } else {
+                        self.0.state.apply_put_with_revision(
+                            item.key.clone(),
+                            item.value.clone(),
+                            item.revision,
+                            item.expires_at_ms,
+                        );
+                    }
+                    self.metrics().inc_state_put();
+                    items.push(item);
+                }
+                None => {
+                    if let Some(db) = &self.0.state_db {
+                        db.apply_state_deleted(&event)?;
+                    } else if let Some(key) = event.data["key"].as_str() {
+                        self.0.state.delete(key);
+                    }
+                    self.metrics().inc_state_delete();
+                }
+            }
+            self.0.events.publish_record(event);
+            self.metrics().inc_events();
+        }
+        Ok(items)
+    }
+
+    /// Revision of `key` if it is live, ignoring the TTL grace like the
+    /// `if_revision` checks do.
+    fn live_state_revision(&self, key: &str) -> Result<Option<u64>, EngineError> {
+        let next = if let Some(db) = &self.0.state_db {
+            db.prepare_put_revision(key, None)?
+        } else {
+            self.0.state.prepare_put_revision(key, None)?
+        };
+        Ok((next > 1).then(|| next - 1))
+    }
+
+    /// Moves `from` to `to` under one commit: `to` gets the value and TTL of `from`
+    /// with a fresh revision, then `from` is deleted. Renaming a key onto itself is
+    /// rejected as `AlreadyExists`.
+    pub fn rename_state(
+        &self,
+        from: &str,
+        to: &str,
+        overwrite: bool,
+    ) -> Result<state::StateItem, EngineError> {
+        let _g = self.lock_commit()?;
+
+        let current = if let Some(db) = &self.0.state_db {
+            db.get_state(from)?
+        } else {
+            self.0.state.get(from)
+        };
+        let Some(current) = current else {
+            return Err(state::StateError::NotFound.into());
+        };
+        let target_exists = if let Some(db) = &self.0.state_db {
+            db.get_state(to)?.is_some()
+        } else {
+            self.0.state.get(to).is_some()
+        };
+        if from == to || (target_exists && !overwrite) {
+            return Err(state::StateError::AlreadyExists.into());
+        }
+
+        let revision = if let Some(db) = &self.0.state_db {
+            db.prepare_put_revision(to, None)?
+        } else {
+            self.0.state.prepare_put_revision(to, None)?
+        };
+        let put = self.0.events.next_record(
+            "state_updated",
+            serde_json::json!({
+                "key": to,
+                "revision": revision,
+                "value": current.value,
+                "expires_at_ms": current.expires_at_ms,
+                "txn_seq": 0,
+                "txn_len": 2,
+            }),
+        );
+        let delete = self.0.events.next_record(
+            "state_deleted",
+            serde_json::json!({
+                "key": from,
+                "reason": "rename",
+                "txn_seq": 1,
+                "txn_len": 2,
+            }),
+        );
+        // One write, tagged like `transact_state`, so replay drops a torn pair.
+        if let Some(persist) = &self.0.persist {
+            persist.append_events(&[put.clone(), delete.clone()])?;
+        }
+        let item = if let Some(db) = &self.0.state_db {
+            db.apply_state_updated(&put)?;
+            db.apply_state_deleted(&delete)?;
+            db.get_state(to)?
+                .ok_or_else(|| anyhow::anyhow!("state missing after rename"))?
+        } else {
+            let item = self.0.state.apply_put_with_revision(
+                to.to_string(),
+                current.value,
+                revision,
+                current.expires_at_ms,
+            );
+            self.0.state.delete(from);
+            item
+        };
+        self.0.events.publish_record(put);
+        self.0.events.publish_record(delete);
+        self.metrics().inc_events();
+        self.metrics().inc_events();
+        self.metrics().inc_state_put();
+        self.metrics().inc_state_delete();
+        Ok(item)
+    }
+
+    /// Writes a document and the index changes `plan` derives from its previous
+    /// version as one `doc_upserted` event, so a crash can never persist one
+    /// without the other. `plan` runs under the commit lock and must only read.
+    pub fn commit_doc_upsert<F>(
+        &self,
+        collection: &str,
+        id: &str,
+        key: String,
+        value: serde_json::Value,
+        plan: F,
+    ) -> Result<state::StateItem, EngineError>
+    where
+        F: FnOnce(Option<&state::StateItem>) -> state::IndexChanges,
+    {
+        let _g = self.lock_commit()?;
+
+        let previous = self.get_state(&key);
+        let changes = plan(previous.as_ref());
+        let prepare = |key: &str| -> Result<u64, EngineError> {
+            Ok(match &self.0.state_db {
+                Some(db) => db.prepare_put_revision(key, None)?,
+                None => self.0.state.prepare_put_revision(key, None)?,
+            })
+        };
+        let revision = prepare(&key)?;
+        let mut index_puts = Vec::with_capacity(changes.puts.len());
+        for (key, value) in changes.puts {
+            index_puts.push(state::IndexPut {
+                revision: prepare(&key)?,
+                key,
+                value,
+            });
+        }
+        let payload = state::DocUpserted {
+            collection: collection.to_string(),
+            id: id.to_string(),
+            key,
+            revision,
+            value,
+            index_puts,
+            index_deletes: changes.deletes,
+        };
+
+        let data = serde_json::to_value(&payload).map_err(anyhow::Error::from)?;
+        let event = self.0.events.next_record("doc_upserted", data);
+        if let Some(persist) = &self.0.persist {
+            persist.append_event(&event)?;
+        }
+        if let Some(db) = &self.0.state_db {
+            db.apply_doc_upserted(&event)?;
+        } else {
+            self.0.state.apply_doc_upserted(&payload);
+        }
+        self.0.events.publish_record(event);
+        self.metrics().inc_events();
+        self.metrics().inc_state_put();
+        Ok(state::StateItem {
+            key: payload.key,
+            value: payload.value,
+            revision,
+            expires_at_ms: None,
+        })
+    }
+
     pub fn vectors(&self) -> &VectorStore {
         &self.0.vectors
     }
@@ -430,6 +1442,7 @@ impl Engine {
                     updated_at_ms: meta.updated_at_ms,
                     segments: None,
                     deleted_count: None,
+                    norm_stats: None,
                 },
             );
         }
@@ -471,20 +1484,43 @@ impl Engine {
         dim: usize,
         metric: Metric,
     ) -> Result<(), EngineError> {
-        let _g = self.0.commit_lock.lock();
+        self.create_vector_collection_with_hnsw(collection, dim, metric, None)
+    }
+
+    /// `hnsw` is recorded in the event only when given, so replay of older
+    /// logs and collections created without it keep the default parameters.
+    pub fn create_vector_collection_with_hnsw(
+        &self,
+        collection: &str,
+        dim: usize,
+        metric: Metric,
+        hnsw: Option<HnswParams>,
+    ) -> Result<(), EngineError> {
+        if dim == 0 {
+            return Err(VectorError::DimMismatch.into());
+        }
+        let _g = self.lock_commit()?;
         if self.0.vectors.get_collection(collection).is_some() {
             return Err(VectorError::CollectionExists.into());
         }
-        let data = serde_json::json!({
+        let mut data = serde_json::json!({
             "collection": collection,
             "dim": dim,
             "metric": metric,
         });
+        if let Some(hnsw) = hnsw {
+            data["hnsw"] = serde_json::json!(hnsw);
+        }
         let event = self.0.events.next_record("vector_collection_created", data);
         if let Some(persist) = &self.0.persist {
             persist.append_event(&event)?;
         }
-        self.0.vectors.create_collection(collection, dim, metric)?;
+        self.0.vectors.create_collection_with_hnsw(
+            collection,
+            dim,
+            metric,
+            hnsw.unwrap_or_default(),
+        )?;
         self.0.vectors.apply_event(&event)?;
         self.0.events.publish_record(event);
         self.metrics().inc_events();
@@ -500,13 +1536,41 @@ impl Engine {
         Ok(())
     }
 
+    /// Drops the collection and its files; the `vector_collection_deleted` event
+    /// makes replay drop it again if the process dies before the directory is gone.
+    pub fn drop_vector_collection(&self, collection: &str) -> Result<(), EngineError> {
+        let _g = self.lock_commit()?;
+        if self.0.vectors.get_collection(collection).is_none() {
+            return Err(VectorError::CollectionNotFound.into());
+        }
+        let data = serde_json::json!({ "collection": collection });
+        let event = self.0.events.next_record("vector_collection_deleted", data);
+        if let Some(persist) = &self.0.persist {
+            persist.append_event(&event)?;
+        }
+        self.0.vectors.apply_event(&event)?;
+        self.0.events.publish_record(event);
+        self.metrics().inc_events();
+        self.metrics().inc_vector_op();
+        self.metrics().forget_vector_collection(collection);
+        drop(_g);
+        if let Err(err) = self.delete_state(&vector_manifest_key(collection)) {
+            tracing::warn!(
+                error = %err,
+                collection,
+                "failed to delete vector manifest metadata"
+            );
+        }
+        Ok(())
+    }
+
     pub fn vector_add(
         &self,
         collection: &str,
         id: &str,
         item: VectorItem,
     ) -> Result<(), EngineError> {
-        let _g = self.0.commit_lock.lock();
+        let _g = self.lock_commit()?;
         let _ = self
             .0
             .vectors
@@ -515,11 +1579,14 @@ impl Engine {
         if self.0.vectors.get(collection, id)?.is_some() {
             return Err(VectorError::IdExists.into());
         }
+        self.ensure_vector_capacity(collection)?;
         let data = serde_json::json!({
             "collection": collection,
             "id": id,
             "vector": item.vector.clone(),
             "meta": item.meta.clone(),
+            "revision": 1,
+            "expires_at_ms": item.expires_at_ms,
         });
         let event = self.0.events.next_record("vector_added", data);
         if let Some(persist) = &self.0.persist {
@@ -532,23 +1599,74 @@ impl Engine {
         Ok(())
     }
 
+    /// Checked before a new id lands in `collection`; overwrites never count.
+    fn ensure_vector_capacity(&self, collection: &str) -> Result<(), VectorError> {
+        let max = self.0.config.max_vectors_per_collection;
+        if max == 0 {
+            return Ok(());
+        }
+        let live = self
+            .0
+            .vectors
+            .get_collection_info(collection)
+            .map(|info| info.live_count)
+            .unwrap_or(0);
+        if live >= max {
+            return Err(VectorError::CollectionFull);
+        }
+        Ok(())
+    }
+
     pub fn vector_upsert(
         &self,
         collection: &str,
         id: &str,
         item: VectorItem,
+        if_revision: Option<u64>,
     ) -> Result<(), EngineError> {
-        let _g = self.0.commit_lock.lock();
-        let _ = self
+        self.vector_upsert_with_options(collection, id, item, if_revision, false)
+            .map(|_| ())
+    }
+
+    /// `vector_upsert` reporting whether the id was created. With
+    /// `only_if_changed`, an item with the same content as the stored one is
+    /// skipped: no event, no revision bump and no reinsert into the segments.
+    pub fn vector_upsert_with_options(
+        &self,
+        collection: &str,
+        id: &str,
+        item: VectorItem,
+        if_revision: Option<u64>,
+        only_if_changed: bool,
+    ) -> Result<VectorUpsertOutcome, EngineError> {
+        let _g = self.lock_commit()?;
+        let (_, metric) = self
             .0
             .vectors
             .get_collection(collection)
             .ok_or(VectorError::CollectionNotFound)?;
+        let current = self.0.vectors.get(collection, id)?;
+        let revision = next_vector_revision(current.as_ref(), if_revision)?;
+        if only_if_changed
+            && current
+                .as_ref()
+                .is_some_and(|stored| stored.same_content(&item, metric))
+        {
+            return Ok(VectorUpsertOutcome {
+                created: false,
+                changed: false,
+            });
+        }
+        if current.is_none() {
+            self.ensure_vector_capacity(collection)?;
+        }
         let data = serde_json::json!({
             "collection": collection,
             "id": id,
             "vector": item.vector.clone(),
             "meta": item.meta.clone(),
+            "revision": revision,
+            "expires_at_ms": item.expires_at_ms,
         });
         let event = self.0.events.next_record("vector_upserted", data);
         if let Some(persist) = &self.0.persist {
@@ -558,7 +1676,10 @@ impl Engine {
         self.0.events.publish_record(event);
         self.metrics().inc_events();
         self.metrics().inc_vector_op();
-        Ok(())
+        Ok(VectorUpsertOutcome {
+            created: current.is_none(),
+            changed: true,
+        })
     }
 
     pub fn vector_update(
@@ -567,8 +1688,9 @@ impl Engine {
         id: &str,
         vector: Option<Vec<f32>>,
         meta: Option<serde_json::Value>,
+        if_revision: Option<u64>,
     ) -> Result<(), EngineError> {
-        let _g = self.0.commit_lock.lock();
+        let _g = self.lock_commit()?;
         let _ = self
             .0
             .vectors
@@ -579,6 +1701,7 @@ impl Engine {
             .vectors
             .get(collection, id)?
             .ok_or(VectorError::IdNotFound)?;
+        let revision = next_vector_revision(Some(&current), if_revision)?;
         let new_vec = vector.unwrap_or(current.vector);
         let new_meta = meta.unwrap_or(current.meta);
         let data = serde_json::json!({
@@ -586,6 +1709,8 @@ impl Engine {
             "id": id,
             "vector": new_vec.clone(),
             "meta": new_meta.clone(),
+            "revision": revision,
+            "expires_at_ms": current.expires_at_ms,
         });
         let event = self.0.events.next_record("vector_updated", data);
         if let Some(persist) = &self.0.persist {
@@ -599,7 +1724,7 @@ impl Engine {
     }
 
     pub fn vector_delete(&self, collection: &str, id: &str) -> Result<(), EngineError> {
-        let _g = self.0.commit_lock.lock();
+        let _g = self.lock_commit()?;
         let _ = self
             .0
             .vectors
@@ -623,6 +1748,41 @@ impl Engine {
         Ok(())
     }
 
+    /// Sets (or clears, with `None`) the filter ANDed into every search on
+    /// `collection`. It goes through the event log like any other write so
+    /// replay restores it.
+    pub fn set_vector_default_filter(
+        &self,
+        collection: &str,
+        filter: Option<serde_json::Value>,
+    ) -> Result<(), EngineError> {
+        let _g = self.lock_commit()?;
+        let _ = self
+            .0
+            .vectors
+            .get_collection(collection)
+            .ok_or(VectorError::CollectionNotFound)?;
+        let data = serde_json::json!({
+            "collection": collection,
+            "filter": filter,
+        });
+        let event = self.0.events.next_record("vector_default_filter_set", data);
+        if let Some(persist) = &self.0.persist {
+            persist.append_event(&event)?;
+        }
+        self.0.vectors.apply_event(&event)?;
+        self.0.events.publish_record(event);
+        self.metrics().inc_events();
+        Ok(())
+    }
+
+    pub fn vector_default_filter(
+        &self,
+        collection: &str,
+    ) -> Result<Option<serde_json::Value>, VectorError> {
+        self.0.vectors.default_filter(collection)
+    }
+
     pub fn vector_compact_collection(&self, collection: &str) -> Result<bool, EngineError> {
         let _ = self
             .0
@@ -635,6 +1795,45 @@ impl Engine {
             .compact_collection_with_options(collection, false)?)
     }
 
+    /// Rewrites the collection's runs without tombstones and rebuilds its
+    /// index. Holds the commit lock throughout, so writes to any collection
+    /// wait for it while searches keep running.
+    pub fn vector_vacuum_collection(
+        &self,
+        collection: &str,
+    ) -> Result<VectorCollectionInfo, EngineError> {
+        let _g = self.lock_commit()?;
+        if self.0.persist.is_none() {
+            return Err(VectorError::UnsupportedOperation.into());
+        }
+        self.0.vectors.vacuum_collection(collection)?;
+        self.0
+            .vectors
+            .get_collection_info(collection)
+            .ok_or_else(|| VectorError::CollectionNotFound.into())
+    }
+
+    /// Rebuilds the collection's index from its stored vectors under the
+    /// commit lock and returns the resulting stats.
+    pub fn vector_reindex_collection(
+        &self,
+        collection: &str,
+    ) -> Result<CollectionStats, EngineError> {
+        let _g = self.lock_commit()?;
+        self.0.vectors.rebuild_collection_index(collection)?;
+        self.0
+            .vectors
+            .collection_stats(collection)
+            .ok_or_else(|| VectorError::CollectionNotFound.into())
+    }
+
+    /// Rebuilds the collection's tombstone-heavy HNSW segments under the
+    /// commit lock; returns how many were rebuilt.
+    pub fn vector_merge_segments(&self, collection: &str) -> Result<usize, EngineError> {
+        let _g = self.lock_commit()?;
+        Ok(self.0.vectors.merge_segments(collection)?)
+    }
+
     pub fn vector_force_compact_collection(&self, collection: &str) -> Result<bool, EngineError> {
         let _ = self
             .0
@@ -709,13 +1908,100 @@ impl Engine {
         self.0.vectors.get(collection, id)
     }
 
+    pub fn vector_get_many(
+        &self,
+        collection: &str,
+        ids: &[String],
+    ) -> Result<HashMap<String, VectorItem>, VectorError> {
+        self.0.vectors.get_many(collection, ids)
+    }
+
+    pub fn vector_collection_stats(&self, collection: &str) -> Option<CollectionStats> {
+        self.0.vectors.collection_stats(collection)
+    }
+
+    pub fn vector_matches_filter(
+        &self,
+        collection: &str,
+        id: &str,
+        filter: Option<&serde_json::Value>,
+    ) -> Result<Option<bool>, VectorError> {
+        self.0.vectors.matches_filter(collection, id, filter)
+    }
+
     pub fn vector_search(
         &self,
         collection: &str,
         req: SearchRequest,
     ) -> Result<Vec<SearchHit>, VectorError> {
+        self.vector_search_detailed(collection, req)
+            .map(|out| out.hits)
+    }
+
+    pub fn vector_search_batch(
+        &self,
+        collection: &str,
+        reqs: Vec<SearchRequest>,
+    ) -> Result<Vec<Vec<SearchHit>>, VectorError> {
+        self.metrics().add_vector_ops(reqs.len() as u64);
+        let outcomes = self.0.vectors.search_batch(collection, reqs)?;
+        let skips = outcomes.iter().map(|out| out.tombstone_skips).sum();
+        self.metrics().add_tombstone_skips(skips);
+        Ok(outcomes.into_iter().map(|out| out.hits).collect())
+    }
+
+    pub fn vector_search_detailed(
+        &self,
+        collection: &str,
+        req: SearchRequest,
+    ) -> Result<SearchOutcome, VectorError> {
+        self.metrics().inc_vector_op();
+        let started = std::time::Instant::now();
+        let outcome = self.0.vectors.search_detailed(collection, req)?;
+        self.metrics()
+            .observe_vector_search(collection, started.elapsed());
+        self.metrics().add_tombstone_skips(outcome.tombstone_skips);
+        let lock_wait_us = outcome.stats.lock_wait_us;
+        self.metrics()
+            .observe_search_lock_wait(std::time::Duration::from_micros(lock_wait_us));
+        let warn_ms = self.0.config.lock_wait_warn_ms;
+        if warn_ms > 0 && lock_wait_us > warn_ms.saturating_mul(1000) {
+            tracing::warn!(
+                collection,
+                lock_wait_ms = lock_wait_us / 1000,
+                "vector search waited on the collections lock; check for a concurrent vacuum or snapshot"
+            );
+        }
+        Ok(outcome)
+    }
+
+    pub fn vector_search_multi(
+        &self,
+        collections: &[String],
+        req: SearchRequest,
+    ) -> Result<Vec<CollectionHit>, VectorError> {
         self.metrics().inc_vector_op();
-        self.0.vectors.search(collection, req)
+        self.0.vectors.search_multi(collections, req)
+    }
+
+    pub fn vector_knn_graph(
+        &self,
+        collection: &str,
+        ids: &[String],
+        k: usize,
+    ) -> Result<Vec<KnnEdge>, VectorError> {
+        self.metrics().add_vector_ops(ids.len() as u64);
+        self.0.vectors.knn_graph(collection, ids, k)
+    }
+
+    pub fn vector_exists_similar(
+        &self,
+        collection: &str,
+        vector: Vec<f32>,
+        threshold: f32,
+    ) -> Result<Option<(String, f32)>, VectorError> {
+        self.metrics().inc_vector_op();
+        self.0.vectors.exists_similar(collection, vector, threshold)
     }
 
     fn expire_due_keys(&self, limit: usize) -> Result<usize, EngineError> {
@@ -760,13 +2046,76 @@ impl Engine {
             self.metrics().inc_state_delete();
             expired += 1;
         }
+        for (collection, id) in self
+            .0
+            .vectors
+            .expired_vectors(now, limit.saturating_sub(expired))
+        {
+            let data = serde_json::json!({
+                "collection": collection,
+                "id": id,
+                "reason": "ttl",
+            });
+            let event = self.0.events.next_record("vector_deleted", data);
+            if let Some(persist) = &self.0.persist {
+                persist.append_event(&event)?;
+            }
+            self.0.vectors.apply_event(&event)?;
+            self.0.events.publish_record(event);
+            self.metrics().inc_events();
+            self.metrics().inc_vector_op();
+            expired += 1;
+        }
         Ok(expired)
     }
 }
 
 pub use events::{EventBus, EventRecord};
-pub use metrics::Metrics;
-pub use state::{StateError, StateItem};
+pub use metrics::{DiskUsage, Metrics, MetricsSnapshot};
+pub use persist::{Checkpoint, VectorCheckpoint, WalFsyncPolicy, WalSegment};
+pub use state::{DocUpserted, IndexChanges, IndexPut, StateError, StateItem, StateOp, TtlEntry};
+
+/// Writes every event still in the WAL under `data_dir` with an offset above
+/// `since` to `out`, one JSON record per line, and returns how many were written.
+/// Works on the files alone, so it can run while no server is up.
+pub fn dump_events(
+    data_dir: impl AsRef<std::path::Path>,
+    since: u64,
+    out: &mut impl std::io::Write,
+) -> std::io::Result<u64> {
+    let data_dir = data_dir.as_ref();
+    if !data_dir.is_dir() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("{} is not a directory", data_dir.display()),
+        ));
+    }
+    // Segment sizing only matters for appends, which never happen here; nor
+    // does the torn-tail trim, so a live server's segment is left alone.
+    let persist = persist::Persist::new(data_dir, 0, 1, persist::WalFsyncPolicy::None)?;
+    let mut written = 0;
+    let mut write_err = None;
+    persist.for_each_event_since(since, |event| {
+        let res = serde_json::to_writer(&mut *out, &event)
+            .map_err(std::io::Error::from)
+            .and_then(|()| out.write_all(b"\n"));
+        match res {
+            Ok(()) => {
+                written += 1;
+                true
+            }
+            Err(err) => {
+                write_err = Some(err);
+                false
+            }
+        }
+    })?;
+    if let Some(err) = write_err {
+        return Err(err);
+    }
+    out.flush()?;
+    Ok(written)
+}
 
 fn now_ms() -> u64 {
     let dur = std::time::SystemTime::now()
@@ -775,6 +2124,20 @@ fn now_ms() -> u64 {
     dur.as_millis() as u64
 }
 
+/// Same contract as state `if_revision`: a missing id never matches an expected revision.
+fn next_vector_revision(
+    current: Option<&VectorItem>,
+    if_revision: Option<u64>,
+) -> Result<u64, VectorError> {
+    let current_revision = current.map(|item| item.revision);
+    if let Some(expected) = if_revision {
+        if current_revision != Some(expected) {
+            return Err(VectorError::RevisionMismatch);
+        }
+    }
+    Ok(current_revision.unwrap_or(0).saturating_add(1))
+}
+
 fn vector_manifest_key(collection: &str) -> String {
     format!("{VECTOR_MANIFEST_PREFIX}{collection}{VECTOR_MANIFEST_SUFFIX}")
 }
@@ -4,24 +4,100 @@ use crate::vector::VectorStore;
 use parking_lot::Mutex;
 use serde::{Deserialize, Serialize};
 use std::fs::{File, OpenOptions};
-use std::io::{BufRead, BufReader, Write};
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
 #[derive(Clone)]
 pub struct Persist(Arc<Inner>);
 
+/// What a WAL or snapshot write waits for before returning.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum WalFsyncPolicy {
+    /// `sync_data`: the bytes are on disk, metadata only as far as needed
+    /// to read them back.
+    #[default]
+    Data,
+    /// `sync_all` on the file, plus the directory whenever a segment or the
+    /// snapshot is created or renamed, so the new names survive a crash too.
+    Full,
+    /// No fsync; the OS flushes when it likes. A crash can lose the last
+    /// writes even after they were acknowledged.
+    None,
+}
+
+impl WalFsyncPolicy {
+    pub fn from_config(value: &str) -> Self {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "data" => Self::Data,
+            "full" => Self::Full,
+            "none" => Self::None,
+            other => {
+                tracing::warn!(value = other, "unknown WAL_FSYNC_POLICY; using data");
+                Self::Data
+            }
+        }
+    }
+
+    fn sync_file(self, file: &File) -> std::io::Result<()> {
+        match self {
+            Self::Data => file.sync_data(),
+            Self::Full => file.sync_all(),
+            Self::None => Ok(()),
+        }
+    }
+
+    fn sync_dir(self, dir: &Path) -> std::io::Result<()> {
+        // Directories can't be opened for syncing on Windows.
+        if cfg!(unix) && self == Self::Full {
+            File::open(dir)?.sync_all()?;
+        }
+        Ok(())
+    }
+}
+
 struct Inner {
     dir: PathBuf,
+    fsync: WalFsyncPolicy,
     wal_lock: Mutex<()>,
     segment_max_bytes: u64,
     retention_segments: usize,
     current_segment: Mutex<u64>,
+    pending: Mutex<Vec<Vec<u8>>>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Snapshot {
     pub last_offset: u64,
+    /// What the state db and the vector collections held at `last_offset`.
+    /// Snapshots written before checkpoints existed only carry the offset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checkpoint: Option<Checkpoint>,
+}
+
+/// The data itself lives in the state db and the vector runs; the checkpoint
+/// records how far each had applied, so a start can tell when one of them
+/// lost writes that the WAL, once compacted, can no longer replay.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub state_applied_offset: u64,
+    pub state_keys: u64,
+    #[serde(default)]
+    pub vectors: Vec<VectorCheckpoint>,
+}
+
+/// One `events-NNNNNN.log` file as listed by `Persist::segment_sizes`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct WalSegment {
+    pub file: String,
+    pub bytes: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VectorCheckpoint {
+    pub collection: String,
+    pub applied_offset: u64,
+    pub live_count: usize,
 }
 
 impl Persist {
@@ -29,41 +105,87 @@ impl Persist {
         dir: impl AsRef<Path>,
         segment_max_bytes: u64,
         retention_segments: usize,
+        fsync: WalFsyncPolicy,
     ) -> std::io::Result<Self> {
         let dir = dir.as_ref().to_path_buf();
         std::fs::create_dir_all(&dir)?;
         let current_segment = find_latest_segment_id(&dir).unwrap_or(1);
         Ok(Self(Arc::new(Inner {
             dir,
+            fsync,
             wal_lock: Mutex::new(()),
             segment_max_bytes: segment_max_bytes.max(1024 * 1024),
             retention_segments: retention_segments.max(1),
             current_segment: Mutex::new(current_segment),
+            pending: Mutex::new(Vec::new()),
         })))
     }
 
+    /// Cuts a partial last line off the active segment so the next append
+    /// starts on a fresh line. Only the process that owns the WAL calls this.
+    pub fn trim_torn_tail(&self) -> std::io::Result<()> {
+        let _g = self.0.wal_lock.lock();
+        trim_torn_tail(&self.segment_path(*self.0.current_segment.lock()))
+    }
+
     pub fn append_event(&self, event: &EventRecord) -> std::io::Result<()> {
+        self.append_events(std::slice::from_ref(event))
+    }
+
+    /// Appends `events` with a single write and fsync, after any buffered ones.
+    pub fn append_events(&self, events: &[EventRecord]) -> std::io::Result<()> {
+        let mut new_lines = Vec::with_capacity(events.len());
+        for event in events {
+            new_lines.push(serde_json::to_vec(event)?);
+        }
+        let _g = self.0.wal_lock.lock();
+        let mut lines = std::mem::take(&mut *self.0.pending.lock());
+        lines.extend(new_lines);
+        self.write_lines_locked(&lines)
+    }
+
+    /// Queues the event for the next group commit instead of fsyncing it now.
+    /// Any later `append_event` or `flush_pending` writes it ahead of newer events.
+    pub fn buffer_event(&self, event: &EventRecord) -> std::io::Result<()> {
+        let line = serde_json::to_vec(event)?;
+        self.0.pending.lock().push(line);
+        Ok(())
+    }
+
+    pub fn flush_pending(&self) -> std::io::Result<usize> {
         let _g = self.0.wal_lock.lock();
+        let lines = std::mem::take(&mut *self.0.pending.lock());
+        if lines.is_empty() {
+            return Ok(0);
+        }
+        self.write_lines_locked(&lines)?;
+        Ok(lines.len())
+    }
 
+    fn write_lines_locked(&self, lines: &[Vec<u8>]) -> std::io::Result<()> {
         let mut seg = *self.0.current_segment.lock();
         let mut path = self.segment_path(seg);
         ensure_file_exists(&path)?;
 
-        let line = serde_json::to_vec(event)?;
-        let estimated = line.len() as u64 + 1;
+        let estimated: u64 = lines.iter().map(|l| l.len() as u64 + 1).sum();
         let current_size = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
         if current_size.saturating_add(estimated) > self.0.segment_max_bytes {
             seg = seg.saturating_add(1);
             *self.0.current_segment.lock() = seg;
             path = self.segment_path(seg);
             ensure_file_exists(&path)?;
+            self.0.fsync.sync_dir(&self.0.dir)?;
         }
 
+        let mut buf = Vec::with_capacity(estimated as usize);
+        for line in lines {
+            buf.extend_from_slice(line);
+            buf.push(b'\n');
+        }
         let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
-        file.write_all(&line)?;
-        file.write_all(b"\n")?;
+        file.write_all(&buf)?;
         file.flush()?;
-        file.sync_data()?;
+        self.0.fsync.sync_file(&file)?;
 
         self.enforce_retention_locked(seg)?;
         Ok(())
@@ -81,12 +203,16 @@ impl Persist {
 
     pub fn write_snapshot_and_rotate(&self, snapshot: &Snapshot) -> std::io::Result<()> {
         let _g = self.0.wal_lock.lock();
+        let pending = std::mem::take(&mut *self.0.pending.lock());
+        if !pending.is_empty() {
+            self.write_lines_locked(&pending)?;
+        }
 
         let tmp = self.0.dir.join("snapshot.json.tmp");
         let mut f = File::create(&tmp)?;
         serde_json::to_writer_pretty(&mut f, snapshot)?;
         f.flush()?;
-        f.sync_data()?;
+        self.0.fsync.sync_file(&f)?;
         drop(f);
         std::fs::rename(tmp, self.snapshot_path())?;
 
@@ -102,12 +228,36 @@ impl Persist {
             .truncate(true)
             .open(path)?;
         f.flush()?;
-        f.sync_data()?;
+        self.0.fsync.sync_file(&f)?;
+        self.0.fsync.sync_dir(&self.0.dir)?;
 
         self.enforce_retention_locked(seg)?;
         Ok(())
     }
 
+    /// Deletes whole WAL segments whose newest event is below `offset`, judged by
+    /// each segment's last line. The active segment is always kept. Returns the
+    /// number of segments removed.
+    pub fn truncate_below(&self, offset: u64) -> std::io::Result<usize> {
+        let _g = self.0.wal_lock.lock();
+        let current = *self.0.current_segment.lock();
+        let mut removed = 0usize;
+        for path in list_segments_sorted(&self.0.dir) {
+            if parse_segment_id(&path).is_none_or(|seg| seg >= current) {
+                continue;
+            }
+            let dead = match last_event_offset(&path)? {
+                Some(max) => max < offset,
+                None => true,
+            };
+            if dead {
+                std::fs::remove_file(&path)?;
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+
     pub fn replay_wal_since(
         &self,
         since_offset: u64,
@@ -116,35 +266,66 @@ impl Persist {
         events: &EventBus,
     ) -> std::io::Result<usize> {
         let mut applied = 0usize;
+        let head = self.for_each_committed_event_since(since_offset, |ev| {
+            apply_event(state, vectors, &ev);
+            applied += 1;
+            true
+        })?;
+        if let Some(head) = head {
+            events.set_next_offset(head.saturating_add(1));
+        }
+        Ok(applied)
+    }
 
+    /// Offset of the oldest event still on disk, read from the first line of
+    /// the oldest segment that has one. `None` when the WAL is empty.
+    pub fn earliest_offset(&self) -> std::io::Result<Option<u64>> {
         for path in list_segments_sorted(&self.0.dir) {
-            let f = File::open(path)?;
-            let reader = BufReader::new(f);
+            let reader = BufReader::new(File::open(path)?);
             for line in reader.lines() {
                 let line = line?;
                 if line.trim().is_empty() {
                     continue;
                 }
-                let ev: EventRecord = match serde_json::from_str(&line) {
-                    Ok(v) => v,
-                    Err(_) => continue,
-                };
-                if ev.offset <= since_offset {
-                    continue;
+                if let Ok(ev) = serde_json::from_str::<EventRecord>(&line) {
+                    return Ok(Some(ev.offset));
                 }
-                apply_event(state, vectors, &ev);
-                events.set_next_offset(ev.offset.saturating_add(1));
-                applied += 1;
             }
         }
+        Ok(None)
+    }
 
-        Ok(applied)
+    /// Oldest offset a replay from disk can still return. Compaction can
+    /// leave the WAL empty; everything the snapshot covers is gone then.
+    pub fn earliest_available_offset(&self) -> std::io::Result<Option<u64>> {
+        match self.earliest_offset()? {
+            Some(offset) => Ok(Some(offset)),
+            None => Ok(self
+                .load_snapshot()?
+                .map(|snap| snap.last_offset.saturating_add(1))),
+        }
     }
 
     pub fn list_segments(&self) -> Vec<PathBuf> {
         list_segments_sorted(&self.0.dir)
     }
 
+    /// WAL segments oldest first, with their size on disk.
+    pub fn segment_sizes(&self) -> std::io::Result<Vec<WalSegment>> {
+        list_segments_sorted(&self.0.dir)
+            .into_iter()
+            .map(|path| {
+                Ok(WalSegment {
+                    bytes: std::fs::metadata(&path)?.len(),
+                    file: path
+                        .file_name()
+                        .map(|name| name.to_string_lossy().into_owned())
+                        .unwrap_or_default(),
+                })
+            })
+            .collect()
+    }
+
     pub fn for_each_event_since<F>(&self, since_offset: u64, mut f: F) -> std::io::Result<()>
     where
         F: FnMut(EventRecord) -> bool,
@@ -172,6 +353,43 @@ impl Persist {
         Ok(())
     }
 
+    /// Like `for_each_event_since`, but the events of one `transact_state`
+    /// call (tagged with `txn_seq`/`txn_len`) are held back until all of them
+    /// are read, so a batch torn by a crash is dropped whole. Returns the
+    /// highest offset read, dropped events included.
+    pub fn for_each_committed_event_since<F>(
+        &self,
+        since_offset: u64,
+        mut f: F,
+    ) -> std::io::Result<Option<u64>>
+    where
+        F: FnMut(EventRecord) -> bool,
+    {
+        let mut head = None;
+        let mut batch: Vec<EventRecord> = Vec::new();
+        self.for_each_event_since(since_offset, |ev| {
+            head = head.max(Some(ev.offset));
+            let Some((seq, len)) = txn_position(&ev) else {
+                drop_torn_batch(&mut batch);
+                return f(ev);
+            };
+            if seq == 0 {
+                drop_torn_batch(&mut batch);
+            } else if seq != batch.len() {
+                drop_torn_batch(&mut batch);
+                tracing::warn!(offset = ev.offset, "dropping event of a torn transaction");
+                return true;
+            }
+            batch.push(ev);
+            if batch.len() < len {
+                return true;
+            }
+            batch.drain(..).all(&mut f)
+        })?;
+        drop_torn_batch(&mut batch);
+        Ok(head)
+    }
+
     fn segment_path(&self, seg: u64) -> PathBuf {
         self.0.dir.join(format!("events-{seg:06}.log"))
     }
@@ -217,10 +435,84 @@ fn apply_event(state: &crate::engine::state::StateStore, _vectors: &VectorStore,
                 let _ = state.delete(key);
             }
         }
+        "doc_upserted" => {
+            match serde_json::from_value::<crate::engine::DocUpserted>(ev.data.clone()) {
+                Ok(doc) => state.apply_doc_upserted(&doc),
+                Err(err) => tracing::error!(
+                    error = %err,
+                    offset = ev.offset,
+                    "failed to replay doc_upserted"
+                ),
+            }
+        }
         _ => {}
     }
 }
 
+/// `(txn_seq, txn_len)` of an event written by `transact_state`.
+fn txn_position(ev: &EventRecord) -> Option<(usize, usize)> {
+    let seq = ev.data.get("txn_seq")?.as_u64()? as usize;
+    let len = ev.data.get("txn_len")?.as_u64()? as usize;
+    Some((seq, len))
+}
+
+fn drop_torn_batch(batch: &mut Vec<EventRecord>) {
+    if let Some(first) = batch.first() {
+        tracing::warn!(
+            offset = first.offset,
+            read = batch.len(),
+            "dropping torn transaction"
+        );
+        batch.clear();
+    }
+}
+
+fn last_event_offset(path: &Path) -> std::io::Result<Option<u64>> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut last = None;
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        if let Ok(ev) = serde_json::from_str::<EventRecord>(&line) {
+            last = Some(ev.offset);
+        }
+    }
+    Ok(last)
+}
+
+/// Cuts a partial last line left by a crash mid-append, so the next append
+/// starts on a line of its own instead of being glued to it.
+fn trim_torn_tail(path: &Path) -> std::io::Result<()> {
+    let mut file = match OpenOptions::new().read(true).write(true).open(path) {
+        Ok(file) => file,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err),
+    };
+    let len = file.metadata()?.len();
+    if len == 0 {
+        return Ok(());
+    }
+    let mut last = [0u8; 1];
+    file.seek(SeekFrom::Start(len - 1))?;
+    file.read_exact(&mut last)?;
+    if last[0] == b'\n' {
+        return Ok(());
+    }
+    let mut bytes = Vec::with_capacity(len as usize);
+    file.seek(SeekFrom::Start(0))?;
+    file.read_to_end(&mut bytes)?;
+    let keep = bytes.iter().rposition(|b| *b == b'\n').map_or(0, |i| i + 1);
+    tracing::warn!(
+        path = %path.display(),
+        dropped_bytes = bytes.len() - keep,
+        "trimming torn wal tail"
+    );
+    file.set_len(keep as u64)?;
+    file.sync_all()
+}
+
 fn ensure_file_exists(path: &Path) -> std::io::Result<()> {
     if path.exists() {
         return Ok(());
@@ -8,6 +8,8 @@ pub struct StateStore(Arc<Inner>);
 
 struct Inner {
     map: RwLock<HashMap<String, Entry>>,
+    /// `get` keeps returning a key this long after it expires.
+    ttl_grace_ms: u64,
 }
 
 #[derive(Clone, Debug)]
@@ -25,24 +27,100 @@ pub struct StateItem {
     pub expires_at_ms: Option<u64>,
 }
 
+/// A key with a pending TTL, as seen by the expiry reaper.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TtlEntry {
+    pub key: String,
+    pub expires_at_ms: u64,
+    pub ttl_remaining_ms: u64,
+}
+
+/// Payload of a `doc_upserted` event: a document write together with the
+/// index keys it touches, applied as one unit.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DocUpserted {
+    pub collection: String,
+    pub id: String,
+    pub key: String,
+    pub revision: u64,
+    pub value: serde_json::Value,
+    #[serde(default)]
+    pub index_puts: Vec<IndexPut>,
+    #[serde(default)]
+    pub index_deletes: Vec<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct IndexPut {
+    pub key: String,
+    pub revision: u64,
+    pub value: serde_json::Value,
+}
+
+/// One write of an `Engine::transact_state` transaction.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum StateOp {
+    Put {
+        key: String,
+        value: serde_json::Value,
+        #[serde(default)]
+        ttl_ms: Option<u64>,
+        #[serde(default)]
+        if_revision: Option<u64>,
+        /// Fail with `AlreadyExists` when the key is live (NX).
+        #[serde(default)]
+        if_not_exists: bool,
+    },
+    Delete {
+        key: String,
+        #[serde(default)]
+        if_revision: Option<u64>,
+    },
+}
+
+impl StateOp {
+    pub fn key(&self) -> &str {
+        match self {
+            StateOp::Put { key, .. } | StateOp::Delete { key, .. } => key,
+        }
+    }
+}
+
+/// Index keys to rewrite (with their full new value) or drop.
+#[derive(Clone, Debug, Default)]
+pub struct IndexChanges {
+    pub puts: Vec<(String, serde_json::Value)>,
+    pub deletes: Vec<String>,
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum StateError {
     #[error("revision mismatch")]
     RevisionMismatch,
+    #[error("key not found")]
+    NotFound,
+    #[error("key already exists")]
+    AlreadyExists,
 }
 
 impl StateStore {
     pub fn new() -> Self {
+        Self::with_ttl_grace_ms(0)
+    }
+
+    pub fn with_ttl_grace_ms(ttl_grace_ms: u64) -> Self {
         Self(Arc::new(Inner {
             map: RwLock::new(HashMap::new()),
+            ttl_grace_ms,
         }))
     }
 
     pub fn get(&self, key: &str) -> Option<StateItem> {
-        let now = now_ms();
+        let cutoff = now_ms().saturating_sub(self.0.ttl_grace_ms);
         let map = self.0.map.read();
         match map.get(key) {
-            Some(e) if !is_expired(e, now) => Some(StateItem {
+            Some(e) if !is_expired(e, cutoff) => Some(StateItem {
                 key: key.to_string(),
                 value: e.value.clone(),
                 revision: e.revision,
@@ -52,30 +130,78 @@ impl StateStore {
         }
     }
 
-    pub fn list(&self, prefix: Option<&str>, limit: usize) -> Vec<StateItem> {
+    /// Like `get` for each key, under a single read lock.
+    pub fn get_many(&self, keys: &[String]) -> Vec<StateItem> {
+        let cutoff = now_ms().saturating_sub(self.0.ttl_grace_ms);
+        let map = self.0.map.read();
+        keys.iter()
+            .filter_map(|key| {
+                let e = map.get(key).filter(|e| !is_expired(e, cutoff))?;
+                Some(StateItem {
+                    key: key.clone(),
+                    value: e.value.clone(),
+                    revision: e.revision,
+                    expires_at_ms: e.expires_at_ms,
+                })
+            })
+            .collect()
+    }
+
+    pub fn key_count(&self) -> usize {
+        self.0.map.read().len()
+    }
+
+    /// Live keys in key order, starting after `start_after` when given, so a
+    /// listing can resume where the previous page ended.
+    pub fn list(
+        &self,
+        prefix: Option<&str>,
+        start_after: Option<&str>,
+        limit: usize,
+    ) -> Vec<StateItem> {
+        self.list_matching(
+            |k| prefix.is_none_or(|p| k.starts_with(p)),
+            start_after,
+            limit,
+        )
+    }
+
+    /// Live keys between `from` and `to`, both inclusive, in key order.
+    pub fn range(
+        &self,
+        from: &str,
+        to: &str,
+        start_after: Option<&str>,
+        limit: usize,
+    ) -> Vec<StateItem> {
+        self.list_matching(|k| from <= k && k <= to, start_after, limit)
+    }
+
+    fn list_matching(
+        &self,
+        keep: impl Fn(&str) -> bool,
+        start_after: Option<&str>,
+        limit: usize,
+    ) -> Vec<StateItem> {
         let now = now_ms();
         let map = self.0.map.read();
-        let mut out = Vec::new();
-        for (k, v) in map.iter() {
-            if let Some(p) = prefix {
-                if !k.starts_with(p) {
-                    continue;
-                }
-            }
-            if is_expired(v, now) {
-                continue;
-            }
-            out.push(StateItem {
+        let mut matching: Vec<(&String, &Entry)> = map
+            .iter()
+            .filter(|(k, _)| keep(k))
+            .filter(|(k, _)| start_after.is_none_or(|after| k.as_str() > after))
+            .filter(|(_, v)| !is_expired(v, now))
+            .collect();
+        matching.sort_unstable_by(|a, b| a.0.cmp(b.0));
+        matching
+            .into_iter()
+            .take(limit)
+            .map(|(k, v)| StateItem {
                 key: k.clone(),
                 value: v.value.clone(),
                 revision: v.revision,
                 expires_at_ms: v.expires_at_ms,
-            });
-            if out.len() >= limit {
-                break;
-            }
-        }
-        out
+            })
+            .collect()
     }
 
     pub fn put(
@@ -106,7 +232,7 @@ impl StateStore {
                 (next_rev, value)
             }
             std::collections::hash_map::Entry::Vacant(e) => {
-                if if_revision.is_some() {
+                if if_revision.is_some_and(|expected| expected != 0) {
                     return Err(StateError::RevisionMismatch);
                 }
                 e.insert(Entry {
@@ -131,6 +257,19 @@ impl StateStore {
         map.remove(key).is_some()
     }
 
+    /// Fails unless `key` is live at exactly `expected` (or absent when
+    /// `expected` is 0); the compare step of a compare-and-delete.
+    pub fn check_revision(&self, key: &str, expected: u64) -> Result<(), StateError> {
+        let now = now_ms();
+        let map = self.0.map.read();
+        let current = map.get(key).filter(|e| !is_expired(e, now));
+        if current.map_or(0, |e| e.revision) == expected {
+            Ok(())
+        } else {
+            Err(StateError::RevisionMismatch)
+        }
+    }
+
     pub fn peek_meta(&self, key: &str) -> Option<(u64, Option<u64>)> {
         let map = self.0.map.read();
         map.get(key).map(|e| (e.revision, e.expires_at_ms))
@@ -188,6 +327,33 @@ impl StateStore {
         );
     }
 
+    pub fn apply_doc_upserted(&self, doc: &DocUpserted) {
+        let mut map = self.0.map.write();
+        map.insert(
+            doc.key.clone(),
+            Entry {
+                value: doc.value.clone(),
+                revision: doc.revision,
+                expires_at_ms: None,
+            },
+        );
+        for put in &doc.index_puts {
+            map.insert(
+                put.key.clone(),
+                Entry {
+                    value: put.value.clone(),
+                    revision: put.revision,
+                    expires_at_ms: None,
+                },
+            );
+        }
+        for key in &doc.index_deletes {
+            map.remove(key);
+        }
+    }
+
+    /// Next revision for a put of `key`. `if_revision` must match the live
+    /// revision; `Some(0)` means the key must not exist (create-only).
     pub fn prepare_put_revision(
         &self,
         key: &str,
@@ -206,7 +372,7 @@ impl StateStore {
                 Ok(e.revision.saturating_add(1))
             }
             None => {
-                if if_revision.is_some() {
+                if if_revision.is_some_and(|expected| expected != 0) {
                     return Err(StateError::RevisionMismatch);
                 }
                 Ok(1)
@@ -258,6 +424,28 @@ impl StateStore {
         }
         out
     }
+
+    /// Every key carrying a TTL (expired or not), ordered by `expires_at_ms`.
+    pub fn ttl_entries(&self, now_ms: u64, limit: usize) -> Vec<TtlEntry> {
+        let map = self.0.map.read();
+        let mut out: Vec<TtlEntry> = map
+            .iter()
+            .filter_map(|(k, v)| {
+                v.expires_at_ms.map(|exp| TtlEntry {
+                    key: k.clone(),
+                    expires_at_ms: exp,
+                    ttl_remaining_ms: exp.saturating_sub(now_ms),
+                })
+            })
+            .collect();
+        out.sort_by(|a, b| {
+            a.expires_at_ms
+                .cmp(&b.expires_at_ms)
+                .then_with(|| a.key.cmp(&b.key))
+        });
+        out.truncate(limit);
+        out
+    }
 }
 
 impl Default for StateStore {
@@ -312,4 +500,23 @@ mod tests {
         assert!(s.delete("k"));
         assert!(s.get("k").is_none());
     }
+
+    #[test]
+    fn if_revision_zero_only_creates_missing_keys() {
+        let s = StateStore::new();
+        let item = s
+            .put("leader".to_string(), serde_json::json!("a"), None, Some(0))
+            .unwrap();
+        assert_eq!(item.revision, 1);
+        assert!(matches!(
+            s.put("leader".to_string(), serde_json::json!("b"), None, Some(0)),
+            Err(StateError::RevisionMismatch)
+        ));
+        assert!(matches!(
+            s.prepare_put_revision("leader", Some(0)),
+            Err(StateError::RevisionMismatch)
+        ));
+        assert_eq!(s.prepare_put_revision("other", Some(0)).unwrap(), 1);
+        assert_eq!(s.get("leader").unwrap().value, "a");
+    }
 }
@@ -1,8 +1,16 @@
 use crate::engine::events::EventRecord;
-use crate::engine::state::{StateError, StateItem};
+use crate::engine::state::{DocUpserted, StateError, StateItem, TtlEntry};
 use anyhow::Context;
-use redb::{Database, ReadableTable, TableDefinition};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+use redb::{Database, ReadableTable, ReadableTableMetadata, TableDefinition};
+use std::io::{Read, Write};
+use std::ops::Bound;
 use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
 const STATE: TableDefinition<&[u8], &[u8]> = TableDefinition::new("state");
@@ -14,24 +22,102 @@ const META_APPLIED_OFFSET: &[u8] = b"applied_offset";
 #[derive(Clone)]
 pub struct StateDb {
     db: Arc<Database>,
+    /// Values whose JSON encoding reaches this size are deflated on disk; 0 disables.
+    compress_min_bytes: usize,
+    /// `get_state` keeps returning a key this long after it expires.
+    ttl_grace_ms: u64,
+    /// Set by `apply_state_updated_deferred`; cleared once `sync` makes it durable.
+    dirty: Arc<AtomicBool>,
 }
 
 #[derive(serde::Serialize, serde::Deserialize)]
 struct StoredValue {
+    #[serde(default)]
     value: serde_json::Value,
     revision: u64,
     expires_at_ms: Option<u64>,
+    /// Base64 of the deflated JSON value; when set, `value` is stored as null.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    compressed: Option<String>,
+}
+
+impl StoredValue {
+    fn new(value: serde_json::Value, revision: u64, expires_at_ms: Option<u64>) -> Self {
+        Self {
+            value,
+            revision,
+            expires_at_ms,
+            compressed: None,
+        }
+    }
+
+    fn decode(bytes: &[u8]) -> anyhow::Result<Self> {
+        let mut stored: StoredValue =
+            serde_json::from_slice(bytes).context("decode stored value")?;
+        if let Some(encoded) = stored.compressed.take() {
+            let deflated = STANDARD
+                .decode(encoded)
+                .context("decode compressed value")?;
+            let mut json = Vec::new();
+            DeflateDecoder::new(deflated.as_slice())
+                .read_to_end(&mut json)
+                .context("inflate stored value")?;
+            stored.value = serde_json::from_slice(&json).context("decode inflated value")?;
+        }
+        Ok(stored)
+    }
+
+    fn encode(&self, compress_min_bytes: usize) -> anyhow::Result<Vec<u8>> {
+        let plain = serde_json::to_vec(self)?;
+        if compress_min_bytes == 0 {
+            return Ok(plain);
+        }
+        let json = serde_json::to_vec(&self.value)?;
+        if json.len() < compress_min_bytes {
+            return Ok(plain);
+        }
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&json)?;
+        let compressed = StoredValue {
+            value: serde_json::Value::Null,
+            revision: self.revision,
+            expires_at_ms: self.expires_at_ms,
+            compressed: Some(STANDARD.encode(encoder.finish()?)),
+        };
+        let packed = serde_json::to_vec(&compressed)?;
+        // Incompressible values stay plain.
+        Ok(if packed.len() < plain.len() {
+            packed
+        } else {
+            plain
+        })
+    }
 }
 
 impl StateDb {
     pub fn open(data_dir: impl AsRef<Path>) -> anyhow::Result<Self> {
         let path = data_dir.as_ref().join("state.redb");
         let db = Database::create(&path).context("create/open redb")?;
-        let this = Self { db: Arc::new(db) };
+        let this = Self {
+            db: Arc::new(db),
+            compress_min_bytes: 0,
+            ttl_grace_ms: 0,
+            dirty: Arc::new(AtomicBool::new(false)),
+        };
         this.init_tables().context("init tables")?;
         Ok(this)
     }
 
+    pub fn with_value_compression(mut self, min_bytes: usize) -> Self {
+        self.compress_min_bytes = min_bytes;
+        self
+    }
+
+    pub fn with_ttl_grace_ms(mut self, grace_ms: u64) -> Self {
+        self.ttl_grace_ms = grace_ms;
+        self
+    }
+
     fn init_tables(&self) -> anyhow::Result<()> {
         let wtx = self.db.begin_write()?;
         let _ = wtx.open_table(STATE)?;
@@ -47,13 +133,41 @@ impl StateDb {
             Ok(t) => t,
             Err(_) => return Ok(None),
         };
+        self.read_item(&table, key, now_ms())
+    }
+
+    /// Point lookups for every key inside one read transaction; missing and
+    /// expired keys are left out.
+    pub fn get_many(&self, keys: &[String]) -> anyhow::Result<Vec<StateItem>> {
+        let tx = self.db.begin_read()?;
+        let table = match tx.open_table(STATE) {
+            Ok(t) => t,
+            Err(_) => return Ok(Vec::new()),
+        };
         let now = now_ms();
+        let mut out = Vec::with_capacity(keys.len());
+        for key in keys {
+            if let Some(item) = self.read_item(&table, key, now)? {
+                out.push(item);
+            }
+        }
+        Ok(out)
+    }
+
+    fn read_item(
+        &self,
+        table: &impl ReadableTable<&'static [u8], &'static [u8]>,
+        key: &str,
+        now: u64,
+    ) -> anyhow::Result<Option<StateItem>> {
         let Some(raw) = table.get(key.as_bytes())? else {
             return Ok(None);
         };
-        let stored: StoredValue =
-            serde_json::from_slice(raw.value()).context("decode stored value")?;
-        if stored.expires_at_ms.is_some_and(|e| e <= now) {
+        let stored = StoredValue::decode(raw.value())?;
+        if stored
+            .expires_at_ms
+            .is_some_and(|e| e.saturating_add(self.ttl_grace_ms) <= now)
+        {
             return Ok(None);
         }
         Ok(Some(StateItem {
@@ -64,11 +178,59 @@ impl StateDb {
         }))
     }
 
+    /// Whether the key still has a row, expired or not; like
+    /// `StateStore::exists_live`, so the TTL sweep finds what it must delete.
     pub fn exists_live(&self, key: &str) -> anyhow::Result<bool> {
-        Ok(self.get_state(key)?.is_some())
+        let tx = self.db.begin_read()?;
+        let table = match tx.open_table(STATE) {
+            Ok(t) => t,
+            Err(_) => return Ok(false),
+        };
+        Ok(table.get(key.as_bytes())?.is_some())
     }
 
-    pub fn list(&self, prefix: Option<&str>, limit: usize) -> anyhow::Result<Vec<StateItem>> {
+    /// Same order and `start_after` contract as `StateStore::list`.
+    pub fn list(
+        &self,
+        prefix: Option<&str>,
+        start_after: Option<&str>,
+        limit: usize,
+    ) -> anyhow::Result<Vec<StateItem>> {
+        let prefix_start = prefix.unwrap_or_default();
+        self.scan(
+            resume_bound(prefix_start, start_after),
+            Bound::Unbounded,
+            |key| prefix.is_none_or(|p| key.starts_with(p)),
+            limit,
+        )
+    }
+
+    /// Live keys between `from` and `to`, both inclusive, read from one
+    /// range of the table.
+    pub fn range(
+        &self,
+        from: &str,
+        to: &str,
+        start_after: Option<&str>,
+        limit: usize,
+    ) -> anyhow::Result<Vec<StateItem>> {
+        self.scan(
+            resume_bound(from, start_after),
+            Bound::Included(to),
+            |_| true,
+            limit,
+        )
+    }
+
+    /// Collects up to `limit` live items from `start` to `end`, stopping at
+    /// the first key `within` rejects.
+    fn scan(
+        &self,
+        start: Bound<&str>,
+        end: Bound<&str>,
+        within: impl Fn(&str) -> bool,
+        limit: usize,
+    ) -> anyhow::Result<Vec<StateItem>> {
         let tx = self.db.begin_read()?;
         let table = match tx.open_table(STATE) {
             Ok(t) => t,
@@ -76,52 +238,40 @@ impl StateDb {
         };
         let now = now_ms();
         let mut out = Vec::new();
+        // An inverted range matches nothing; don't hand it to redb.
+        if let (Bound::Included(s) | Bound::Excluded(s), Bound::Included(e)) = (start, end) {
+            if s > e {
+                return Ok(out);
+            }
+        }
 
-        if let Some(prefix) = prefix {
-            let start = prefix.as_bytes().to_vec();
-            for kv in table.range(start.as_slice()..)? {
-                let (k, v) = kv?;
-                let key = std::str::from_utf8(k.value()).unwrap_or_default();
-                if !key.starts_with(prefix) {
-                    break;
-                }
-                let stored: StoredValue = serde_json::from_slice(v.value())?;
-                if stored.expires_at_ms.is_some_and(|e| e <= now) {
-                    continue;
-                }
-                out.push(StateItem {
-                    key: key.to_string(),
-                    value: stored.value,
-                    revision: stored.revision,
-                    expires_at_ms: stored.expires_at_ms,
-                });
-                if out.len() >= limit {
-                    break;
-                }
+        let bounds = (start.map(str::as_bytes), end.map(str::as_bytes));
+        for kv in table.range::<&[u8]>(bounds)? {
+            let (k, v) = kv?;
+            let key = std::str::from_utf8(k.value()).unwrap_or_default();
+            if !within(key) {
+                break;
             }
-        } else {
-            for kv in table.iter()? {
-                let (k, v) = kv?;
-                let key = std::str::from_utf8(k.value()).unwrap_or_default();
-                let stored: StoredValue = serde_json::from_slice(v.value())?;
-                if stored.expires_at_ms.is_some_and(|e| e <= now) {
-                    continue;
-                }
-                out.push(StateItem {
-                    key: key.to_string(),
-                    value: stored.value,
-                    revision: stored.revision,
-                    expires_at_ms: stored.expires_at_ms,
-                });
-                if out.len() >= limit {
-                    break;
-                }
+            let stored = StoredValue::decode(v.value())?;
+            if stored.expires_at_ms.is_some_and(|e| e <= now) {
+                continue;
+            }
+            out.push(StateItem {
+                key: key.to_string(),
+                value: stored.value,
+                revision: stored.revision,
+                expires_at_ms: stored.expires_at_ms,
+            });
+            if out.len() >= limit {
+                break;
             }
         }
 
         Ok(out)
     }
 
+    /// Same `if_revision` rules as `StateStore::prepare_put_revision`,
+    /// including `Some(0)` for create-only.
     pub fn prepare_put_revision(
         &self,
         key: &str,
@@ -134,7 +284,7 @@ impl StateDb {
         let table = match tx.open_table(STATE) {
             Ok(t) => t,
             Err(_) => {
-                if if_revision.is_some() {
+                if if_revision.is_some_and(|expected| expected != 0) {
                     return Err(StateError::RevisionMismatch);
                 }
                 return Ok(1);
@@ -159,7 +309,7 @@ impl StateDb {
                 Ok(v.revision.saturating_add(1))
             }
             None => {
-                if if_revision.is_some() {
+                if if_revision.is_some_and(|expected| expected != 0) {
                     return Err(StateError::RevisionMismatch);
                 }
                 Ok(1)
@@ -167,7 +317,43 @@ impl StateDb {
         }
     }
 
+    /// Same contract as `StateStore::check_revision`.
+    pub fn check_revision(&self, key: &str, expected: u64) -> Result<(), StateError> {
+        self.prepare_put_revision(key, Some(expected)).map(|_| ())
+    }
+
     pub fn apply_state_updated(&self, ev: &EventRecord) -> anyhow::Result<()> {
+        self.write_state_updated(ev, redb::Durability::Immediate)
+    }
+
+    /// Like `apply_state_updated`, but the commit is not fsynced: reads see it
+    /// right away and it becomes durable with the next `sync` or durable
+    /// commit. For events whose WAL line is still buffered, so a crash loses
+    /// both together.
+    pub fn apply_state_updated_deferred(&self, ev: &EventRecord) -> anyhow::Result<()> {
+        self.write_state_updated(ev, redb::Durability::None)?;
+        self.dirty.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Makes every deferred commit durable; a no-op when there are none.
+    pub fn sync(&self) -> anyhow::Result<()> {
+        if !self.dirty.swap(false, Ordering::SeqCst) {
+            return Ok(());
+        }
+        let wtx = self.db.begin_write()?;
+        if let Err(err) = wtx.commit() {
+            self.dirty.store(true, Ordering::SeqCst);
+            return Err(err.into());
+        }
+        Ok(())
+    }
+
+    fn write_state_updated(
+        &self,
+        ev: &EventRecord,
+        durability: redb::Durability,
+    ) -> anyhow::Result<()> {
         let key = ev
             .data
             .get("key")
@@ -186,35 +372,13 @@ impl StateDb {
             .unwrap_or(serde_json::Value::Null);
 
         let mut wtx = self.db.begin_write()?;
+        wtx.set_durability(durability);
         {
             let mut state = wtx.open_table(STATE)?;
             let mut expires = wtx.open_table(EXPIRES)?;
-
-            let prev = if let Some(prev_raw) = state.get(key.as_bytes())? {
-                let bytes = prev_raw.value().to_vec();
-                serde_json::from_slice::<StoredValue>(&bytes).ok()
-            } else {
-                None
-            };
-            if let Some(prev) = prev {
-                if let Some(exp) = prev.expires_at_ms {
-                    let idx = expires_key(exp, key.as_bytes());
-                    let _ = expires.remove(idx.as_slice())?;
-                }
-            }
-
-            let stored = StoredValue {
-                value,
-                revision,
-                expires_at_ms,
-            };
-            let bytes = serde_json::to_vec(&stored)?;
-            state.insert(key.as_bytes(), bytes.as_slice())?;
-
-            if let Some(exp) = expires_at_ms {
-                let idx = expires_key(exp, key.as_bytes());
-                expires.insert(idx.as_slice(), 0u8)?;
-            }
+            let stored = StoredValue::new(value, revision, expires_at_ms);
+            let bytes = stored.encode(self.compress_min_bytes)?;
+            write_entry(&mut state, &mut expires, key, &stored, &bytes)?;
         }
         set_applied_offset(&mut wtx, ev.offset)?;
         wtx.commit()?;
@@ -232,18 +396,33 @@ impl StateDb {
         {
             let mut state = wtx.open_table(STATE)?;
             let mut expires = wtx.open_table(EXPIRES)?;
-            let prev = if let Some(prev_raw) = state.remove(key.as_bytes())? {
-                let bytes = prev_raw.value().to_vec();
-                serde_json::from_slice::<StoredValue>(&bytes).ok()
-            } else {
-                None
-            };
-            if let Some(prev) = prev {
-                if let Some(exp) = prev.expires_at_ms {
-                    let idx = expires_key(exp, key.as_bytes());
-                    let _ = expires.remove(idx.as_slice())?;
-                }
-            };
+            remove_entry(&mut state, &mut expires, key)?;
+        }
+        set_applied_offset(&mut wtx, ev.offset)?;
+        wtx.commit()?;
+        Ok(())
+    }
+
+    /// Applies the document and all its index changes in one transaction.
+    pub fn apply_doc_upserted(&self, ev: &EventRecord) -> anyhow::Result<()> {
+        let doc: DocUpserted =
+            serde_json::from_value(ev.data.clone()).context("invalid doc_upserted payload")?;
+
+        let mut wtx = self.db.begin_write()?;
+        {
+            let mut state = wtx.open_table(STATE)?;
+            let mut expires = wtx.open_table(EXPIRES)?;
+            let stored = StoredValue::new(doc.value, doc.revision, None);
+            let bytes = stored.encode(self.compress_min_bytes)?;
+            write_entry(&mut state, &mut expires, &doc.key, &stored, &bytes)?;
+            for put in doc.index_puts {
+                let stored = StoredValue::new(put.value, put.revision, None);
+                let bytes = stored.encode(self.compress_min_bytes)?;
+                write_entry(&mut state, &mut expires, &put.key, &stored, &bytes)?;
+            }
+            for key in &doc.index_deletes {
+                remove_entry(&mut state, &mut expires, key)?;
+            }
         }
         set_applied_offset(&mut wtx, ev.offset)?;
         wtx.commit()?;
@@ -262,6 +441,15 @@ impl StateDb {
         Ok(u64::from_le_bytes(v.value().try_into().unwrap_or([0; 8])))
     }
 
+    /// Number of stored keys, including expired ones the sweeper hasn't removed yet.
+    pub fn key_count(&self) -> anyhow::Result<u64> {
+        let tx = self.db.begin_read()?;
+        match tx.open_table(STATE) {
+            Ok(t) => Ok(t.len()?),
+            Err(_) => Ok(0),
+        }
+    }
+
     pub fn expired_keys_due(&self, now_ms: u64, limit: usize) -> anyhow::Result<Vec<String>> {
         let tx = self.db.begin_read()?;
         let expires = match tx.open_table(EXPIRES) {
@@ -283,6 +471,79 @@ impl StateDb {
         }
         Ok(out)
     }
+
+    pub fn ttl_entries(&self, now_ms: u64, limit: usize) -> anyhow::Result<Vec<TtlEntry>> {
+        let tx = self.db.begin_read()?;
+        let expires = match tx.open_table(EXPIRES) {
+            Ok(t) => t,
+            Err(_) => return Ok(Vec::new()),
+        };
+
+        let mut out = Vec::new();
+        for kv in expires.iter()? {
+            if out.len() >= limit {
+                break;
+            }
+            let (k, _) = kv?;
+            let bytes = k.value();
+            let Some(key) = parse_expires_key(bytes) else {
+                continue;
+            };
+            let expires_at_ms = u64::from_be_bytes(bytes[..8].try_into().unwrap_or([0; 8]));
+            out.push(TtlEntry {
+                key,
+                expires_at_ms,
+                ttl_remaining_ms: expires_at_ms.saturating_sub(now_ms),
+            });
+        }
+        Ok(out)
+    }
+}
+
+type StateTable<'txn> = redb::Table<'txn, &'static [u8], &'static [u8]>;
+type ExpiresTable<'txn> = redb::Table<'txn, &'static [u8], u8>;
+
+fn write_entry(
+    state: &mut StateTable<'_>,
+    expires: &mut ExpiresTable<'_>,
+    key: &str,
+    stored: &StoredValue,
+    bytes: &[u8],
+) -> anyhow::Result<()> {
+    remove_expiry(state, expires, key)?;
+    state.insert(key.as_bytes(), bytes)?;
+    if let Some(exp) = stored.expires_at_ms {
+        let idx = expires_key(exp, key.as_bytes());
+        expires.insert(idx.as_slice(), 0u8)?;
+    }
+    Ok(())
+}
+
+fn remove_entry(
+    state: &mut StateTable<'_>,
+    expires: &mut ExpiresTable<'_>,
+    key: &str,
+) -> anyhow::Result<()> {
+    remove_expiry(state, expires, key)?;
+    state.remove(key.as_bytes())?;
+    Ok(())
+}
+
+/// Drops the EXPIRES index entry of the value currently stored under `key`.
+fn remove_expiry(
+    state: &mut StateTable<'_>,
+    expires: &mut ExpiresTable<'_>,
+    key: &str,
+) -> anyhow::Result<()> {
+    let prev = match state.get(key.as_bytes())? {
+        Some(raw) => serde_json::from_slice::<StoredValue>(raw.value()).ok(),
+        None => None,
+    };
+    if let Some(exp) = prev.and_then(|p| p.expires_at_ms) {
+        let idx = expires_key(exp, key.as_bytes());
+        let _ = expires.remove(idx.as_slice())?;
+    }
+    Ok(())
 }
 
 fn set_applied_offset(wtx: &mut redb::WriteTransaction, offset: u64) -> anyhow::Result<()> {
@@ -311,3 +572,88 @@ fn now_ms() -> u64 {
         .unwrap_or_default();
     dur.as_millis() as u64
 }
+
+/// Where a scan from `from` starts: strictly after the cursor, unless the
+/// cursor sorts before `from`.
+fn resume_bound<'a>(from: &'a str, start_after: Option<&'a str>) -> Bound<&'a str> {
+    match start_after {
+        Some(after) if after >= from => Bound::Excluded(after),
+        _ => Bound::Included(from),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state_updated(offset: u64, key: &str, value: serde_json::Value) -> EventRecord {
+        EventRecord {
+            offset,
+            ts_ms: 0,
+            event_type: "state_updated".to_string(),
+            data: serde_json::json!({"key": key, "revision": 1, "value": value}),
+        }
+    }
+
+    fn raw_stored(db: &StateDb, key: &str) -> Vec<u8> {
+        let rtx = db.db.begin_read().unwrap();
+        let table = rtx.open_table(STATE).unwrap();
+        let raw = table.get(key.as_bytes()).unwrap().unwrap();
+        raw.value().to_vec()
+    }
+
+    #[test]
+    fn large_values_are_compressed_on_disk() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = StateDb::open(dir.path())
+            .unwrap()
+            .with_value_compression(64);
+
+        let large = serde_json::json!({"text": "lorem ipsum ".repeat(500)});
+        db.apply_state_updated(&state_updated(1, "big", large.clone()))
+            .unwrap();
+        let raw = raw_stored(&db, "big");
+        assert!(raw.len() < serde_json::to_vec(&large).unwrap().len());
+        let stored: StoredValue = serde_json::from_slice(&raw).unwrap();
+        assert!(stored.compressed.is_some());
+        assert_eq!(db.get_state("big").unwrap().unwrap().value, large);
+
+        let small = serde_json::json!({"a": 1});
+        db.apply_state_updated(&state_updated(2, "small", small.clone()))
+            .unwrap();
+        let stored: StoredValue = serde_json::from_slice(&raw_stored(&db, "small")).unwrap();
+        assert!(stored.compressed.is_none());
+        assert_eq!(stored.value, small);
+    }
+
+    #[test]
+    fn if_revision_zero_only_creates_missing_keys() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = StateDb::open(dir.path()).unwrap();
+        assert_eq!(db.prepare_put_revision("leader", Some(0)).unwrap(), 1);
+
+        db.apply_state_updated(&state_updated(1, "leader", serde_json::json!("node-a")))
+            .unwrap();
+        assert!(matches!(
+            db.prepare_put_revision("leader", Some(0)),
+            Err(StateError::RevisionMismatch)
+        ));
+        assert_eq!(db.prepare_put_revision("leader", Some(1)).unwrap(), 2);
+    }
+
+    #[test]
+    fn range_is_inclusive_and_resumes_after_the_cursor() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = StateDb::open(dir.path()).unwrap();
+        for (offset, key) in ["a", "b", "c", "d"].into_iter().enumerate() {
+            db.apply_state_updated(&state_updated(offset as u64 + 1, key, serde_json::json!(1)))
+                .unwrap();
+        }
+        let keys = |items: Vec<StateItem>| items.into_iter().map(|i| i.key).collect::<Vec<_>>();
+
+        assert_eq!(keys(db.range("b", "c", None, 10).unwrap()), ["b", "c"]);
+        assert_eq!(keys(db.range("a", "d", Some("b"), 1).unwrap()), ["c"]);
+        assert!(db.range("a", "c", Some("c"), 10).unwrap().is_empty());
+        assert!(db.range("c", "b", None, 10).unwrap().is_empty());
+    }
+}
@@ -28,6 +28,15 @@ async fn main() -> anyhow::Result<()> {
         cli::Command::DiskAnnStatus { collection } => {
             diskann::run_status(&config, collection)?;
         }
+        cli::Command::DumpEvents(opts) => {
+            cli::run_dump_events(&config, opts)?;
+        }
+        cli::Command::Export(opts) => {
+            cli::run_export(&config, opts)?;
+        }
+        cli::Command::Import(opts) => {
+            cli::run_import(&config, opts)?;
+        }
     }
 
     Ok(())
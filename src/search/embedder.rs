@@ -0,0 +1,126 @@
+use crate::config::Config;
+use anyhow::{bail, Context};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Turns query text into a vector for the search engine.
+pub trait Embedder: Send + Sync {
+    /// Identifies the model; part of the embedding cache key.
+    fn model(&self) -> &str;
+    /// An empty vector means the text could not be embedded.
+    fn embed(&self, text: &str, dim: usize) -> Vec<f32>;
+}
+
+/// `HttpEmbedder` when `EMBED_URL` is set, `HashEmbedder` otherwise.
+pub fn from_config(config: &Config) -> anyhow::Result<Arc<dyn Embedder>> {
+    match &config.embed_url {
+        Some(url) => Ok(Arc::new(HttpEmbedder::new(
+            url.clone(),
+            config.embed_model.clone(),
+            Duration::from_millis(config.embed_timeout_ms),
+        )?)),
+        None => Ok(Arc::new(HashEmbedder)),
+    }
+}
+
+/// Texts of the form `TEST_VEC:1,0,...` are parsed verbatim so tests can
+/// control scores whatever the embedder.
+fn test_vec(text: &str) -> Option<Vec<f32>> {
+    let vec = text
+        .strip_prefix("TEST_VEC:")?
+        .split(',')
+        .map(|s| s.trim().parse::<f32>())
+        .collect::<Result<Vec<_>, _>>()
+        .ok()?;
+    (!vec.is_empty()).then_some(vec)
+}
+
+/// Deterministic pseudo-embedding seeded from the text hash. Honors
+/// `TEST_VEC:`.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct HashEmbedder;
+
+impl Embedder for HashEmbedder {
+    fn model(&self) -> &str {
+        "hash"
+    }
+
+    fn embed(&self, text: &str, dim: usize) -> Vec<f32> {
+        if let Some(vec) = test_vec(text) {
+            return vec;
+        }
+
+        let hash = crc32fast::hash(text.as_bytes());
+        let mut rng = StdRng::seed_from_u64(hash as u64);
+        (0..dim).map(|_| rng.gen::<f32>()).collect()
+    }
+}
+
+/// Asks an embedding service over HTTP. Each text is sent as
+/// `POST {url}` with `{"model", "input", "dim"}`; the reply may be a bare
+/// float array, `{"embedding": [...]}` or OpenAI-style
+/// `{"data": [{"embedding": [...]}]}`. Honors `TEST_VEC:`.
+///
+/// Calls block, so use it from blocking threads only.
+pub struct HttpEmbedder {
+    url: String,
+    model: String,
+    client: reqwest::blocking::Client,
+}
+
+impl HttpEmbedder {
+    pub fn new(url: String, model: String, timeout: Duration) -> anyhow::Result<Self> {
+        // The blocking builder panics on a runtime thread, and the server
+        // calls this from `main`'s, so build it on a thread of its own.
+        let client = std::thread::spawn(move || {
+            reqwest::blocking::Client::builder()
+                .timeout(timeout)
+                .build()
+        })
+        .join()
+        .map_err(|_| anyhow::anyhow!("embedding http client builder panicked"))?
+        .context("build embedding http client")?;
+        Ok(Self { url, model, client })
+    }
+
+    fn request(&self, text: &str, dim: usize) -> anyhow::Result<Vec<f32>> {
+        let body: serde_json::Value = self
+            .client
+            .post(&self.url)
+            .json(&serde_json::json!({"model": self.model, "input": text, "dim": dim}))
+            .send()?
+            .error_for_status()?
+            .json()?;
+        let vector = match &body {
+            serde_json::Value::Array(_) => &body,
+            _ => body
+                .get("embedding")
+                .or_else(|| body.pointer("/data/0/embedding"))
+                .context("no embedding in response")?,
+        };
+        let vector: Vec<f32> =
+            serde_json::from_value(vector.clone()).context("embedding is not a float array")?;
+        if vector.is_empty() {
+            bail!("empty embedding");
+        }
+        Ok(vector)
+    }
+}
+
+impl Embedder for HttpEmbedder {
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    fn embed(&self, text: &str, dim: usize) -> Vec<f32> {
+        if let Some(vec) = test_vec(text) {
+            return vec;
+        }
+        self.request(text, dim).unwrap_or_else(|err| {
+            tracing::warn!(url = %self.url, error = %format!("{err:#}"), "embedding request failed");
+            Vec::new()
+        })
+    }
+}
@@ -1,156 +1,272 @@
+use crate::config::Config;
+use crate::search::embedder::{self, Embedder, HashEmbedder};
 use crate::search::grouping::{extract_key, GroupKey, GroupedResults};
+use crate::search::index::AnnIndex;
 use crate::search::storage::AppendLog;
 use crate::search::types::{
-    Document, DocumentResponse, LanguageFilter, SearchRequest, SearchResponse, SearchResult,
+    Document, DocumentMetadata, DocumentResponse, LanguageFilter, SearchFilters, SearchMode,
+    SearchRequest, SearchResponse, SearchResult,
 };
-use rand::rngs::StdRng;
-use rand::{Rng, SeedableRng};
-use std::cmp::{Ordering, Reverse};
-use std::collections::{BinaryHeap, HashMap};
+use crate::vector::HnswParams;
+use lru::LruCache;
+use parking_lot::{Mutex, RwLock};
+use std::collections::HashMap;
+use std::num::NonZeroUsize;
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering as AtomicOrdering};
+use std::sync::Arc;
+
+pub const DEFAULT_EMBED_CACHE_SIZE: usize = 256;
+
+/// Dimension asked of the embedder for queries and re-embedded documents.
+const EMBED_DIM: usize = 384;
+
+/// Candidates asked of the index per wanted result, to leave room for the
+/// ones filters and version resolution drop.
+const CANDIDATE_OVERSAMPLE: usize = 4;
+const MIN_CANDIDATES: usize = 32;
+
+/// Share of the cosine score in `hybrid` mode; BM25 gets the rest.
+const HYBRID_VECTOR_WEIGHT: f32 = 0.5;
+
+/// Documents re-embedded between two progress log lines.
+const REEMBED_PROGRESS_EVERY: usize = 1000;
+
+type EmbedCache = LruCache<(String, String), Arc<Vec<f32>>>;
 
 pub struct SearchEngine {
     storage: AppendLog,
+    // Vectors and metadata of every record in `storage`, by offset.
+    index: RwLock<AnnIndex>,
+    embedder: RwLock<Arc<dyn Embedder>>,
+    // Held by appends and by `reembed`, so no document lands in the log
+    // while it is being rewritten.
+    write_lock: Mutex<()>,
+    // Searches read offsets from the log across several opens; `reembed`
+    // takes it exclusively to swap the file underneath them.
+    swap_lock: RwLock<()>,
+    // None when the cache is disabled (size 0).
+    embed_cache: Option<Mutex<EmbedCache>>,
+    embed_cache_hits: AtomicU64,
 }
 
 impl SearchEngine {
     pub fn new(data_dir: PathBuf) -> anyhow::Result<Self> {
+        Self::with_embedder(data_dir, Arc::new(HashEmbedder), DEFAULT_EMBED_CACHE_SIZE)
+    }
+
+    /// Opens the engine under `DATA_DIR` (default `data`) with the embedder
+    /// and cache size configured in `config`.
+    pub fn from_config(config: &Config) -> anyhow::Result<Self> {
+        let data_dir = config.data_dir.as_deref().unwrap_or("data");
+        Self::with_embedder(
+            PathBuf::from(data_dir),
+            embedder::from_config(config)?,
+            config.embed_cache_size,
+        )
+    }
+
+    pub fn with_embedder(
+        data_dir: PathBuf,
+        embedder: Arc<dyn Embedder>,
+        embed_cache_size: usize,
+    ) -> anyhow::Result<Self> {
         let path = data_dir.join("search").join("documents.log");
+        let storage = AppendLog::new(path)?;
+        let index = AnnIndex::build(&storage, HnswParams::default())?;
         Ok(Self {
-            storage: AppendLog::new(path)?,
+            storage,
+            index: RwLock::new(index),
+            embedder: RwLock::new(embedder),
+            write_lock: Mutex::new(()),
+            swap_lock: RwLock::new(()),
+            embed_cache: NonZeroUsize::new(embed_cache_size).map(|n| Mutex::new(LruCache::new(n))),
+            embed_cache_hits: AtomicU64::new(0),
         })
     }
 
+    pub fn embed_cache_hits(&self) -> u64 {
+        self.embed_cache_hits.load(AtomicOrdering::Relaxed)
+    }
+
+    pub fn render_metrics(&self) -> String {
+        format!(
+            "# TYPE embed_cache_hits_total counter\nembed_cache_hits_total {}\n",
+            self.embed_cache_hits()
+        )
+    }
+
     pub fn ingest(&self, doc: Document) -> anyhow::Result<()> {
-        self.storage.append(&doc)?;
+        let _w = self.write_lock.lock();
+        let offset = self.storage.append(&doc)?;
+        self.index.write().insert(offset, doc);
+        Ok(())
+    }
+
+    /// Stores `doc` as the only version of its id: earlier documents with
+    /// the same id stop matching searches.
+    pub fn update(&self, doc: Document) -> anyhow::Result<()> {
+        let _w = self.write_lock.lock();
+        let offset = self.storage.append_replacing(&doc)?;
+        let mut index = self.index.write();
+        index.remove(doc.id);
+        index.insert(offset, doc);
         Ok(())
     }
 
+    /// Appends a tombstone for `id`. Returns false, writing nothing, when no
+    /// document has that id.
+    pub fn delete(&self, id: u32) -> anyhow::Result<bool> {
+        let _w = self.write_lock.lock();
+        if !self.index.read().contains(id) {
+            return Ok(false);
+        }
+        self.storage.append_tombstone(id)?;
+        self.index.write().remove(id);
+        Ok(true)
+    }
+
+    /// Re-embeds the content of every stored document with `embedder`,
+    /// keeping ids, content and metadata, and swaps the rewritten log in
+    /// once it is complete. Queries use `embedder` from then on. Ingests
+    /// wait until it finishes; searches keep running against the old log.
+    /// Returns how many documents were re-embedded.
+    pub fn reembed(&self, embedder: Arc<dyn Embedder>) -> anyhow::Result<usize> {
+        let _w = self.write_lock.lock();
+        let started = std::time::Instant::now();
+        let model = embedder.model().to_string();
+        let mut done = 0usize;
+        let staged = self.storage.rewrite(|mut doc| {
+            doc.vector = embedder.embed(&doc.content, EMBED_DIM);
+            if doc.vector.is_empty() {
+                return Err(std::io::Error::other(format!(
+                    "embedder returned no vector for document {}",
+                    doc.id
+                )));
+            }
+            done += 1;
+            if done.is_multiple_of(REEMBED_PROGRESS_EVERY) {
+                tracing::info!(model = %model, done, "re-embedding documents");
+            }
+            Ok(doc)
+        })?;
+        let written = staged.documents;
+        {
+            let _swap = self.swap_lock.write();
+            staged.install()?;
+            *self.index.write() = AnnIndex::build(&self.storage, HnswParams::default())?;
+            *self.embedder.write() = embedder;
+            if let Some(cache) = &self.embed_cache {
+                cache.lock().clear();
+            }
+        }
+        tracing::info!(
+            model = %model,
+            documents = written,
+            elapsed_ms = started.elapsed().as_millis() as u64,
+            "re-embedded search documents"
+        );
+        Ok(written)
+    }
+
     pub fn search(&self, req: SearchRequest) -> anyhow::Result<SearchResponse> {
-        // 1. Embed
-        // Assume 384 dimensions for now.
-        let query_vector = self.embed(&req.query, 384);
+        let _swap = self.swap_lock.read();
+        let mode = req.search_mode()?;
+        // 1. Embed (keyword search never needs the vector)
+        let query_vector = match mode {
+            SearchMode::Keyword => Arc::new(Vec::new()),
+            SearchMode::Vector | SearchMode::Hybrid => {
+                let vector = self.embed(&req.query, EMBED_DIM);
+                if vector.is_empty() {
+                    anyhow::bail!("embedder returned no vector for the query");
+                }
+                vector
+            }
+        };
 
-        // 2. Filter & Version Resolution
-        // Map group_id -> (offset, processed_at, grouping_key)
-        let mut candidates = HashMap::new();
-        let mut ungroupped_candidates = Vec::new(); // (offset, grouping_key)
+        // 2. Candidates from the ANN and/or keyword index, filtered and
+        // version-resolved afterwards. If too few survive, ask for more
+        // until the indexes have none left.
+        let index = self.index.read();
+        let keyword_hits = match mode {
+            SearchMode::Vector => Vec::new(),
+            SearchMode::Keyword | SearchMode::Hybrid => index.keyword_search(&req.query),
+        };
+        let filters = req.filters.as_ref();
 
         // Default to "all" if not specified.
-        let version_policy = req
-            .filters
-            .as_ref()
+        let version_policy = filters
             .and_then(|f| f.version_policy.as_deref())
             .unwrap_or("all");
 
         let is_latest = version_policy == "latest";
         let group_field = req.group_by.as_deref();
 
-        let mut iter = self.storage.scan_metadata()?;
-        while let Some(res) = iter.next() {
-            let (offset, _id, meta) = res?;
+        let wanted = match group_field {
+            Some(_) => req.top_k.saturating_mul(req.group_limit.max(1)),
+            None => req.top_k,
+        };
+        let available = match mode {
+            SearchMode::Vector => index.points(query_vector.len()),
+            SearchMode::Keyword => keyword_hits.len(),
+            SearchMode::Hybrid => index.points(query_vector.len()).max(keyword_hits.len()),
+        };
+        let mut candidate_k = wanted
+            .saturating_mul(CANDIDATE_OVERSAMPLE)
+            .max(MIN_CANDIDATES);
+        let mut latest_by_group = HashMap::new();
 
-            // Filters
-            if let Some(filters) = &req.filters {
-                if let Some(c) = &filters.category {
-                    if meta.category.as_ref() != Some(c) {
-                        continue;
-                    }
+        // 3. Score
+        let results_vec = loop {
+            let mut eligible = Vec::new();
+            let candidates = match mode {
+                SearchMode::Vector => index.search(&query_vector, candidate_k),
+                SearchMode::Keyword => keyword_hits.iter().take(candidate_k).copied().collect(),
+                SearchMode::Hybrid => {
+                    self.hybrid_candidates(&index, &query_vector, &keyword_hits, candidate_k)?
                 }
-                if let Some(s) = &filters.status {
-                    if meta.status.as_ref() != Some(s) {
-                        continue;
-                    }
+            };
+            for (score, offset) in candidates {
+                let Some(meta) = index.metadata(offset) else {
+                    continue;
+                };
+                if !matches_filters(meta, filters) {
+                    continue;
                 }
-                if let Some(lang_filter) = &filters.language {
-                    match lang_filter {
-                        LanguageFilter::Single(l) => {
-                            if meta.language.as_ref() != Some(l) {
-                                continue;
-                            }
-                        }
-                        LanguageFilter::Multiple(langs) => {
-                            if let Some(l) = &meta.language {
-                                if !langs.contains(l) {
-                                    continue;
-                                }
-                            } else {
-                                continue;
-                            }
+                if is_latest {
+                    if let Some(gid) = meta.group_id {
+                        let latest = *latest_by_group
+                            .entry(gid)
+                            .or_insert_with(|| latest_version(&index, gid, filters));
+                        if latest != Some(offset) {
+                            continue;
                         }
                     }
                 }
+                eligible.push((score, offset, meta));
             }
 
-            let group_key = group_field.and_then(|f| extract_key(&meta, f));
-
-            if is_latest {
-                if let Some(gid) = meta.group_id {
-                    let pat = meta.processed_at.unwrap_or(0);
-                    candidates
-                        .entry(gid)
-                        .and_modify(|(off, old_pat, old_key)| {
-                            if pat > *old_pat {
-                                *off = offset;
-                                *old_pat = pat;
-                                *old_key = group_key.clone();
-                            }
-                        })
-                        .or_insert((offset, pat, group_key));
-                } else {
-                    ungroupped_candidates.push((offset, group_key));
+            let results: Vec<(f32, u64)> = if let Some(field) = group_field {
+                let mut grouped = GroupedResults::new(req.group_limit);
+                for (score, offset, meta) in eligible {
+                    // If key is None (field missing), treat as unique group
+                    let k = extract_key(meta, field).unwrap_or(GroupKey::Unique(offset));
+                    grouped.push(k, score, offset);
                 }
+                grouped.into_sorted_vec()
             } else {
-                ungroupped_candidates.push((offset, group_key));
+                eligible
+                    .into_iter()
+                    .take(req.top_k)
+                    .map(|(score, offset, _)| (score, offset))
+                    .collect()
+            };
+            if results.len() >= req.top_k || candidate_k >= available {
+                break results;
             }
-        }
-
-        let mut final_candidates = ungroupped_candidates;
-        if is_latest {
-            for (_, (off, _, key)) in candidates {
-                final_candidates.push((off, key));
-            }
-        }
-
-        // 3. Score
-        let mut results_vec: Vec<(f32, u64)> = Vec::new();
-
-        if group_field.is_some() {
-            let mut grouped = GroupedResults::new(req.group_limit);
-
-            for (offset, key) in final_candidates {
-                let vec = self.storage.read_vector(offset)?;
-                if vec.len() != query_vector.len() {
-                    continue;
-                }
-                let score = cosine_similarity(&query_vector, &vec);
-
-                // If key is None (field missing), treat as unique group
-                let k = key.unwrap_or(GroupKey::Unique(offset));
-                grouped.push(k, score, offset);
-            }
-            results_vec = grouped.into_sorted_vec();
-        } else {
-            // Min-heap of top-k results (stores Reverse(ScoredDoc) so smallest score is popped)
-            let mut heap = BinaryHeap::with_capacity(req.top_k + 1);
-
-            for (offset, _) in final_candidates {
-                let vec = self.storage.read_vector(offset)?;
-                if vec.len() != query_vector.len() {
-                    continue;
-                }
-                let score = cosine_similarity(&query_vector, &vec);
-
-                heap.push(Reverse(ScoredDoc { score, offset }));
-                if heap.len() > req.top_k {
-                    heap.pop();
-                }
-            }
-            let sorted_docs = heap.into_sorted_vec();
-            for Reverse(doc) in sorted_docs {
-                results_vec.push((doc.score, doc.offset));
-            }
-        }
+            candidate_k = candidate_k.saturating_mul(2);
+        };
+        drop(index);
 
         // 4. Retrieve
         // Truncate to top_k if needed (GroupedResults already sorted by group score, but we might have more than top_k items if group_limit > 1)
@@ -200,23 +316,65 @@ impl SearchEngine {
         })
     }
 
-    fn embed(&self, text: &str, dim: usize) -> Vec<f32> {
-        if text.starts_with("TEST_VEC:") {
-            let parts: Vec<&str> = text["TEST_VEC:".len()..].split(',').collect();
-            if let Ok(vec) = parts
-                .iter()
-                .map(|s| s.trim().parse::<f32>())
-                .collect::<Result<Vec<_>, _>>()
-            {
-                if !vec.is_empty() {
-                    return vec;
-                }
+    /// The `k` best vector and keyword candidates, merged and rescored as
+    /// `HYBRID_VECTOR_WEIGHT * cosine + (1 - HYBRID_VECTOR_WEIGHT) * bm25`,
+    /// with cosine clamped to `[0, 1]` and BM25 divided by the best keyword
+    /// score. Keyword-only hits get their cosine from the stored vector.
+    fn hybrid_candidates(
+        &self,
+        index: &AnnIndex,
+        query_vector: &[f32],
+        keyword_hits: &[(f32, u64)],
+        k: usize,
+    ) -> anyhow::Result<Vec<(f32, u64)>> {
+        let best_bm25 = keyword_hits.first().map_or(0.0, |(score, _)| *score);
+        let mut parts: HashMap<u64, (Option<f32>, f32)> = HashMap::new();
+        for (cosine, offset) in index.search(query_vector, k) {
+            parts.entry(offset).or_default().0 = Some(cosine);
+        }
+        for &(bm25, offset) in keyword_hits.iter().take(k) {
+            if best_bm25 > 0.0 {
+                parts.entry(offset).or_default().1 = bm25 / best_bm25;
             }
         }
+        let mut scored = Vec::with_capacity(parts.len());
+        for (offset, (cosine, bm25)) in parts {
+            let cosine = match cosine {
+                Some(cosine) => cosine,
+                None => {
+                    let vector = self.storage.read_vector(offset)?;
+                    if vector.len() == query_vector.len() {
+                        cosine_similarity(query_vector, &vector)
+                    } else {
+                        0.0
+                    }
+                }
+            };
+            let score =
+                HYBRID_VECTOR_WEIGHT * cosine.clamp(0.0, 1.0) + (1.0 - HYBRID_VECTOR_WEIGHT) * bm25;
+            scored.push((score, offset));
+        }
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0).then(a.1.cmp(&b.1)));
+        Ok(scored)
+    }
 
-        let hash = crc32fast::hash(text.as_bytes());
-        let mut rng = StdRng::seed_from_u64(hash as u64);
-        (0..dim).map(|_| rng.gen::<f32>()).collect()
+    fn embed(&self, text: &str, dim: usize) -> Arc<Vec<f32>> {
+        let embedder = self.embedder.read().clone();
+        let Some(cache) = &self.embed_cache else {
+            return Arc::new(embedder.embed(text, dim));
+        };
+        let key = (embedder.model().to_string(), text.to_string());
+        if let Some(hit) = cache.lock().get(&key) {
+            self.embed_cache_hits.fetch_add(1, AtomicOrdering::Relaxed);
+            return hit.clone();
+        }
+        // Embed outside the lock; a concurrent miss on the same text just
+        // computes it twice.
+        let vector = Arc::new(embedder.embed(text, dim));
+        if !vector.is_empty() {
+            cache.lock().put(key, vector.clone());
+        }
+        vector
     }
 }
 
@@ -231,24 +389,39 @@ fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
     }
 }
 
-struct ScoredDoc {
-    score: f32,
-    offset: u64,
-}
-
-impl PartialEq for ScoredDoc {
-    fn eq(&self, other: &Self) -> bool {
-        self.score == other.score
+fn matches_filters(meta: &DocumentMetadata, filters: Option<&SearchFilters>) -> bool {
+    let Some(filters) = filters else {
+        return true;
+    };
+    if filters.category.is_some() && meta.category != filters.category {
+        return false;
     }
-}
-impl Eq for ScoredDoc {}
-impl PartialOrd for ScoredDoc {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        self.score.partial_cmp(&other.score)
+    if filters.status.is_some() && meta.status != filters.status {
+        return false;
+    }
+    match (&filters.language, &meta.language) {
+        (None, _) => true,
+        (Some(LanguageFilter::Single(l)), lang) => lang.as_ref() == Some(l),
+        (Some(LanguageFilter::Multiple(langs)), Some(l)) => langs.contains(l),
+        (Some(LanguageFilter::Multiple(_)), None) => false,
     }
 }
-impl Ord for ScoredDoc {
-    fn cmp(&self, other: &Self) -> Ordering {
-        self.partial_cmp(other).unwrap_or(Ordering::Equal)
+
+/// Offset of the newest version of `group_id` among those passing
+/// `filters`: highest `processed_at`, the earliest in the log on ties.
+fn latest_version(index: &AnnIndex, group_id: u32, filters: Option<&SearchFilters>) -> Option<u64> {
+    let mut latest: Option<(u64, u64)> = None;
+    for &offset in index.group(group_id) {
+        let Some(meta) = index.metadata(offset) else {
+            continue;
+        };
+        if !matches_filters(meta, filters) {
+            continue;
+        }
+        let pat = meta.processed_at.unwrap_or(0);
+        if latest.is_none_or(|(_, best)| pat > best) {
+            latest = Some((offset, pat));
+        }
     }
+    latest.map(|(offset, _)| offset)
 }
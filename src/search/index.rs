@@ -0,0 +1,156 @@
+use crate::search::keyword::KeywordIndex;
+use crate::search::storage::AppendLog;
+use crate::search::types::{Document, DocumentMetadata};
+use crate::vector::HnswParams;
+use anndists::dist::distances::DistCosine;
+use hnsw_rs::prelude::Hnsw;
+use std::collections::HashMap;
+
+/// Size hint handed to each graph; it grows past it if needed.
+const GRAPH_CAPACITY_HINT: usize = 10_000;
+const GRAPH_MAX_LAYERS: usize = 16;
+
+struct Graph {
+    hnsw: Hnsw<'static, f32, DistCosine>,
+    /// HNSW data id -> offset of the record in the log.
+    offsets: Vec<u64>,
+}
+
+/// In-memory HNSW over the document log. Documents are keyed by the byte
+/// offset of their record and split into one graph per vector dimension,
+/// since a query only ever matches vectors of its own length. Metadata is
+/// kept alongside so filters and version resolution never touch the log,
+/// and content feeds a keyword index for BM25.
+pub struct AnnIndex {
+    params: HnswParams,
+    graphs: HashMap<usize, Graph>,
+    keywords: KeywordIndex,
+    docs: HashMap<u64, DocumentMetadata>,
+    /// Document id -> offsets of its live records.
+    ids: HashMap<u32, Vec<u64>>,
+    /// group_id -> offsets of its versions, in log order.
+    groups: HashMap<u32, Vec<u64>>,
+}
+
+impl AnnIndex {
+    pub fn new(params: HnswParams) -> Self {
+        Self {
+            params,
+            graphs: HashMap::new(),
+            keywords: KeywordIndex::default(),
+            docs: HashMap::new(),
+            ids: HashMap::new(),
+            groups: HashMap::new(),
+        }
+    }
+
+    /// Indexes every record of `log` via `scan_metadata`.
+    pub fn build(log: &AppendLog, params: HnswParams) -> std::io::Result<Self> {
+        let mut index = Self::new(params);
+        for res in log.scan_metadata()? {
+            let (offset, _id, _meta) = res?;
+            index.insert(offset, log.read_document(offset)?);
+        }
+        Ok(index)
+    }
+
+    pub fn insert(&mut self, offset: u64, doc: Document) {
+        let Document {
+            id,
+            vector,
+            content,
+            metadata: meta,
+        } = doc;
+        if !vector.is_empty() {
+            let params = self.params;
+            let graph = self.graphs.entry(vector.len()).or_insert_with(|| Graph {
+                hnsw: Hnsw::new(
+                    params.max_nb_conn,
+                    GRAPH_CAPACITY_HINT,
+                    GRAPH_MAX_LAYERS,
+                    params.ef_construction,
+                    DistCosine {},
+                ),
+                offsets: Vec::new(),
+            });
+            graph.hnsw.insert_slice((&vector, graph.offsets.len()));
+            graph.offsets.push(offset);
+        }
+        if let Some(gid) = meta.group_id {
+            self.groups.entry(gid).or_default().push(offset);
+        }
+        self.keywords.insert(offset, &content);
+        self.ids.entry(id).or_default().push(offset);
+        self.docs.insert(offset, meta);
+    }
+
+    /// Forgets every record of `id` and returns how many there were. Their
+    /// graph nodes stay behind and are skipped by `search` until the next
+    /// rebuild.
+    pub fn remove(&mut self, id: u32) -> usize {
+        let Some(offsets) = self.ids.remove(&id) else {
+            return 0;
+        };
+        for offset in &offsets {
+            self.keywords.remove(*offset);
+            let Some(meta) = self.docs.remove(offset) else {
+                continue;
+            };
+            if let Some(gid) = meta.group_id {
+                if let Some(group) = self.groups.get_mut(&gid) {
+                    group.retain(|o| o != offset);
+                    if group.is_empty() {
+                        self.groups.remove(&gid);
+                    }
+                }
+            }
+        }
+        offsets.len()
+    }
+
+    /// Indexed vectors with `dim` components, removed ones included.
+    pub fn points(&self, dim: usize) -> usize {
+        self.graphs.get(&dim).map_or(0, |g| g.offsets.len())
+    }
+
+    /// Up to `k` `(score, offset)` pairs nearest to `query`, best first; the
+    /// score is the cosine similarity. Removed records still show up here;
+    /// `metadata` tells them apart.
+    pub fn search(&self, query: &[f32], k: usize) -> Vec<(f32, u64)> {
+        let Some(graph) = self.graphs.get(&query.len()) else {
+            return Vec::new();
+        };
+        if k == 0 || graph.offsets.is_empty() {
+            return Vec::new();
+        }
+        graph
+            .hnsw
+            .search(query, k, self.params.search_ef(k))
+            .into_iter()
+            .filter_map(|n| {
+                graph
+                    .offsets
+                    .get(n.d_id)
+                    .map(|off| (1.0 - n.distance, *off))
+            })
+            .collect()
+    }
+
+    /// BM25 hits for `query`, best first.
+    pub fn keyword_search(&self, query: &str) -> Vec<(f32, u64)> {
+        self.keywords.search(query)
+    }
+
+    pub fn metadata(&self, offset: u64) -> Option<&DocumentMetadata> {
+        self.docs.get(&offset)
+    }
+
+    pub fn contains(&self, id: u32) -> bool {
+        self.ids.contains_key(&id)
+    }
+
+    /// Offsets of every version of `group_id`, in log order.
+    pub fn group(&self, group_id: u32) -> &[u64] {
+        self.groups.get(&group_id).map_or(&[], Vec::as_slice)
+    }
+}
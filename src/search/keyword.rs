@@ -0,0 +1,89 @@
+use std::collections::{HashMap, HashSet};
+
+const BM25_K1: f32 = 1.2;
+const BM25_B: f32 = 0.75;
+
+/// Lowercased runs of alphanumeric characters.
+pub fn tokenize(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|t| !t.is_empty())
+        .map(str::to_lowercase)
+}
+
+/// Inverted index over document content, keyed by record offset like
+/// `AnnIndex`, scored with BM25.
+#[derive(Default)]
+pub struct KeywordIndex {
+    /// term -> offset -> occurrences in that document.
+    postings: HashMap<String, HashMap<u64, u32>>,
+    /// offset -> token count; also the set of indexed documents.
+    lengths: HashMap<u64, u32>,
+    /// offset -> its distinct terms, to find its postings on removal.
+    terms: HashMap<u64, Vec<String>>,
+    total_len: u64,
+}
+
+impl KeywordIndex {
+    pub fn insert(&mut self, offset: u64, content: &str) {
+        let mut counts: HashMap<String, u32> = HashMap::new();
+        let mut len = 0u32;
+        for term in tokenize(content) {
+            *counts.entry(term).or_default() += 1;
+            len += 1;
+        }
+        let mut terms = Vec::with_capacity(counts.len());
+        for (term, tf) in counts {
+            self.postings
+                .entry(term.clone())
+                .or_default()
+                .insert(offset, tf);
+            terms.push(term);
+        }
+        self.terms.insert(offset, terms);
+        self.lengths.insert(offset, len);
+        self.total_len += u64::from(len);
+    }
+
+    pub fn remove(&mut self, offset: u64) {
+        let Some(len) = self.lengths.remove(&offset) else {
+            return;
+        };
+        self.total_len -= u64::from(len);
+        for term in self.terms.remove(&offset).unwrap_or_default() {
+            if let Some(docs) = self.postings.get_mut(&term) {
+                docs.remove(&offset);
+                if docs.is_empty() {
+                    self.postings.remove(&term);
+                }
+            }
+        }
+    }
+
+    /// Every document containing at least one term of `query` as
+    /// `(score, offset)`, best first. Repeated query terms count once.
+    pub fn search(&self, query: &str) -> Vec<(f32, u64)> {
+        let docs = self.lengths.len() as f32;
+        if docs == 0.0 {
+            return Vec::new();
+        }
+        let avg_len = (self.total_len as f32 / docs).max(1.0);
+        let mut scores: HashMap<u64, f32> = HashMap::new();
+        for term in tokenize(query).collect::<HashSet<_>>() {
+            let Some(postings) = self.postings.get(&term) else {
+                continue;
+            };
+            let df = postings.len() as f32;
+            let idf = (1.0 + (docs - df + 0.5) / (df + 0.5)).ln();
+            for (&offset, &tf) in postings {
+                let tf = tf as f32;
+                let len = self.lengths.get(&offset).copied().unwrap_or(0) as f32;
+                let norm = BM25_K1 * (1.0 - BM25_B + BM25_B * len / avg_len);
+                *scores.entry(offset).or_default() += idf * tf * (BM25_K1 + 1.0) / (tf + norm);
+            }
+        }
+        let mut hits: Vec<(f32, u64)> = scores.into_iter().map(|(o, s)| (s, o)).collect();
+        // Offsets break ties so equal scores come back in log order.
+        hits.sort_by(|a, b| b.0.total_cmp(&a.0).then(a.1.cmp(&b.1)));
+        hits
+    }
+}
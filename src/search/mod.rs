@@ -1,4 +1,7 @@
+pub mod embedder;
 pub mod engine;
+pub mod index;
+pub mod keyword;
 pub mod storage;
 pub mod types;
 pub mod grouping;
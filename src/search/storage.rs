@@ -1,8 +1,14 @@
 use crate::search::types::{Document, DocumentMetadata};
+use std::collections::HashMap;
 use std::fs::{File, OpenOptions};
-use std::io::{self, BufReader, Read, Seek, SeekFrom, Write};
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
 use std::path::PathBuf;
 
+/// Stands in for `MetaLen` to mark a tombstone: `[TotalLen:4][MARKER:4][Id:4]`.
+/// It removes every earlier record with that id.
+const TOMBSTONE_MARKER: u32 = u32::MAX;
+const TOMBSTONE_LEN: u32 = 8;
+
 pub struct AppendLog {
     path: PathBuf,
 }
@@ -15,49 +21,96 @@ impl AppendLog {
         Ok(Self { path })
     }
 
-    pub fn append(&self, doc: &Document) -> io::Result<()> {
+    /// Appends `doc` and returns the offset of its record. Callers serialize
+    /// appends, so the file length read first is where it lands.
+    pub fn append(&self, doc: &Document) -> io::Result<u64> {
         let mut file = OpenOptions::new()
             .create(true)
             .append(true)
             .open(&self.path)?;
+        let offset = file.metadata()?.len();
+        write_record(&mut file, doc)?;
+        Ok(offset)
+    }
 
-        let meta_data = (doc.id, &doc.metadata);
-        let meta_bytes = serde_json::to_vec(&meta_data)?;
-        let vector_bytes = bincode::serialize(&doc.vector)
-            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
-        let content_bytes = doc.content.as_bytes();
-
-        let meta_len = meta_bytes.len() as u32;
-        let vector_len = vector_bytes.len() as u32;
-        let content_len = content_bytes.len() as u32;
-
-        // Total len excluding the TotalLen field itself
-        // Structure: [TotalLen:4][MetaLen:4][MetaBytes][VectorLen:4][VectorBytes][ContentBytes]
-        let total_len = 4 + meta_len + 4 + vector_len + content_len;
+    /// Appends a tombstone for `id`.
+    pub fn append_tombstone(&self, id: u32) -> io::Result<()> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        write_tombstone(&mut file, id)
+    }
 
-        file.write_all(&total_len.to_le_bytes())?;
-        file.write_all(&meta_len.to_le_bytes())?;
-        file.write_all(&meta_bytes)?;
-        file.write_all(&vector_len.to_le_bytes())?;
-        file.write_all(&vector_bytes)?;
-        file.write_all(content_bytes)?;
+    /// Appends a tombstone for `doc.id` followed by `doc` in a single write,
+    /// so the new version replaces the old ones or neither lands. Returns the
+    /// offset of the document record.
+    pub fn append_replacing(&self, doc: &Document) -> io::Result<u64> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        let offset = file.metadata()?.len();
+        let mut buf = Vec::new();
+        write_tombstone(&mut buf, doc.id)?;
+        write_record(&mut buf, doc)?;
+        file.write_all(&buf)?;
+        Ok(offset + u64::from(4 + TOMBSTONE_LEN))
+    }
 
-        Ok(())
+    /// Passes every stored document through `map` in log order and writes
+    /// the results, synced, next to the log. Nothing changes for readers
+    /// until `StagedLog::install` renames it over the current one.
+    pub fn rewrite(
+        &self,
+        mut map: impl FnMut(Document) -> io::Result<Document>,
+    ) -> io::Result<StagedLog> {
+        let tmp = self.path.with_extension("log.tmp");
+        let mut out = BufWriter::new(File::create(&tmp)?);
+        let mut documents = 0;
+        for res in self.scan_metadata()? {
+            let (offset, _id, _meta) = res?;
+            let doc = map(self.read_document(offset)?)?;
+            write_record(&mut out, &doc)?;
+            documents += 1;
+        }
+        let file = out.into_inner().map_err(|e| e.into_error())?;
+        file.sync_all()?;
+        Ok(StagedLog {
+            tmp,
+            path: self.path.clone(),
+            documents,
+        })
     }
 
+    /// Live documents in log order, skipping those removed by a later
+    /// tombstone. Tombstones are collected in a first pass over the log.
     pub fn scan_metadata(&self) -> io::Result<MetadataIterator> {
+        let mut tombstones = HashMap::new();
+        for entry in self.scan_entries()? {
+            if let Entry::Tombstone { offset, id } = entry? {
+                tombstones.insert(id, offset);
+            }
+        }
+        Ok(MetadataIterator {
+            entries: self.scan_entries()?,
+            tombstones,
+        })
+    }
+
+    fn scan_entries(&self) -> io::Result<EntryIterator> {
         // If file doesn't exist, return empty iterator logic (handle in open)
         let file = match File::open(&self.path) {
             Ok(f) => f,
             Err(e) if e.kind() == io::ErrorKind::NotFound => {
-                return Ok(MetadataIterator {
+                return Ok(EntryIterator {
                     reader: None,
                     offset: 0,
                 });
             }
             Err(e) => return Err(e),
         };
-        Ok(MetadataIterator {
+        Ok(EntryIterator {
             reader: Some(BufReader::new(file)),
             offset: 0,
         })
@@ -151,14 +204,97 @@ impl AppendLog {
     }
 }
 
+/// A fully written replacement for the log, see `AppendLog::rewrite`.
+pub struct StagedLog {
+    tmp: PathBuf,
+    path: PathBuf,
+    pub documents: usize,
+}
+
+impl StagedLog {
+    /// Atomically replaces the log; offsets read from the old one are stale
+    /// afterwards.
+    pub fn install(self) -> io::Result<()> {
+        std::fs::rename(&self.tmp, &self.path)
+    }
+}
+
+fn write_record(out: &mut impl Write, doc: &Document) -> io::Result<()> {
+    let meta_data = (doc.id, &doc.metadata);
+    let meta_bytes = serde_json::to_vec(&meta_data)?;
+    let vector_bytes = bincode::serialize(&doc.vector)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    let content_bytes = doc.content.as_bytes();
+
+    let meta_len = meta_bytes.len() as u32;
+    let vector_len = vector_bytes.len() as u32;
+    let content_len = content_bytes.len() as u32;
+
+    // Total len excluding the TotalLen field itself
+    // Structure: [TotalLen:4][MetaLen:4][MetaBytes][VectorLen:4][VectorBytes][ContentBytes]
+    let total_len = 4 + meta_len + 4 + vector_len + content_len;
+
+    out.write_all(&total_len.to_le_bytes())?;
+    out.write_all(&meta_len.to_le_bytes())?;
+    out.write_all(&meta_bytes)?;
+    out.write_all(&vector_len.to_le_bytes())?;
+    out.write_all(&vector_bytes)?;
+    out.write_all(content_bytes)?;
+
+    Ok(())
+}
+
+fn write_tombstone(out: &mut impl Write, id: u32) -> io::Result<()> {
+    out.write_all(&TOMBSTONE_LEN.to_le_bytes())?;
+    out.write_all(&TOMBSTONE_MARKER.to_le_bytes())?;
+    out.write_all(&id.to_le_bytes())
+}
+
 pub struct MetadataIterator {
-    reader: Option<BufReader<File>>,
-    offset: u64,
+    entries: EntryIterator,
+    /// id -> offset of its last tombstone.
+    tombstones: HashMap<u32, u64>,
 }
 
 impl Iterator for MetadataIterator {
     type Item = io::Result<(u64, u32, DocumentMetadata)>;
 
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.entries.next()? {
+                Ok(Entry::Document { offset, id, meta }) => {
+                    if self.tombstones.get(&id).is_some_and(|&t| t > offset) {
+                        continue;
+                    }
+                    return Some(Ok((offset, id, meta)));
+                }
+                Ok(Entry::Tombstone { .. }) => continue,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+enum Entry {
+    Document {
+        offset: u64,
+        id: u32,
+        meta: DocumentMetadata,
+    },
+    Tombstone {
+        offset: u64,
+        id: u32,
+    },
+}
+
+struct EntryIterator {
+    reader: Option<BufReader<File>>,
+    offset: u64,
+}
+
+impl Iterator for EntryIterator {
+    type Item = io::Result<Entry>;
+
     fn next(&mut self) -> Option<Self::Item> {
         let reader = match &mut self.reader {
             Some(r) => r,
@@ -185,6 +321,17 @@ impl Iterator for MetadataIterator {
         let meta_len = u32::from_le_bytes(len_buf);
         self.offset += 4;
 
+        if meta_len == TOMBSTONE_MARKER {
+            if let Err(e) = reader.read_exact(&mut len_buf) {
+                return Some(Err(e));
+            }
+            self.offset += 4;
+            return Some(Ok(Entry::Tombstone {
+                offset: start_offset,
+                id: u32::from_le_bytes(len_buf),
+            }));
+        }
+
         // Read Meta
         let mut meta_buf = vec![0u8; meta_len as usize];
         if let Err(e) = reader.read_exact(&mut meta_buf) {
@@ -204,6 +351,10 @@ impl Iterator for MetadataIterator {
             Err(e) => return Some(Err(io::Error::new(io::ErrorKind::InvalidData, e))),
         };
 
-        Some(Ok((start_offset, id, metadata)))
+        Some(Ok(Entry::Document {
+            offset: start_offset,
+            id,
+            meta: metadata,
+        }))
     }
 }
@@ -52,6 +52,42 @@ pub struct SearchRequest {
     pub group_by: Option<String>,
     #[serde(default = "default_group_limit")]
     pub group_limit: usize,
+    /// `vector` (default), `keyword` or `hybrid`, see `SearchMode`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode: Option<String>,
+}
+
+impl SearchRequest {
+    pub fn search_mode(&self) -> anyhow::Result<SearchMode> {
+        match self.mode.as_deref() {
+            None => Ok(SearchMode::Vector),
+            Some(mode) => SearchMode::parse(mode).ok_or_else(|| {
+                anyhow::anyhow!("unknown search mode `{mode}`, expected vector, keyword or hybrid")
+            }),
+        }
+    }
+}
+
+/// How `/search` ranks documents.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchMode {
+    /// Cosine between the embedded query and the document vectors.
+    Vector,
+    /// BM25 over the document content; the query is not embedded.
+    Keyword,
+    /// Both, blended: cosine and BM25 normalized by the best keyword score.
+    Hybrid,
+}
+
+impl SearchMode {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "vector" => Some(Self::Vector),
+            "keyword" | "bm25" => Some(Self::Keyword),
+            "hybrid" => Some(Self::Hybrid),
+            _ => None,
+        }
+    }
 }
 
 fn default_top_k() -> usize {
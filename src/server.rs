@@ -4,7 +4,7 @@ use rust_kiss_vdb::search::engine::SearchEngine;
 use rust_kiss_vdb::sqlite::SqliteService;
 use std::net::SocketAddr;
 use std::fs;
-use std::path::{Path, PathBuf};
+use std::path::Path;
 use std::sync::Arc;
 use tokio_util::sync::CancellationToken;
 
@@ -15,14 +15,15 @@ pub async fn run(config: Config) -> anyhow::Result<()> {
         tracing::info!("💽 Data Directory: {}", abs_path.display());
     }
 
-    let sqlite = if config.sqlite_enabled {
-        Some(init_sqlite(&config)?)
+    let (sqlite, auth_sqlite) = if config.sqlite_enabled {
+        let (sql, auth) = init_sqlite(&config).await?;
+        (Some(sql), Some(auth))
     } else {
-        None
+        (None, None)
     };
 
-    let auth_store = if let Some(svc) = &sqlite {
-        let store = Arc::new(rust_kiss_vdb::api::auth_store::AuthStore::new(Arc::new(svc.clone())));
+    let auth_store = if let Some(svc) = auth_sqlite {
+        let store = Arc::new(rust_kiss_vdb::api::auth_store::AuthStore::new(Arc::new(svc)));
         store.init().await?;
         // Ensure the key configured in env/args (default "dev") exists
         store.ensure_bootstrap_key(&config.api_key).await?;
@@ -34,8 +35,7 @@ pub async fn run(config: Config) -> anyhow::Result<()> {
     let shutdown_token = CancellationToken::new();
     let engine = Engine::new(config.clone(), shutdown_token.clone())?;
 
-    let data_dir = config.data_dir.clone().map(PathBuf::from).unwrap_or(PathBuf::from("data"));
-    let search_engine = Arc::new(SearchEngine::new(data_dir)?);
+    let search_engine = Arc::new(SearchEngine::from_config(&config)?);
 
     let app = rust_kiss_vdb::api::router(engine.clone(), config.clone(), sqlite, search_engine, auth_store);
     let addr = SocketAddr::new(config.bind_addr, config.port);
@@ -45,7 +45,7 @@ pub async fn run(config: Config) -> anyhow::Result<()> {
 
     let listener = tokio::net::TcpListener::bind(addr).await?;
     axum::serve(listener, app)
-        .with_graceful_shutdown(shutdown_signal(shutdown_token))
+        .with_graceful_shutdown(shutdown_signal(engine))
         .await?;
 
     tracing::info!("Server stopped.");
@@ -64,7 +64,10 @@ fn ensure_data_dir(path: &str) -> anyhow::Result<()> {
     Ok(())
 }
 
-fn init_sqlite(config: &Config) -> anyhow::Result<SqliteService> {
+/// Returns the service behind `/v1/sql/*` and the one the `AuthStore` writes
+/// API keys through. They are the same connection unless `SQLITE_READONLY`
+/// is set; the writable one is opened first so the file exists either way.
+async fn init_sqlite(config: &Config) -> anyhow::Result<(SqliteService, SqliteService)> {
     let path = config.sqlite_path.clone()
         .or_else(|| {
             config.data_dir
@@ -73,10 +76,22 @@ fn init_sqlite(config: &Config) -> anyhow::Result<SqliteService> {
         })
         .ok_or_else(|| anyhow::anyhow!("SQLITE_ENABLED requiere DATA_DIR o SQLITE_DB_PATH"))?;
 
-    SqliteService::new(path)
+    let writable = SqliteService::new(&path)?.with_busy_retries(config.sqlite_busy_retries);
+    if let Some(dir) = &config.sqlite_migrations_dir {
+        let migrations = rust_kiss_vdb::sqlite::read_migrations_dir(dir)?;
+        let migrations: Vec<&str> = migrations.iter().map(String::as_str).collect();
+        let applied = writable.run_migrations(&migrations).await?;
+        tracing::info!(dir, applied, total = migrations.len(), "SQLite migrations");
+    }
+    if !config.sqlite_readonly {
+        return Ok((writable.clone(), writable));
+    }
+    tracing::info!("SQLite endpoints are read-only (SQLITE_READONLY)");
+    let read_only = SqliteService::open(&path, true)?.with_busy_retries(config.sqlite_busy_retries);
+    Ok((read_only, writable))
 }
 
-async fn shutdown_signal(token: CancellationToken) {
+async fn shutdown_signal(engine: Engine) {
     let ctrl_c = async {
         tokio::signal::ctrl_c()
             .await
@@ -102,7 +117,14 @@ async fn shutdown_signal(token: CancellationToken) {
         },
     }
     
-    token.cancel();
+    // Drain the commit path before axum stops: writes still running finish and
+    // become durable, later ones get 503 instead of being lost on exit.
+    let drained = tokio::task::spawn_blocking(move || engine.drain()).await;
+    match drained {
+        Ok(Ok(())) => tracing::info!("engine drained"),
+        Ok(Err(err)) => tracing::error!(error = %err, "engine drain failed"),
+        Err(err) => tracing::error!(error = %err, "engine drain task failed"),
+    }
 
     // Force exit if graceful shutdown takes too long (e.g. open streams)
     tokio::spawn(async {
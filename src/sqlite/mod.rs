@@ -1,108 +1,417 @@
-use base64::engine::general_purpose::STANDARD_NO_PAD;
+use anyhow::Context;
+use base64::alphabet;
+use base64::engine::general_purpose::{GeneralPurpose, GeneralPurposeConfig, STANDARD_NO_PAD};
+use base64::engine::DecodePaddingMode;
 use base64::Engine;
-use rusqlite::types::{Value, ValueRef};
-use rusqlite::{params_from_iter, Connection, Row};
+use rusqlite::types::{ToSql, Value, ValueRef};
+use rusqlite::{
+    params_from_iter, Connection, ErrorCode, OpenFlags, Row, Rows, Statement, TransactionBehavior,
+};
+use serde::Deserialize;
+use std::collections::HashSet;
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+const BUSY_BACKOFF_START: Duration = Duration::from_millis(10);
+const BUSY_BACKOFF_MAX: Duration = Duration::from_secs(1);
+
+/// Decodes `$blob` params. Blobs are read back unpadded, so padding is
+/// optional to let a value read from a row be written again as is.
+const BLOB_PARAM: GeneralPurpose = GeneralPurpose::new(
+    &alphabet::STANDARD,
+    GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent),
+);
+
+/// One write of a `SqliteService::transaction`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SqlStatement {
+    pub sql: String,
+    #[serde(default)]
+    pub params: Vec<serde_json::Value>,
+    #[serde(default)]
+    pub named_params: Option<serde_json::Map<String, serde_json::Value>>,
+}
+
+/// Values bound to a statement: `?` placeholders in order, or `:name`
+/// placeholders by name. A name without a `:`, `@` or `$` prefix gets `:`.
+#[derive(Debug, Clone)]
+pub enum SqlParams {
+    Positional(Vec<serde_json::Value>),
+    Named(serde_json::Map<String, serde_json::Value>),
+}
+
+impl SqlParams {
+    /// Picks the binding style of a request body; sending non-empty
+    /// `params` and `named_params` together is an error.
+    pub fn from_request(
+        params: Option<Vec<serde_json::Value>>,
+        named_params: Option<serde_json::Map<String, serde_json::Value>>,
+    ) -> anyhow::Result<Self> {
+        let params = params.unwrap_or_default();
+        match named_params {
+            Some(_) if !params.is_empty() => Err(anyhow::anyhow!(
+                "use either params or named_params, not both"
+            )),
+            Some(named) => Ok(Self::Named(named)),
+            None => Ok(Self::Positional(params)),
+        }
+    }
+}
+
+impl From<Vec<serde_json::Value>> for SqlParams {
+    fn from(params: Vec<serde_json::Value>) -> Self {
+        Self::Positional(params)
+    }
+}
+
+/// `SqlParams` converted to SQLite values, ready to bind.
+enum BoundParams {
+    Positional(Vec<Value>),
+    Named(Vec<(String, Value)>),
+}
+
+impl BoundParams {
+    fn new(params: SqlParams) -> anyhow::Result<Self> {
+        Ok(match params {
+            SqlParams::Positional(values) => Self::Positional(json_params_to_values(values)?),
+            SqlParams::Named(named) => {
+                let mut out = Vec::with_capacity(named.len());
+                for (name, value) in named {
+                    let name = if name.starts_with([':', '@', '$']) {
+                        name
+                    } else {
+                        format!(":{name}")
+                    };
+                    out.push((name, json_to_value(value)?));
+                }
+                Self::Named(out)
+            }
+        })
+    }
+
+    fn query<'s>(&self, stmt: &'s mut Statement<'_>) -> rusqlite::Result<Rows<'s>> {
+        match self {
+            Self::Positional(values) => stmt.query(params_from_iter(values.iter())),
+            Self::Named(named) => stmt.query(named_refs(named).as_slice()),
+        }
+    }
+
+    fn execute(&self, conn: &Connection, sql: &str) -> rusqlite::Result<usize> {
+        match self {
+            Self::Positional(values) => conn.execute(sql, params_from_iter(values.iter())),
+            Self::Named(named) => conn.execute(sql, named_refs(named).as_slice()),
+        }
+    }
+}
+
+fn named_refs(named: &[(String, Value)]) -> Vec<(&str, &dyn ToSql)> {
+    named
+        .iter()
+        .map(|(name, value)| (name.as_str(), value as &dyn ToSql))
+        .collect()
+}
 
 #[derive(Clone)]
 pub struct SqliteService {
     conn: Arc<Mutex<Connection>>,
     path: PathBuf,
+    busy_retries: u32,
 }
 
 impl SqliteService {
     pub fn new(db_path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        Self::open(db_path, false)
+    }
+
+    /// Like `new`, but `read_only` opens the file with
+    /// `SQLITE_OPEN_READ_ONLY`: it must already exist and every write fails
+    /// inside SQLite, whatever the statement.
+    pub fn open(db_path: impl AsRef<Path>, read_only: bool) -> anyhow::Result<Self> {
         let db_path = db_path.as_ref().to_path_buf();
-        if let Some(parent) = db_path.parent() {
-            std::fs::create_dir_all(parent)?;
-        }
-        let conn = Connection::open(&db_path)?;
-        conn.pragma_update(None, "journal_mode", &"WAL")?;
-        conn.pragma_update(None, "synchronous", &"NORMAL")?;
+        let conn = if read_only {
+            let flags = OpenFlags::SQLITE_OPEN_READ_ONLY
+                | OpenFlags::SQLITE_OPEN_URI
+                | OpenFlags::SQLITE_OPEN_NO_MUTEX;
+            Connection::open_with_flags(&db_path, flags)
+                .with_context(|| format!("open sqlite read-only at {}", db_path.display()))?
+        } else {
+            if let Some(parent) = db_path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            let conn = Connection::open(&db_path)?;
+            conn.pragma_update(None, "journal_mode", &"WAL")?;
+            conn.pragma_update(None, "synchronous", &"NORMAL")?;
+            conn
+        };
         conn.busy_timeout(std::time::Duration::from_secs(5))?;
         Ok(Self {
             conn: Arc::new(Mutex::new(conn)),
             path: db_path,
+            busy_retries: 0,
         })
     }
 
+    /// Retries statements that fail with `SQLITE_BUSY`/`SQLITE_LOCKED` up to
+    /// `retries` more times, doubling the pause between attempts.
+    pub fn with_busy_retries(mut self, retries: u32) -> Self {
+        self.busy_retries = retries;
+        self
+    }
+
+    /// How long SQLite itself waits on a lock before reporting busy (5s by default).
+    pub fn with_busy_timeout(self, timeout: Duration) -> anyhow::Result<Self> {
+        self.conn
+            .lock()
+            .map_err(|_| anyhow::anyhow!("sqlite lock poisoned"))?
+            .busy_timeout(timeout)?;
+        Ok(self)
+    }
+
     pub async fn query(
         &self,
         sql: String,
-        params: Vec<serde_json::Value>,
+        params: impl Into<SqlParams>,
     ) -> anyhow::Result<Vec<serde_json::Value>> {
         let conn = self.conn.clone();
+        let retries = self.busy_retries;
+        let params = params.into();
         tokio::task::spawn_blocking(move || {
             let conn = conn
                 .lock()
                 .map_err(|_| anyhow::anyhow!("sqlite lock poisoned"))?;
-            let mut stmt = conn.prepare(&sql)?;
-            let columns = stmt
-                .column_names()
-                .iter()
-                .map(|s| s.to_string())
-                .collect::<Vec<_>>();
-            let values = json_params_to_values(params)?;
-            let mut rows = stmt.query(params_from_iter(values.iter()))?;
-            let mut out = Vec::new();
-            while let Some(row) = rows.next()? {
-                out.push(row_to_json(row, &columns)?);
-            }
-            Ok(out)
+            let bound = BoundParams::new(params)?;
+            retry_busy(retries, || {
+                let mut stmt = conn.prepare(&sql)?;
+                let columns = stmt
+                    .column_names()
+                    .iter()
+                    .map(|s| s.to_string())
+                    .collect::<Vec<_>>();
+                let mut rows = bound.query(&mut stmt)?;
+                let mut out = Vec::new();
+                while let Some(row) = rows.next()? {
+                    out.push(row_to_json(row, &columns)?);
+                }
+                Ok(out)
+            })
         })
         .await
         .map_err(|err| anyhow::anyhow!(err))?
     }
 
-    pub async fn execute(
-        &self,
-        sql: String,
-        params: Vec<serde_json::Value>,
-    ) -> anyhow::Result<u64> {
+    pub async fn execute(&self, sql: String, params: impl Into<SqlParams>) -> anyhow::Result<u64> {
         let conn = self.conn.clone();
+        let retries = self.busy_retries;
+        let params = params.into();
         tokio::task::spawn_blocking(move || {
             let conn = conn
                 .lock()
                 .map_err(|_| anyhow::anyhow!("sqlite lock poisoned"))?;
-            let values = json_params_to_values(params)?;
-            let affected = conn.execute(&sql, params_from_iter(values.iter()))?;
-            Ok(affected as u64)
+            let bound = BoundParams::new(params)?;
+            retry_busy(retries, || {
+                let affected = bound.execute(&conn, &sql)?;
+                Ok(affected as u64)
+            })
+        })
+        .await
+        .map_err(|err| anyhow::anyhow!(err))?
+    }
+
+    /// Runs `statements` in order inside one immediate transaction and
+    /// returns the rows each one changed. The first failing statement rolls
+    /// everything back; busy errors retry the whole transaction.
+    pub async fn transaction(&self, statements: Vec<SqlStatement>) -> anyhow::Result<Vec<u64>> {
+        let conn = self.conn.clone();
+        let retries = self.busy_retries;
+        tokio::task::spawn_blocking(move || {
+            let mut conn = conn
+                .lock()
+                .map_err(|_| anyhow::anyhow!("sqlite lock poisoned"))?;
+            let prepared = statements
+                .into_iter()
+                .enumerate()
+                .map(|(i, stmt)| {
+                    let params = SqlParams::from_request(Some(stmt.params), stmt.named_params)
+                        .and_then(BoundParams::new)
+                        .with_context(|| format!("statement {i}"))?;
+                    Ok((stmt.sql, params))
+                })
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            retry_busy(retries, || {
+                let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+                let mut affected = Vec::with_capacity(prepared.len());
+                for (i, (sql, params)) in prepared.iter().enumerate() {
+                    let rows = params
+                        .execute(&tx, sql)
+                        .with_context(|| format!("statement {i}"))?;
+                    affected.push(rows as u64);
+                }
+                tx.commit()?;
+                Ok(affected)
+            })
         })
         .await
         .map_err(|err| anyhow::anyhow!(err))?
     }
 
+    /// Applies the entries of `statements` whose index is not yet recorded
+    /// in `_migrations`, all inside one immediate transaction, and returns
+    /// how many ran. An entry may hold several `;`-separated statements.
+    /// Migrations are identified by position only: append new ones, never
+    /// reorder or edit those already applied.
+    pub async fn run_migrations(&self, statements: &[&str]) -> anyhow::Result<usize> {
+        let statements: Vec<String> = statements.iter().map(|s| s.to_string()).collect();
+        let conn = self.conn.clone();
+        let retries = self.busy_retries;
+        tokio::task::spawn_blocking(move || {
+            let mut conn = conn
+                .lock()
+                .map_err(|_| anyhow::anyhow!("sqlite lock poisoned"))?;
+            retry_busy(retries, || {
+                let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+                tx.execute_batch(
+                    "CREATE TABLE IF NOT EXISTS _migrations (
+                        id INTEGER PRIMARY KEY,
+                        applied_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+                    )",
+                )?;
+                let applied: HashSet<i64> = tx
+                    .prepare("SELECT id FROM _migrations")?
+                    .query_map([], |row| row.get(0))?
+                    .collect::<rusqlite::Result<_>>()?;
+                let mut ran = 0;
+                for (i, sql) in statements.iter().enumerate() {
+                    if applied.contains(&(i as i64)) {
+                        continue;
+                    }
+                    tx.execute_batch(sql)
+                        .with_context(|| format!("migration {i}"))?;
+                    tx.execute("INSERT INTO _migrations (id) VALUES (?1)", [i as i64])?;
+                    ran += 1;
+                }
+                tx.commit()?;
+                Ok(ran)
+            })
+        })
+        .await
+        .map_err(|err| anyhow::anyhow!(err))?
+    }
+
+    /// Readiness probe. Reads the schema rather than a bare `SELECT 1` so a
+    /// corrupt or replaced database file is caught, not just a live handle.
+    pub async fn ping(&self, timeout: std::time::Duration) -> anyhow::Result<()> {
+        let conn = self.conn.clone();
+        let probe = tokio::task::spawn_blocking(move || {
+            let conn = conn
+                .lock()
+                .map_err(|_| anyhow::anyhow!("sqlite lock poisoned"))?;
+            conn.query_row("SELECT count(*) FROM sqlite_master", [], |row| {
+                row.get::<_, i64>(0)
+            })?;
+            Ok(())
+        });
+        tokio::time::timeout(timeout, probe)
+            .await
+            .map_err(|_| anyhow::anyhow!("sqlite ping timed out"))?
+            .map_err(|err| anyhow::anyhow!(err))?
+    }
+
     pub fn path(&self) -> &Path {
         &self.path
     }
 }
 
+/// Reads the `.sql` files of `dir` in lexical order of their names, ready
+/// for `run_migrations`; prefix them with a zero-padded number to fix it.
+pub fn read_migrations_dir(dir: impl AsRef<Path>) -> anyhow::Result<Vec<String>> {
+    let dir = dir.as_ref();
+    let mut files = std::fs::read_dir(dir)
+        .with_context(|| format!("read migrations dir {}", dir.display()))?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<std::io::Result<Vec<_>>>()?;
+    files.retain(|path| path.is_file() && path.extension().is_some_and(|ext| ext == "sql"));
+    files.sort();
+    files
+        .iter()
+        .map(|path| {
+            std::fs::read_to_string(path)
+                .with_context(|| format!("read migration {}", path.display()))
+        })
+        .collect()
+}
+
+/// True when `err` is SQLite reporting contention rather than a bad statement,
+/// i.e. the same request may succeed if sent again.
+pub fn is_busy(err: &anyhow::Error) -> bool {
+    matches!(
+        err.downcast_ref::<rusqlite::Error>(),
+        Some(rusqlite::Error::SqliteFailure(e, _))
+            if matches!(e.code, ErrorCode::DatabaseBusy | ErrorCode::DatabaseLocked)
+    )
+}
+
+fn retry_busy<T>(retries: u32, mut op: impl FnMut() -> anyhow::Result<T>) -> anyhow::Result<T> {
+    let mut backoff = BUSY_BACKOFF_START;
+    let mut attempt = 0;
+    loop {
+        match op() {
+            Err(err) if attempt < retries && is_busy(&err) => {
+                attempt += 1;
+                tracing::debug!(attempt, ?backoff, "sqlite busy, retrying");
+                std::thread::sleep(backoff);
+                backoff = (backoff * 2).min(BUSY_BACKOFF_MAX);
+            }
+            res => return res,
+        }
+    }
+}
+
 fn json_params_to_values(params: Vec<serde_json::Value>) -> anyhow::Result<Vec<Value>> {
-    let mut out = Vec::new();
-    for value in params {
-        let sql_value = match value {
-            serde_json::Value::Null => Value::Null,
-            serde_json::Value::Bool(b) => Value::Integer(if b { 1 } else { 0 }),
-            serde_json::Value::Number(n) => {
-                if let Some(i) = n.as_i64() {
-                    Value::Integer(i)
-                } else if let Some(f) = n.as_f64() {
-                    Value::Real(f)
-                } else {
-                    Value::Null
-                }
+    params.into_iter().map(json_to_value).collect()
+}
+
+/// Scalars map to the matching SQLite type. The only objects accepted are
+/// tagged ones: `{"$blob": "<base64>"}` binds a BLOB and `{"$json": ...}`
+/// binds the serialized JSON as TEXT.
+fn json_to_value(value: serde_json::Value) -> anyhow::Result<Value> {
+    Ok(match value {
+        serde_json::Value::Null => Value::Null,
+        serde_json::Value::Bool(b) => Value::Integer(if b { 1 } else { 0 }),
+        serde_json::Value::Number(n) => {
+            if let Some(i) = n.as_i64() {
+                Value::Integer(i)
+            } else if let Some(f) = n.as_f64() {
+                Value::Real(f)
+            } else {
+                Value::Null
             }
-            serde_json::Value::String(s) => Value::Text(s),
-            _ => {
-                return Err(anyhow::anyhow!(
-                    "unsupported parameter type (only null, bool, number, string)"
-                ));
+        }
+        serde_json::Value::String(s) => Value::Text(s),
+        serde_json::Value::Object(obj) if obj.len() == 1 => {
+            let (tag, inner) = obj.into_iter().next().unwrap_or_default();
+            match tag.as_str() {
+                "$blob" => {
+                    let encoded = inner
+                        .as_str()
+                        .ok_or_else(|| anyhow::anyhow!("$blob must be a base64 string"))?;
+                    let bytes = BLOB_PARAM
+                        .decode(encoded)
+                        .map_err(|err| anyhow::anyhow!("invalid $blob base64: {err}"))?;
+                    Value::Blob(bytes)
+                }
+                "$json" => Value::Text(serde_json::to_string(&inner)?),
+                _ => return Err(unsupported_param()),
             }
-        };
-        out.push(sql_value);
-    }
-    Ok(out)
+        }
+        _ => return Err(unsupported_param()),
+    })
+}
+
+fn unsupported_param() -> anyhow::Error {
+    anyhow::anyhow!(
+        "unsupported parameter type (only null, bool, number, string, {{\"$blob\": base64}} or {{\"$json\": value}})"
+    )
 }
 
 fn row_to_json(row: &Row<'_>, columns: &[String]) -> anyhow::Result<serde_json::Value> {
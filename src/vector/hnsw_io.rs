@@ -0,0 +1,189 @@
+use super::persist::{self, CollectionLayout, HnswDumpManifest, Manifest};
+use super::{HnswIndex, HnswParams, Metric, SegmentIndex};
+use anndists::dist::distances::{DistCosine, DistDot};
+use anyhow::bail;
+use hnsw_rs::api::AnnT;
+use hnsw_rs::hnswio::HnswIo;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::ptr::NonNull;
+
+/// Bump when the graph files or the sidecar change shape; older dumps are
+/// then ignored and the segments are rebuilt from `vectors.bin`.
+pub const HNSW_DUMP_VERSION: u32 = 1;
+
+const DUMP_DIR: &str = "hnsw";
+const DUMP_TMP_DIR: &str = "hnsw.tmp";
+const SIDECAR_FILE: &str = "segments.bin";
+
+/// What the graph files don't carry: the data id -> vector id mapping and
+/// the tombstones of each segment.
+#[derive(Serialize, Deserialize)]
+struct SegmentMeta {
+    basename: String,
+    id_by_data_id: Vec<String>,
+    deleted: Vec<bool>,
+    capacity: usize,
+}
+
+/// Owns the `HnswIo` a loaded graph borrows from. The graph is typed
+/// `'static`, so the loader sits behind a raw pointer that stays put while
+/// the segment moves around, and is freed only after the graph is dropped.
+pub(super) struct HnswLoader(NonNull<HnswIo>);
+
+impl HnswLoader {
+    fn new(io: HnswIo) -> Self {
+        Self(NonNull::from(Box::leak(Box::new(io))))
+    }
+
+    /// # Safety
+    /// The returned borrow must not outlive `self`.
+    unsafe fn borrow(&mut self) -> &'static mut HnswIo {
+        unsafe { &mut *self.0.as_ptr() }
+    }
+}
+
+impl Drop for HnswLoader {
+    fn drop(&mut self) {
+        // SAFETY: the pointer came from `Box::leak` in `new` and is freed once.
+        drop(unsafe { Box::from_raw(self.0.as_ptr()) });
+    }
+}
+
+// SAFETY: the loader is only reached through the graph that borrows it, and
+// `HnswIo` itself is `Send + Sync` (checked below).
+unsafe impl Send for HnswLoader {}
+unsafe impl Sync for HnswLoader {}
+
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<HnswIo>();
+};
+
+fn dump_dir(layout: &CollectionLayout) -> PathBuf {
+    layout.dir.join(DUMP_DIR)
+}
+
+/// Writes every segment graph with `file_dump` into a fresh directory, swaps
+/// it in place of the previous dump and records it in the manifest.
+pub fn write_dump(
+    layout: &CollectionLayout,
+    segments: &[SegmentIndex],
+    manifest: &mut Manifest,
+    applied_offset: u64,
+) -> anyhow::Result<()> {
+    let tmp = layout.dir.join(DUMP_TMP_DIR);
+    if tmp.exists() {
+        fs::remove_dir_all(&tmp)?;
+    }
+    fs::create_dir_all(&tmp)?;
+
+    let mut metas = Vec::with_capacity(segments.len());
+    for (i, seg) in segments.iter().enumerate() {
+        let name = format!("seg-{i}");
+        let basename = match &seg.hnsw {
+            HnswIndex::Cosine(h) => h.file_dump(&tmp, &name)?,
+            HnswIndex::Dot(h) => h.file_dump(&tmp, &name)?,
+        };
+        metas.push(SegmentMeta {
+            basename,
+            id_by_data_id: seg.id_by_data_id.clone(),
+            deleted: seg.deleted.clone(),
+            capacity: seg.capacity,
+        });
+    }
+    fs::write(tmp.join(SIDECAR_FILE), bincode::serialize(&metas)?)?;
+
+    let dir = dump_dir(layout);
+    if dir.exists() {
+        fs::remove_dir_all(&dir)?;
+    }
+    fs::rename(&tmp, &dir)?;
+
+    manifest.applied_offset = applied_offset;
+    manifest.hnsw_dump = Some(HnswDumpManifest {
+        version: HNSW_DUMP_VERSION,
+        applied_offset,
+        total_records: manifest.total_records,
+        params: manifest.hnsw,
+    });
+    persist::store_manifest(layout, manifest)?;
+    Ok(())
+}
+
+/// Whether the manifest points at a dump taken from exactly the records that
+/// were just loaded.
+pub fn dump_matches(manifest: &Manifest, applied_offset: u64) -> bool {
+    manifest.hnsw_dump.is_some_and(|dump| {
+        dump.version == HNSW_DUMP_VERSION
+            && dump.applied_offset == applied_offset
+            && dump.total_records == manifest.total_records
+            && dump.params == manifest.hnsw
+    })
+}
+
+pub fn load_segments(
+    layout: &CollectionLayout,
+    metric: Metric,
+    params: HnswParams,
+) -> anyhow::Result<Vec<SegmentIndex>> {
+    let dir = dump_dir(layout);
+    let metas: Vec<SegmentMeta> = bincode::deserialize(&fs::read(dir.join(SIDECAR_FILE))?)?;
+    metas
+        .into_iter()
+        .map(|meta| load_segment(&dir, metric, params, meta))
+        .collect()
+}
+
+fn load_segment(
+    dir: &Path,
+    metric: Metric,
+    params: HnswParams,
+    meta: SegmentMeta,
+) -> anyhow::Result<SegmentIndex> {
+    if meta.deleted.len() != meta.id_by_data_id.len() {
+        bail!("hnsw segment {} has a truncated sidecar", meta.basename);
+    }
+    let mut loader = HnswLoader::new(HnswIo::new(dir, &meta.basename));
+    // SAFETY: the graph is stored next to `loader` in the segment, which
+    // drops the graph first.
+    let io = unsafe { loader.borrow() };
+    let (hnsw, points) = match metric {
+        Metric::Cosine => {
+            let h = io.load_hnsw::<f32, DistCosine>()?;
+            let points = h.get_nb_point();
+            (HnswIndex::Cosine(h), points)
+        }
+        Metric::Dot => {
+            let h = io.load_hnsw::<f32, DistDot>()?;
+            let points = h.get_nb_point();
+            (HnswIndex::Dot(h), points)
+        }
+    };
+    if points != meta.id_by_data_id.len() {
+        bail!(
+            "hnsw segment {} holds {points} points, sidecar lists {}",
+            meta.basename,
+            meta.id_by_data_id.len()
+        );
+    }
+
+    let mut data_ids = HashMap::new();
+    for (data_id, (id, deleted)) in meta.id_by_data_id.iter().zip(&meta.deleted).enumerate() {
+        if !deleted {
+            data_ids.insert(id.clone(), data_id);
+        }
+    }
+    Ok(SegmentIndex {
+        hnsw,
+        _loader: Some(loader),
+        live: data_ids.len(),
+        data_ids,
+        id_by_data_id: meta.id_by_data_id,
+        deleted: meta.deleted,
+        capacity: meta.capacity,
+        params,
+    })
+}
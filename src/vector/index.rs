@@ -47,6 +47,7 @@ pub trait VectorIndex: Send + Sync {
     fn list_collections(&self) -> Vec<VectorCollectionInfo>;
     fn get_collection(&self, name: &str) -> Option<(usize, Metric)>;
     fn create_collection(&self, name: &str, dim: usize, metric: Metric) -> Result<(), VectorError>;
+    fn drop_collection(&self, name: &str) -> Result<(), VectorError>;
     fn upsert(&self, collection: &str, id: &str, item: VectorItem) -> Result<(), VectorError>;
     fn delete(&self, collection: &str, id: &str) -> Result<(), VectorError>;
     fn search(&self, collection: &str, req: SearchRequest) -> Result<Vec<SearchHit>, VectorError>;
@@ -1,10 +1,13 @@
 mod diskann;
+mod export;
+mod hnsw_io;
 pub mod index;
 mod ivf;
 mod persist;
 pub mod q8;
 mod simd;
 
+pub use export::ExportQuantize;
 pub use index::{DiskAnnIndex, DiskVectorIndex, VectorIndex};
 pub use ivf::IndexKind;
 
@@ -19,13 +22,14 @@ use crate::vector::q8::QuantizedVec;
 use anyhow::Context;
 use hnsw_rs::prelude::*;
 use index::{DiskAnnBuildParams, DiskIndexStatus};
-use parking_lot::RwLock;
+use parking_lot::{RwLock, RwLockUpgradableReadGuard};
 use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
 use std::cmp::Ordering;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::path::{Path, PathBuf};
-use std::sync::{Arc, OnceLock};
+use std::sync::Arc;
 
 use rayon::prelude::*;
 
@@ -40,8 +44,10 @@ struct Inner {
 
 const DEFAULT_SEGMENT_MAX: usize = 8_192;
 const DEFAULT_PARALLEL_SEGMENT_MIN: usize = 4;
+const DEFAULT_MERGE_TOMBSTONE_RATIO: f32 = 0.3;
+/// Below this many tombstones the automatic merge isn't worth a rebuild.
+const MERGE_MIN_TOMBSTONES: usize = 64;
 const DEFAULT_DISKANN_SEARCH_LIST_SIZE: usize = 64;
-static RAYON_INIT: OnceLock<()> = OnceLock::new();
 
 #[derive(Clone, Debug)]
 pub struct VectorSettings {
@@ -50,6 +56,11 @@ pub struct VectorSettings {
     pub simd_enabled: bool,
     pub hnsw_fallback_enabled: bool,
     pub search_threads: Option<usize>,
+    /// Segment size given to new collections.
+    pub segment_max_items: usize,
+    /// Share of tombstoned HNSW nodes, across a collection, past which writes
+    /// repack the sparse segments. `0` turns the automatic merge off.
+    pub merge_tombstone_ratio: f32,
     pub index_kind: IndexKind,
     pub ivf: IvfConfig,
     pub run_target_bytes: u64,
@@ -60,6 +71,16 @@ pub struct VectorSettings {
     pub diskann_search_list_size: usize,
     pub diskann_max_degree: usize,
     pub diskann_build_threads: usize,
+    /// When false, search queries one element off the collection dim are
+    /// zero-padded or truncated instead of rejected.
+    pub strict_query_dim: bool,
+    /// Distinct string values a metadata field may hold in the keyword index
+    /// before the field is dropped from it and filtered by scan. `0` means
+    /// no limit.
+    pub max_index_values_per_field: usize,
+    /// Dedicated pool for parallel segment probing, sized by `search_threads`.
+    /// `None` probes on rayon's global pool.
+    search_pool: Option<Arc<rayon::ThreadPool>>,
 }
 
 impl Default for VectorSettings {
@@ -70,6 +91,8 @@ impl Default for VectorSettings {
             simd_enabled: true,
             hnsw_fallback_enabled: true,
             search_threads: None,
+            segment_max_items: DEFAULT_SEGMENT_MAX,
+            merge_tombstone_ratio: DEFAULT_MERGE_TOMBSTONE_RATIO,
             index_kind: IndexKind::Hnsw,
             ivf: IvfConfig {
                 clusters: 1024,
@@ -89,6 +112,9 @@ impl Default for VectorSettings {
             diskann_build_threads: std::thread::available_parallelism()
                 .map(|n| n.get())
                 .unwrap_or(1),
+            strict_query_dim: true,
+            max_index_values_per_field: 0,
+            search_pool: None,
         }
     }
 }
@@ -112,6 +138,8 @@ impl VectorSettings {
             simd_enabled: config.simd_enabled,
             hnsw_fallback_enabled: true,
             search_threads: (config.search_threads > 0).then_some(config.search_threads),
+            segment_max_items: config.vector_segment_max_items.max(1),
+            merge_tombstone_ratio: config.vector_merge_tombstone_ratio.clamp(0.0, 1.0),
             index_kind,
             ivf: IvfConfig {
                 clusters,
@@ -129,21 +157,37 @@ impl VectorSettings {
             diskann_search_list_size: config.diskann_search_list_size.max(4),
             diskann_max_degree: config.diskann_max_degree.max(4),
             diskann_build_threads: config.diskann_build_threads.max(1),
+            strict_query_dim: config.strict_query_dim,
+            max_index_values_per_field: config.max_index_values_per_field,
+            search_pool: None,
         }
     }
 
-    fn init_rayon(&self) {
-        if let Some(threads) = self.search_threads {
-            let _ = RAYON_INIT.get_or_init(|| {
-                let _ = rayon::ThreadPoolBuilder::new()
-                    .num_threads(threads)
-                    .build_global();
-            });
+    /// Builds the bounded segment-probing pool once per store, so a fan-out
+    /// over many segments can't take more than `search_threads` cores.
+    fn init_search_pool(&mut self) {
+        let Some(threads) = self.search_threads else {
+            self.search_pool = None;
             return;
+        };
+        match rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .thread_name(|i| format!("vector-search-{i}"))
+            .build()
+        {
+            Ok(pool) => self.search_pool = Some(Arc::new(pool)),
+            Err(err) => {
+                tracing::warn!(error = %err, threads, "search pool unavailable; using the global rayon pool");
+                self.search_pool = None;
+            }
+        }
+    }
+
+    fn probe_in_pool<R: Send>(&self, op: impl FnOnce() -> R + Send) -> R {
+        match &self.search_pool {
+            Some(pool) => pool.install(op),
+            None => op(),
         }
-        let _ = RAYON_INIT.get_or_init(|| {
-            let _ = rayon::ThreadPoolBuilder::new().build_global();
-        });
     }
 
     fn should_parallel_segments(&self, segments: usize) -> bool {
@@ -159,12 +203,110 @@ impl VectorSettings {
 pub struct CreateCollectionRequest {
     pub dim: usize,
     pub metric: Metric,
+    #[serde(default)]
+    pub hnsw: Option<HnswParams>,
+}
+
+const DEFAULT_HNSW_MAX_NB_CONN: usize = 16;
+const DEFAULT_HNSW_EF_CONSTRUCTION: usize = 200;
+const DEFAULT_HNSW_EF_SEARCH_FLOOR: usize = 50;
+/// hnsw_rs rejects larger connection counts.
+pub const MAX_HNSW_NB_CONN: usize = 256;
+/// Upper bound for every beam width; wider ones cost latency for no recall.
+pub const MAX_HNSW_EF: usize = 10_000;
+
+/// Per-collection HNSW knobs for the segment indexes. Omitted fields keep the
+/// built-in defaults.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct HnswParams {
+    #[serde(default = "default_hnsw_max_nb_conn")]
+    pub max_nb_conn: usize,
+    #[serde(default = "default_hnsw_ef_construction")]
+    pub ef_construction: usize,
+    /// Search-time ef; unset means `2 * candidates` capped at 10_000.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ef_search: Option<usize>,
+    /// Lower bound on the effective ef, whatever `k` or `ef_search` say, so
+    /// top-1 queries still explore enough of the graph.
+    #[serde(default = "default_hnsw_ef_search_floor")]
+    pub ef_search_floor: usize,
+}
+
+impl Default for HnswParams {
+    fn default() -> Self {
+        Self {
+            max_nb_conn: DEFAULT_HNSW_MAX_NB_CONN,
+            ef_construction: DEFAULT_HNSW_EF_CONSTRUCTION,
+            ef_search: None,
+            ef_search_floor: DEFAULT_HNSW_EF_SEARCH_FLOOR,
+        }
+    }
+}
+
+impl HnswParams {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_nb_conn == 0 || self.max_nb_conn > MAX_HNSW_NB_CONN {
+            return Err(format!(
+                "hnsw.max_nb_conn must be in 1..={MAX_HNSW_NB_CONN}"
+            ));
+        }
+        if self.ef_construction == 0 || self.ef_construction > MAX_HNSW_EF {
+            return Err(format!("hnsw.ef_construction must be in 1..={MAX_HNSW_EF}"));
+        }
+        if self.ef_search.is_some_and(|ef| ef == 0 || ef > MAX_HNSW_EF) {
+            return Err(format!("hnsw.ef_search must be in 1..={MAX_HNSW_EF}"));
+        }
+        if self.ef_search_floor == 0 || self.ef_search_floor > MAX_HNSW_EF {
+            return Err(format!("hnsw.ef_search_floor must be in 1..={MAX_HNSW_EF}"));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn search_ef(&self, candidate_k: usize) -> usize {
+        let ef = match self.ef_search {
+            Some(ef) => ef.max(candidate_k),
+            None => candidate_k.saturating_mul(2),
+        };
+        ef.max(self.ef_search_floor).min(MAX_HNSW_EF)
+    }
+}
+
+fn default_hnsw_max_nb_conn() -> usize {
+    DEFAULT_HNSW_MAX_NB_CONN
+}
+
+fn default_hnsw_ef_construction() -> usize {
+    DEFAULT_HNSW_EF_CONSTRUCTION
+}
+
+fn default_hnsw_ef_search_floor() -> usize {
+    DEFAULT_HNSW_EF_SEARCH_FLOOR
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct VectorItem {
     pub vector: Vec<f32>,
     pub meta: serde_json::Value,
+    /// Bumped on every upsert/update of the id; ignored on input.
+    #[serde(default)]
+    pub revision: u64,
+    /// Unix ms after which searches skip the item and the TTL sweep deletes it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at_ms: Option<u64>,
+}
+
+impl VectorItem {
+    fn is_expired(&self) -> bool {
+        self.expires_at_ms.is_some_and(|at| at <= now_ms())
+    }
+
+    /// Whether writing `other` over this item would leave it as it is: the
+    /// vectors are compared as indexed for `metric`, along with meta and expiry.
+    pub fn same_content(&self, other: &VectorItem, metric: Metric) -> bool {
+        self.meta == other.meta
+            && self.expires_at_ms == other.expires_at_ms
+            && index_vector(metric, &self.vector) == index_vector(metric, &other.vector)
+    }
 }
 
 #[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
@@ -192,6 +334,10 @@ pub enum VectorError {
     Persistence,
     #[error("operation not supported")]
     UnsupportedOperation,
+    #[error("revision mismatch")]
+    RevisionMismatch,
+    #[error("collection is full")]
+    CollectionFull,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -200,6 +346,41 @@ pub struct SearchRequest {
     pub k: usize,
     pub filters: Option<serde_json::Value>,
     pub include_meta: Option<bool>,
+    #[serde(default)]
+    pub tie_break: Option<TieBreak>,
+    /// Return each hit's stored vector, as it was upserted.
+    #[serde(default)]
+    pub include_vector: Option<bool>,
+    /// Drop hits scoring below this, compared against the returned `score`
+    /// (cosine similarity, i.e. `1 - distance`, or the dot product).
+    #[serde(default)]
+    pub min_score: Option<f32>,
+    /// Skip this many ranked hits before returning `k`, for paging.
+    #[serde(default)]
+    pub offset: Option<usize>,
+    /// Score every live vector with the exact metric instead of using the
+    /// index; slow, meant for measuring recall.
+    #[serde(default)]
+    pub exact: Option<bool>,
+    /// Also report how many vectors match the filters, for "page X of Y".
+    #[serde(default)]
+    pub with_total: Option<bool>,
+}
+
+/// Orders hits with equal scores by a meta field before falling back to the id.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct TieBreak {
+    pub field: String,
+    #[serde(default)]
+    pub order: Order,
+}
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Order {
+    #[default]
+    Asc,
+    Desc,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -207,6 +388,96 @@ pub struct SearchHit {
     pub id: String,
     pub score: f32,
     pub meta: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vector: Option<Vec<f32>>,
+}
+
+/// A hit from a federated search, tagged with the collection it came from.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CollectionHit {
+    pub collection: String,
+    #[serde(flatten)]
+    pub hit: SearchHit,
+}
+
+/// One edge of a kNN graph: `to` is among the nearest neighbours of `from`.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct KnnEdge {
+    pub from: String,
+    pub to: String,
+    pub score: f32,
+}
+
+/// Search hits together with the path that produced them.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SearchOutcome {
+    pub hits: Vec<SearchHit>,
+    /// True when every candidate was scored with the exact metric (small
+    /// filtered subsets), false when an approximate index ranked them.
+    pub exact: bool,
+    /// HNSW candidates discarded because they point at deleted vectors; a
+    /// high ratio to `k` means the collection is due for compaction.
+    pub tombstone_skips: u64,
+    #[serde(default)]
+    pub stats: SearchStats,
+    /// Matching vectors when `with_total` was asked for. Exact without
+    /// filters or when the filters narrow through the keyword index (or with
+    /// `exact`); otherwise the size of the scored candidate pool, which can
+    /// undercount on the HNSW path.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total: Option<usize>,
+}
+
+/// Per-query diagnostics for tuning `ef` and segment sizes.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub struct SearchStats {
+    pub elapsed_us: u64,
+    /// Time spent waiting for the collections read lock before searching,
+    /// e.g. behind a vacuum swapping its index in.
+    #[serde(default)]
+    pub lock_wait_us: u64,
+    /// HNSW segments queried; 0 when IVF, DiskANN or brute force ranked the hits.
+    pub segments_probed: usize,
+    /// Candidates gathered before dedup and filtering: segment results, IVF
+    /// cluster members or the brute-forced subset.
+    pub candidates: usize,
+    pub bruteforce: bool,
+}
+
+impl SearchOutcome {
+    fn exact(hits: Vec<SearchHit>) -> Self {
+        Self {
+            hits,
+            exact: true,
+            tombstone_skips: 0,
+            stats: SearchStats::default(),
+            total: None,
+        }
+    }
+
+    fn approximate(hits: Vec<SearchHit>) -> Self {
+        Self {
+            hits,
+            exact: false,
+            tombstone_skips: 0,
+            stats: SearchStats::default(),
+            total: None,
+        }
+    }
+
+    fn bruteforce(hits: Vec<SearchHit>, exact: bool, candidates: usize) -> Self {
+        Self {
+            hits,
+            exact,
+            tombstone_skips: 0,
+            stats: SearchStats {
+                candidates,
+                bruteforce: true,
+                ..SearchStats::default()
+            },
+            total: None,
+        }
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -223,6 +494,62 @@ pub struct VectorCollectionInfo {
     pub updated_at_ms: Option<u64>,
     pub segments: Option<usize>,
     pub deleted_count: Option<u64>,
+    pub norm_stats: Option<NormStats>,
+}
+
+/// Fill and tombstone counters for one collection, used to judge when a vacuum pays off.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CollectionStats {
+    pub dim: usize,
+    pub metric: Metric,
+    pub live_count: usize,
+    pub total_records: u64,
+    pub upsert_count: u64,
+    pub file_len: u64,
+    pub segment_count: usize,
+    pub segments: Vec<SegmentStats>,
+    /// True when the segment graphs were loaded from the `hnsw/` dump at open
+    /// instead of being rebuilt from the records.
+    pub hnsw_reloaded: bool,
+    /// Distinct values held in the keyword index, per metadata field.
+    pub keyword_index_values: BTreeMap<String, usize>,
+    /// Fields that went past `max_index_values_per_field`; filters on them scan.
+    pub unindexed_fields: Vec<String>,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SegmentStats {
+    pub live: usize,
+    pub capacity: usize,
+    /// Tombstoned graph nodes; HNSW can't remove them until the segment is rebuilt.
+    pub deleted: usize,
+}
+
+/// L2 norms of upserted vectors as sent by clients, i.e. before any normalization.
+/// Deletes do not shrink the range.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct NormStats {
+    pub count: u64,
+    pub min: f32,
+    pub max: f32,
+    pub mean: f32,
+}
+
+impl NormStats {
+    fn observe(&mut self, norm: f32) {
+        if !norm.is_finite() {
+            return;
+        }
+        if self.count == 0 {
+            self.min = norm;
+            self.max = norm;
+        } else {
+            self.min = self.min.min(norm);
+            self.max = self.max.max(norm);
+        }
+        self.count = self.count.saturating_add(1);
+        self.mean += (norm - self.mean) / self.count as f32;
+    }
 }
 
 struct Collection {
@@ -238,11 +565,14 @@ struct Collection {
     item_runs: HashMap<String, String>,
     cluster_members: HashMap<usize, HashSet<String>>,
     segment_max_items: usize,
-    keyword_index: HashMap<String, HashMap<String, HashSet<String>>>,
+    keyword_index: KeywordIndex,
     settings: VectorSettings,
     ivf: Option<IvfState>,
     item_clusters: HashMap<String, usize>,
     disk_graph: Option<diskann::DiskGraph>,
+    hnsw_reloaded: bool,
+    /// `(expires_at_ms, id)` of every item with a TTL, soonest first.
+    expiring: BTreeSet<(u64, String)>,
 }
 
 enum HnswIndex {
@@ -252,22 +582,34 @@ enum HnswIndex {
 
 struct SegmentIndex {
     hnsw: HnswIndex,
+    /// Set when `hnsw` was loaded from a dump and borrows from it; declared
+    /// after `hnsw` so it is dropped after the graph.
+    _loader: Option<hnsw_io::HnswLoader>,
     data_ids: HashMap<String, usize>,
     id_by_data_id: Vec<String>,
     deleted: Vec<bool>,
     live: usize,
     capacity: usize,
+    params: HnswParams,
 }
 
 impl SegmentIndex {
-    fn new(metric: Metric, capacity: usize) -> Self {
+    fn new(metric: Metric, capacity: usize, params: HnswParams) -> Self {
         Self {
-            hnsw: make_hnsw(metric, 16, capacity.max(1024), 16, 200),
+            hnsw: make_hnsw(
+                metric,
+                params.max_nb_conn,
+                capacity.max(1024),
+                16,
+                params.ef_construction,
+            ),
+            _loader: None,
             data_ids: HashMap::new(),
             id_by_data_id: Vec::new(),
             deleted: Vec::new(),
             live: 0,
-            capacity: capacity.max(1024),
+            capacity: capacity.max(1),
+            params,
         }
     }
 
@@ -280,6 +622,25 @@ impl SegmentIndex {
         self.live = self.live.saturating_add(1);
     }
 
+    fn stats(&self) -> SegmentStats {
+        SegmentStats {
+            live: self.live,
+            capacity: self.capacity,
+            deleted: self.tombstones(),
+        }
+    }
+
+    /// Graph nodes whose vector was deleted or replaced; every node is either
+    /// live or one of these.
+    fn tombstones(&self) -> usize {
+        self.id_by_data_id.len().saturating_sub(self.live)
+    }
+
+    fn is_sparse(&self, ratio: f32) -> bool {
+        let tombstones = self.tombstones();
+        tombstones > 0 && tombstones as f32 >= ratio * self.id_by_data_id.len() as f32
+    }
+
     fn mark_deleted(&mut self, id: &str) {
         if let Some(idx) = self.data_ids.remove(id) {
             if idx < self.deleted.len() && !self.deleted[idx] {
@@ -289,29 +650,26 @@ impl SegmentIndex {
         }
     }
 
-    fn search_candidates(&self, query: &[f32], candidate_k: usize) -> Vec<(String, f32)> {
+    /// Live neighbours of `query`, plus how many graph hits were dropped
+    /// because they point at deleted vectors.
+    fn search_candidates(&self, query: &[f32], candidate_k: usize) -> (Vec<(String, f32)>, u64) {
         if self.live == 0 {
-            return Vec::new();
+            return (Vec::new(), 0);
         }
+        let ef = self.params.search_ef(candidate_k);
         let neighbours = match &self.hnsw {
-            HnswIndex::Cosine(h) => h.search(
-                query,
-                candidate_k,
-                candidate_k.saturating_mul(2).clamp(50, 10_000),
-            ),
-            HnswIndex::Dot(h) => h.search(
-                query,
-                candidate_k,
-                candidate_k.saturating_mul(2).clamp(50, 10_000),
-            ),
+            HnswIndex::Cosine(h) => h.search(query, candidate_k, ef),
+            HnswIndex::Dot(h) => h.search(query, candidate_k, ef),
         };
         let mut hits = Vec::new();
+        let mut tombstone_skips = 0;
         for n in neighbours {
             let data_id = n.d_id;
             if data_id >= self.id_by_data_id.len() {
                 continue;
             }
             if self.deleted.get(data_id).copied().unwrap_or(true) {
+                tombstone_skips += 1;
                 continue;
             }
             let id = self.id_by_data_id[data_id].clone();
@@ -321,7 +679,7 @@ impl SegmentIndex {
                 break;
             }
         }
-        hits
+        (hits, tombstone_skips)
     }
 }
 
@@ -330,8 +688,8 @@ impl VectorStore {
         Self::with_settings(VectorSettings::default())
     }
 
-    pub fn with_settings(settings: VectorSettings) -> Self {
-        settings.init_rayon();
+    pub fn with_settings(mut settings: VectorSettings) -> Self {
+        settings.init_search_pool();
         Self(Arc::new(Inner {
             data_dir: None,
             collections: RwLock::new(HashMap::new()),
@@ -345,9 +703,9 @@ impl VectorStore {
 
     pub fn open_with_settings(
         data_dir: impl AsRef<Path>,
-        settings: VectorSettings,
+        mut settings: VectorSettings,
     ) -> anyhow::Result<Self> {
-        settings.init_rayon();
+        settings.init_search_pool();
         let data_dir = data_dir.as_ref().to_path_buf();
         let vectors_dir = data_dir.join("vectors");
         std::fs::create_dir_all(&vectors_dir)?;
@@ -363,7 +721,7 @@ impl VectorStore {
             let (manifest, items, quantized, item_runs, applied_offset) =
                 persist::load_collection(&layout)
                     .with_context(|| format!("load vector collection {name}"))?;
-            let mut c = Collection::new(
+            let c = Collection::new(
                 Some(layout),
                 manifest,
                 items,
@@ -372,7 +730,6 @@ impl VectorStore {
                 applied_offset,
                 settings.clone(),
             )?;
-            c.rebuild_index();
             collections.insert(name, c);
         }
 
@@ -411,6 +768,30 @@ impl VectorStore {
                     .total_records
                     .saturating_sub(c.manifest.live_count as u64),
             ),
+            norm_stats: Some(c.manifest.norm_stats),
+        })
+    }
+
+    pub fn default_filter(&self, name: &str) -> Result<Option<serde_json::Value>, VectorError> {
+        let cols = self.0.collections.read();
+        let c = cols.get(name).ok_or(VectorError::CollectionNotFound)?;
+        Ok(c.manifest.default_filter.clone())
+    }
+
+    pub fn collection_stats(&self, name: &str) -> Option<CollectionStats> {
+        let cols = self.0.collections.read();
+        cols.get(name).map(|c| CollectionStats {
+            dim: c.dim,
+            metric: c.metric,
+            live_count: c.manifest.live_count,
+            total_records: c.manifest.total_records,
+            upsert_count: c.manifest.upsert_count,
+            file_len: c.manifest.file_len,
+            segment_count: c.segments.len(),
+            segments: c.segments.iter().map(SegmentIndex::stats).collect(),
+            hnsw_reloaded: c.hnsw_reloaded,
+            keyword_index_values: c.keyword_index.value_counts(),
+            unindexed_fields: c.keyword_index.unindexed_fields(),
         })
     }
 
@@ -420,6 +801,19 @@ impl VectorStore {
         dim: usize,
         metric: Metric,
     ) -> Result<(), VectorError> {
+        self.create_collection_with_hnsw(name, dim, metric, HnswParams::default())
+    }
+
+    pub fn create_collection_with_hnsw(
+        &self,
+        name: &str,
+        dim: usize,
+        metric: Metric,
+        hnsw: HnswParams,
+    ) -> Result<(), VectorError> {
+        if dim == 0 {
+            return Err(VectorError::DimMismatch);
+        }
         let mut cols = self.0.collections.write();
         if cols.contains_key(name) {
             return Err(VectorError::CollectionExists);
@@ -427,11 +821,16 @@ impl VectorStore {
         let layout = self.layout_for(name);
         let (manifest, items, quantized, item_runs, applied_offset) = if let Some(layout) = &layout
         {
-            persist::init_collection(layout, dim, metric).map_err(|_| VectorError::Persistence)?;
+            persist::init_collection(layout, dim, metric, hnsw, self.0.settings.segment_max_items)
+                .map_err(|_| VectorError::Persistence)?;
             persist::load_collection(layout).map_err(|_| VectorError::Persistence)?
         } else {
             (
-                Manifest::new(dim, metric),
+                Manifest {
+                    hnsw,
+                    segment_max_items: self.0.settings.segment_max_items,
+                    ..Manifest::new(dim, metric)
+                },
                 HashMap::new(),
                 HashMap::new(),
                 HashMap::new(),
@@ -453,6 +852,30 @@ impl VectorStore {
         Ok(())
     }
 
+    /// Removes the collection and, when persistent, its directory on disk.
+    pub fn drop_collection(&self, name: &str) -> Result<(), VectorError> {
+        if self.remove_collection(name)? {
+            Ok(())
+        } else {
+            Err(VectorError::CollectionNotFound)
+        }
+    }
+
+    /// Returns whether the collection was loaded. The directory is removed either
+    /// way so a replayed drop also clears leftovers of a crashed one.
+    fn remove_collection(&self, name: &str) -> Result<bool, VectorError> {
+        let mut cols = self.0.collections.write();
+        let removed = cols.remove(name).is_some();
+        if let Some(layout) = self.layout_for(name) {
+            match std::fs::remove_dir_all(&layout.dir) {
+                Ok(()) => {}
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+                Err(_) => return Err(VectorError::Persistence),
+            }
+        }
+        Ok(removed)
+    }
+
     pub fn list_collections(&self) -> Vec<VectorCollectionInfo> {
         let cols = self.0.collections.read();
         cols.iter()
@@ -473,6 +896,7 @@ impl VectorStore {
                         .total_records
                         .saturating_sub(c.manifest.live_count as u64),
                 ),
+                norm_stats: Some(c.manifest.norm_stats),
             })
             .collect()
     }
@@ -550,6 +974,54 @@ impl VectorStore {
         Ok(c.items.get(id).cloned())
     }
 
+    /// Looks up several ids under one read lock; ids that are absent are left out.
+    pub fn get_many(
+        &self,
+        collection: &str,
+        ids: &[String],
+    ) -> Result<HashMap<String, VectorItem>, VectorError> {
+        let cols = self.0.collections.read();
+        let c = cols
+            .get(collection)
+            .ok_or(VectorError::CollectionNotFound)?;
+        Ok(ids
+            .iter()
+            .filter_map(|id| c.items.get(id).map(|item| (id.clone(), item.clone())))
+            .collect())
+    }
+
+    /// Up to `limit` `(collection, id)` pairs whose TTL ran out by `now`,
+    /// soonest first within each collection.
+    pub fn expired_vectors(&self, now: u64, limit: usize) -> Vec<(String, String)> {
+        let cols = self.0.collections.read();
+        let mut out = Vec::new();
+        for (name, c) in cols.iter() {
+            for id in c.expired_ids(now).take(limit - out.len()) {
+                out.push((name.clone(), id.clone()));
+            }
+            if out.len() >= limit {
+                break;
+            }
+        }
+        out
+    }
+
+    /// Evaluates a search filter against one item's metadata; `None` when the id is absent.
+    pub fn matches_filter(
+        &self,
+        collection: &str,
+        id: &str,
+        filter: Option<&serde_json::Value>,
+    ) -> Result<Option<bool>, VectorError> {
+        let cols = self.0.collections.read();
+        let c = cols
+            .get(collection)
+            .ok_or(VectorError::CollectionNotFound)?;
+        Ok(c.items
+            .get(id)
+            .map(|item| matches_filters(&item.meta, filter)))
+    }
+
     pub fn apply_event(&self, ev: &crate::engine::EventRecord) -> Result<(), VectorError> {
         match ev.event_type.as_str() {
             "vector_collection_created" => {
@@ -558,7 +1030,12 @@ impl VectorStore {
                     .get("collection")
                     .and_then(|v| v.as_str())
                     .ok_or(VectorError::InvalidManifest)?;
-                let dim = ev.data.get("dim").and_then(|v| v.as_u64()).unwrap_or(0) as usize;
+                let dim = ev
+                    .data
+                    .get("dim")
+                    .and_then(|v| v.as_u64())
+                    .filter(|d| *d >= 1)
+                    .ok_or(VectorError::InvalidManifest)? as usize;
                 let metric: Metric = serde_json::from_value(
                     ev.data
                         .get("metric")
@@ -566,6 +1043,11 @@ impl VectorStore {
                         .unwrap_or(serde_json::Value::String("cosine".into())),
                 )
                 .map_err(|_| VectorError::InvalidManifest)?;
+                let hnsw: HnswParams = match ev.data.get("hnsw") {
+                    Some(v) if !v.is_null() => serde_json::from_value(v.clone())
+                        .map_err(|_| VectorError::InvalidManifest)?,
+                    _ => HnswParams::default(),
+                };
 
                 let mut cols = self.0.collections.write();
                 if let Some(existing) = cols.get_mut(name) {
@@ -579,12 +1061,22 @@ impl VectorStore {
                 let layout = self.layout_for(name);
                 let (manifest, items, quantized, item_runs, applied_offset) =
                     if let Some(layout) = &layout {
-                        persist::init_collection(layout, dim, metric)
-                            .map_err(|_| VectorError::Persistence)?;
+                        persist::init_collection(
+                            layout,
+                            dim,
+                            metric,
+                            hnsw,
+                            self.0.settings.segment_max_items,
+                        )
+                        .map_err(|_| VectorError::Persistence)?;
                         persist::load_collection(layout).map_err(|_| VectorError::Persistence)?
                     } else {
                         (
-                            Manifest::new(dim, metric),
+                            Manifest {
+                                hnsw,
+                                segment_max_items: self.0.settings.segment_max_items,
+                                ..Manifest::new(dim, metric)
+                            },
                             HashMap::new(),
                             HashMap::new(),
                             HashMap::new(),
@@ -606,6 +1098,32 @@ impl VectorStore {
                 cols.insert(name.to_string(), c);
                 Ok(())
             }
+            "vector_collection_deleted" => {
+                let name = ev
+                    .data
+                    .get("collection")
+                    .and_then(|v| v.as_str())
+                    .ok_or(VectorError::InvalidManifest)?;
+                self.remove_collection(name)?;
+                Ok(())
+            }
+            "vector_default_filter_set" => {
+                let name = ev
+                    .data
+                    .get("collection")
+                    .and_then(|v| v.as_str())
+                    .ok_or(VectorError::InvalidManifest)?;
+                let filter = ev.data.get("filter").filter(|f| !f.is_null()).cloned();
+                let mut cols = self.0.collections.write();
+                let c = cols.get_mut(name).ok_or(VectorError::CollectionNotFound)?;
+                if ev.offset <= c.applied_offset {
+                    return Ok(());
+                }
+                c.manifest.default_filter = filter;
+                // Also persists the manifest, filter included.
+                c.mark_applied_offset(ev.offset)?;
+                Ok(())
+            }
             "vector_added" | "vector_upserted" | "vector_updated" | "vector_deleted" => {
                 let collection = ev
                     .data
@@ -635,8 +1153,10 @@ impl VectorStore {
                             vector: None,
                             meta: None,
                             quantized: None,
+                            revision: 0,
+                            expires_at_ms: None,
                         };
-                        c.apply_record(record, None)?;
+                        c.apply_record(record)?;
                     }
                     _ => {
                         let vector: Vec<f32> = serde_json::from_value(
@@ -651,6 +1171,11 @@ impl VectorStore {
                             .get("meta")
                             .cloned()
                             .unwrap_or(serde_json::Value::Null);
+                        let revision = ev
+                            .data
+                            .get("revision")
+                            .and_then(|v| v.as_u64())
+                            .unwrap_or(0);
                         let record = Record {
                             offset: ev.offset,
                             op: RecordOp::Upsert,
@@ -658,8 +1183,10 @@ impl VectorStore {
                             vector: Some(vector),
                             meta: Some(meta),
                             quantized: None,
+                            revision,
+                            expires_at_ms: ev.data.get("expires_at_ms").and_then(|v| v.as_u64()),
                         };
-                        c.apply_record(record, None)?;
+                        c.apply_record(record)?;
                     }
                 }
                 Ok(())
@@ -686,8 +1213,10 @@ impl VectorStore {
             vector: Some(item.vector),
             meta: Some(item.meta),
             quantized: None,
+            revision: 0,
+            expires_at_ms: item.expires_at_ms,
         };
-        c.apply_record(record, Some(ApplyMode::InMemoryOnly))?;
+        c.apply_record(record)?;
         Ok(())
     }
 
@@ -706,8 +1235,10 @@ impl VectorStore {
             vector: Some(item.vector),
             meta: Some(item.meta),
             quantized: None,
+            revision: 0,
+            expires_at_ms: item.expires_at_ms,
         };
-        c.apply_record(record, Some(ApplyMode::InMemoryOnly))?;
+        c.apply_record(record)?;
         Ok(())
     }
 
@@ -735,8 +1266,10 @@ impl VectorStore {
             vector: Some(new_vec),
             meta: Some(new_meta),
             quantized: None,
+            revision: 0,
+            expires_at_ms: current.expires_at_ms,
         };
-        c.apply_record(record, Some(ApplyMode::InMemoryOnly))?;
+        c.apply_record(record)?;
         Ok(())
     }
 
@@ -755,8 +1288,10 @@ impl VectorStore {
             vector: None,
             meta: None,
             quantized: None,
+            revision: 0,
+            expires_at_ms: None,
         };
-        c.apply_record(record, Some(ApplyMode::InMemoryOnly))?;
+        c.apply_record(record)?;
         Ok(())
     }
 
@@ -765,11 +1300,175 @@ impl VectorStore {
         collection: &str,
         req: SearchRequest,
     ) -> Result<Vec<SearchHit>, VectorError> {
+        self.search_detailed(collection, req).map(|out| out.hits)
+    }
+
+    pub fn search_detailed(
+        &self,
+        collection: &str,
+        req: SearchRequest,
+    ) -> Result<SearchOutcome, VectorError> {
+        let waiting = std::time::Instant::now();
         let cols = self.0.collections.read();
+        let lock_wait = waiting.elapsed();
         let c = cols
             .get(collection)
             .ok_or(VectorError::CollectionNotFound)?;
-        c.search(req)
+        let mut outcome = c.search(req)?;
+        outcome.stats.lock_wait_us = lock_wait.as_micros() as u64;
+        Ok(outcome)
+    }
+
+    /// Top-1 probe: the nearest live vector when its score reaches
+    /// `threshold`, otherwise `None`. Meant for dedup checks before an insert.
+    pub fn exists_similar(
+        &self,
+        collection: &str,
+        vector: Vec<f32>,
+        threshold: f32,
+    ) -> Result<Option<(String, f32)>, VectorError> {
+        let hits = self.search(
+            collection,
+            SearchRequest {
+                vector,
+                k: 1,
+                filters: None,
+                include_meta: Some(false),
+                tie_break: None,
+                include_vector: None,
+                min_score: Some(threshold),
+                offset: None,
+                exact: None,
+                with_total: None,
+            },
+        )?;
+        Ok(hits.into_iter().next().map(|hit| (hit.id, hit.score)))
+    }
+
+    /// Runs `req` against each of `collections` under one read of the
+    /// collection map and merges the hits into a single top-k, best score
+    /// first (ties by collection, then id). Every collection must have the
+    /// query's dimension.
+    pub fn search_multi(
+        &self,
+        collections: &[String],
+        req: SearchRequest,
+    ) -> Result<Vec<CollectionHit>, VectorError> {
+        let cols = self.0.collections.read();
+        let mut targets: Vec<(&str, &Collection)> = Vec::with_capacity(collections.len());
+        for name in collections {
+            if targets.iter().any(|(seen, _)| *seen == name.as_str()) {
+                continue;
+            }
+            let c = cols.get(name).ok_or(VectorError::CollectionNotFound)?;
+            if c.dim != req.vector.len() {
+                return Err(VectorError::DimMismatch);
+            }
+            targets.push((name.as_str(), c));
+        }
+
+        let offset = req.offset.unwrap_or(0);
+        let per_collection = SearchRequest {
+            k: req.k.saturating_add(offset),
+            offset: None,
+            with_total: None,
+            ..req
+        };
+        let mut merged = Vec::new();
+        for (name, c) in targets {
+            let outcome = c.search(per_collection.clone())?;
+            merged.extend(outcome.hits.into_iter().map(|hit| CollectionHit {
+                collection: name.to_string(),
+                hit,
+            }));
+        }
+        merged.sort_by(|a, b| {
+            b.hit
+                .score
+                .partial_cmp(&a.hit.score)
+                .unwrap_or(Ordering::Equal)
+                .then_with(|| a.collection.cmp(&b.collection))
+                .then_with(|| a.hit.id.cmp(&b.hit.id))
+        });
+        Ok(merged.into_iter().skip(offset).take(req.k).collect())
+    }
+
+    /// Runs several queries under a single read of the collection map, so a
+    /// concurrent create or drop can't interleave between them. Results keep
+    /// the order of `reqs`; the first failing query aborts the batch.
+    pub fn search_batch(
+        &self,
+        collection: &str,
+        reqs: Vec<SearchRequest>,
+    ) -> Result<Vec<SearchOutcome>, VectorError> {
+        let cols = self.0.collections.read();
+        let c = cols
+            .get(collection)
+            .ok_or(VectorError::CollectionNotFound)?;
+        reqs.into_iter().map(|req| c.search(req)).collect()
+    }
+
+    /// For each of `ids`, searches with its stored vector and keeps the `k`
+    /// nearest other vectors, all under one read of the collection map.
+    /// Edges come grouped by `ids` order, best score first; an unknown or
+    /// expired id fails the whole call.
+    pub fn knn_graph(
+        &self,
+        collection: &str,
+        ids: &[String],
+        k: usize,
+    ) -> Result<Vec<KnnEdge>, VectorError> {
+        let cols = self.0.collections.read();
+        let c = cols
+            .get(collection)
+            .ok_or(VectorError::CollectionNotFound)?;
+        let mut edges = Vec::with_capacity(ids.len().saturating_mul(k));
+        for from in ids {
+            let item = c
+                .items
+                .get(from)
+                .filter(|item| !item.is_expired())
+                .ok_or(VectorError::IdNotFound)?;
+            let outcome = c.search(SearchRequest {
+                vector: item.vector.clone(),
+                k: k.saturating_add(1),
+                filters: None,
+                include_meta: Some(false),
+                tie_break: None,
+                include_vector: None,
+                min_score: None,
+                offset: None,
+                exact: None,
+                with_total: None,
+            })?;
+            edges.extend(
+                outcome
+                    .hits
+                    .into_iter()
+                    .filter(|hit| hit.id != *from)
+                    .take(k)
+                    .map(|hit| KnnEdge {
+                        from: from.clone(),
+                        to: hit.id,
+                        score: hit.score,
+                    }),
+            );
+        }
+        Ok(edges)
+    }
+
+    /// Scores every live vector with the exact metric, ignoring HNSW and IVF.
+    /// Slow on large collections; meant as ground truth for recall checks.
+    pub fn search_exact(
+        &self,
+        collection: &str,
+        req: SearchRequest,
+    ) -> Result<Vec<SearchHit>, VectorError> {
+        let cols = self.0.collections.read();
+        let c = cols
+            .get(collection)
+            .ok_or(VectorError::CollectionNotFound)?;
+        c.search_exact(req)
     }
 
     fn layout_for(&self, collection: &str) -> Option<CollectionLayout> {
@@ -777,19 +1476,78 @@ impl VectorStore {
         Some(CollectionLayout::new(&base, collection))
     }
 
-    pub fn vacuum_collection(&self, collection: &str) -> Result<(), VectorError> {
+    /// Repacks the live vectors of segments carrying at least
+    /// `merge_tombstone_ratio` tombstones (any, when the ratio is `0`) into
+    /// fresh segments. In-memory only: unlike `vacuum_collection` the records
+    /// on disk are left alone. Returns how many segments were rebuilt.
+    pub fn merge_segments(&self, collection: &str) -> Result<usize, VectorError> {
+        let mut cols = self.0.collections.write();
+        let c = cols
+            .get_mut(collection)
+            .ok_or(VectorError::CollectionNotFound)?;
+        Ok(c.merge_sparse_segments(self.0.settings.merge_tombstone_ratio))
+    }
+
+    /// Rebuilds every search structure of the collection from the vectors
+    /// already in memory, then refreshes the HNSW dump so a stale or corrupt
+    /// graph is not reloaded on the next open. Unlike `vacuum_collection`,
+    /// `vectors.bin` is not rewritten.
+    pub fn rebuild_collection_index(&self, collection: &str) -> Result<(), VectorError> {
         let mut cols = self.0.collections.write();
         let c = cols
             .get_mut(collection)
             .ok_or(VectorError::CollectionNotFound)?;
+        c.rebuild_index();
+        if let Err(err) = c.dump_hnsw() {
+            tracing::warn!(error = %err, collection, "hnsw dump after reindex failed");
+        }
+        Ok(())
+    }
+
+    pub fn vacuum_collection(&self, collection: &str) -> Result<(), VectorError> {
+        // The upgradable read keeps writers out while the rewrite and the new index
+        // are built, but searches keep running against the current index until the
+        // short write lock that swaps them in.
+        let cols = self.0.collections.upgradable_read();
+        let c = cols
+            .get(collection)
+            .ok_or(VectorError::CollectionNotFound)?;
         let layout = c.layout.clone().ok_or(VectorError::Persistence)?;
         let result = persist::rewrite_collection(&layout, &c.manifest, &c.items, &c.q8_store)
             .map_err(|_| VectorError::Persistence)?;
-        c.manifest = result.manifest;
+        let parts = c.build_index_parts();
+        let mut manifest = result.manifest;
+        if !parts.segments.is_empty() {
+            if let Err(err) =
+                hnsw_io::write_dump(&layout, &parts.segments, &mut manifest, c.applied_offset)
+            {
+                tracing::warn!(error = %err, collection, "hnsw dump after vacuum failed");
+            }
+        }
+        let mut cols = RwLockUpgradableReadGuard::upgrade(cols);
+        let c = cols
+            .get_mut(collection)
+            .ok_or(VectorError::CollectionNotFound)?;
+        c.manifest = manifest;
         c.item_runs = result.item_runs;
-        c.rebuild_index();
+        c.install_index_parts(parts);
         Ok(())
     }
+
+    /// Dumps the HNSW segment graphs of every persisted collection; returns
+    /// how many collections were written.
+    pub fn dump_hnsw_graphs(&self) -> anyhow::Result<usize> {
+        let mut cols = self.0.collections.write();
+        let mut dumped = 0;
+        for (name, c) in cols.iter_mut() {
+            if c.dump_hnsw()
+                .with_context(|| format!("dump hnsw graphs of {name}"))?
+            {
+                dumped += 1;
+            }
+        }
+        Ok(dumped)
+    }
 }
 
 impl Default for VectorStore {
@@ -798,11 +1556,6 @@ impl Default for VectorStore {
     }
 }
 
-#[derive(Clone, Copy)]
-enum ApplyMode {
-    InMemoryOnly,
-}
-
 impl Collection {
     fn new(
         layout: Option<CollectionLayout>,
@@ -815,6 +1568,11 @@ impl Collection {
     ) -> Result<Self, VectorError> {
         let dim = manifest.dim;
         let metric = manifest.metric;
+        let segment_max_items = manifest.segment_max_items.max(1);
+        let expiring = items
+            .iter()
+            .filter_map(|(id, item)| item.expires_at_ms.map(|at| (at, id.clone())))
+            .collect();
         let mut c = Self {
             dim,
             metric,
@@ -827,33 +1585,169 @@ impl Collection {
             segments: Vec::new(),
             item_segments: HashMap::new(),
             cluster_members: HashMap::new(),
-            segment_max_items: DEFAULT_SEGMENT_MAX,
-            keyword_index: HashMap::new(),
+            segment_max_items,
+            keyword_index: KeywordIndex::new(settings.max_index_values_per_field),
             settings,
             ivf: None,
             item_clusters: HashMap::new(),
             disk_graph: None,
+            hnsw_reloaded: false,
+            expiring,
         };
         c.load_ivf_from_disk()
             .map_err(|_| VectorError::Persistence)?;
         c.load_disk_graph().map_err(|_| VectorError::Persistence)?;
-        c.rebuild_index();
+        let dumped = c.load_hnsw_dump();
+        c.hnsw_reloaded = dumped.is_some();
+        let parts = c.build_index_parts_with(dumped);
+        c.install_index_parts(parts);
         c.sync_manifest_run_settings()?;
         Ok(c)
     }
 
+    fn untrack_expiry(&mut self, id: &str, item: &VectorItem) {
+        if let Some(at) = item.expires_at_ms {
+            self.expiring.remove(&(at, id.to_string()));
+        }
+    }
+
+    fn expired_ids(&self, now: u64) -> impl Iterator<Item = &String> {
+        self.expiring
+            .iter()
+            .take_while(move |(at, _)| *at <= now)
+            .map(|(_, id)| id)
+    }
+
     fn rebuild_index(&mut self) {
-        self.keyword_index.clear();
-        let metas: Vec<(String, serde_json::Value)> = self
+        let parts = self.build_index_parts();
+        self.install_index_parts(parts);
+    }
+
+    /// Segment graphs from the `hnsw/` dump, if it was taken from exactly the
+    /// records loaded and lists every live item.
+    fn load_hnsw_dump(&self) -> Option<Vec<SegmentIndex>> {
+        if !self.settings.hnsw_fallback_enabled
+            || !hnsw_io::dump_matches(&self.manifest, self.applied_offset)
+        {
+            return None;
+        }
+        let layout = self.layout.as_ref()?;
+        let segments = match hnsw_io::load_segments(layout, self.metric, self.manifest.hnsw) {
+            Ok(segments) => segments,
+            Err(err) => {
+                tracing::warn!(error = %err, dir = %layout.dir.display(), "hnsw dump unreadable; rebuilding");
+                return None;
+            }
+        };
+        let live: usize = segments.iter().map(|s| s.live).sum();
+        let complete = live == self.items.len()
+            && segments
+                .iter()
+                .all(|s| s.data_ids.keys().all(|id| self.items.contains_key(id)));
+        complete.then_some(segments)
+    }
+
+    /// Dumps the segment graphs so the next open can skip re-inserting them.
+    fn dump_hnsw(&mut self) -> anyhow::Result<bool> {
+        let Some(layout) = &self.layout else {
+            return Ok(false);
+        };
+        if self.segments.is_empty() {
+            return Ok(false);
+        }
+        hnsw_io::write_dump(
+            layout,
+            &self.segments,
+            &mut self.manifest,
+            self.applied_offset,
+        )?;
+        Ok(true)
+    }
+
+    /// Derives every search structure from `items` without touching the live ones.
+    fn build_index_parts(&self) -> IndexParts {
+        self.build_index_parts_with(None)
+    }
+
+    /// Like `build_index_parts`, but reuses `segments` when they were loaded
+    /// from a dump instead of inserting every vector again.
+    fn build_index_parts_with(&self, segments: Option<Vec<SegmentIndex>>) -> IndexParts {
+        let mut keyword_index = KeywordIndex::new(self.settings.max_index_values_per_field);
+        for (id, item) in self.items.iter() {
+            keyword_index.insert(id, &item.meta);
+        }
+
+        let q8_store = self
             .items
             .iter()
-            .map(|(id, item)| (id.clone(), item.meta.clone()))
+            .map(|(id, item)| {
+                let q = self.q8_store.get(id).cloned().unwrap_or_else(|| {
+                    q8ops::quantize_per_vector(&index_vector(self.metric, &item.vector))
+                });
+                (id.clone(), q)
+            })
             .collect();
-        for (id, meta) in metas {
-            self.add_meta_to_index(&id, &meta);
+
+        let (segments, item_segments) = match segments {
+            Some(segments) => {
+                let item_segments = segment_assignments(&segments);
+                (segments, item_segments)
+            }
+            None => self.build_segments(),
+        };
+
+        let mut cluster_members: HashMap<usize, HashSet<String>> = HashMap::new();
+        let item_clusters = match &self.ivf {
+            Some(ivf) => assign_all_clusters(ivf, &self.items, self.settings.simd_enabled),
+            None => HashMap::new(),
+        };
+        for (id, cluster) in item_clusters.iter() {
+            cluster_members
+                .entry(*cluster)
+                .or_default()
+                .insert(id.clone());
+        }
+
+        IndexParts {
+            keyword_index,
+            q8_store,
+            segments,
+            item_segments,
+            item_clusters,
+            cluster_members,
+        }
+    }
+
+    fn build_segments(&self) -> (Vec<SegmentIndex>, HashMap<String, usize>) {
+        let mut segments = Vec::new();
+        let mut item_segments = HashMap::new();
+        if self.settings.hnsw_fallback_enabled {
+            let mut current =
+                SegmentIndex::new(self.metric, self.segment_max_items, self.manifest.hnsw);
+            for (id, item) in self.items.iter() {
+                if current.live >= current.capacity {
+                    segments.push(current);
+                    current =
+                        SegmentIndex::new(self.metric, self.segment_max_items, self.manifest.hnsw);
+                }
+                current.insert(
+                    id.clone(),
+                    index_vector(self.metric, &item.vector).into_owned(),
+                );
+                item_segments.insert(id.clone(), segments.len());
+            }
+            segments.push(current);
         }
-        self.ensure_quantized_store();
-        self.rebuild_segments();
+        (segments, item_segments)
+    }
+
+    fn install_index_parts(&mut self, parts: IndexParts) {
+        self.keyword_index = parts.keyword_index;
+        self.q8_store = parts.q8_store;
+        self.segments = parts.segments;
+        self.item_segments = parts.item_segments;
+        self.item_clusters = parts.item_clusters;
+        self.cluster_members = parts.cluster_members;
     }
 
     fn update_cluster_membership(&mut self, id: &str, cluster: usize) {
@@ -893,35 +1787,6 @@ impl Collection {
         Ok(())
     }
 
-    fn rebuild_segments(&mut self) {
-        self.item_segments.clear();
-        self.segments.clear();
-        if !self.settings.hnsw_fallback_enabled {
-            return;
-        }
-        if self.items.is_empty() {
-            self.segments
-                .push(SegmentIndex::new(self.metric, self.segment_max_items));
-            return;
-        }
-        let mut current = SegmentIndex::new(self.metric, self.segment_max_items);
-        for (id, item) in self.items.iter() {
-            if current.live >= current.capacity {
-                self.segments.push(current);
-                current = SegmentIndex::new(self.metric, self.segment_max_items);
-            }
-            current.insert(id.clone(), item.vector.clone());
-            let idx = self.segments.len();
-            self.item_segments.insert(id.clone(), idx);
-        }
-        self.segments.push(current);
-        if self.segments.is_empty() {
-            self.segments
-                .push(SegmentIndex::new(self.metric, self.segment_max_items));
-        }
-        self.refresh_item_clusters();
-    }
-
     fn force_compact(&mut self, force: bool) -> Result<bool, VectorError> {
         if self.layout.is_none() {
             return Ok(false);
@@ -951,7 +1816,10 @@ impl Collection {
             .items
             .iter()
             .filter(|(id, _)| !self.q8_store.contains_key(*id))
-            .map(|(id, item)| (id.clone(), item.vector.clone()))
+            .map(|(id, item)| {
+                let vector = index_vector(self.metric, &item.vector).into_owned();
+                (id.clone(), vector)
+            })
             .collect();
         for (id, vector) in missing {
             let q = q8ops::quantize_per_vector(&vector);
@@ -1029,7 +1897,7 @@ impl Collection {
         entries.sort_by(|a, b| a.0.cmp(b.0));
         let mut vectors: Vec<Vec<f32>> = entries
             .into_iter()
-            .map(|(_, item)| item.vector.clone())
+            .map(|(_, item)| index_vector(self.metric, &item.vector).into_owned())
             .collect();
         let limit = self.settings.ivf.training_sample.min(vectors.len());
         if limit == 0 {
@@ -1137,13 +2005,19 @@ impl Collection {
 
     fn ensure_active_segment(&mut self) -> usize {
         if self.segments.is_empty() {
-            self.segments
-                .push(SegmentIndex::new(self.metric, self.segment_max_items));
+            self.segments.push(SegmentIndex::new(
+                self.metric,
+                self.segment_max_items,
+                self.manifest.hnsw,
+            ));
         }
         let last_idx = self.segments.len() - 1;
         if self.segments[last_idx].live >= self.segments[last_idx].capacity {
-            self.segments
-                .push(SegmentIndex::new(self.metric, self.segment_max_items));
+            self.segments.push(SegmentIndex::new(
+                self.metric,
+                self.segment_max_items,
+                self.manifest.hnsw,
+            ));
             return self.segments.len() - 1;
         }
         last_idx
@@ -1177,59 +2051,59 @@ impl Collection {
     }
 
     fn add_meta_to_index(&mut self, id: &str, meta: &serde_json::Value) {
-        let Some(obj) = meta.as_object() else {
-            return;
-        };
-        for (k, v) in obj {
-            let Some(value) = v.as_str() else {
-                continue;
-            };
-            self.keyword_index
-                .entry(k.clone())
-                .or_default()
-                .entry(value.to_string())
-                .or_default()
-                .insert(id.to_string());
-        }
+        self.keyword_index.insert(id, meta);
     }
 
     fn remove_meta_from_index(&mut self, id: &str, meta: Option<&serde_json::Value>) {
-        let Some(meta) = meta else { return };
-        let Some(obj) = meta.as_object() else { return };
-        for (k, v) in obj {
-            let Some(value) = v.as_str() else {
-                continue;
-            };
-            if let Some(by_value) = self.keyword_index.get_mut(k) {
-                if let Some(set) = by_value.get_mut(value) {
-                    set.remove(id);
-                    if set.is_empty() {
-                        by_value.remove(value);
-                    }
-                }
-                if by_value.is_empty() {
-                    self.keyword_index.remove(k);
-                }
-            }
+        if let Some(meta) = meta {
+            self.keyword_index.remove(id, meta);
         }
     }
 
+    /// Candidate ids from the keyword index, or `None` when some part of the
+    /// filter can't be answered from it and a post-filtered scan is needed.
     fn keyword_candidates(&self, filters: &serde_json::Value) -> Option<HashSet<String>> {
         let obj = filters.as_object()?;
         let mut current: Option<HashSet<String>> = None;
         for (k, v) in obj {
-            let Some(value) = v.as_str() else {
-                return None;
-            };
-            let Some(by_value) = self.keyword_index.get(k) else {
-                return Some(HashSet::new());
-            };
-            let Some(ids) = by_value.get(value) else {
-                return Some(HashSet::new());
+            let ids = if k == "$or" {
+                let mut union = HashSet::new();
+                for branch in v.as_array()? {
+                    union.extend(self.keyword_candidates(branch)?);
+                }
+                union
+            } else if k == "$and" {
+                // Every branch must hold, so unindexable ones can be left to
+                // the per-item check instead of disabling the index.
+                let mut branches = v
+                    .as_array()?
+                    .iter()
+                    .filter_map(|branch| self.keyword_candidates(branch));
+                let Some(mut acc) = branches.next() else {
+                    continue;
+                };
+                for ids in branches {
+                    acc.retain(|id| ids.contains(id));
+                }
+                acc
+            } else if let Some(value) = v.as_str() {
+                self.keyword_index.postings(k, value)?
+            } else {
+                // The index is exact-match on strings; numbers and other
+                // operators such as `{"$lt": 10}` fall back to a full scan.
+                let ops = filter_operators(v)?;
+                let values = match ops.get("$in") {
+                    Some(serde_json::Value::Array(values)) if ops.len() == 1 => values,
+                    _ => return None,
+                };
+                let mut union = HashSet::new();
+                for value in values {
+                    union.extend(self.keyword_index.postings(k, value.as_str()?)?);
+                }
+                union
             };
-            let ids_cloned: HashSet<String> = ids.iter().cloned().collect();
             current = match current {
-                None => Some(ids_cloned),
+                None => Some(ids),
                 Some(mut acc) => {
                     acc.retain(|id| ids.contains(id));
                     Some(acc)
@@ -1239,29 +2113,37 @@ impl Collection {
         current
     }
 
-    fn apply_record(
-        &mut self,
-        mut record: Record,
-        mode: Option<ApplyMode>,
-    ) -> Result<(), VectorError> {
+    /// Applies the record in memory and, for persistent collections, appends it to
+    /// the collection's runs first, whether it came from the engine or a direct call.
+    fn apply_record(&mut self, mut record: Record) -> Result<(), VectorError> {
         if matches!(record.op, RecordOp::Upsert | RecordOp::Delete) {
             self.invalidate_disk_index_if_needed();
         }
-        let normalized_vec = if record.op == RecordOp::Upsert {
-            let Some(vec) = record.vector.take() else {
+        // The record and `items` keep the vector exactly as sent; the
+        // normalized copy only feeds the HNSW segments and q8 store.
+        let indexed_vec = if record.op == RecordOp::Upsert {
+            let Some(vec) = record.vector.as_ref() else {
                 return Err(VectorError::InvalidManifest);
             };
             if vec.len() != self.dim {
                 return Err(VectorError::DimMismatch);
             }
-            let normalized = normalize_if_needed(self.metric, vec);
-            record.vector = Some(normalized.clone());
-            Some(normalized)
+            let norm = simd::dot(vec, vec, self.settings.simd_enabled).sqrt();
+            self.manifest.norm_stats.observe(norm);
+            Some(index_vector(self.metric, vec).into_owned())
         } else {
             None
         };
+        if record.op == RecordOp::Upsert && record.revision == 0 {
+            record.revision = self
+                .items
+                .get(&record.id)
+                .map(|item| item.revision)
+                .unwrap_or(0)
+                .saturating_add(1);
+        }
         let quantized_vec = if record.op == RecordOp::Upsert {
-            let Some(vec) = normalized_vec.as_ref() else {
+            let Some(vec) = indexed_vec.as_ref() else {
                 return Err(VectorError::InvalidManifest);
             };
             let q = record
@@ -1276,13 +2158,11 @@ impl Collection {
         };
 
         if let Some(layout) = &self.layout {
-            if mode.is_none() {
-                let _ = persist::append_record(layout, &mut self.manifest, &record)
-                    .map_err(|_| VectorError::Persistence)?;
-                if record.op == RecordOp::Upsert {
-                    if let Some(run) = self.manifest.runs.last() {
-                        self.item_runs.insert(record.id.clone(), run.file.clone());
-                    }
+            let _ = persist::append_record(layout, &mut self.manifest, &record)
+                .map_err(|_| VectorError::Persistence)?;
+            if record.op == RecordOp::Upsert {
+                if let Some(run) = self.manifest.runs.last() {
+                    self.item_runs.insert(record.id.clone(), run.file.clone());
                 }
             }
         }
@@ -1298,6 +2178,7 @@ impl Collection {
                 let removed = self.items.remove(&record.id);
                 if let Some(old) = removed.as_ref() {
                     self.remove_meta_from_index(&record.id, Some(&old.meta));
+                    self.untrack_expiry(&record.id, old);
                 }
                 self.remove_from_segments(&record.id);
                 self.q8_store.remove(&record.id);
@@ -1309,18 +2190,25 @@ impl Collection {
             }
             RecordOp::Upsert => {
                 self.manifest.upsert_count = self.manifest.upsert_count.saturating_add(1);
-                let vec = normalized_vec.clone().ok_or(VectorError::InvalidManifest)?;
+                let vec = record.vector.take().ok_or(VectorError::InvalidManifest)?;
+                let indexed = indexed_vec.ok_or(VectorError::InvalidManifest)?;
                 let meta = record.meta.take().unwrap_or(serde_json::Value::Null);
                 let new_item = VectorItem {
-                    vector: vec.clone(),
+                    vector: vec,
                     meta,
+                    revision: record.revision,
+                    expires_at_ms: record.expires_at_ms,
                 };
                 let previous = self.items.insert(record.id.clone(), new_item.clone());
                 if let Some(prev) = previous.as_ref() {
                     self.remove_meta_from_index(&record.id, Some(&prev.meta));
+                    self.untrack_expiry(&record.id, prev);
+                }
+                if let Some(at) = new_item.expires_at_ms {
+                    self.expiring.insert((at, record.id.clone()));
                 }
                 self.add_meta_to_index(&record.id, &new_item.meta);
-                self.insert_into_segments(&record.id, new_item.vector.clone());
+                self.insert_into_segments(&record.id, indexed);
                 if let Some(qvec) = quantized_vec {
                     self.q8_store.insert(record.id.clone(), qvec);
                 }
@@ -1333,7 +2221,7 @@ impl Collection {
 
         self.manifest.live_count = self.items.len();
 
-        if self.layout.is_some() && mode.is_none() {
+        if self.layout.is_some() {
             let compacted = self.maybe_compact_runs(false)?;
             if !compacted {
                 self.persist_manifest()
@@ -1342,10 +2230,61 @@ impl Collection {
         }
 
         self.maybe_train_ivf()?;
+        self.maybe_merge_segments();
 
         Ok(())
     }
 
+    fn maybe_merge_segments(&mut self) {
+        let ratio = self.settings.merge_tombstone_ratio;
+        if ratio <= 0.0 {
+            return;
+        }
+        let (tombstones, nodes) = self.segments.iter().fold((0, 0), |(t, n), s| {
+            (t + s.tombstones(), n + s.id_by_data_id.len())
+        });
+        if tombstones >= MERGE_MIN_TOMBSTONES && tombstones as f32 >= ratio * nodes as f32 {
+            self.merge_sparse_segments(ratio);
+        }
+    }
+
+    /// Re-inserts the live vectors of every segment at least `ratio`
+    /// tombstones into fresh, tightly packed segments, leaving the others
+    /// alone. Returns how many segments were rebuilt.
+    fn merge_sparse_segments(&mut self, ratio: f32) -> usize {
+        let (sparse, mut kept): (Vec<_>, Vec<_>) = std::mem::take(&mut self.segments)
+            .into_iter()
+            .partition(|s| s.is_sparse(ratio));
+        if sparse.is_empty() {
+            self.segments = kept;
+            return 0;
+        }
+        let mut current =
+            SegmentIndex::new(self.metric, self.segment_max_items, self.manifest.hnsw);
+        for seg in &sparse {
+            for id in seg.data_ids.keys() {
+                let Some(item) = self.items.get(id) else {
+                    continue;
+                };
+                if current.live >= current.capacity {
+                    kept.push(current);
+                    current =
+                        SegmentIndex::new(self.metric, self.segment_max_items, self.manifest.hnsw);
+                }
+                current.insert(
+                    id.clone(),
+                    index_vector(self.metric, &item.vector).into_owned(),
+                );
+            }
+        }
+        if current.live > 0 || kept.is_empty() {
+            kept.push(current);
+        }
+        self.item_segments = segment_assignments(&kept);
+        self.segments = kept;
+        sparse.len()
+    }
+
     fn build_disk_index(
         &mut self,
         params: DiskAnnBuildParams,
@@ -1360,7 +2299,9 @@ impl Collection {
                 let q = self
                     .q8_store
                     .entry(id.clone())
-                    .or_insert_with(|| q8ops::quantize_per_vector(&item.vector))
+                    .or_insert_with(|| {
+                        q8ops::quantize_per_vector(&index_vector(self.metric, &item.vector))
+                    })
                     .clone();
                 (id.clone(), q)
             })
@@ -1462,15 +2403,66 @@ impl Collection {
         Ok(())
     }
 
-    fn search(&self, req: SearchRequest) -> Result<Vec<SearchHit>, VectorError> {
-        if req.vector.len() != self.dim {
-            return Err(VectorError::DimMismatch);
+    fn search(&self, req: SearchRequest) -> Result<SearchOutcome, VectorError> {
+        let started = std::time::Instant::now();
+        let exact = req.exact == Some(true);
+        let known_total =
+            (req.with_total == Some(true)).then(|| self.count_matches(req.filters.clone(), exact));
+        let offset = req.offset.unwrap_or(0);
+        let mut outcome = if exact {
+            let hits = self.search_exact(req)?;
+            SearchOutcome::bruteforce(hits, true, self.items.len())
+        } else {
+            self.search_ranked(req)?
+        };
+        if let Some(known) = known_total {
+            let seen = offset.saturating_add(outcome.hits.len());
+            outcome.total = Some(known.unwrap_or(outcome.stats.candidates).max(seen));
+        }
+        outcome.stats.elapsed_us = started.elapsed().as_micros() as u64;
+        Ok(outcome)
+    }
+
+    /// Live vectors matching `filters`, when that can be counted without
+    /// scanning the collection: no filters, or filters the keyword index
+    /// narrows down. `scan` falls back to checking every vector.
+    fn count_matches(&self, filters: Option<serde_json::Value>, scan: bool) -> Option<usize> {
+        let filters = self.scoped_filters(filters);
+        let matches =
+            |item: &VectorItem| !item.is_expired() && matches_filters(&item.meta, filters.as_ref());
+        let Some(f) = filters.as_ref() else {
+            // `expiring` is sorted by expiry, so only the expired prefix is walked.
+            let expired = self.expired_ids(now_ms()).count();
+            return Some(self.items.len().saturating_sub(expired));
+        };
+        if let Some(set) = self.keyword_candidates(f) {
+            return Some(
+                set.iter()
+                    .filter_map(|id| self.items.get(id))
+                    .filter(|item| matches(item))
+                    .count(),
+            );
         }
+        scan.then(|| self.items.values().filter(|item| matches(item)).count())
+    }
+
+    fn search_ranked(&self, mut req: SearchRequest) -> Result<SearchOutcome, VectorError> {
+        req.vector = self.fit_query_dim(req.vector)?;
+        req.filters = self.scoped_filters(req.filters);
         let include_meta = req.include_meta.unwrap_or(false);
         let k = req.k.max(1);
+        let tie_break = req.tie_break.as_ref();
+        let opts = HitOptions {
+            include_meta,
+            include_vector: req.include_vector.unwrap_or(false),
+            min_score: req.min_score,
+            k,
+            offset: req.offset.unwrap_or(0),
+            tie_break,
+        };
         let query = normalize_if_needed(self.metric, req.vector);
         if self.items.is_empty() {
-            return Ok(Vec::new());
+            return Ok(SearchOutcome::exact(Vec::new()));
         }
         let filter_candidates = req
             .filters
@@ -1478,67 +2470,84 @@ impl Collection {
             .and_then(|f| self.keyword_candidates(f));
         if let Some(ref set) = filter_candidates {
             if set.is_empty() {
-                return Ok(Vec::new());
+                return Ok(SearchOutcome::exact(Vec::new()));
             }
         }
         if self.settings.index_kind.is_diskann() {
             if let Some(hits) = self.search_diskann(
                 query.as_slice(),
-                include_meta,
+                opts,
                 req.filters.as_ref(),
                 filter_candidates.as_ref(),
-                k,
             )? {
-                return Ok(hits);
+                return Ok(SearchOutcome::approximate(hits));
             }
         }
         let ivf_probes = self.ivf_probe_set(query.as_slice());
         if let Some(ref set) = filter_candidates {
             if set.is_empty() {
-                return Ok(Vec::new());
+                return Ok(SearchOutcome::exact(Vec::new()));
             }
             if set.len() <= 512 {
-                return Ok(self.search_subset_bruteforce(
+                let hits = self.search_subset_bruteforce(
                     query.as_slice(),
-                    include_meta,
+                    opts,
                     set,
                     req.filters.as_ref(),
-                    k,
                     ivf_probes.as_ref(),
+                );
+                // IVF probing still restricts the subset to the nearest clusters.
+                return Ok(SearchOutcome::bruteforce(
+                    hits,
+                    ivf_probes.is_none(),
+                    set.len(),
                 ));
             }
         }
         if let Some(ref probes) = ivf_probes {
-            return Ok(self.search_ivf_flat(
+            let mut outcome = SearchOutcome::approximate(self.search_ivf_flat(
                 query.as_slice(),
-                include_meta,
+                opts,
                 req.filters.as_ref(),
                 filter_candidates.as_ref(),
-                k,
                 probes,
             ));
-        }
-
-        let candidate_k = (k * 10).min(self.items.len()).max(k);
-        let mut combined: Vec<(String, f32)> =
+            outcome.stats.candidates = probes
+                .iter()
+                .filter_map(|cluster| self.cluster_members.get(cluster))
+                .map(|members| members.len())
+                .sum();
+            return Ok(outcome);
+        }
+
+        let window = opts.window().min(self.items.len());
+        let candidate_k = window.saturating_mul(10).min(self.items.len()).max(window);
+        let merge = |mut acc: (Vec<(String, f32)>, u64), mut part: (Vec<(String, f32)>, u64)| {
+            acc.0.append(&mut part.0);
+            acc.1 += part.1;
+            acc
+        };
+        let (mut combined, tombstone_skips) =
             if self.settings.should_parallel_segments(self.segments.len()) {
-                self.segments
-                    .par_iter()
-                    .map(|segment| segment.search_candidates(query.as_slice(), candidate_k))
-                    .reduce(Vec::new, |mut acc, mut part| {
-                        acc.append(&mut part);
-                        acc
-                    })
+                self.settings.probe_in_pool(|| {
+                    self.segments
+                        .par_iter()
+                        .map(|segment| segment.search_candidates(query.as_slice(), candidate_k))
+                        .reduce(|| (Vec::new(), 0), merge)
+                })
             } else {
                 self.segments
                     .iter()
-                    .flat_map(|segment| segment.search_candidates(query.as_slice(), candidate_k))
-                    .collect()
+                    .map(|segment| segment.search_candidates(query.as_slice(), candidate_k))
+                    .fold((Vec::new(), 0), merge)
             };
-        combined.sort_by(compare_scores_desc);
+        let candidates = combined.len();
+        combined.retain(|(_, score)| opts.keeps(*score));
+        combined.sort_by(|a, b| self.compare_ranked(a, b, tie_break));
 
         let mut hits = Vec::new();
         let mut seen = HashSet::new();
+        let mut skipped = 0;
         for (id, score) in combined {
             if !seen.insert(id.clone()) {
                 continue;
@@ -1559,29 +2568,100 @@ impl Collection {
             let Some(item) = self.items.get(&id) else {
                 continue;
             };
-            if !matches_filters(&item.meta, req.filters.as_ref()) {
+            if item.is_expired() || !matches_filters(&item.meta, req.filters.as_ref()) {
                 continue;
             }
-            hits.push(SearchHit {
-                id: id.clone(),
-                score,
-                meta: include_meta.then(|| item.meta.clone()),
-            });
+            if skipped < opts.offset {
+                skipped += 1;
+                continue;
+            }
+            hits.push(opts.hit(id, score, item));
             if hits.len() >= k {
                 break;
             }
         }
 
-        Ok(hits)
+        Ok(SearchOutcome {
+            hits,
+            exact: false,
+            tombstone_skips,
+            stats: SearchStats {
+                segments_probed: self.segments.len(),
+                candidates,
+                ..SearchStats::default()
+            },
+            total: None,
+        })
+    }
+
+    fn search_exact(&self, mut req: SearchRequest) -> Result<Vec<SearchHit>, VectorError> {
+        req.vector = self.fit_query_dim(req.vector)?;
+        req.filters = self.scoped_filters(req.filters);
+        let opts = HitOptions {
+            include_meta: req.include_meta.unwrap_or(false),
+            include_vector: req.include_vector.unwrap_or(false),
+            min_score: req.min_score,
+            k: req.k.max(1),
+            offset: req.offset.unwrap_or(0),
+            tie_break: req.tie_break.as_ref(),
+        };
+        let query = normalize_if_needed(self.metric, req.vector);
+        let all: HashSet<String> = self.items.keys().cloned().collect();
+        Ok(self.search_subset_bruteforce(query.as_slice(), opts, &all, req.filters.as_ref(), None))
+    }
+
+    /// ANDs the collection's default filter into the request's, so a client
+    /// can narrow a search but never widen it past the default.
+    fn scoped_filters(&self, filters: Option<serde_json::Value>) -> Option<serde_json::Value> {
+        let Some(default) = self.manifest.default_filter.clone() else {
+            return filters;
+        };
+        match filters {
+            None => Some(default),
+            Some(filters) => Some(serde_json::json!({ "$and": [default, filters] })),
+        }
+    }
+
+    fn fit_query_dim(&self, mut vector: Vec<f32>) -> Result<Vec<f32>, VectorError> {
+        if vector.len() == self.dim {
+            return Ok(vector);
+        }
+        if self.settings.strict_query_dim || vector.len().abs_diff(self.dim) > 1 {
+            return Err(VectorError::DimMismatch);
+        }
+        tracing::warn!(
+            expected = self.dim,
+            got = vector.len(),
+            "query vector dim off by one; padding/truncating"
+        );
+        vector.resize(self.dim, 0.0);
+        Ok(vector)
+    }
+
+    fn compare_ranked(
+        &self,
+        a: &(String, f32),
+        b: &(String, f32),
+        tie_break: Option<&TieBreak>,
+    ) -> Ordering {
+        let Some(tie) = tie_break else {
+            return compare_scores_desc(a, b);
+        };
+        b.1.partial_cmp(&a.1)
+            .unwrap_or(Ordering::Equal)
+            .then_with(|| {
+                let field = |id: &str| self.items.get(id).and_then(|i| i.meta.get(&tie.field));
+                compare_tie_field(field(&a.0), field(&b.0), tie.order)
+            })
+            .then_with(|| a.0.cmp(&b.0))
     }
 
     fn search_subset_bruteforce(
         &self,
         query: &[f32],
-        include_meta: bool,
+        opts: HitOptions<'_>,
         candidates: &HashSet<String>,
         filters: Option<&serde_json::Value>,
-        k: usize,
         cluster_filter: Option<&HashSet<usize>>,
     ) -> Vec<SearchHit> {
         let mut scored = Vec::new();
@@ -1597,21 +2677,18 @@ impl Collection {
                     continue;
                 }
             }
-            if !matches_filters(&item.meta, filters) {
+            if item.is_expired() || !matches_filters(&item.meta, filters) {
                 continue;
             }
             let score = exact_score(self.metric, &item.vector, query, self.settings.simd_enabled);
             scored.push((id.clone(), score));
         }
-        scored.sort_by(compare_scores_desc);
+        scored.retain(|(_, score)| opts.keeps(*score));
+        scored.sort_by(|a, b| self.compare_ranked(a, b, opts.tie_break));
         let mut hits = Vec::new();
-        for (id, score) in scored.into_iter().take(k) {
+        for (id, score) in scored.into_iter().skip(opts.offset).take(opts.k) {
             if let Some(item) = self.items.get(&id) {
-                hits.push(SearchHit {
-                    id,
-                    score,
-                    meta: include_meta.then(|| item.meta.clone()),
-                });
+                hits.push(opts.hit(id, score, item));
             }
         }
         hits
@@ -1620,10 +2697,9 @@ impl Collection {
     fn search_ivf_flat(
         &self,
         query: &[f32],
-        include_meta: bool,
+        opts: HitOptions<'_>,
         filters: Option<&serde_json::Value>,
         filter_candidates: Option<&HashSet<String>>,
-        k: usize,
         probes: &HashSet<usize>,
     ) -> Vec<SearchHit> {
         let q_query = q8ops::quantize_per_vector(query);
@@ -1641,7 +2717,7 @@ impl Collection {
                 let Some(item) = self.items.get(id) else {
                     continue;
                 };
-                if !matches_filters(&item.meta, filters) {
+                if item.is_expired() || !matches_filters(&item.meta, filters) {
                     continue;
                 }
                 let Some(qvec) = self.q8_store.get(id) else {
@@ -1655,7 +2731,11 @@ impl Collection {
             return Vec::new();
         }
         scored.sort_by(compare_scores_desc);
-        let refine_topk = self.manifest.q8_refine_topk.max(k).min(scored.len());
+        let refine_topk = self
+            .manifest
+            .q8_refine_topk
+            .max(opts.window())
+            .min(scored.len());
         let mut refined = Vec::new();
         for (id, _) in scored.into_iter().take(refine_topk) {
             if let Some(item) = self.items.get(&id) {
@@ -1664,15 +2744,12 @@ impl Collection {
                 refined.push((id, exact));
             }
         }
-        refined.sort_by(compare_scores_desc);
+        refined.retain(|(_, score)| opts.keeps(*score));
+        refined.sort_by(|a, b| self.compare_ranked(a, b, opts.tie_break));
         let mut hits = Vec::new();
-        for (id, score) in refined.into_iter().take(k) {
+        for (id, score) in refined.into_iter().skip(opts.offset).take(opts.k) {
             if let Some(item) = self.items.get(&id) {
-                hits.push(SearchHit {
-                    id,
-                    score,
-                    meta: include_meta.then(|| item.meta.clone()),
-                });
+                hits.push(opts.hit(id, score, item));
             }
         }
         hits
@@ -1681,10 +2758,9 @@ impl Collection {
     fn search_diskann(
         &self,
         query: &[f32],
-        include_meta: bool,
+        opts: HitOptions<'_>,
         filters: Option<&serde_json::Value>,
         filter_candidates: Option<&HashSet<String>>,
-        k: usize,
     ) -> Result<Option<Vec<SearchHit>>, VectorError> {
         let graph = match &self.disk_graph {
             Some(graph) => graph,
@@ -1699,7 +2775,10 @@ impl Collection {
                 query,
                 self.settings.simd_enabled,
                 search_list,
-                (k * 5).max(k),
+                opts.window()
+                    .min(self.items.len())
+                    .saturating_mul(5)
+                    .max(opts.k),
             )
             .map_err(|_| VectorError::Persistence)?;
         if approx.is_empty() {
@@ -1718,27 +2797,208 @@ impl Collection {
             let Some(item) = self.items.get(&id) else {
                 continue;
             };
-            if !matches_filters(&item.meta, filters) {
+            if item.is_expired() || !matches_filters(&item.meta, filters) {
                 continue;
             }
             let exact = exact_score(self.metric, &item.vector, query, self.settings.simd_enabled);
             refined.push((id.to_string(), exact));
         }
-        refined.sort_by(compare_scores_desc);
+        refined.retain(|(_, score)| opts.keeps(*score));
+        refined.sort_by(|a, b| self.compare_ranked(a, b, opts.tie_break));
         let mut hits = Vec::new();
-        for (id, score) in refined.into_iter().take(k) {
+        for (id, score) in refined.into_iter().skip(opts.offset).take(opts.k) {
             if let Some(item) = self.items.get(&id) {
-                hits.push(SearchHit {
-                    id: id.clone(),
-                    score,
-                    meta: include_meta.then(|| item.meta.clone()),
-                });
+                hits.push(opts.hit(id, score, item));
             }
         }
         Ok(Some(hits))
     }
 }
 
+/// Exact-match postings per metadata field and string value. A field that
+/// outgrows `max_values` distinct values is dropped for good (until the next
+/// rebuild) and its filters are answered by scanning the items.
+struct KeywordIndex {
+    fields: HashMap<String, HashMap<String, HashSet<String>>>,
+    unindexed: HashSet<String>,
+    max_values: usize,
+}
+
+impl KeywordIndex {
+    fn new(max_values: usize) -> Self {
+        Self {
+            fields: HashMap::new(),
+            unindexed: HashSet::new(),
+            max_values,
+        }
+    }
+
+    fn insert(&mut self, id: &str, meta: &serde_json::Value) {
+        let Some(obj) = meta.as_object() else {
+            return;
+        };
+        for (k, v) in obj {
+            let Some(value) = v.as_str() else {
+                continue;
+            };
+            if self.unindexed.contains(k) {
+                continue;
+            }
+            let by_value = self.fields.entry(k.clone()).or_default();
+            if self.max_values > 0
+                && by_value.len() >= self.max_values
+                && !by_value.contains_key(value)
+            {
+                tracing::warn!(
+                    field = %k,
+                    max_values = self.max_values,
+                    "metadata field exceeded the keyword index value limit; filters on it will scan"
+                );
+                self.fields.remove(k);
+                self.unindexed.insert(k.clone());
+                continue;
+            }
+            by_value
+                .entry(value.to_string())
+                .or_default()
+                .insert(id.to_string());
+        }
+    }
+
+    fn remove(&mut self, id: &str, meta: &serde_json::Value) {
+        let Some(obj) = meta.as_object() else { return };
+        for (k, v) in obj {
+            let Some(value) = v.as_str() else {
+                continue;
+            };
+            if let Some(by_value) = self.fields.get_mut(k) {
+                if let Some(set) = by_value.get_mut(value) {
+                    set.remove(id);
+                    if set.is_empty() {
+                        by_value.remove(value);
+                    }
+                }
+                if by_value.is_empty() {
+                    self.fields.remove(k);
+                }
+            }
+        }
+    }
+
+    /// Ids whose `field` equals `value`, or `None` when the field is no
+    /// longer indexed and the caller has to scan.
+    fn postings(&self, field: &str, value: &str) -> Option<HashSet<String>> {
+        if self.unindexed.contains(field) {
+            return None;
+        }
+        Some(
+            self.fields
+                .get(field)
+                .and_then(|by_value| by_value.get(value))
+                .map(|ids| ids.iter().cloned().collect())
+                .unwrap_or_default(),
+        )
+    }
+
+    fn value_counts(&self) -> BTreeMap<String, usize> {
+        self.fields
+            .iter()
+            .map(|(field, by_value)| (field.clone(), by_value.len()))
+            .collect()
+    }
+
+    fn unindexed_fields(&self) -> Vec<String> {
+        let mut fields: Vec<String> = self.unindexed.iter().cloned().collect();
+        fields.sort();
+        fields
+    }
+}
+
+/// Search structures derived from a collection's items, built apart from the live
+/// collection so they can be swapped in at once.
+struct IndexParts {
+    keyword_index: KeywordIndex,
+    q8_store: HashMap<String, QuantizedVec>,
+    segments: Vec<SegmentIndex>,
+    item_segments: HashMap<String, usize>,
+    item_clusters: HashMap<String, usize>,
+    cluster_members: HashMap<usize, HashSet<String>>,
+}
+
+/// Per-query knobs shared by every search strategy when turning scores into hits.
+#[derive(Clone, Copy)]
+struct HitOptions<'a> {
+    include_meta: bool,
+    include_vector: bool,
+    min_score: Option<f32>,
+    k: usize,
+    offset: usize,
+    tie_break: Option<&'a TieBreak>,
+}
+
+impl HitOptions<'_> {
+    /// Ranked hits a strategy has to gather to serve the requested page.
+    fn window(&self) -> usize {
+        self.k.saturating_add(self.offset)
+    }
+
+    /// Applied to scored candidates before they are ranked and cut to `k`.
+    fn keeps(&self, score: f32) -> bool {
+        self.min_score.is_none_or(|min| score >= min)
+    }
+
+    fn hit(&self, id: String, score: f32, item: &VectorItem) -> SearchHit {
+        SearchHit {
+            id,
+            score,
+            meta: self.include_meta.then(|| item.meta.clone()),
+            vector: self.include_vector.then(|| item.vector.clone()),
+        }
+    }
+}
+
+const RANGE_OPERATORS: [&str; 4] = ["$gt", "$gte", "$lt", "$lte"];
+
+/// Checks that every operator object in a search filter uses a supported
+/// operator, so typos are rejected instead of silently matching nothing.
+pub fn validate_filters(filters: &serde_json::Value) -> Result<(), String> {
+    let serde_json::Value::Object(f) = filters else {
+        return Err("filters must be an object".to_string());
+    };
+    for (field, v) in f {
+        if field == "$or" || field == "$and" {
+            let branches = v
+                .as_array()
+                .filter(|b| !b.is_empty())
+                .ok_or(format!("{field} expects a non-empty array of filters"))?;
+            for branch in branches {
+                validate_filters(branch)?;
+            }
+            continue;
+        }
+        if field.starts_with('$') {
+            return Err(format!("unsupported operator {field}"));
+        }
+        let Some(ops) = filter_operators(v) else {
+            continue;
+        };
+        for (op, operand) in ops {
+            if RANGE_OPERATORS.contains(&op.as_str()) {
+                if !(operand.is_number() || operand.is_string()) {
+                    return Err(format!("{field}: {op} expects a number or string"));
+                }
+            } else if op == "$in" {
+                if !operand.is_array() {
+                    return Err(format!("{field}: $in expects an array"));
+                }
+            } else if op != "$ne" {
+                return Err(format!("{field}: unsupported operator {op}"));
+            }
+        }
+    }
+    Ok(())
+}
+
 fn matches_filters(meta: &serde_json::Value, filters: Option<&serde_json::Value>) -> bool {
     let Some(filters) = filters else { return true };
     let serde_json::Value::Object(f) = filters else {
@@ -1749,14 +3009,105 @@ fn matches_filters(meta: &serde_json::Value, filters: Option<&serde_json::Value>
     };
 
     for (k, v) in f.iter() {
-        match m.get(k) {
-            Some(mv) if mv == v => {}
-            _ => return false,
+        let matched = if k == "$or" {
+            v.as_array().is_some_and(|branches| {
+                branches
+                    .iter()
+                    .any(|branch| matches_filters(meta, Some(branch)))
+            })
+        } else if k == "$and" {
+            v.as_array().is_some_and(|branches| {
+                branches
+                    .iter()
+                    .all(|branch| matches_filters(meta, Some(branch)))
+            })
+        } else {
+            match filter_operators(v) {
+                Some(ops) => matches_operators(m.get(k), ops),
+                None => m.get(k) == Some(v),
+            }
+        };
+        if !matched {
+            return false;
         }
     }
     true
 }
 
+/// An object whose keys are all `$`-prefixed is an operator object; any other
+/// value, objects included, is matched by equality.
+fn filter_operators(v: &serde_json::Value) -> Option<&serde_json::Map<String, serde_json::Value>> {
+    let obj = v.as_object()?;
+    (!obj.is_empty() && obj.keys().all(|k| k.starts_with('$'))).then_some(obj)
+}
+
+fn matches_operators(
+    value: Option<&serde_json::Value>,
+    ops: &serde_json::Map<String, serde_json::Value>,
+) -> bool {
+    ops.iter().all(|(op, operand)| {
+        if op == "$ne" {
+            return value != Some(operand);
+        }
+        if op == "$in" {
+            return value.is_some_and(|v| {
+                operand
+                    .as_array()
+                    .is_some_and(|candidates| candidates.contains(v))
+            });
+        }
+        let Some(ord) = value.and_then(|v| compare_filter_values(v, operand)) else {
+            return false;
+        };
+        match op.as_str() {
+            "$gt" => ord == Ordering::Greater,
+            "$gte" => ord != Ordering::Less,
+            "$lt" => ord == Ordering::Less,
+            "$lte" => ord != Ordering::Greater,
+            _ => false,
+        }
+    })
+}
+
+/// Orders numbers against numbers and strings against strings; mixed types
+/// never satisfy a range operator.
+fn compare_filter_values(a: &serde_json::Value, b: &serde_json::Value) -> Option<Ordering> {
+    use serde_json::Value;
+    match (a, b) {
+        (Value::Number(x), Value::Number(y)) => x.as_f64()?.partial_cmp(&y.as_f64()?),
+        (Value::String(x), Value::String(y)) => Some(x.cmp(y)),
+        _ => None,
+    }
+}
+
+/// Numbers and strings compare naturally (numbers first when mixed); items
+/// missing the field sort after those that have it, regardless of `order`.
+fn compare_tie_field(
+    a: Option<&serde_json::Value>,
+    b: Option<&serde_json::Value>,
+    order: Order,
+) -> Ordering {
+    use serde_json::Value;
+    let directed = |ord: Ordering| match order {
+        Order::Asc => ord,
+        Order::Desc => ord.reverse(),
+    };
+    match (a, b) {
+        (Some(Value::Number(x)), Some(Value::Number(y))) => directed(
+            x.as_f64()
+                .unwrap_or(0.0)
+                .partial_cmp(&y.as_f64().unwrap_or(0.0))
+                .unwrap_or(Ordering::Equal),
+        ),
+        (Some(Value::String(x)), Some(Value::String(y))) => directed(x.cmp(y)),
+        (Some(Value::Number(_)), Some(Value::String(_))) => Ordering::Less,
+        (Some(Value::String(_)), Some(Value::Number(_))) => Ordering::Greater,
+        (Some(Value::Number(_) | Value::String(_)), _) => Ordering::Less,
+        (_, Some(Value::Number(_) | Value::String(_))) => Ordering::Greater,
+        _ => Ordering::Equal,
+    }
+}
+
 fn compare_scores_desc(a: &(String, f32), b: &(String, f32)) -> Ordering {
     b.1.partial_cmp(&a.1)
         .unwrap_or(Ordering::Equal)
@@ -1773,7 +3124,23 @@ fn exact_score(metric: Metric, a: &[f32], b: &[f32], simd_enabled: bool) -> f32
                 dot / (norm_a.sqrt() * norm_b.sqrt())
             }
         }
-        Metric::Dot => simd::dot(a, b, simd_enabled),
+        // `a` is the stored vector, which Dot collections keep un-normalized;
+        // scaling it here matches the normalized copy held by the indexes.
+        Metric::Dot => {
+            let (dot, norm_a, _) = simd::dot_and_norms(a, b, simd_enabled);
+            if norm_a == 0.0 {
+                0.0
+            } else {
+                dot / norm_a.sqrt()
+            }
+        }
+    }
+}
+
+fn index_vector(metric: Metric, v: &[f32]) -> Cow<'_, [f32]> {
+    match metric {
+        Metric::Dot => Cow::Owned(normalize_if_needed(metric, v.to_vec())),
+        Metric::Cosine => Cow::Borrowed(v),
     }
 }
 
@@ -1811,6 +3178,15 @@ fn make_hnsw(
     }
 }
 
+/// Live id -> index of the segment holding it.
+fn segment_assignments(segments: &[SegmentIndex]) -> HashMap<String, usize> {
+    segments
+        .iter()
+        .enumerate()
+        .flat_map(|(i, s)| s.data_ids.keys().map(move |id| (id.clone(), i)))
+        .collect()
+}
+
 fn insert_into_hnsw(hnsw: &mut HnswIndex, v: Vec<f32>, data_id: usize) {
     match hnsw {
         HnswIndex::Cosine(h) => h.insert((&v, data_id)),
@@ -1838,6 +3214,10 @@ impl index::VectorIndex for VectorStore {
         VectorStore::create_collection(self, name, dim, metric)
     }
 
+    fn drop_collection(&self, name: &str) -> Result<(), VectorError> {
+        VectorStore::drop_collection(self, name)
+    }
+
     fn upsert(&self, collection: &str, id: &str, item: VectorItem) -> Result<(), VectorError> {
         VectorStore::upsert(self, collection, id, item)
     }
@@ -1897,3 +3277,77 @@ impl index::DiskAnnIndex for VectorStore {
         VectorStore::update_disk_index_params(self, collection, params)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query(vector: Vec<f32>) -> SearchRequest {
+        SearchRequest {
+            vector,
+            k: 3,
+            filters: None,
+            include_meta: None,
+            tie_break: None,
+            include_vector: None,
+            min_score: None,
+            offset: None,
+            exact: None,
+            with_total: None,
+        }
+    }
+
+    #[test]
+    fn search_ef_is_capped_for_large_candidate_windows() {
+        let defaults = HnswParams::default();
+        assert_eq!(defaults.search_ef(10), DEFAULT_HNSW_EF_SEARCH_FLOOR);
+        assert_eq!(defaults.search_ef(1_000), 2_000);
+        assert_eq!(defaults.search_ef(50_000), MAX_HNSW_EF);
+
+        let fixed = HnswParams {
+            ef_search: Some(200),
+            ..HnswParams::default()
+        };
+        assert_eq!(fixed.search_ef(100), 200);
+        assert_eq!(fixed.search_ef(5_000), 5_000);
+        assert_eq!(fixed.search_ef(50_000), MAX_HNSW_EF);
+    }
+
+    #[test]
+    fn rebuild_collection_index_restores_cleared_segments() {
+        let store = VectorStore::new();
+        store.create_collection("docs", 2, Metric::Cosine).unwrap();
+        for (id, vector) in [("a", [1.0, 0.0]), ("b", [0.0, 1.0]), ("c", [0.7, 0.7])] {
+            let item = VectorItem {
+                vector: vector.to_vec(),
+                meta: serde_json::Value::Null,
+                revision: 0,
+                expires_at_ms: None,
+            };
+            store.upsert("docs", id, item).unwrap();
+        }
+        let top = |store: &VectorStore| {
+            store
+                .search("docs", query(vec![1.0, 0.0]))
+                .unwrap()
+                .first()
+                .map(|hit| hit.id.clone())
+        };
+        assert_eq!(top(&store).as_deref(), Some("a"));
+
+        {
+            let mut cols = store.0.collections.write();
+            let c = cols.get_mut("docs").unwrap();
+            c.segments.clear();
+            c.item_segments.clear();
+        }
+        assert_eq!(top(&store), None);
+
+        store.rebuild_collection_index("docs").unwrap();
+        assert_eq!(top(&store).as_deref(), Some("a"));
+        assert!(matches!(
+            store.rebuild_collection_index("missing"),
+            Err(VectorError::CollectionNotFound)
+        ));
+    }
+}
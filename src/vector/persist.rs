@@ -1,7 +1,7 @@
 use super::index::DiskAnnBuildParams;
 use super::q8::{quantize_per_vector, QuantizedVec};
 use super::VectorSettings;
-use crate::vector::{Metric, VectorError, VectorItem};
+use crate::vector::{HnswParams, Metric, NormStats, VectorError, VectorItem};
 use crc32fast::Hasher;
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
@@ -35,6 +35,10 @@ fn default_compaction_max_bytes_per_pass() -> u64 {
     DEFAULT_COMPACTION_MAX_BYTES_PER_PASS
 }
 
+fn default_segment_max_items() -> usize {
+    super::DEFAULT_SEGMENT_MAX
+}
+
 fn default_ivf_clusters() -> usize {
     DEFAULT_IVF_CLUSTERS
 }
@@ -123,6 +127,29 @@ pub struct Manifest {
     pub ivf_last_trained_upsert: u64,
     #[serde(default)]
     pub disk_index: DiskIndexManifest,
+    #[serde(default)]
+    pub norm_stats: NormStats,
+    #[serde(default)]
+    pub hnsw: HnswParams,
+    /// Points per HNSW segment, fixed when the collection is created.
+    #[serde(default = "default_segment_max_items")]
+    pub segment_max_items: usize,
+    /// ANDed into every search on the collection, e.g. `{"tenant": "a"}`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_filter: Option<serde_json::Value>,
+    /// Set when the segment graphs under `hnsw/` match this manifest's records.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hnsw_dump: Option<HnswDumpManifest>,
+}
+
+/// Which state the dumped HNSW graphs were taken from; a reopen only trusts
+/// them when every field still matches.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct HnswDumpManifest {
+    pub version: u32,
+    pub applied_offset: u64,
+    pub total_records: u64,
+    pub params: HnswParams,
 }
 
 #[derive(Clone, Debug, Default, Serialize, Deserialize)]
@@ -164,10 +191,53 @@ pub struct Record {
     pub vector: Option<Vec<f32>>,
     pub meta: Option<serde_json::Value>,
     pub quantized: Option<QuantizedVec>,
+    /// Item revision; 0 on an upsert means "previous revision + 1".
+    pub revision: u64,
+    pub expires_at_ms: Option<u64>,
 }
 
 #[derive(Serialize, Deserialize)]
 struct DiskRecord {
+    offset: u64,
+    op: RecordOp,
+    id: String,
+    vector: Option<Vec<f32>>,
+    meta: Option<Vec<u8>>,
+    quantized: Option<QuantizedVec>,
+    revision: u64,
+    expires_at_ms: Option<u64>,
+}
+
+/// Record layout of run version 2, before vectors could expire.
+#[derive(Deserialize)]
+struct DiskRecordV2 {
+    offset: u64,
+    op: RecordOp,
+    id: String,
+    vector: Option<Vec<f32>>,
+    meta: Option<Vec<u8>>,
+    quantized: Option<QuantizedVec>,
+    revision: u64,
+}
+
+impl From<DiskRecordV2> for DiskRecord {
+    fn from(v2: DiskRecordV2) -> Self {
+        Self {
+            offset: v2.offset,
+            op: v2.op,
+            id: v2.id,
+            vector: v2.vector,
+            meta: v2.meta,
+            quantized: v2.quantized,
+            revision: v2.revision,
+            expires_at_ms: None,
+        }
+    }
+}
+
+/// Record layout of run version 1 and of the legacy `vectors.bin` file.
+#[derive(Deserialize)]
+struct DiskRecordV1 {
     offset: u64,
     op: RecordOp,
     id: String,
@@ -177,6 +247,33 @@ struct DiskRecord {
     quantized: Option<QuantizedVec>,
 }
 
+impl From<DiskRecordV1> for DiskRecord {
+    fn from(v1: DiskRecordV1) -> Self {
+        Self {
+            offset: v1.offset,
+            op: v1.op,
+            id: v1.id,
+            vector: v1.vector,
+            meta: v1.meta,
+            quantized: v1.quantized,
+            revision: 0,
+            expires_at_ms: None,
+        }
+    }
+}
+
+fn decode_disk_record(version: u16, payload: &[u8]) -> Option<DiskRecord> {
+    match version {
+        1 => bincode::deserialize::<DiskRecordV1>(payload)
+            .ok()
+            .map(DiskRecord::from),
+        2 => bincode::deserialize::<DiskRecordV2>(payload)
+            .ok()
+            .map(DiskRecord::from),
+        _ => bincode::deserialize(payload).ok(),
+    }
+}
+
 struct CollectionRecords {
     items: HashMap<String, VectorItem>,
     quantized: HashMap<String, QuantizedVec>,
@@ -236,6 +333,11 @@ impl Manifest {
             ivf_retrain_min_vectors: default_ivf_retrain_min_vectors(),
             ivf_last_trained_upsert: 0,
             disk_index: DiskIndexManifest::default(),
+            norm_stats: NormStats::default(),
+            hnsw: HnswParams::default(),
+            segment_max_items: default_segment_max_items(),
+            default_filter: None,
+            hnsw_dump: None,
         }
     }
 
@@ -317,11 +419,15 @@ pub fn init_collection(
     layout: &CollectionLayout,
     dim: usize,
     metric: Metric,
+    hnsw: HnswParams,
+    segment_max_items: usize,
 ) -> std::io::Result<()> {
     std::fs::create_dir_all(&layout.dir)?;
     std::fs::create_dir_all(&layout.runs_dir)?;
     if !layout.manifest_path.exists() {
-        let manifest = Manifest::new(dim, metric);
+        let mut manifest = Manifest::new(dim, metric);
+        manifest.hnsw = hnsw;
+        manifest.segment_max_items = segment_max_items;
         store_manifest(layout, &manifest)?;
     }
     if !layout.bin_path.exists() {
@@ -426,6 +532,8 @@ pub fn rewrite_collection(
             vector: Some(item.vector.clone()),
             meta: Some(item.meta.clone()),
             quantized: quantized.get(id).cloned(),
+            revision: item.revision,
+            expires_at_ms: item.expires_at_ms,
         };
         let _ = append_record(layout, &mut new_manifest, &record)?;
         if let Some(run) = new_manifest.runs.last() {
@@ -492,6 +600,8 @@ pub fn compact_runs(
             vector: Some(item.vector.clone()),
             meta: Some(item.meta.clone()),
             quantized: quantized.get(id).cloned(),
+            revision: item.revision,
+            expires_at_ms: item.expires_at_ms,
         };
         let _ = append_record(layout, &mut new_manifest, &record)?;
         if let Some(run) = new_manifest.runs.last() {
@@ -603,9 +713,8 @@ fn read_legacy_file(
             }
             return Err(err.into());
         }
-        let record: DiskRecord = match bincode::deserialize(&payload) {
-            Ok(r) => r,
-            Err(_) => break,
+        let Some(record) = decode_disk_record(1, &payload) else {
+            break;
         };
         apply_disk_record(record, dim, state, Some("legacy"));
     }
@@ -648,9 +757,8 @@ fn read_run_files(
             if hasher.finalize() != header.crc32 {
                 break;
             }
-            let record: DiskRecord = match bincode::deserialize(&payload) {
-                Ok(rec) => rec,
-                Err(_) => break,
+            let Some(record) = decode_disk_record(header.version, &payload) else {
+                break;
             };
             if record.op != header_op {
                 continue;
@@ -691,9 +799,25 @@ fn apply_disk_record(
                 .quantized
                 .clone()
                 .unwrap_or_else(|| quantize_per_vector(&v));
-            state
-                .items
-                .insert(record.id.clone(), VectorItem { vector: v, meta });
+            let revision = if record.revision > 0 {
+                record.revision
+            } else {
+                state
+                    .items
+                    .get(&record.id)
+                    .map(|item| item.revision)
+                    .unwrap_or(0)
+                    .saturating_add(1)
+            };
+            state.items.insert(
+                record.id.clone(),
+                VectorItem {
+                    vector: v,
+                    meta,
+                    revision,
+                    expires_at_ms: record.expires_at_ms,
+                },
+            );
             state.quantized.insert(record.id.clone(), q);
             if let Some(run) = run_file {
                 state.item_runs.insert(record.id, run.to_string());
@@ -793,10 +917,12 @@ fn disk_record_from(record: &Record) -> std::io::Result<DiskRecord> {
         vector: record.vector.clone(),
         meta: meta_bytes,
         quantized: record.quantized.clone(),
+        revision: record.revision,
+        expires_at_ms: record.expires_at_ms,
     })
 }
 const RUN_MAGIC: u32 = 0x524B5631;
-const RUN_VERSION: u16 = 1;
+const RUN_VERSION: u16 = 3;
 const RUN_HEADER_BYTES: usize = 16;
 
 #[derive(Clone, Copy)]
@@ -842,7 +968,7 @@ impl RunHeader {
             return None;
         }
         let version = u16::from_le_bytes([buf[4], buf[5]]);
-        if version != RUN_VERSION {
+        if version == 0 || version > RUN_VERSION {
             return None;
         }
         let flags = u16::from_le_bytes([buf[6], buf[7]]);
@@ -4,17 +4,17 @@ use rust_kiss_vdb::config::Config;
 use rust_kiss_vdb::engine::Engine;
 use rust_kiss_vdb::search::engine::SearchEngine;
 use std::net::SocketAddr;
-use std::path::PathBuf;
 use std::sync::Arc;
 use tokio::sync::oneshot;
+use tokio_util::sync::CancellationToken;
 
 async fn start_with_config(config: Config) -> (String, oneshot::Sender<()>) {
-    let engine = Engine::new(config.clone()).unwrap();
+    let engine = Engine::new(config.clone(), CancellationToken::new()).unwrap();
     
     let temp_dir = tempfile::tempdir().unwrap(); 
     let search_engine = Arc::new(SearchEngine::new(temp_dir.path().to_path_buf()).unwrap());
     
-    let app = api::router(engine, config, None, search_engine);
+    let app = api::router(engine, config, None, search_engine, None);
 
     let listener = tokio::net::TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0)))
         .await
@@ -76,13 +76,95 @@ fn base_config() -> Config {
         run_retention: 4,
         compaction_trigger_tombstone_ratio: 0.2,
         compaction_max_bytes_per_pass: 64 * 1024 * 1024,
+        verify_on_start: false,
+        max_response_bytes: 8 * 1024 * 1024,
+        default_durability: "sync".to_string(),
+        max_vectors_per_collection: 0,
+        embed_cache_size: 256,
+        sse_idle_timeout_secs: 0,
+        value_compress_min_bytes: 0,
+        wal_truncate_after_snapshot: false,
+        max_list_limit: 1000,
+        strict_query_dim: true,
+        sqlite_busy_retries: 3,
+        vector_segment_max_items: 8192,
+        vector_merge_tombstone_ratio: 0.3,
+        ttl_grace_ms: 0,
+        max_index_values_per_field: 0,
+        response_compress_min_bytes: 0,
+        wal_fsync_policy: "data".to_string(),
+        max_doc_indexed_fields: 0,
+        lock_wait_warn_ms: 100,
+        sqlite_readonly: false,
+        sqlite_migrations_dir: None,
+        embed_url: None,
+        embed_model: "default".to_string(),
+        embed_timeout_ms: 10_000,
     }
 }
 
+fn client() -> reqwest::Client {
+    let mut headers = reqwest::header::HeaderMap::new();
+    headers.insert(
+        reqwest::header::AUTHORIZATION,
+        reqwest::header::HeaderValue::from_static("Bearer test"),
+    );
+    reqwest::Client::builder()
+        .default_headers(headers)
+        .build()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn ping_requires_auth_and_reports_offset() {
+    let (base, shutdown) = start_with_config(base_config()).await;
+
+    let anon = reqwest::Client::new()
+        .get(format!("{}/v1/ping", base))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(anon.status(), reqwest::StatusCode::UNAUTHORIZED);
+
+    let client = client();
+    let before: serde_json::Value = client
+        .get(format!("{}/v1/ping", base))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(before["pong"], true);
+    assert!(before["now_ms"].as_u64().unwrap() > 0);
+
+    let resp = client
+        .put(format!("{}/v1/state/ping:1", base))
+        .json(&serde_json::json!({"value":1}))
+        .send()
+        .await
+        .unwrap();
+    assert!(resp.status().is_success());
+    let after: serde_json::Value = client
+        .get(format!("{}/v1/ping", base))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(
+        after["offset"].as_u64().unwrap(),
+        before["offset"].as_u64().unwrap() + 1
+    );
+
+    let _ = shutdown.send(());
+}
+
 #[tokio::test]
 async fn ttl_emits_event() {
     let (base, shutdown) = start_with_config(base_config()).await;
-    let client = reqwest::Client::new();
+    let client = client();
 
     let resp = client
         .get(format!(
@@ -122,12 +204,68 @@ async fn ttl_emits_event() {
     let _ = shutdown.send(());
 }
 
+#[tokio::test]
+async fn admin_ttl_lists_keys_ordered_by_expiry() {
+    let (base, shutdown) = start_with_config(base_config()).await;
+    let client = client();
+
+    for (key, ttl) in [
+        ("ttl:late", 120_000u64),
+        ("ttl:soon", 30_000),
+        ("ttl:mid", 60_000),
+    ] {
+        let resp = client
+            .put(format!("{}/v1/state/{}", base, key))
+            .json(&serde_json::json!({"value":{"v":1},"ttl_ms":ttl}))
+            .send()
+            .await
+            .unwrap();
+        assert!(resp.status().is_success());
+    }
+    let resp = client
+        .put(format!("{}/v1/state/no_ttl", base))
+        .json(&serde_json::json!({"value":{"v":1}}))
+        .send()
+        .await
+        .unwrap();
+    assert!(resp.status().is_success());
+
+    let entries: Vec<serde_json::Value> = client
+        .get(format!("{}/v1/admin/ttl?limit=10", base))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let keys: Vec<&str> = entries.iter().map(|e| e["key"].as_str().unwrap()).collect();
+    assert_eq!(keys, vec!["ttl:soon", "ttl:mid", "ttl:late"]);
+    for (entry, ttl) in entries.iter().zip([30_000u64, 60_000, 120_000]) {
+        let remaining = entry["ttl_remaining_ms"].as_u64().unwrap();
+        assert!(remaining <= ttl && remaining > ttl - 5_000, "{entry}");
+        assert!(entry["expires_at_ms"].as_u64().unwrap() > 0);
+    }
+
+    let limited: Vec<serde_json::Value> = client
+        .get(format!("{}/v1/admin/ttl?limit=1", base))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(limited.len(), 1);
+    assert_eq!(limited[0]["key"], "ttl:soon");
+
+    let _ = shutdown.send(());
+}
+
 #[tokio::test]
 async fn sse_lagged_emits_gap_instead_of_dying() {
     let mut config = base_config();
     config.live_broadcast_capacity = 1;
     let (base, shutdown) = start_with_config(config).await;
-    let client = reqwest::Client::new();
+    let client = client();
 
     let resp = client
         .get(format!("{}/v1/stream?types=state_updated&since=0", base))
@@ -170,3 +308,148 @@ async fn sse_lagged_emits_gap_instead_of_dying() {
 
     let _ = shutdown.send(());
 }
+
+#[tokio::test]
+async fn sse_reports_truncated_when_since_predates_the_wal() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut config = base_config();
+    config.data_dir = Some(dir.path().to_string_lossy().to_string());
+    config.wal_truncate_after_snapshot = true;
+    // Built off the runtime so no background snapshot trims the WAL again.
+    let engine = {
+        let config = config.clone();
+        std::thread::spawn(move || Engine::new(config, CancellationToken::new()).unwrap())
+            .join()
+            .unwrap()
+    };
+
+    for i in 0..3 {
+        engine
+            .put_state(format!("old:{i}"), serde_json::json!(i), None, None)
+            .unwrap();
+    }
+    engine.force_snapshot().unwrap();
+    for i in 0..2 {
+        engine
+            .put_state(format!("new:{i}"), serde_json::json!(i), None, None)
+            .unwrap();
+    }
+    let earliest = engine.persist().unwrap().earliest_offset().unwrap();
+    assert_eq!(earliest, Some(4));
+
+    let search_dir = tempfile::tempdir().unwrap();
+    let search_engine = Arc::new(SearchEngine::new(search_dir.path().to_path_buf()).unwrap());
+    let app = api::router(engine, config, None, search_engine, None);
+    let listener = tokio::net::TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0)))
+        .await
+        .unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        let _ = axum::serve(listener, app).await;
+    });
+
+    let resp = client()
+        .get(format!("{}/v1/stream?since=1", base))
+        .send()
+        .await
+        .unwrap();
+    assert!(resp.status().is_success());
+    let mut stream = resp.bytes_stream();
+    let deadline = tokio::time::Instant::now() + std::time::Duration::from_secs(5);
+    let mut buf = String::new();
+    while !buf.contains("\"key\":\"new:1\"") {
+        tokio::select! {
+            _ = tokio::time::sleep_until(deadline) => break,
+            chunk = stream.next() => {
+                let Some(chunk) = chunk else { break };
+                buf.push_str(&String::from_utf8_lossy(&chunk.unwrap()));
+            }
+        }
+    }
+
+    let marker = buf.find("event: truncated").expect(&buf);
+    assert!(
+        buf.contains(r#"{"earliest_available":4,"requested_since":1}"#),
+        "{buf}"
+    );
+    assert!(marker < buf.find("new:0").unwrap());
+    assert!(!buf.contains("old:"), "{buf}");
+
+    // Resuming from an offset the WAL still covers carries no marker.
+    let resp = client()
+        .get(format!("{}/v1/stream?since=3", base))
+        .send()
+        .await
+        .unwrap();
+    let mut stream = resp.bytes_stream();
+    let mut buf = String::new();
+    while !buf.contains("\"key\":\"new:1\"") {
+        tokio::select! {
+            _ = tokio::time::sleep_until(deadline) => break,
+            chunk = stream.next() => {
+                let Some(chunk) = chunk else { break };
+                buf.push_str(&String::from_utf8_lossy(&chunk.unwrap()));
+            }
+        }
+    }
+    assert!(buf.contains("new:1"), "{buf}");
+    assert!(!buf.contains("truncated"), "{buf}");
+}
+
+#[tokio::test]
+async fn sse_reports_truncated_after_compaction_empties_the_wal() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut config = base_config();
+    config.data_dir = Some(dir.path().to_string_lossy().to_string());
+    config.wal_truncate_after_snapshot = true;
+    let engine = {
+        let config = config.clone();
+        std::thread::spawn(move || Engine::new(config, CancellationToken::new()).unwrap())
+            .join()
+            .unwrap()
+    };
+
+    for i in 0..3 {
+        engine
+            .put_state(format!("old:{i}"), serde_json::json!(i), None, None)
+            .unwrap();
+    }
+    engine.force_snapshot().unwrap();
+    assert_eq!(engine.persist().unwrap().earliest_offset().unwrap(), None);
+
+    let search_dir = tempfile::tempdir().unwrap();
+    let search_engine = Arc::new(SearchEngine::new(search_dir.path().to_path_buf()).unwrap());
+    let app = api::router(engine, config, None, search_engine, None);
+    let listener = tokio::net::TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0)))
+        .await
+        .unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        let _ = axum::serve(listener, app).await;
+    });
+
+    let resp = client()
+        .get(format!("{}/v1/stream?since=1", base))
+        .send()
+        .await
+        .unwrap();
+    assert!(resp.status().is_success());
+    let mut stream = resp.bytes_stream();
+    let deadline = tokio::time::Instant::now() + std::time::Duration::from_secs(5);
+    let mut buf = String::new();
+    while !buf.contains("earliest_available") {
+        tokio::select! {
+            _ = tokio::time::sleep_until(deadline) => break,
+            chunk = stream.next() => {
+                let Some(chunk) = chunk else { break };
+                buf.push_str(&String::from_utf8_lossy(&chunk.unwrap()));
+            }
+        }
+    }
+    assert!(buf.contains("event: truncated"), "{buf}");
+    assert!(
+        buf.contains(r#"{"earliest_available":4,"requested_since":1}"#),
+        "{buf}"
+    );
+    assert!(!buf.contains("old:"), "{buf}");
+}
@@ -1,11 +1,13 @@
 use rust_kiss_vdb::api;
 use rust_kiss_vdb::config::Config;
 use rust_kiss_vdb::engine::Engine;
+use rust_kiss_vdb::search::embedder;
 use rust_kiss_vdb::search::engine::SearchEngine;
+use rust_kiss_vdb::vector::{Metric, VectorItem};
 use std::net::SocketAddr;
-use std::path::PathBuf;
 use std::sync::Arc;
 use tokio::sync::oneshot;
+use tokio_util::sync::CancellationToken;
 
 async fn start() -> (String, oneshot::Sender<()>) {
     start_with_config(base_test_config()).await
@@ -21,13 +23,13 @@ async fn start_with_diskann() -> (String, oneshot::Sender<()>, tempfile::TempDir
 
 async fn start_with_config(config: Config) -> (String, oneshot::Sender<()>) {
     let config = config;
-    let engine = Engine::new(config.clone()).unwrap();
+    let engine = Engine::new(config.clone(), CancellationToken::new()).unwrap();
     
     // For tests not using search, a temporary dropped dir is fine.
     let temp_dir = tempfile::tempdir().unwrap(); 
     let search_engine = Arc::new(SearchEngine::new(temp_dir.path().to_path_buf()).unwrap());
     
-    let app = api::router(engine, config, None, search_engine);
+    let app = api::router(engine, config, None, search_engine, None);
 
     let listener = tokio::net::TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0)))
         .await
@@ -46,10 +48,22 @@ async fn start_with_config(config: Config) -> (String, oneshot::Sender<()>) {
     (format!("http://{}", addr), tx)
 }
 
+fn client() -> reqwest::Client {
+    let mut headers = reqwest::header::HeaderMap::new();
+    headers.insert(
+        reqwest::header::AUTHORIZATION,
+        reqwest::header::HeaderValue::from_static("Bearer test"),
+    );
+    reqwest::Client::builder()
+        .default_headers(headers)
+        .build()
+        .unwrap()
+}
+
 #[tokio::test]
 async fn state_put_get() {
     let (base, shutdown) = start().await;
-    let client = reqwest::Client::new();
+    let client = client();
 
     let put = client
         .put(format!("{}/v1/state/job:1", base))
@@ -72,10 +86,476 @@ async fn state_put_get() {
     let _ = shutdown.send(());
 }
 
+#[tokio::test]
+async fn state_put_if_revision_zero_creates_only_once() {
+    let (base, shutdown) = start().await;
+    let client = client();
+
+    let first = client
+        .put(format!("{}/v1/state/leader", base))
+        .json(&serde_json::json!({"value":"node-a","if_revision":0}))
+        .send()
+        .await
+        .unwrap();
+    assert!(first.status().is_success());
+    let body: serde_json::Value = first.json().await.unwrap();
+    assert_eq!(body["revision"], 1);
+
+    let second = client
+        .put(format!("{}/v1/state/leader", base))
+        .json(&serde_json::json!({"value":"node-b","if_revision":0}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(second.status(), reqwest::StatusCode::CONFLICT);
+
+    let got: serde_json::Value = client
+        .get(format!("{}/v1/state/leader", base))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(got["value"], "node-a");
+
+    let _ = shutdown.send(());
+}
+
+#[tokio::test]
+async fn state_delete_honours_if_revision() {
+    let (base, shutdown) = start().await;
+    let client = client();
+
+    for progress in [1, 2] {
+        client
+            .put(format!("{}/v1/state/job:1", base))
+            .json(&serde_json::json!({"value":{"progress":progress}}))
+            .send()
+            .await
+            .unwrap();
+    }
+
+    let stale = client
+        .delete(format!("{}/v1/state/job:1?if_revision=1", base))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(stale.status(), reqwest::StatusCode::CONFLICT);
+    let body: serde_json::Value = stale.json().await.unwrap();
+    assert_eq!(body["error"], "revision_mismatch");
+
+    let fresh = client
+        .delete(format!("{}/v1/state/job:1?if_revision=2", base))
+        .send()
+        .await
+        .unwrap();
+    assert!(fresh.status().is_success());
+    let body: serde_json::Value = fresh.json().await.unwrap();
+    assert_eq!(body["deleted"], true);
+
+    let _ = shutdown.send(());
+}
+
+#[tokio::test]
+async fn state_increment_adds_delta_atomically() {
+    let (base, shutdown) = start().await;
+    let client = client();
+
+    let mut last = serde_json::Value::Null;
+    for delta in [3, 4, -2] {
+        let res = client
+            .post(format!("{}/v1/state/counter/increment", base))
+            .json(&serde_json::json!({ "delta": delta }))
+            .send()
+            .await
+            .unwrap();
+        assert!(res.status().is_success());
+        last = res.json().await.unwrap();
+    }
+    assert_eq!(last["value"], 5);
+    assert_eq!(last["revision"], 3);
+
+    let got: serde_json::Value = client
+        .get(format!("{}/v1/state/counter", base))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(got["value"], 5);
+
+    let _ = shutdown.send(());
+}
+
+#[tokio::test]
+async fn state_touch_extends_ttl_of_live_keys_only() {
+    let (base, shutdown) = start().await;
+    let client = client();
+    let touch = |key: &'static str| {
+        client
+            .post(format!("{}/v1/state/{}/touch", base, key))
+            .json(&serde_json::json!({ "ttl_ms": 600_000 }))
+            .send()
+    };
+
+    let put: serde_json::Value = client
+        .put(format!("{}/v1/state/lease", base))
+        .json(&serde_json::json!({ "value": {"owner": "w1"}, "ttl_ms": 5_000 }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let res = touch("lease").await.unwrap();
+    assert_eq!(res.status(), reqwest::StatusCode::OK);
+    let touched: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(touched["value"], serde_json::json!({"owner": "w1"}));
+    assert_eq!(touched["revision"], 2);
+    assert!(touched["expires_at_ms"].as_u64().unwrap() > put["expires_at_ms"].as_u64().unwrap());
+
+    assert_eq!(
+        touch("nope").await.unwrap().status(),
+        reqwest::StatusCode::NOT_FOUND
+    );
+    client
+        .put(format!("{}/v1/state/gone", base))
+        .json(&serde_json::json!({ "value": 1, "ttl_ms": 1 }))
+        .send()
+        .await
+        .unwrap();
+    tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    assert_eq!(
+        touch("gone").await.unwrap().status(),
+        reqwest::StatusCode::NOT_FOUND
+    );
+
+    let _ = shutdown.send(());
+}
+
+#[tokio::test]
+async fn state_delete_prefix_deletes_in_batches() {
+    let (base, shutdown) = start().await;
+    let client = client();
+    for key in [
+        "tenant:1:a",
+        "tenant:1:b",
+        "tenant:1:c",
+        "tenant:1:d",
+        "tenant:10:a",
+    ] {
+        client
+            .put(format!("{}/v1/state/{}", base, key))
+            .json(&serde_json::json!({ "value": 1 }))
+            .send()
+            .await
+            .unwrap();
+    }
+    let delete_prefix = |body: serde_json::Value| {
+        client
+            .post(format!("{}/v1/state/delete_prefix", base))
+            .json(&body)
+            .send()
+    };
+
+    let first: serde_json::Value =
+        delete_prefix(serde_json::json!({"prefix": "tenant:1:", "limit": 3}))
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+    assert_eq!(first, serde_json::json!({"deleted": 3, "more": true}));
+    let second: serde_json::Value = delete_prefix(serde_json::json!({"prefix": "tenant:1:"}))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(second, serde_json::json!({"deleted": 1, "more": false}));
+
+    let left: serde_json::Value = client
+        .get(format!("{}/v1/state?prefix=tenant:", base))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(left["items"].as_array().unwrap().len(), 1);
+    assert_eq!(left["items"][0]["key"], "tenant:10:a");
+
+    for body in [
+        serde_json::json!({"prefix": ""}),
+        serde_json::json!({"prefix": "tenant:", "limit": 1_000_000}),
+    ] {
+        let res = delete_prefix(body).await.unwrap();
+        assert_eq!(res.status(), reqwest::StatusCode::BAD_REQUEST);
+    }
+
+    let _ = shutdown.send(());
+}
+
+#[tokio::test]
+async fn gzip_responses_are_counted_in_metrics() {
+    let mut config = base_test_config();
+    config.response_compress_min_bytes = 256;
+    let (base, shutdown) = start_with_config(config).await;
+    let client = client();
+
+    let text = "compressible ".repeat(200);
+    client
+        .put(format!("{}/v1/state/big", base))
+        .json(&serde_json::json!({ "value": { "text": text } }))
+        .send()
+        .await
+        .unwrap();
+
+    let got = client
+        .get(format!("{}/v1/state/big", base))
+        .header(reqwest::header::ACCEPT_ENCODING, "gzip")
+        .send()
+        .await
+        .unwrap();
+    assert!(got.status().is_success());
+    assert_eq!(got.headers()[reqwest::header::CONTENT_ENCODING], "gzip");
+    let packed = got.bytes().await.unwrap();
+    let mut plain = String::new();
+    std::io::Read::read_to_string(
+        &mut flate2::read::GzDecoder::new(packed.as_ref()),
+        &mut plain,
+    )
+    .unwrap();
+    let v: serde_json::Value = serde_json::from_str(&plain).unwrap();
+    assert_eq!(v["value"]["text"], text);
+
+    // Without Accept-Encoding the body goes out as is.
+    let metrics = client
+        .get(format!("{}/v1/metrics", base))
+        .send()
+        .await
+        .unwrap();
+    assert!(metrics
+        .headers()
+        .get(reqwest::header::CONTENT_ENCODING)
+        .is_none());
+    let metrics = metrics.text().await.unwrap();
+    assert!(metrics.contains("responses_compressed_total 1\n"));
+    let saved: u64 = metrics
+        .lines()
+        .find_map(|l| l.strip_prefix("response_bytes_saved_total "))
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!(saved as usize > plain.len() / 2);
+
+    let _ = shutdown.send(());
+}
+
+#[tokio::test]
+async fn state_list_pages_with_next_cursor() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut on_disk = base_test_config();
+    on_disk.data_dir = Some(dir.path().to_string_lossy().to_string());
+    for config in [base_test_config(), on_disk] {
+        let (base, shutdown) = start_with_config(config).await;
+        let client = client();
+
+        for i in (0..25).rev() {
+            client
+                .put(format!("{}/v1/state/page:{i:02}", base))
+                .json(&serde_json::json!({ "value": i }))
+                .send()
+                .await
+                .unwrap();
+        }
+        client
+            .put(format!("{}/v1/state/other", base))
+            .json(&serde_json::json!({ "value": 0 }))
+            .send()
+            .await
+            .unwrap();
+
+        let mut keys = Vec::new();
+        let mut cursor: Option<String> = None;
+        let mut pages = 0;
+        loop {
+            let mut url = format!("{}/v1/state?prefix=page:&limit=10", base);
+            if let Some(cursor) = &cursor {
+                url.push_str(&format!("&start_after={cursor}"));
+            }
+            let page: serde_json::Value =
+                client.get(url).send().await.unwrap().json().await.unwrap();
+            pages += 1;
+            for item in page["items"].as_array().unwrap() {
+                keys.push(item["key"].as_str().unwrap().to_string());
+            }
+            match page["next_cursor"].as_str() {
+                Some(next) => cursor = Some(next.to_string()),
+                None => break,
+            }
+        }
+        let expected: Vec<String> = (0..25).map(|i| format!("page:{i:02}")).collect();
+        assert_eq!(keys, expected);
+        assert_eq!(pages, 3);
+
+        let _ = shutdown.send(());
+    }
+}
+
+#[tokio::test]
+async fn state_list_scans_an_inclusive_key_range() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut on_disk = base_test_config();
+    on_disk.data_dir = Some(dir.path().to_string_lossy().to_string());
+    for config in [base_test_config(), on_disk] {
+        let (base, shutdown) = start_with_config(config).await;
+        let client = client();
+        for i in 0..20 {
+            client
+                .put(format!("{}/v1/state/ts:{i:02}", base))
+                .json(&serde_json::json!({ "value": i }))
+                .send()
+                .await
+                .unwrap();
+        }
+
+        let mut keys = Vec::new();
+        let mut url = format!("{}/v1/state?from=ts:05&to=ts:14&limit=4", base);
+        loop {
+            let page: serde_json::Value =
+                client.get(&url).send().await.unwrap().json().await.unwrap();
+            for item in page["items"].as_array().unwrap() {
+                keys.push(item["key"].as_str().unwrap().to_string());
+            }
+            match page["next_cursor"].as_str() {
+                Some(next) => {
+                    url = format!(
+                        "{}/v1/state?from=ts:05&to=ts:14&limit=4&start_after={next}",
+                        base
+                    )
+                }
+                None => break,
+            }
+        }
+        let expected: Vec<String> = (5..=14).map(|i| format!("ts:{i:02}")).collect();
+        assert_eq!(keys, expected);
+
+        for query in [
+            "from=ts:09&to=ts:01",
+            "from=ts:01",
+            "from=a&to=b&prefix=ts:",
+        ] {
+            let res = client
+                .get(format!("{}/v1/state?{}", base, query))
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(res.status(), reqwest::StatusCode::BAD_REQUEST, "{query}");
+        }
+
+        let _ = shutdown.send(());
+    }
+}
+
+#[tokio::test]
+async fn state_batch_get_returns_found_keys_in_order() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut on_disk = base_test_config();
+    on_disk.data_dir = Some(dir.path().to_string_lossy().to_string());
+    for mut config in [base_test_config(), on_disk] {
+        config.max_state_batch = 4;
+        let (base, shutdown) = start_with_config(config).await;
+        let client = client();
+
+        for key in ["a", "b", "c"] {
+            client
+                .put(format!("{}/v1/state/{key}", base))
+                .json(&serde_json::json!({ "value": { "name": key } }))
+                .send()
+                .await
+                .unwrap();
+        }
+
+        let res = client
+            .post(format!("{}/v1/state/batch_get", base))
+            .json(&serde_json::json!({ "keys": ["c", "missing", "a"] }))
+            .send()
+            .await
+            .unwrap();
+        assert!(res.status().is_success());
+        let body: serde_json::Value = res.json().await.unwrap();
+        let items = body["items"].as_array().unwrap();
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0]["key"], "c");
+        assert_eq!(items[0]["value"]["name"], "c");
+        assert_eq!(items[0]["revision"], 1);
+        assert_eq!(items[1]["key"], "a");
+
+        let too_many = client
+            .post(format!("{}/v1/state/batch_get", base))
+            .json(&serde_json::json!({ "keys": ["a", "b", "c", "d", "e"] }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(too_many.status(), reqwest::StatusCode::BAD_REQUEST);
+
+        let _ = shutdown.send(());
+    }
+}
+
+#[tokio::test]
+async fn state_txn_rejects_the_whole_batch_on_a_stale_revision() {
+    let (base, shutdown) = start().await;
+    let client = client();
+
+    client
+        .put(format!("{}/v1/state/order:1", base))
+        .json(&serde_json::json!({ "value": { "status": "open" } }))
+        .send()
+        .await
+        .unwrap();
+
+    let stale = client
+        .post(format!("{}/v1/state/txn", base))
+        .json(&serde_json::json!({ "ops": [
+            { "op": "put", "key": "order:1", "value": { "status": "paid" }, "if_revision": 7 },
+            { "op": "put", "key": "invoice:1", "value": { "order": 1 }, "if_not_exists": true },
+        ]}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(stale.status(), reqwest::StatusCode::CONFLICT);
+    let missing = client
+        .get(format!("{}/v1/state/invoice:1", base))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(missing.status(), reqwest::StatusCode::NOT_FOUND);
+
+    let ok = client
+        .post(format!("{}/v1/state/txn", base))
+        .json(&serde_json::json!({ "ops": [
+            { "op": "put", "key": "order:1", "value": { "status": "paid" }, "if_revision": 1 },
+            { "op": "put", "key": "invoice:1", "value": { "order": 1 }, "if_not_exists": true },
+        ]}))
+        .send()
+        .await
+        .unwrap();
+    assert!(ok.status().is_success());
+    let body: serde_json::Value = ok.json().await.unwrap();
+    assert_eq!(body["items"][0]["revision"], 2);
+    assert_eq!(body["items"][1]["key"], "invoice:1");
+
+    let _ = shutdown.send(());
+}
+
 #[tokio::test]
 async fn vector_create_upsert_search() {
     let (base, shutdown) = start().await;
-    let client = reqwest::Client::new();
+    let client = client();
 
     let create = client
         .post(format!("{}/v1/vector/docs", base))
@@ -91,17 +571,1808 @@ async fn vector_create_upsert_search() {
         .send()
         .await
         .unwrap();
-    assert!(upsert.status().is_success());
+    assert!(upsert.status().is_success());
+
+    let search = client
+        .post(format!("{}/v1/vector/docs/search", base))
+        .json(&serde_json::json!({"vector":[0.9,0.1],"k":1,"include_meta":true}))
+        .send()
+        .await
+        .unwrap();
+    assert!(search.status().is_success());
+    let v: serde_json::Value = search.json().await.unwrap();
+    assert_eq!(v["hits"][0]["id"], "a");
+
+    let _ = shutdown.send(());
+}
+
+#[tokio::test]
+async fn match_endpoints_evaluate_filters_against_one_item() {
+    let (base, shutdown) = start().await;
+    let client = client();
+
+    client
+        .post(format!("{}/v1/vector/docs", base))
+        .json(&serde_json::json!({"dim":2,"metric":"cosine"}))
+        .send()
+        .await
+        .unwrap();
+    client
+        .post(format!("{}/v1/vector/docs/upsert", base))
+        .json(&serde_json::json!({"id":"a","vector":[1.0,0.0],"meta":{"tag":"x","n":1}}))
+        .send()
+        .await
+        .unwrap();
+    client
+        .put(format!("{}/v1/doc/users/u1", base))
+        .json(&serde_json::json!({"name":"ana","role":"admin"}))
+        .send()
+        .await
+        .unwrap();
+
+    let cases = [
+        (
+            "vector/docs",
+            "a",
+            serde_json::json!({"tag":"x","n":1}),
+            true,
+        ),
+        ("vector/docs", "a", serde_json::json!({"tag":"y"}), false),
+        ("doc/users", "u1", serde_json::json!({"role":"admin"}), true),
+        (
+            "doc/users",
+            "u1",
+            serde_json::json!({"role":"guest"}),
+            false,
+        ),
+    ];
+    for (path, id, filter, expected) in cases {
+        let resp = client
+            .post(format!("{}/v1/{path}/match", base))
+            .json(&serde_json::json!({"id":id,"filter":filter}))
+            .send()
+            .await
+            .unwrap();
+        assert!(resp.status().is_success());
+        let body: serde_json::Value = resp.json().await.unwrap();
+        assert_eq!(body["matched"], expected, "{path} {filter}");
+    }
+
+    for path in ["vector/docs", "doc/users"] {
+        let missing = client
+            .post(format!("{}/v1/{path}/match", base))
+            .json(&serde_json::json!({"id":"nope","filter":{}}))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(missing.status(), reqwest::StatusCode::NOT_FOUND);
+    }
+
+    let _ = shutdown.send(());
+}
+
+#[tokio::test]
+async fn vector_collection_stats_report_tombstones() {
+    let (base, shutdown) = start().await;
+    let client = client();
+
+    client
+        .post(format!("{}/v1/vector/docs", base))
+        .json(&serde_json::json!({"dim":2,"metric":"cosine"}))
+        .send()
+        .await
+        .unwrap();
+    for id in ["a", "b", "c"] {
+        client
+            .post(format!("{}/v1/vector/docs/upsert", base))
+            .json(&serde_json::json!({"id":id,"vector":[1.0,0.5]}))
+            .send()
+            .await
+            .unwrap();
+    }
+    client
+        .post(format!("{}/v1/vector/docs/delete", base))
+        .json(&serde_json::json!({"id":"b"}))
+        .send()
+        .await
+        .unwrap();
+
+    let stats: serde_json::Value = client
+        .get(format!("{}/v1/vector/docs/stats", base))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(stats["dim"], 2);
+    assert_eq!(stats["metric"], "cosine");
+    assert_eq!(stats["live_count"], 2);
+    assert_eq!(stats["total_records"], 4);
+    assert_eq!(stats["upsert_count"], 3);
+    assert_eq!(stats["segment_count"], 1);
+    assert_eq!(stats["segments"][0]["live"], 2);
+    assert_eq!(stats["segments"][0]["deleted"], 1);
+    assert!(stats["segments"][0]["capacity"].as_u64().unwrap() >= 3);
+
+    let missing = client
+        .get(format!("{}/v1/vector/nope/stats", base))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(missing.status(), reqwest::StatusCode::NOT_FOUND);
+
+    let _ = shutdown.send(());
+}
+
+#[tokio::test]
+async fn upsert_batch_rejects_wrong_expected_dim_up_front() {
+    let (base, shutdown) = start().await;
+    let client = client();
+
+    client
+        .post(format!("{}/v1/vector/emb", base))
+        .json(&serde_json::json!({"dim":3,"metric":"cosine"}))
+        .send()
+        .await
+        .unwrap();
+
+    let wrong_model = client
+        .post(format!("{}/v1/vector/emb/upsert_batch", base))
+        .json(&serde_json::json!({
+            "expected_dim": 4,
+            "items": [
+                {"id":"a","vector":[1.0,0.0,0.0,0.0]},
+                {"id":"b","vector":[0.0,1.0,0.0,0.0]},
+            ]
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(wrong_model.status(), reqwest::StatusCode::BAD_REQUEST);
+    let body: serde_json::Value = wrong_model.json().await.unwrap();
+    assert_eq!(body["error"], "dim_mismatch");
+    assert!(body["message"].as_str().unwrap().contains("dim 3"));
+
+    // A matching expected_dim still catches a stray vector before any write.
+    let stray = client
+        .post(format!("{}/v1/vector/emb/upsert_batch", base))
+        .json(&serde_json::json!({
+            "expected_dim": 3,
+            "items": [
+                {"id":"a","vector":[1.0,0.0,0.0]},
+                {"id":"b","vector":[0.0,1.0]},
+            ]
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(stray.status(), reqwest::StatusCode::BAD_REQUEST);
+    let body: serde_json::Value = stray.json().await.unwrap();
+    assert_eq!(body["error"], "dim_mismatch");
+    assert!(body["message"].as_str().unwrap().contains("`b`"));
+
+    let missing = client
+        .get(format!("{}/v1/vector/emb/get?id=a", base))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(missing.status(), reqwest::StatusCode::NOT_FOUND);
+
+    let single = client
+        .post(format!("{}/v1/vector/emb/add", base))
+        .json(&serde_json::json!({"id":"c","vector":[1.0,0.0,0.0],"expected_dim":3}))
+        .send()
+        .await
+        .unwrap();
+    assert!(single.status().is_success());
+
+    let _ = shutdown.send(());
+}
+
+#[tokio::test]
+async fn vector_update_if_revision_conflict() {
+    let (base, shutdown) = start().await;
+    let client = client();
+
+    let create = client
+        .post(format!("{}/v1/vector/docs", base))
+        .json(&serde_json::json!({"dim":2,"metric":"cosine"}))
+        .send()
+        .await
+        .unwrap();
+    assert!(create.status().is_success());
+
+    for _ in 0..2 {
+        let upsert = client
+            .post(format!("{}/v1/vector/docs/upsert", base))
+            .json(&serde_json::json!({"id":"a","vector":[1.0,0.0]}))
+            .send()
+            .await
+            .unwrap();
+        assert!(upsert.status().is_success());
+    }
+    let got: serde_json::Value = client
+        .get(format!("{}/v1/vector/docs/get?id=a", base))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(got["revision"], 2);
+
+    let stale = client
+        .post(format!("{}/v1/vector/docs/update", base))
+        .json(&serde_json::json!({"id":"a","meta":{"v":"stale"},"if_revision":1}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(stale.status(), reqwest::StatusCode::CONFLICT);
+    let body: serde_json::Value = stale.json().await.unwrap();
+    assert_eq!(body["error"], "revision_mismatch");
+
+    let fresh = client
+        .post(format!("{}/v1/vector/docs/update", base))
+        .json(&serde_json::json!({"id":"a","meta":{"v":"fresh"},"if_revision":2}))
+        .send()
+        .await
+        .unwrap();
+    assert!(fresh.status().is_success());
+    let got: serde_json::Value = client
+        .get(format!("{}/v1/vector/docs/get?id=a", base))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(got["revision"], 3);
+    assert_eq!(got["meta"]["v"], "fresh");
+
+    // `add` only creates, so conditions it can't honour are refused.
+    for extra in [
+        serde_json::json!({"if_revision": 3}),
+        serde_json::json!({"only_if_changed": true}),
+    ] {
+        let mut body = serde_json::json!({"id":"b","vector":[0.0,1.0]});
+        body.as_object_mut()
+            .unwrap()
+            .extend(extra.as_object().unwrap().clone());
+        let add = client
+            .post(format!("{}/v1/vector/docs/add", base))
+            .json(&body)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(add.status(), reqwest::StatusCode::BAD_REQUEST, "{extra}");
+        let err: serde_json::Value = add.json().await.unwrap();
+        assert_eq!(err["error"], "invalid_argument");
+    }
+    let missing = client
+        .get(format!("{}/v1/vector/docs/get?id=b", base))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(missing.status(), reqwest::StatusCode::NOT_FOUND);
+
+    let _ = shutdown.send(());
+}
+
+#[tokio::test]
+async fn vector_search_rejects_oversized_response() {
+    let mut config = base_test_config();
+    config.max_response_bytes = 16 * 1024;
+    let (base, shutdown) = start_with_config(config).await;
+    let client = client();
+
+    let create = client
+        .post(format!("{}/v1/vector/big", base))
+        .json(&serde_json::json!({"dim":4,"metric":"cosine"}))
+        .send()
+        .await
+        .unwrap();
+    assert!(create.status().is_success());
+
+    let blob = "x".repeat(256);
+    let items: Vec<serde_json::Value> = (0..200)
+        .map(|i| {
+            serde_json::json!({
+                "id": format!("v{i}"),
+                "vector": [1.0, i as f32, 0.5, 0.25],
+                "meta": {"blob": blob},
+            })
+        })
+        .collect();
+    let batch = client
+        .post(format!("{}/v1/vector/big/upsert_batch", base))
+        .json(&serde_json::json!({ "items": items }))
+        .send()
+        .await
+        .unwrap();
+    assert!(batch.status().is_success());
+
+    let too_big = client
+        .post(format!("{}/v1/vector/big/search", base))
+        .json(&serde_json::json!({"vector":[1.0,1.0,0.5,0.25],"k":200,"include_meta":true}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(too_big.status(), reqwest::StatusCode::PAYLOAD_TOO_LARGE);
+    let body: serde_json::Value = too_big.json().await.unwrap();
+    assert_eq!(body["error"], "response_too_large");
+    assert!(body["message"].as_str().unwrap().contains("lower k"));
+
+    let small = client
+        .post(format!("{}/v1/vector/big/search", base))
+        .json(&serde_json::json!({"vector":[1.0,1.0,0.5,0.25],"k":5,"include_meta":true}))
+        .send()
+        .await
+        .unwrap();
+    assert!(small.status().is_success());
+    let body: serde_json::Value = small.json().await.unwrap();
+    assert_eq!(body["hits"].as_array().unwrap().len(), 5);
+
+    let _ = shutdown.send(());
+}
+
+#[tokio::test]
+async fn vector_search_reports_exact_for_small_filtered_subsets() {
+    let mut config = base_test_config();
+    config.index_kind = "HNSW".to_string();
+    let (base, shutdown) = start_with_config(config).await;
+    let client = client();
+
+    let create = client
+        .post(format!("{}/v1/vector/flags", base))
+        .json(&serde_json::json!({"dim":2,"metric":"cosine"}))
+        .send()
+        .await
+        .unwrap();
+    assert!(create.status().is_success());
+
+    for chunk in 0..3 {
+        let items: Vec<serde_json::Value> = (chunk * 200..(chunk + 1) * 200)
+            .map(|i| {
+                let tag = if i % 100 == 0 { "rare" } else { "common" };
+                serde_json::json!({
+                    "id": format!("v{i}"),
+                    "vector": [1.0, i as f32 / 600.0],
+                    "meta": {"tag": tag},
+                })
+            })
+            .collect();
+        let batch = client
+            .post(format!("{}/v1/vector/flags/upsert_batch", base))
+            .json(&serde_json::json!({ "items": items }))
+            .send()
+            .await
+            .unwrap();
+        assert!(batch.status().is_success());
+    }
+
+    let filtered: serde_json::Value = client
+        .post(format!("{}/v1/vector/flags/search", base))
+        .json(&serde_json::json!({"vector":[1.0,0.0],"k":3,"filters":{"tag":"rare"}}))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(filtered["exact"], true);
+    assert_eq!(filtered["hits"].as_array().unwrap().len(), 3);
+
+    let unfiltered: serde_json::Value = client
+        .post(format!("{}/v1/vector/flags/search", base))
+        .json(&serde_json::json!({"vector":[1.0,0.0],"k":3}))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(unfiltered["exact"], false);
+    assert_eq!(unfiltered["hits"].as_array().unwrap().len(), 3);
+
+    let _ = shutdown.send(());
+}
+
+#[tokio::test]
+async fn vector_search_supports_range_filters() {
+    let (base, shutdown) = start().await;
+    let client = client();
+
+    let create = client
+        .post(format!("{}/v1/vector/priced", base))
+        .json(&serde_json::json!({"dim":2,"metric":"cosine"}))
+        .send()
+        .await
+        .unwrap();
+    assert!(create.status().is_success());
+    let items = [
+        ("cheap", 5, "apple", "fruit"),
+        ("mid", 50, "banana", "fruit"),
+        ("pricey", 150, "cherry", "fruit"),
+        ("tool", 60, "drill", "hardware"),
+    ];
+    for (id, price, name, kind) in items {
+        let resp = client
+            .post(format!("{}/v1/vector/priced/upsert", base))
+            .json(&serde_json::json!({
+                "id": id,
+                "vector": [1.0, price as f32 / 100.0],
+                "meta": {"price": price, "name": name, "kind": kind},
+            }))
+            .send()
+            .await
+            .unwrap();
+        assert!(resp.status().is_success());
+    }
+
+    let cases = [
+        (
+            serde_json::json!({"price":{"$gte":10,"$lt":100}}),
+            vec!["mid", "tool"],
+        ),
+        (
+            serde_json::json!({"price":{"$gt":50}}),
+            vec!["pricey", "tool"],
+        ),
+        (
+            serde_json::json!({"price":{"$lte":50.0}}),
+            vec!["cheap", "mid"],
+        ),
+        (
+            serde_json::json!({"name":{"$gt":"b","$lt":"c"}}),
+            vec!["mid"],
+        ),
+        (serde_json::json!({"kind":{"$ne":"fruit"}}), vec!["tool"]),
+        (
+            serde_json::json!({"kind":"fruit","price":{"$gte":50}}),
+            vec!["mid", "pricey"],
+        ),
+        (serde_json::json!({"price":50}), vec!["mid"]),
+    ];
+    for (filters, expected) in cases {
+        let body: serde_json::Value = client
+            .post(format!("{}/v1/vector/priced/search", base))
+            .json(&serde_json::json!({"vector":[1.0,0.5],"k":10,"filters":filters}))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        let mut ids: Vec<&str> = body["hits"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|h| h["id"].as_str().unwrap())
+            .collect();
+        ids.sort_unstable();
+        assert_eq!(ids, expected, "{filters}");
+    }
+
+    let bad = client
+        .post(format!("{}/v1/vector/priced/search", base))
+        .json(&serde_json::json!({"vector":[1.0,0.5],"k":10,"filters":{"price":{"$lt":[1]}}}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(bad.status(), reqwest::StatusCode::BAD_REQUEST);
+    let unknown = client
+        .post(format!("{}/v1/vector/priced/search", base))
+        .json(
+            &serde_json::json!({"vector":[1.0,0.5],"k":10,"filters":{"price":{"$between":[1,2]}}}),
+        )
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(unknown.status(), reqwest::StatusCode::BAD_REQUEST);
+
+    let _ = shutdown.send(());
+}
+
+#[tokio::test]
+async fn vector_search_supports_in_and_or_filters() {
+    let mut config = base_test_config();
+    config.index_kind = "HNSW".to_string();
+    let (base, shutdown) = start_with_config(config).await;
+    let client = client();
+
+    let create = client
+        .post(format!("{}/v1/vector/langs", base))
+        .json(&serde_json::json!({"dim":2,"metric":"cosine"}))
+        .send()
+        .await
+        .unwrap();
+    assert!(create.status().is_success());
+    let items: Vec<serde_json::Value> = (0..20)
+        .map(|i| {
+            let tag = ["a", "b", "c", "d"][i % 4];
+            let lang = ["en", "es", "fr"][i % 3];
+            serde_json::json!({
+                "id": format!("v{i}"),
+                "vector": [1.0, i as f32 / 20.0],
+                "meta": {"tag": tag, "lang": lang, "n": i},
+            })
+        })
+        .collect();
+    let batch = client
+        .post(format!("{}/v1/vector/langs/upsert_batch", base))
+        .json(&serde_json::json!({ "items": items }))
+        .send()
+        .await
+        .unwrap();
+    assert!(batch.status().is_success());
+
+    // `exact: true` means the keyword index produced the candidate set;
+    // `false` means the filter fell back to HNSW plus post-filtering.
+    let cases = [
+        (serde_json::json!({"tag":{"$in":["a","b"]}}), 10, true),
+        (
+            serde_json::json!({"$or":[{"lang":"en"},{"lang":"es"}]}),
+            14,
+            true,
+        ),
+        (
+            serde_json::json!({"tag":"a","$or":[{"lang":"en"},{"tag":{"$in":["b"]}}]}),
+            2,
+            true,
+        ),
+        (
+            serde_json::json!({"$or":[{"tag":"a"},{"n":{"$gte":18}}]}),
+            7,
+            false,
+        ),
+        (serde_json::json!({"n":{"$in":[1,2,3]}}), 3, false),
+        (serde_json::json!({"tag":{"$in":["a"],"$ne":"b"}}), 5, false),
+    ];
+    for (filters, expected, exact) in cases {
+        let body: serde_json::Value = client
+            .post(format!("{}/v1/vector/langs/search", base))
+            .json(&serde_json::json!({"vector":[1.0,0.5],"k":20,"filters":filters}))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(
+            body["hits"].as_array().unwrap().len(),
+            expected,
+            "{filters}"
+        );
+        assert_eq!(body["exact"], exact, "{filters}");
+    }
+
+    for filters in [
+        serde_json::json!({"$or":{"tag":"a"}}),
+        serde_json::json!({"$or":[]}),
+        serde_json::json!({"$and":[]}),
+        serde_json::json!({"tag":{"$in":"a"}}),
+    ] {
+        let resp = client
+            .post(format!("{}/v1/vector/langs/search", base))
+            .json(&serde_json::json!({"vector":[1.0,0.5],"k":20,"filters":filters}))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), reqwest::StatusCode::BAD_REQUEST, "{filters}");
+    }
+
+    let _ = shutdown.send(());
+}
+
+#[tokio::test]
+async fn vector_search_batch_returns_results_in_query_order() {
+    let mut config = base_test_config();
+    config.max_vector_batch = 3;
+    let (base, shutdown) = start_with_config(config).await;
+    let client = client();
+
+    let create = client
+        .post(format!("{}/v1/vector/batchq", base))
+        .json(&serde_json::json!({"dim":2,"metric":"cosine"}))
+        .send()
+        .await
+        .unwrap();
+    assert!(create.status().is_success());
+    for (id, vector) in [("x", [1.0, 0.0]), ("y", [0.0, 1.0])] {
+        let resp = client
+            .post(format!("{}/v1/vector/batchq/upsert", base))
+            .json(&serde_json::json!({"id": id, "vector": vector, "meta": {"axis": id}}))
+            .send()
+            .await
+            .unwrap();
+        assert!(resp.status().is_success());
+    }
+
+    let body: serde_json::Value = client
+        .post(format!("{}/v1/vector/batchq/search_batch", base))
+        .json(&serde_json::json!({"queries": [
+            {"vector": [0.1, 1.0], "k": 1},
+            {"vector": [1.0, 0.1], "k": 2},
+            {"vector": [1.0, 0.1], "k": 2, "filters": {"axis": "y"}},
+        ]}))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let ids: Vec<Vec<&str>> = body["results"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|hits| {
+            hits.as_array()
+                .unwrap()
+                .iter()
+                .map(|h| h["id"].as_str().unwrap())
+                .collect()
+        })
+        .collect();
+    assert_eq!(ids, vec![vec!["y"], vec!["x", "y"], vec!["y"]]);
+
+    let bad_queries = [
+        serde_json::Value::Array(vec![serde_json::json!({"vector": [1.0, 0.0], "k": 1}); 4]),
+        serde_json::json!([{"vector": [1.0, 0.0], "k": 1}, {"vector": [1.0, 0.0], "k": 0}]),
+        serde_json::json!([{"vector": [1.0, 0.0, 0.0], "k": 1}]),
+    ];
+    for queries in bad_queries {
+        let resp = client
+            .post(format!("{}/v1/vector/batchq/search_batch", base))
+            .json(&serde_json::json!({ "queries": queries }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), reqwest::StatusCode::BAD_REQUEST, "{queries}");
+    }
+
+    let _ = shutdown.send(());
+}
+
+#[tokio::test]
+async fn lenient_query_dim_pads_or_truncates_off_by_one_queries() {
+    for strict in [true, false] {
+        let mut config = base_test_config();
+        config.strict_query_dim = strict;
+        let (base, shutdown) = start_with_config(config).await;
+        let client = client();
+
+        let create = client
+            .post(format!("{}/v1/vector/dims", base))
+            .json(&serde_json::json!({"dim":3,"metric":"cosine"}))
+            .send()
+            .await
+            .unwrap();
+        assert!(create.status().is_success());
+        let upsert = client
+            .post(format!("{}/v1/vector/dims/upsert", base))
+            .json(&serde_json::json!({"id":"a","vector":[1.0,0.0,0.0]}))
+            .send()
+            .await
+            .unwrap();
+        assert!(upsert.status().is_success());
+
+        let search = |vector: serde_json::Value| {
+            let client = client.clone();
+            let url = format!("{}/v1/vector/dims/search", base);
+            async move {
+                client
+                    .post(url)
+                    .json(&serde_json::json!({"vector": vector, "k": 1}))
+                    .send()
+                    .await
+                    .unwrap()
+            }
+        };
+        for vector in [
+            serde_json::json!([1.0, 0.0]),
+            serde_json::json!([1.0, 0.0, 0.0, 0.5]),
+        ] {
+            let resp = search(vector.clone()).await;
+            if strict {
+                assert_eq!(resp.status(), reqwest::StatusCode::BAD_REQUEST, "{vector}");
+                let body: serde_json::Value = resp.json().await.unwrap();
+                assert_eq!(body["error"], "dim_mismatch");
+            } else {
+                assert!(resp.status().is_success(), "{vector}");
+                let body: serde_json::Value = resp.json().await.unwrap();
+                assert_eq!(body["hits"][0]["id"], "a");
+            }
+        }
+        let too_short = search(serde_json::json!([1.0])).await;
+        assert_eq!(too_short.status(), reqwest::StatusCode::BAD_REQUEST);
+
+        let short_insert = client
+            .post(format!("{}/v1/vector/dims/upsert", base))
+            .json(&serde_json::json!({"id":"b","vector":[1.0,0.0]}))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(short_insert.status(), reqwest::StatusCode::BAD_REQUEST);
+
+        let _ = shutdown.send(());
+    }
+}
+
+#[tokio::test]
+async fn vector_search_returns_stored_vectors_on_request() {
+    let (base, shutdown) = start().await;
+    let client = client();
+
+    let create = client
+        .post(format!("{}/v1/vector/withvec", base))
+        .json(&serde_json::json!({"dim":2,"metric":"cosine"}))
+        .send()
+        .await
+        .unwrap();
+    assert!(create.status().is_success());
+    let upsert = client
+        .post(format!("{}/v1/vector/withvec/upsert", base))
+        .json(&serde_json::json!({"id":"a","vector":[3.0,4.0],"meta":{"tag":"x"}}))
+        .send()
+        .await
+        .unwrap();
+    assert!(upsert.status().is_success());
+
+    let search = |body: serde_json::Value| {
+        let client = client.clone();
+        let url = format!("{}/v1/vector/withvec/search", base);
+        async move {
+            let resp = client.post(url).json(&body).send().await.unwrap();
+            assert!(resp.status().is_success());
+            resp.json::<serde_json::Value>().await.unwrap()
+        }
+    };
+    let plain = search(serde_json::json!({"vector":[1.0,1.0],"k":1})).await;
+    assert!(plain["hits"][0].get("vector").is_none());
+
+    let with_vector =
+        search(serde_json::json!({"vector":[1.0,1.0],"k":1,"include_vector":true})).await;
+    assert_eq!(
+        with_vector["hits"][0]["vector"],
+        serde_json::json!([3.0, 4.0])
+    );
+
+    let filtered = search(serde_json::json!({
+        "vector": [1.0, 1.0],
+        "k": 1,
+        "include_vector": true,
+        "filters": {"tag": "x"},
+    }))
+    .await;
+    assert_eq!(filtered["hits"][0]["vector"], serde_json::json!([3.0, 4.0]));
+
+    let _ = shutdown.send(());
+}
+
+#[tokio::test]
+async fn admin_config_reports_effective_config_with_secrets_redacted() {
+    let mut config = base_test_config();
+    config.max_k = 77;
+    let (base, shutdown) = start_with_config(config).await;
+
+    let resp = client()
+        .get(format!("{}/v1/admin/config", base))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::OK);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["max_k"], 77);
+    assert_eq!(body["api_key"], "***");
+    assert!(!body.to_string().contains("\"test\""));
+
+    let anonymous = reqwest::Client::new()
+        .get(format!("{}/v1/admin/config", base))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(anonymous.status(), reqwest::StatusCode::UNAUTHORIZED);
+
+    let _ = shutdown.send(());
+}
+
+#[tokio::test]
+async fn admin_metrics_reset_returns_previous_counts_and_starts_over() {
+    let (base, shutdown) = start().await;
+    let client = client();
+    let put = |key: &'static str| {
+        client
+            .put(format!("{}/v1/state/{}", base, key))
+            .json(&serde_json::json!({"value": 1}))
+            .send()
+    };
+
+    for key in ["a", "b", "c"] {
+        assert!(put(key).await.unwrap().status().is_success());
+    }
+    let reset = |client: &reqwest::Client| {
+        client
+            .post(format!("{}/v1/admin/metrics/reset", base))
+            .send()
+    };
+    let first = reset(&client).await.unwrap();
+    assert_eq!(first.status(), reqwest::StatusCode::OK);
+    let before: serde_json::Value = first.json().await.unwrap();
+    assert_eq!(before["state_put_total"], 3);
+
+    assert!(put("d").await.unwrap().status().is_success());
+    let text = client
+        .get(format!("{}/v1/metrics", base))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(text.contains("state_put_total 1\n"));
+    assert!(text
+        .contains("http_requests_total{route=\"/v1/state/:key\",method=\"PUT\",status=\"200\"} 1"));
+
+    let second: serde_json::Value = reset(&client).await.unwrap().json().await.unwrap();
+    assert_eq!(second["state_put_total"], 1);
+    assert_eq!(second["sse_clients"], 0);
+
+    let anonymous = reset(&reqwest::Client::new()).await.unwrap();
+    assert_eq!(anonymous.status(), reqwest::StatusCode::UNAUTHORIZED);
+
+    let _ = shutdown.send(());
+}
+
+#[tokio::test]
+async fn admin_vector_reindex_rebuilds_and_reports_stats() {
+    let (base, shutdown) = start().await;
+    let client = client();
+    client
+        .post(format!("{}/v1/vector/docs", base))
+        .json(&serde_json::json!({"dim":2,"metric":"cosine"}))
+        .send()
+        .await
+        .unwrap();
+    for (id, vector) in [("a", [1.0, 0.0]), ("b", [0.0, 1.0])] {
+        let upsert = client
+            .post(format!("{}/v1/vector/docs/upsert", base))
+            .json(&serde_json::json!({"id": id, "vector": vector}))
+            .send()
+            .await
+            .unwrap();
+        assert!(upsert.status().is_success());
+    }
+
+    let reindex = |client: &reqwest::Client, collection: &str| {
+        client
+            .post(format!("{}/v1/admin/vector/{}/reindex", base, collection))
+            .send()
+    };
+    let resp = reindex(&client, "docs").await.unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::OK);
+    let stats: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(stats["live_count"], 2);
+    assert_eq!(stats["segment_count"], 1);
+
+    let search: serde_json::Value = client
+        .post(format!("{}/v1/vector/docs/search", base))
+        .json(&serde_json::json!({"vector":[0.0,1.0],"k":1}))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(search["hits"][0]["id"], "b");
+
+    let missing = reindex(&client, "missing").await.unwrap();
+    assert_eq!(missing.status(), reqwest::StatusCode::NOT_FOUND);
+    let anonymous = reindex(&reqwest::Client::new(), "docs").await.unwrap();
+    assert_eq!(anonymous.status(), reqwest::StatusCode::UNAUTHORIZED);
+
+    let _ = shutdown.send(());
+}
+
+#[tokio::test]
+async fn admin_compact_snapshots_and_drops_old_wal_segments() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut config = base_test_config();
+    config.data_dir = Some(dir.path().to_string_lossy().to_string());
+    let (base, shutdown) = start_with_config(config).await;
+    let client = client();
+    for i in 0..3 {
+        let put = client
+            .put(format!("{}/v1/state/compact:{}", base, i))
+            .json(&serde_json::json!({"value": i}))
+            .send()
+            .await
+            .unwrap();
+        assert!(put.status().is_success());
+    }
+
+    let resp = client
+        .post(format!("{}/v1/admin/compact", base))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::OK);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["last_offset"], 3);
+    // The snapshot task may already have rotated once at startup.
+    assert!(body["removed_segments"].as_u64().unwrap() >= 1);
+    let item: serde_json::Value = client
+        .get(format!("{}/v1/state/compact:2", base))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(item["value"], 2);
+
+    let anonymous = reqwest::Client::new()
+        .post(format!("{}/v1/admin/compact", base))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(anonymous.status(), reqwest::StatusCode::UNAUTHORIZED);
+    let _ = shutdown.send(());
+
+    let (base, shutdown) = start().await;
+    let resp = client
+        .post(format!("{}/v1/admin/compact", base))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::NOT_FOUND);
+    let _ = shutdown.send(());
+}
+
+#[tokio::test]
+async fn admin_status_reports_offsets_wal_segments_and_snapshot() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut config = base_test_config();
+    config.data_dir = Some(dir.path().to_string_lossy().to_string());
+    let (base, shutdown) = start_with_config(config).await;
+    let client = client();
+    client
+        .post(format!("{}/v1/vector/docs", base))
+        .json(&serde_json::json!({"dim": 2, "metric": "cosine"}))
+        .send()
+        .await
+        .unwrap();
+    client
+        .post(format!("{}/v1/vector/docs/upsert", base))
+        .json(&serde_json::json!({"id": "a", "vector": [1.0, 0.0]}))
+        .send()
+        .await
+        .unwrap();
+    client
+        .put(format!("{}/v1/state/status:k", base))
+        .json(&serde_json::json!({"value": 1}))
+        .send()
+        .await
+        .unwrap();
+
+    let status: serde_json::Value = client
+        .get(format!("{}/v1/admin/status", base))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(status["persistence"], true);
+    let published = status["last_published_offset"].as_u64().unwrap();
+    assert!(published >= 3);
+    assert_eq!(status["state_applied_offset"], published);
+    assert!(status["vector_applied_offset"].as_u64().unwrap() <= published);
+    let segments = status["wal_segments"].as_array().unwrap();
+    assert!(segments
+        .iter()
+        .all(|seg| seg["file"].as_str().unwrap().starts_with("events-")));
+    let bytes: u64 = segments
+        .iter()
+        .map(|seg| seg["bytes"].as_u64().unwrap())
+        .sum();
+    assert!(bytes > 0);
+    assert_eq!(status["wal_bytes"], bytes);
+
+    let anonymous = reqwest::Client::new()
+        .get(format!("{}/v1/admin/status", base))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(anonymous.status(), reqwest::StatusCode::UNAUTHORIZED);
+    let _ = shutdown.send(());
+
+    let (base, shutdown) = start().await;
+    let status: serde_json::Value = client
+        .get(format!("{}/v1/admin/status", base))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(status["persistence"], false);
+    assert_eq!(status["snapshot_exists"], false);
+    assert!(status["wal_segments"].as_array().unwrap().is_empty());
+    let _ = shutdown.send(());
+}
+
+#[tokio::test]
+async fn vector_exists_similar_reports_only_matches_above_threshold() {
+    let (base, shutdown) = start().await;
+    let client = client();
+
+    client
+        .post(format!("{}/v1/vector/dedup", base))
+        .json(&serde_json::json!({"dim":2,"metric":"cosine"}))
+        .send()
+        .await
+        .unwrap();
+    for (id, vector) in [("east", [1.0, 0.0]), ("north", [0.0, 1.0])] {
+        let resp = client
+            .post(format!("{}/v1/vector/dedup/upsert", base))
+            .json(&serde_json::json!({"id": id, "vector": vector}))
+            .send()
+            .await
+            .unwrap();
+        assert!(resp.status().is_success());
+    }
+    let probe = |vector: [f32; 2], threshold: f32| {
+        client
+            .post(format!("{}/v1/vector/dedup/exists_similar", base))
+            .json(&serde_json::json!({"vector": vector, "threshold": threshold}))
+            .send()
+    };
+
+    let near: serde_json::Value = probe([0.99, 0.05], 0.95)
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(near["exists"], true);
+    assert_eq!(near["id"], "east");
+    assert!(near["score"].as_f64().unwrap() >= 0.95);
+
+    let far: serde_json::Value = probe([-1.0, -0.2], 0.5)
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(far["exists"], false);
+    assert!(far["id"].is_null());
+
+    let missing = client
+        .post(format!("{}/v1/vector/nope/exists_similar", base))
+        .json(&serde_json::json!({"vector": [1.0, 0.0], "threshold": 0.5}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(missing.status(), reqwest::StatusCode::NOT_FOUND);
+
+    let _ = shutdown.send(());
+}
+
+#[tokio::test]
+async fn vector_knn_graph_scores_intra_cluster_edges_above_cross_cluster_ones() {
+    let (base, shutdown) = start().await;
+    let client = client();
+
+    client
+        .post(format!("{}/v1/vector/graph", base))
+        .json(&serde_json::json!({"dim":2,"metric":"cosine"}))
+        .send()
+        .await
+        .unwrap();
+    let points = [
+        ("a1", [1.0, 0.05]),
+        ("a2", [0.98, 0.1]),
+        ("a3", [0.95, -0.05]),
+        ("b1", [0.05, 1.0]),
+        ("b2", [-0.1, 0.97]),
+        ("b3", [0.1, 0.95]),
+    ];
+    for (id, vector) in points {
+        let resp = client
+            .post(format!("{}/v1/vector/graph/upsert", base))
+            .json(&serde_json::json!({"id": id, "vector": vector}))
+            .send()
+            .await
+            .unwrap();
+        assert!(resp.status().is_success());
+    }
+
+    let ids: Vec<&str> = points.iter().map(|(id, _)| *id).collect();
+    let graph: serde_json::Value = client
+        .post(format!("{}/v1/vector/graph/knn_graph", base))
+        .json(&serde_json::json!({"ids": ids, "k": 4}))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let edges = graph["edges"].as_array().unwrap();
+    assert_eq!(edges.len(), ids.len() * 4);
+    let (mut intra, mut cross) = (Vec::new(), Vec::new());
+    for edge in edges {
+        let from = edge["from"].as_str().unwrap();
+        let to = edge["to"].as_str().unwrap();
+        assert_ne!(from, to);
+        let score = edge["score"].as_f64().unwrap();
+        if from[..1] == to[..1] {
+            intra.push(score);
+        } else {
+            cross.push(score);
+        }
+    }
+    assert_eq!(intra.len(), ids.len() * 2);
+    let worst_intra = intra.iter().cloned().fold(f64::INFINITY, f64::min);
+    let best_cross = cross.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+    assert!(worst_intra > best_cross);
+
+    let zero_k = client
+        .post(format!("{}/v1/vector/graph/knn_graph", base))
+        .json(&serde_json::json!({"ids": ["a1"], "k": 0}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(zero_k.status(), reqwest::StatusCode::BAD_REQUEST);
+    let unknown = client
+        .post(format!("{}/v1/vector/graph/knn_graph", base))
+        .json(&serde_json::json!({"ids": ["a1", "zz"], "k": 2}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(unknown.status(), reqwest::StatusCode::NOT_FOUND);
+
+    let _ = shutdown.send(());
+}
+
+#[tokio::test]
+async fn vector_search_multi_merges_hits_across_collections() {
+    let (base, shutdown) = start().await;
+    let client = client();
+
+    for (collection, dim) in [("tenant_a", 2), ("tenant_b", 2), ("wide", 3)] {
+        client
+            .post(format!("{}/v1/vector/{}", base, collection))
+            .json(&serde_json::json!({"dim": dim, "metric": "cosine"}))
+            .send()
+            .await
+            .unwrap();
+    }
+    for (collection, id, vector) in [
+        ("tenant_a", "a1", [1.0, 0.0]),
+        ("tenant_a", "a2", [0.0, 1.0]),
+        ("tenant_b", "b1", [1.0, 0.1]),
+        ("tenant_b", "b2", [1.0, 0.6]),
+    ] {
+        let resp = client
+            .post(format!("{}/v1/vector/{}/upsert", base, collection))
+            .json(&serde_json::json!({"id": id, "vector": vector}))
+            .send()
+            .await
+            .unwrap();
+        assert!(resp.status().is_success());
+    }
+    let search = |body: serde_json::Value| {
+        client
+            .post(format!("{}/v1/vector/search_multi", base))
+            .json(&body)
+            .send()
+    };
+
+    let resp = search(serde_json::json!({
+        "collections": ["tenant_a", "tenant_b"],
+        "vector": [1.0, 0.0],
+        "k": 3,
+        "include_meta": false
+    }))
+    .await
+    .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::OK);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    let hits: Vec<(String, String)> = body["hits"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|h| {
+            (
+                h["collection"].as_str().unwrap().to_string(),
+                h["id"].as_str().unwrap().to_string(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        hits,
+        vec![
+            ("tenant_a".to_string(), "a1".to_string()),
+            ("tenant_b".to_string(), "b1".to_string()),
+            ("tenant_b".to_string(), "b2".to_string()),
+        ]
+    );
+
+    let mixed = search(serde_json::json!({
+        "collections": ["tenant_a", "wide"],
+        "vector": [1.0, 0.0],
+        "k": 3
+    }))
+    .await
+    .unwrap();
+    assert_eq!(mixed.status(), reqwest::StatusCode::BAD_REQUEST);
+
+    let missing = search(serde_json::json!({
+        "collections": ["tenant_a", "nope"],
+        "vector": [1.0, 0.0],
+        "k": 3
+    }))
+    .await
+    .unwrap();
+    assert_eq!(missing.status(), reqwest::StatusCode::NOT_FOUND);
+
+    let _ = shutdown.send(());
+}
+
+#[tokio::test]
+async fn vector_search_min_score_drops_weak_hits() {
+    let mut config = base_test_config();
+    config.index_kind = "HNSW".to_string();
+    let (base, shutdown) = start_with_config(config).await;
+    let client = client();
+
+    let create = client
+        .post(format!("{}/v1/vector/scored", base))
+        .json(&serde_json::json!({"dim":2,"metric":"cosine"}))
+        .send()
+        .await
+        .unwrap();
+    assert!(create.status().is_success());
+    let items: Vec<serde_json::Value> = [
+        ("same", [1.0, 0.0]),
+        ("diag", [1.0, 1.0]),
+        ("far", [0.5, 1.0]),
+        ("ortho", [0.0, 1.0]),
+    ]
+    .iter()
+    .map(|(id, vector)| serde_json::json!({"id": id, "vector": vector, "meta": {"tag": "t"}}))
+    .collect();
+    let batch = client
+        .post(format!("{}/v1/vector/scored/upsert_batch", base))
+        .json(&serde_json::json!({ "items": items }))
+        .send()
+        .await
+        .unwrap();
+    assert!(batch.status().is_success());
+
+    // Unfiltered goes through HNSW, the tag filter through the exact subset path.
+    for filters in [serde_json::Value::Null, serde_json::json!({"tag": "t"})] {
+        let body: serde_json::Value = client
+            .post(format!("{}/v1/vector/scored/search", base))
+            .json(&serde_json::json!({
+                "vector": [1.0, 0.0],
+                "k": 10,
+                "min_score": 0.7,
+                "filters": filters,
+            }))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        let ids: Vec<&str> = body["hits"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|h| h["id"].as_str().unwrap())
+            .collect();
+        assert_eq!(ids, vec!["same", "diag"], "{filters}");
+        assert!(body["hits"][1]["score"].as_f64().unwrap() >= 0.7);
+    }
+
+    let _ = shutdown.send(());
+}
+
+#[tokio::test]
+async fn vector_collection_cap_rejects_new_ids_only() {
+    let mut config = base_test_config();
+    config.max_vectors_per_collection = 3;
+    let (base, shutdown) = start_with_config(config).await;
+    let client = client();
+
+    let create = client
+        .post(format!("{}/v1/vector/capped", base))
+        .json(&serde_json::json!({"dim":2,"metric":"cosine"}))
+        .send()
+        .await
+        .unwrap();
+    assert!(create.status().is_success());
+
+    for i in 0..3 {
+        let resp = client
+            .post(format!("{}/v1/vector/capped/upsert", base))
+            .json(&serde_json::json!({"id": format!("v{i}"), "vector":[1.0, i as f32]}))
+            .send()
+            .await
+            .unwrap();
+        assert!(resp.status().is_success());
+    }
+
+    for route in ["upsert", "add"] {
+        let full = client
+            .post(format!("{}/v1/vector/capped/{}", base, route))
+            .json(&serde_json::json!({"id":"v3","vector":[1.0,3.0]}))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(full.status(), reqwest::StatusCode::CONFLICT);
+        let body: serde_json::Value = full.json().await.unwrap();
+        assert_eq!(body["error"], "collection_full");
+    }
+
+    let overwrite = client
+        .post(format!("{}/v1/vector/capped/upsert", base))
+        .json(&serde_json::json!({"id":"v0","vector":[0.0,1.0],"meta":{"v":2}}))
+        .send()
+        .await
+        .unwrap();
+    assert!(overwrite.status().is_success());
+    let update = client
+        .post(format!("{}/v1/vector/capped/update", base))
+        .json(&serde_json::json!({"id":"v1","meta":{"v":2}}))
+        .send()
+        .await
+        .unwrap();
+    assert!(update.status().is_success());
+
+    let deleted = client
+        .post(format!("{}/v1/vector/capped/delete", base))
+        .json(&serde_json::json!({"id":"v2"}))
+        .send()
+        .await
+        .unwrap();
+    assert!(deleted.status().is_success());
+    let refill = client
+        .post(format!("{}/v1/vector/capped/upsert", base))
+        .json(&serde_json::json!({"id":"v3","vector":[1.0,3.0]}))
+        .send()
+        .await
+        .unwrap();
+    assert!(refill.status().is_success());
+
+    let _ = shutdown.send(());
+}
+
+#[tokio::test]
+async fn http_metrics_are_labeled_by_route_and_status() {
+    let (base, shutdown) = start().await;
+    let client = client();
+
+    let missing = client
+        .get(format!("{}/v1/state/nope", base))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(missing.status(), reqwest::StatusCode::NOT_FOUND);
+    for key in ["a", "b"] {
+        let put = client
+            .put(format!("{}/v1/state/{}", base, key))
+            .json(&serde_json::json!({"value": 1}))
+            .send()
+            .await
+            .unwrap();
+        assert!(put.status().is_success());
+    }
+
+    let text = client
+        .get(format!("{}/v1/metrics", base))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(text
+        .contains("http_requests_total{route=\"/v1/state/:key\",method=\"GET\",status=\"404\"} 1"));
+    assert!(text
+        .contains("http_requests_total{route=\"/v1/state/:key\",method=\"PUT\",status=\"200\"} 2"));
+    assert!(text.contains("http_request_duration_seconds_count{route=\"/v1/state/:key\"} 3"));
+    assert!(!text.contains("/v1/state/nope"));
+
+    let _ = shutdown.send(());
+}
+
+#[tokio::test]
+async fn engine_latency_histograms_record_puts_gets_and_searches() {
+    let (base, shutdown) = start().await;
+    let client = client();
+    let scrape = || async {
+        client
+            .get(format!("{}/v1/metrics", base))
+            .send()
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap()
+    };
+    // A series not scraped yet counts as zero.
+    let value = |text: &str, series: &str| -> u64 {
+        text.lines()
+            .find_map(|line| line.strip_prefix(series)?.strip_prefix(' '))
+            .map_or(0, |v| v.parse().unwrap())
+    };
+
+    client
+        .post(format!("{}/v1/vector/lat", base))
+        .json(&serde_json::json!({"dim": 2, "metric": "cosine"}))
+        .send()
+        .await
+        .unwrap();
+    let before = scrape().await;
+    assert!(before.contains("# TYPE state_put_duration_microseconds histogram"));
+
+    for key in ["a", "b"] {
+        client
+            .put(format!("{}/v1/state/{}", base, key))
+            .json(&serde_json::json!({"value": 1}))
+            .send()
+            .await
+            .unwrap();
+    }
+    client
+        .get(format!("{}/v1/state/a", base))
+        .send()
+        .await
+        .unwrap();
+    let search = client
+        .post(format!("{}/v1/vector/lat/search", base))
+        .json(&serde_json::json!({"vector": [1.0, 0.0], "k": 1}))
+        .send()
+        .await
+        .unwrap();
+    assert!(search.status().is_success());
+
+    let after = scrape().await;
+    for (series, delta) in [
+        ("state_put_duration_microseconds_count", 2),
+        ("state_put_duration_microseconds_bucket{le=\"+Inf\"}", 2),
+        ("state_get_duration_microseconds_count", 1),
+        (
+            "vector_search_duration_microseconds_count{collection=\"lat\"}",
+            1,
+        ),
+        (
+            "vector_search_duration_microseconds_bucket{collection=\"lat\",le=\"+Inf\"}",
+            1,
+        ),
+    ] {
+        assert_eq!(
+            value(&after, series) - value(&before, series),
+            delta,
+            "{series}"
+        );
+    }
+
+    let _ = shutdown.send(());
+}
+
+#[tokio::test]
+async fn metrics_json_mirrors_the_prometheus_counters() {
+    let (base, shutdown) = start().await;
+    let client = client();
+
+    for key in ["a", "b"] {
+        client
+            .put(format!("{}/v1/state/{}", base, key))
+            .json(&serde_json::json!({"value": 1}))
+            .send()
+            .await
+            .unwrap();
+    }
+    let missing = client
+        .get(format!("{}/v1/state/nope", base))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(missing.status(), reqwest::StatusCode::NOT_FOUND);
+
+    let resp = client
+        .get(format!("{}/v1/metrics.json", base))
+        .send()
+        .await
+        .unwrap();
+    assert!(resp.status().is_success());
+    let metrics: serde_json::Value = resp.json().await.unwrap();
+    let text = client
+        .get(format!("{}/v1/metrics", base))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    let puts = metrics["state_put_total"].as_u64().unwrap();
+    assert!(puts >= 2);
+    assert!(text.contains(&format!("state_put_total {puts}\n")));
+    assert_eq!(metrics["embed_cache_hits_total"], 0);
+
+    // Only the two PUTs: internal writes (docstore, manifests) are not timed.
+    let put_latency = &metrics["latency"]["state_put_duration_microseconds"];
+    assert_eq!(put_latency["count"], 2);
+    assert!(put_latency["buckets"].as_array().unwrap().len() > 1);
+    for name in metrics["latency"].as_object().unwrap().keys() {
+        assert!(text.contains(&format!("# TYPE {name} histogram")), "{name}");
+    }
+    let requests = metrics["http"]["http_requests_total"].as_array().unwrap();
+    assert!(requests.iter().any(|r| r["route"] == "/v1/state/:key"
+        && r["method"] == "PUT"
+        && r["status"] == 200
+        && r["count"] == 2));
+    assert_eq!(
+        metrics["http"]["http_request_duration_seconds"]["/v1/state/:key"]["count"],
+        3
+    );
+    assert!(metrics["derived"]["http_requests"].as_u64().unwrap() >= 3);
+    assert_eq!(metrics["derived"]["http_server_error_ratio"], 0.0);
+
+    let _ = shutdown.send(());
+}
+
+#[tokio::test]
+async fn disk_gauges_report_wal_and_vector_data_sizes() {
+    let gauge = |text: &str, name: &str| -> u64 {
+        text.lines()
+            .find_map(|line| line.strip_prefix(name)?.strip_prefix(' '))
+            .unwrap()
+            .parse()
+            .unwrap()
+    };
+    let dir = tempfile::tempdir().unwrap();
+    let mut config = base_test_config();
+    config.data_dir = Some(dir.path().to_string_lossy().to_string());
+    let (base, shutdown) = start_with_config(config).await;
+    let client = client();
+    client
+        .post(format!("{}/v1/vector/disk", base))
+        .json(&serde_json::json!({"dim": 2, "metric": "cosine"}))
+        .send()
+        .await
+        .unwrap();
+    for id in ["a", "b"] {
+        let resp = client
+            .post(format!("{}/v1/vector/disk/upsert", base))
+            .json(&serde_json::json!({"id": id, "vector": [1.0, 0.5]}))
+            .send()
+            .await
+            .unwrap();
+        assert!(resp.status().is_success());
+    }
+
+    let text = client
+        .get(format!("{}/v1/metrics", base))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(text.contains("# TYPE wal_bytes_total gauge"));
+    assert!(gauge(&text, "wal_bytes_total") > 0);
+    assert!(gauge(&text, "vector_data_bytes_total") > 0);
+    let json: serde_json::Value = client
+        .get(format!("{}/v1/metrics.json", base))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert!(json["vector_data_bytes_total"].as_u64().unwrap() > 0);
+    let _ = shutdown.send(());
+
+    let (base, shutdown) = start().await;
+    let text = client
+        .get(format!("{}/v1/metrics", base))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert_eq!(gauge(&text, "wal_bytes_total"), 0);
+    assert_eq!(gauge(&text, "vector_data_bytes_total"), 0);
+    let _ = shutdown.send(());
+}
+
+#[tokio::test]
+async fn metrics_report_uptime_and_build_version() {
+    let (base, shutdown) = start().await;
+    let client = client();
+    let scrape = || async {
+        client
+            .get(format!("{}/v1/metrics", base))
+            .send()
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap()
+    };
+    let uptime = |text: &str| -> f64 {
+        text.lines()
+            .find_map(|line| line.strip_prefix("uptime_seconds "))
+            .unwrap()
+            .parse()
+            .unwrap()
+    };
+
+    let first = scrape().await;
+    assert!(first.contains(&format!(
+        "build_info{{version=\"{}\"}} 1\n",
+        env!("CARGO_PKG_VERSION")
+    )));
+    tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    let second = scrape().await;
+    assert!(uptime(&second) > uptime(&first));
+
+    let json: serde_json::Value = client
+        .get(format!("{}/v1/metrics.json", base))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(json["build_info"]["version"], env!("CARGO_PKG_VERSION"));
+    assert!(json["process_start_ms"].as_u64().unwrap() > 0);
+
+    let _ = shutdown.send(());
+}
+
+#[tokio::test]
+async fn rejected_requests_are_counted_by_reason() {
+    let (base, shutdown) = start().await;
+    let client = client();
+
+    let create = client
+        .post(format!("{}/v1/vector/docs", base))
+        .json(&serde_json::json!({"dim": 2, "metric": "cosine"}))
+        .send()
+        .await
+        .unwrap();
+    assert!(create.status().is_success());
+    for _ in 0..2 {
+        let bad = client
+            .post(format!("{}/v1/vector/docs/upsert", base))
+            .json(&serde_json::json!({"id": "a", "vector": [1.0, 0.0, 0.0]}))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(bad.status(), reqwest::StatusCode::BAD_REQUEST);
+    }
+    let malformed = client
+        .post(format!("{}/v1/vector/docs/upsert", base))
+        .header("content-type", "application/json")
+        .body("{not json")
+        .send()
+        .await
+        .unwrap();
+    assert!(malformed.status().is_client_error());
+    let missing = client
+        .get(format!("{}/v1/state/nope", base))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(missing.status(), reqwest::StatusCode::NOT_FOUND);
+
+    let text = client
+        .get(format!("{}/v1/metrics", base))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(text.contains("requests_rejected_total{reason=\"dim_mismatch\"} 2"));
+    assert!(text.contains("requests_rejected_total{reason=\"malformed_request\"} 1"));
+    assert!(!text.contains("reason=\"not_found\""));
 
-    let search = client
-        .post(format!("{}/v1/vector/docs/search", base))
-        .json(&serde_json::json!({"vector":[0.9,0.1],"k":1,"include_meta":true}))
+    let _ = shutdown.send(());
+}
+
+#[tokio::test]
+async fn info_reports_loaded_data_after_reopen() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut config = base_test_config();
+    config.data_dir = Some(dir.path().to_string_lossy().to_string());
+
+    let engine = Engine::new(config.clone(), CancellationToken::new()).unwrap();
+    for name in ["a", "b"] {
+        engine
+            .create_vector_collection(name, 2, Metric::Cosine)
+            .unwrap();
+    }
+    for i in 0..3 {
+        engine
+            .vector_upsert(
+                "a",
+                &format!("v{i}"),
+                VectorItem {
+                    vector: vec![1.0, i as f32],
+                    meta: serde_json::json!({}),
+                    revision: 0,
+                    expires_at_ms: None,
+                },
+                None,
+            )
+            .unwrap();
+    }
+    engine
+        .put_state("k".to_string(), serde_json::json!(1), None, None)
+        .unwrap();
+    drop(engine);
+
+    let (base, shutdown) = start_with_config(config).await;
+    let info: serde_json::Value = client()
+        .get(format!("{}/v1/info", base))
         .send()
         .await
+        .unwrap()
+        .json()
+        .await
         .unwrap();
-    assert!(search.status().is_success());
-    let v: serde_json::Value = search.json().await.unwrap();
-    assert_eq!(v["hits"][0]["id"], "a");
+    assert_eq!(info["vector_collections"], 2);
+    assert_eq!(info["live_vectors"], 3);
+    assert!(info["state_keys"].as_u64().unwrap() >= 1);
+    assert!(info["offset"].as_u64().unwrap() >= 6);
+
+    let _ = shutdown.send(());
+}
+
+#[tokio::test]
+async fn listing_endpoints_reject_limits_above_max_list_limit() {
+    let mut config = base_test_config();
+    config.max_list_limit = 10;
+    let (base, shutdown) = start_with_config(config).await;
+    let client = client();
+
+    for limit in [10, 11] {
+        let responses = [
+            client
+                .get(format!("{}/v1/state?limit={limit}", base))
+                .send()
+                .await
+                .unwrap(),
+            client
+                .get(format!("{}/v1/admin/ttl?limit={limit}", base))
+                .send()
+                .await
+                .unwrap(),
+            client
+                .post(format!("{}/v1/doc/users/find", base))
+                .json(&serde_json::json!({ "limit": limit }))
+                .send()
+                .await
+                .unwrap(),
+        ];
+        for resp in responses {
+            if limit <= 10 {
+                assert!(resp.status().is_success());
+            } else {
+                assert_eq!(resp.status(), reqwest::StatusCode::BAD_REQUEST);
+                let body: serde_json::Value = resp.json().await.unwrap();
+                assert_eq!(body["error"], "limit_too_large");
+            }
+        }
+    }
 
     let _ = shutdown.send(());
 }
@@ -149,13 +2420,37 @@ fn base_test_config() -> Config {
         run_retention: 4,
         compaction_trigger_tombstone_ratio: 0.2,
         compaction_max_bytes_per_pass: 64 * 1024 * 1024,
+        verify_on_start: false,
+        max_response_bytes: 8 * 1024 * 1024,
+        default_durability: "sync".to_string(),
+        max_vectors_per_collection: 0,
+        embed_cache_size: 256,
+        sse_idle_timeout_secs: 0,
+        value_compress_min_bytes: 0,
+        wal_truncate_after_snapshot: false,
+        max_list_limit: 1000,
+        strict_query_dim: true,
+        sqlite_busy_retries: 3,
+        vector_segment_max_items: 8192,
+        vector_merge_tombstone_ratio: 0.3,
+        ttl_grace_ms: 0,
+        max_index_values_per_field: 0,
+        response_compress_min_bytes: 0,
+        wal_fsync_policy: "data".to_string(),
+        max_doc_indexed_fields: 0,
+        lock_wait_warn_ms: 100,
+        sqlite_readonly: false,
+        sqlite_migrations_dir: None,
+        embed_url: None,
+        embed_model: "default".to_string(),
+        embed_timeout_ms: 10_000,
     }
 }
 
 #[tokio::test]
 async fn vector_list_collections_endpoint() {
     let (base, shutdown) = start().await;
-    let client = reqwest::Client::new();
+    let client = client();
 
     let initial = client
         .get(format!("{}/v1/vector", base))
@@ -189,10 +2484,68 @@ async fn vector_list_collections_endpoint() {
     let _ = shutdown.send(());
 }
 
+#[tokio::test]
+async fn vector_bulk_create_collections() {
+    let (base, shutdown) = start().await;
+    let client = client();
+
+    let created = client
+        .post(format!("{}/v1/vector", base))
+        .json(&serde_json::json!({"collections": [
+            {"name": "a", "dim": 2, "metric": "cosine"},
+            {"name": "b", "dim": 3, "metric": "dot"},
+            {"name": "c", "dim": 4, "metric": "cosine"},
+        ]}))
+        .send()
+        .await
+        .unwrap();
+    assert!(created.status().is_success());
+    let body: serde_json::Value = created.json().await.unwrap();
+    let results = body["results"].as_array().unwrap();
+    assert_eq!(results.len(), 3);
+    assert!(results.iter().all(|r| r["status"] == "created"));
+
+    let listed: serde_json::Value = client
+        .get(format!("{}/v1/vector", base))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let mut names: Vec<&str> = listed["collections"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|c| c["collection"].as_str().unwrap())
+        .collect();
+    names.sort();
+    assert_eq!(names, vec!["a", "b", "c"]);
+
+    let again = client
+        .post(format!("{}/v1/vector", base))
+        .json(&serde_json::json!({"collections": [
+            {"name": "a", "dim": 2, "metric": "cosine", "if_not_exists": true},
+            {"name": "b", "dim": 3, "metric": "dot"},
+            {"name": "c", "dim": 8, "metric": "cosine", "if_not_exists": true},
+        ]}))
+        .send()
+        .await
+        .unwrap();
+    assert!(again.status().is_success());
+    let body: serde_json::Value = again.json().await.unwrap();
+    assert_eq!(body["results"][0]["status"], "exists");
+    assert_eq!(body["results"][1]["status"], "error");
+    assert_eq!(body["results"][1]["error"]["error"], "already_exists");
+    assert_eq!(body["results"][2]["status"], "error");
+
+    let _ = shutdown.send(());
+}
+
 #[tokio::test]
 async fn vector_collection_detail_endpoint() {
     let (base, shutdown) = start().await;
-    let client = reqwest::Client::new();
+    let client = client();
 
     let missing = client
         .get(format!("{}/v1/vector/none", base))
@@ -230,7 +2583,7 @@ async fn vector_collection_detail_endpoint() {
 #[tokio::test]
 async fn docstore_put_get_find() {
     let (base, shutdown) = start().await;
-    let client = reqwest::Client::new();
+    let client = client();
 
     let put = client
         .put(format!("{}/v1/doc/users/u1", base))
@@ -263,10 +2616,154 @@ async fn docstore_put_get_find() {
     let _ = shutdown.send(());
 }
 
+#[tokio::test]
+async fn docstore_schema_rejects_invalid_documents() {
+    let (base, shutdown) = start().await;
+    let client = client();
+
+    let bad_schema = client
+        .post(format!("{}/v1/doc/users/schema", base))
+        .json(&serde_json::json!({"type": 12}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(bad_schema.status(), reqwest::StatusCode::BAD_REQUEST);
+
+    let schema = client
+        .post(format!("{}/v1/doc/users/schema", base))
+        .json(&serde_json::json!({
+            "type": "object",
+            "required": ["name"],
+            "properties": {"name": {"type": "string"}}
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert!(schema.status().is_success());
+
+    let missing = client
+        .put(format!("{}/v1/doc/users/u1", base))
+        .json(&serde_json::json!({"role": "admin"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(missing.status(), reqwest::StatusCode::UNPROCESSABLE_ENTITY);
+    let body: serde_json::Value = missing.json().await.unwrap();
+    assert_eq!(body["error"], "schema_violation");
+    assert!(body["message"].as_str().unwrap().contains("name"));
+
+    let wrong_type = client
+        .put(format!("{}/v1/doc/users/u1", base))
+        .json(&serde_json::json!({"name": 7}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(
+        wrong_type.status(),
+        reqwest::StatusCode::UNPROCESSABLE_ENTITY
+    );
+    let body: serde_json::Value = wrong_type.json().await.unwrap();
+    assert!(body["message"].as_str().unwrap().starts_with("/name:"));
+
+    let valid = client
+        .put(format!("{}/v1/doc/users/u1", base))
+        .json(&serde_json::json!({"name": "Ada", "role": "admin"}))
+        .send()
+        .await
+        .unwrap();
+    assert!(valid.status().is_success());
+
+    // Collections without a schema stay unvalidated.
+    let other = client
+        .put(format!("{}/v1/doc/teams/t1", base))
+        .json(&serde_json::json!({"role": "admin"}))
+        .send()
+        .await
+        .unwrap();
+    assert!(other.status().is_success());
+
+    let _ = shutdown.send(());
+}
+
+#[tokio::test]
+async fn docstore_reindex_repairs_corrupted_index() {
+    let (base, shutdown) = start().await;
+    let client = client();
+
+    for (id, role) in [("u1", "admin"), ("u2", "admin"), ("u3", "viewer")] {
+        let put = client
+            .put(format!("{}/v1/doc/users/{}", base, id))
+            .json(&serde_json::json!({"name": id, "role": role}))
+            .send()
+            .await
+            .unwrap();
+        assert!(put.status().is_success());
+    }
+
+    // Point the admin index at a ghost and leave a stale key behind.
+    for (key, ids) in [
+        ("docidx:users:role:admin", vec!["ghost"]),
+        ("docidx:users:role:owner", vec!["u3"]),
+    ] {
+        let corrupt = client
+            .put(format!("{}/v1/state/{}", base, key))
+            .json(&serde_json::json!({"value": {"ids": ids}}))
+            .send()
+            .await
+            .unwrap();
+        assert!(corrupt.status().is_success());
+    }
+    let find = |role: &'static str| {
+        let client = client.clone();
+        let base = base.clone();
+        async move {
+            let v: serde_json::Value = client
+                .post(format!("{}/v1/doc/users/find", base))
+                .json(&serde_json::json!({"filter": {"role": role}, "limit": 10}))
+                .send()
+                .await
+                .unwrap()
+                .json()
+                .await
+                .unwrap();
+            let mut ids: Vec<String> = v["documents"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|d| d["id"].as_str().unwrap().to_string())
+                .collect();
+            ids.sort();
+            ids
+        }
+    };
+    assert!(find("admin").await.is_empty());
+
+    let reindex = client
+        .post(format!("{}/v1/doc/users/reindex", base))
+        .send()
+        .await
+        .unwrap();
+    assert!(reindex.status().is_success());
+    let report: serde_json::Value = reindex.json().await.unwrap();
+    assert_eq!(report["documents"], 3);
+    assert_eq!(report["repaired"], 2);
+
+    assert_eq!(find("admin").await, vec!["u1", "u2"]);
+    assert_eq!(find("viewer").await, vec!["u3"]);
+    let stale = client
+        .get(format!("{}/v1/state/docidx:users:role:owner", base))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(stale.status(), reqwest::StatusCode::NOT_FOUND);
+
+    let _ = shutdown.send(());
+}
+
 #[tokio::test]
 async fn vector_diskann_build_status_and_tune() {
     let (base, shutdown, _dir) = start_with_diskann().await;
-    let client = reqwest::Client::new();
+    let client = client();
 
     let create = client
         .post(format!("{}/v1/vector/docs", base))
@@ -318,3 +2815,362 @@ async fn vector_diskann_build_status_and_tune() {
 
     let _ = shutdown.send(());
 }
+
+#[tokio::test]
+async fn vector_search_offset_pages_through_ranked_hits() {
+    let mut config = base_test_config();
+    config.index_kind = "HNSW".to_string();
+    let (base, shutdown) = start_with_config(config).await;
+    let client = client();
+
+    let create = client
+        .post(format!("{}/v1/vector/paged", base))
+        .json(&serde_json::json!({"dim":2,"metric":"cosine"}))
+        .send()
+        .await
+        .unwrap();
+    assert!(create.status().is_success());
+    let items: Vec<serde_json::Value> = (0..5)
+        .map(|i| {
+            let angle = i as f32 * 0.3;
+            serde_json::json!({
+                "id": format!("p{i}"),
+                "vector": [angle.cos(), angle.sin()],
+                "meta": {"tag": "t"},
+            })
+        })
+        .collect();
+    let batch = client
+        .post(format!("{}/v1/vector/paged/upsert_batch", base))
+        .json(&serde_json::json!({ "items": items }))
+        .send()
+        .await
+        .unwrap();
+    assert!(batch.status().is_success());
+
+    // Unfiltered goes through HNSW, the tag filter through the exact subset path.
+    for filters in [serde_json::Value::Null, serde_json::json!({"tag": "t"})] {
+        let mut pages = Vec::new();
+        for offset in [0, 2, 4, 6] {
+            let body: serde_json::Value = client
+                .post(format!("{}/v1/vector/paged/search", base))
+                .json(&serde_json::json!({
+                    "vector": [1.0, 0.0],
+                    "k": 2,
+                    "offset": offset,
+                    "filters": filters,
+                }))
+                .send()
+                .await
+                .unwrap()
+                .json()
+                .await
+                .unwrap();
+            let ids: Vec<String> = body["hits"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|h| h["id"].as_str().unwrap().to_string())
+                .collect();
+            pages.push(ids);
+        }
+        assert_eq!(
+            pages,
+            vec![
+                vec!["p0".to_string(), "p1".to_string()],
+                vec!["p2".to_string(), "p3".to_string()],
+                vec!["p4".to_string()],
+                Vec::new(),
+            ],
+            "{filters}"
+        );
+    }
+
+    let _ = shutdown.send(());
+}
+
+#[tokio::test]
+async fn vector_vacuum_endpoint_drops_tombstones_online() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut config = base_test_config();
+    config.data_dir = Some(dir.path().to_string_lossy().to_string());
+    let (base, shutdown) = start_with_config(config).await;
+    let client = client();
+
+    let create = client
+        .post(format!("{}/v1/vector/vac", base))
+        .json(&serde_json::json!({"dim":2,"metric":"cosine"}))
+        .send()
+        .await
+        .unwrap();
+    assert!(create.status().is_success());
+    let items: Vec<serde_json::Value> = (0..20)
+        .map(|i| serde_json::json!({"id": format!("v{i}"), "vector": [1.0, i as f32]}))
+        .collect();
+    let batch = client
+        .post(format!("{}/v1/vector/vac/upsert_batch", base))
+        .json(&serde_json::json!({ "items": items }))
+        .send()
+        .await
+        .unwrap();
+    assert!(batch.status().is_success());
+    let ids: Vec<String> = (0..10).map(|i| format!("v{i}")).collect();
+    let deleted = client
+        .post(format!("{}/v1/vector/vac/delete_batch", base))
+        .json(&serde_json::json!({ "ids": ids }))
+        .send()
+        .await
+        .unwrap();
+    assert!(deleted.status().is_success());
+    let before: serde_json::Value = client
+        .get(format!("{}/v1/vector/vac/stats", base))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+
+    let resp = client
+        .post(format!("{}/v1/vector/vac/vacuum", base))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::OK);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["collection"], "vac");
+    assert_eq!(body["live_count"], 10);
+    assert!(body["file_len"].as_u64().unwrap() < before["file_len"].as_u64().unwrap());
+
+    let stats: serde_json::Value = client
+        .get(format!("{}/v1/vector/vac/stats", base))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(stats["total_records"], 10);
+    let search: serde_json::Value = client
+        .post(format!("{}/v1/vector/vac/search", base))
+        .json(&serde_json::json!({"vector":[1.0, 15.0],"k":1}))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(search["hits"][0]["id"], "v15");
+
+    let missing = client
+        .post(format!("{}/v1/vector/nope/vacuum", base))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(missing.status(), reqwest::StatusCode::NOT_FOUND);
+
+    let _ = shutdown.send(());
+}
+
+#[tokio::test]
+async fn vector_get_batch_returns_found_ids_only() {
+    let (base, shutdown) = start().await;
+    let client = client();
+
+    let create = client
+        .post(format!("{}/v1/vector/hydrate", base))
+        .json(&serde_json::json!({"dim":2,"metric":"cosine"}))
+        .send()
+        .await
+        .unwrap();
+    assert!(create.status().is_success());
+    let batch = client
+        .post(format!("{}/v1/vector/hydrate/upsert_batch", base))
+        .json(&serde_json::json!({"items": [
+            {"id": "a", "vector": [1.0, 0.0], "meta": {"n": 1}},
+            {"id": "b", "vector": [0.0, 1.0], "meta": {"n": 2}},
+        ]}))
+        .send()
+        .await
+        .unwrap();
+    assert!(batch.status().is_success());
+
+    let body: serde_json::Value = client
+        .post(format!("{}/v1/vector/hydrate/get_batch", base))
+        .json(&serde_json::json!({"ids": ["b", "missing", "a"]}))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let items = body["items"].as_object().unwrap();
+    assert_eq!(items.len(), 2);
+    assert_eq!(items["a"]["vector"], serde_json::json!([1.0, 0.0]));
+    assert_eq!(items["b"]["meta"], serde_json::json!({"n": 2}));
+    assert_eq!(items["b"]["revision"], 1);
+
+    let too_many: Vec<String> = (0..257).map(|i| format!("v{i}")).collect();
+    for ids in [
+        serde_json::json!([]),
+        serde_json::json!(too_many),
+        serde_json::json!(["x".repeat(129)]),
+    ] {
+        let resp = client
+            .post(format!("{}/v1/vector/hydrate/get_batch", base))
+            .json(&serde_json::json!({ "ids": ids }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), reqwest::StatusCode::BAD_REQUEST);
+    }
+    let missing = client
+        .post(format!("{}/v1/vector/nope/get_batch", base))
+        .json(&serde_json::json!({"ids": ["a"]}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(missing.status(), reqwest::StatusCode::NOT_FOUND);
+
+    let _ = shutdown.send(());
+}
+
+#[tokio::test]
+async fn vector_search_debug_returns_query_stats() {
+    let (base, shutdown) = start().await;
+    let client = client();
+
+    client
+        .post(format!("{}/v1/vector/tuning", base))
+        .json(&serde_json::json!({"dim":2,"metric":"cosine"}))
+        .send()
+        .await
+        .unwrap();
+    for (id, vector) in [("a", [1.0, 0.0]), ("b", [0.0, 1.0]), ("c", [0.7, 0.7])] {
+        let resp = client
+            .post(format!("{}/v1/vector/tuning/upsert", base))
+            .json(&serde_json::json!({"id": id, "vector": vector}))
+            .send()
+            .await
+            .unwrap();
+        assert!(resp.status().is_success());
+    }
+    let search = |path: &'static str, body: serde_json::Value| {
+        client
+            .post(format!("{}/v1/vector/tuning/{}", base, path))
+            .json(&body)
+            .send()
+    };
+
+    let plain: serde_json::Value =
+        search("search", serde_json::json!({"vector": [1.0, 0.0], "k": 2}))
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+    assert_eq!(plain["hits"].as_array().unwrap().len(), 2);
+    assert!(plain.get("stats").is_none(), "{plain}");
+
+    let debug: serde_json::Value = search(
+        "search?debug=true",
+        serde_json::json!({"vector": [1.0, 0.0], "k": 2}),
+    )
+    .await
+    .unwrap()
+    .json()
+    .await
+    .unwrap();
+    assert_eq!(debug["hits"], plain["hits"]);
+    let stats = &debug["stats"];
+    assert_eq!(stats["segments_probed"], 1);
+    assert!(stats["candidates"].as_u64().unwrap() >= 2, "{stats}");
+    assert_eq!(stats["bruteforce"], false);
+    assert!(stats["elapsed_us"].is_u64());
+
+    let exact: serde_json::Value = search(
+        "search?debug=true",
+        serde_json::json!({"vector": [1.0, 0.0], "k": 2, "exact": true}),
+    )
+    .await
+    .unwrap()
+    .json()
+    .await
+    .unwrap();
+    assert_eq!(exact["stats"]["bruteforce"], true);
+    assert_eq!(exact["stats"]["candidates"], 3);
+    assert_eq!(exact["stats"]["segments_probed"], 0);
+
+    let _ = shutdown.send(());
+}
+
+#[tokio::test]
+async fn http_embedder_from_config_builds_inside_the_runtime() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let app = axum::Router::new().route(
+        "/embed",
+        axum::routing::post(|| async { axum::Json(serde_json::json!([1.0, 0.0])) }),
+    );
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+    let dir = tempfile::tempdir().unwrap();
+    let mut config = base_test_config();
+    config.data_dir = Some(dir.path().to_string_lossy().to_string());
+    config.embed_url = Some(format!("http://{addr}/embed"));
+
+    let embedder = embedder::from_config(&config).unwrap();
+    let vector = tokio::task::spawn_blocking(move || embedder.embed("north", 2))
+        .await
+        .unwrap();
+    assert_eq!(vector, vec![1.0, 0.0]);
+    assert!(SearchEngine::from_config(&config).is_ok());
+}
+
+#[tokio::test]
+async fn search_delete_returns_not_found_for_unknown_ids() {
+    let config = base_test_config();
+    let engine = Engine::new(config.clone(), CancellationToken::new()).unwrap();
+    // Kept alive for the whole test: the document log lives here.
+    let search_dir = tempfile::tempdir().unwrap();
+    let search_engine = Arc::new(SearchEngine::new(search_dir.path().to_path_buf()).unwrap());
+    let app = api::router(engine, config, None, search_engine, None);
+    let listener = tokio::net::TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0)))
+        .await
+        .unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        let _ = axum::serve(listener, app).await;
+    });
+    let client = client();
+
+    let ingest = client
+        .post(format!("{}/search/ingest", base))
+        .json(&serde_json::json!({"document": {
+            "id": 7,
+            "vector": vec![0.5f32; 384],
+            "content": "north",
+            "metadata": {},
+        }}))
+        .send()
+        .await
+        .unwrap();
+    assert!(ingest.status().is_success());
+
+    let delete = |id: u32| {
+        let client = client.clone();
+        let url = format!("{}/v1/search/{}", base, id);
+        async move { client.delete(url).send().await.unwrap() }
+    };
+    let first = delete(7).await;
+    assert_eq!(first.status(), reqwest::StatusCode::OK);
+    let body: serde_json::Value = first.json().await.unwrap();
+    assert_eq!(body["deleted"], true);
+
+    for id in [7, 8] {
+        let missing = delete(id).await;
+        assert_eq!(missing.status(), reqwest::StatusCode::NOT_FOUND, "{id}");
+        let body: serde_json::Value = missing.json().await.unwrap();
+        assert_eq!(body["error"], "not_found");
+    }
+}
@@ -1,5 +1,81 @@
 use rust_kiss_vdb::config::Config;
-use rust_kiss_vdb::engine::Engine;
+use rust_kiss_vdb::docstore;
+use rust_kiss_vdb::engine::{
+    Durability, Engine, EngineError, EventRecord, MetricsSnapshot, StateError, StateOp,
+    WalFsyncPolicy,
+};
+use rust_kiss_vdb::vector::{Metric, VectorItem};
+use tokio_util::sync::CancellationToken;
+
+fn test_config(data_dir: Option<String>) -> Config {
+    Config {
+        port: 0,
+        bind_addr: "127.0.0.1".parse().unwrap(),
+        api_key: "test".to_string(),
+        data_dir,
+        snapshot_interval_secs: 3600,
+        event_buffer_size: 1000,
+        live_broadcast_capacity: 1024,
+        wal_segment_max_bytes: 256 * 1024,
+        wal_retention_segments: 16,
+        request_timeout_secs: 30,
+        max_body_bytes: 1_048_576,
+        max_key_len: 512,
+        max_collection_len: 64,
+        max_id_len: 128,
+        max_vector_dim: 4096,
+        max_k: 256,
+        max_json_bytes: 64 * 1024,
+        max_state_batch: 256,
+        max_vector_batch: 256,
+        max_doc_find: 100,
+        cors_allowed_origins: None,
+        sqlite_enabled: false,
+        sqlite_path: None,
+        search_threads: 0,
+        parallel_probe: true,
+        parallel_probe_min_segments: 4,
+        simd_enabled: true,
+        index_kind: "IVF_FLAT_Q8".to_string(),
+        ivf_clusters: 64,
+        ivf_nprobe: 8,
+        ivf_training_sample: 1024,
+        ivf_min_train_vectors: 64,
+        ivf_retrain_min_deltas: 32,
+        q8_refine_topk: 256,
+        diskann_max_degree: 32,
+        diskann_build_threads: 1,
+        diskann_search_list_size: 64,
+        run_target_bytes: 8 * 1024 * 1024,
+        run_retention: 4,
+        compaction_trigger_tombstone_ratio: 0.2,
+        compaction_max_bytes_per_pass: 64 * 1024 * 1024,
+        verify_on_start: false,
+        max_response_bytes: 8 * 1024 * 1024,
+        default_durability: "sync".to_string(),
+        max_vectors_per_collection: 0,
+        embed_cache_size: 256,
+        sse_idle_timeout_secs: 0,
+        value_compress_min_bytes: 0,
+        wal_truncate_after_snapshot: false,
+        max_list_limit: 1000,
+        strict_query_dim: true,
+        sqlite_busy_retries: 3,
+        vector_segment_max_items: 8192,
+        vector_merge_tombstone_ratio: 0.3,
+        ttl_grace_ms: 0,
+        max_index_values_per_field: 0,
+        response_compress_min_bytes: 0,
+        wal_fsync_policy: "data".to_string(),
+        max_doc_indexed_fields: 0,
+        lock_wait_warn_ms: 100,
+        sqlite_readonly: false,
+        sqlite_migrations_dir: None,
+        embed_url: None,
+        embed_model: "default".to_string(),
+        embed_timeout_ms: 10_000,
+    }
+}
 
 #[tokio::test]
 async fn snapshot_and_wal_replay_no_loss() {
@@ -48,9 +124,33 @@ async fn snapshot_and_wal_replay_no_loss() {
         run_retention: 4,
         compaction_trigger_tombstone_ratio: 0.2,
         compaction_max_bytes_per_pass: 64 * 1024 * 1024,
+        verify_on_start: false,
+        max_response_bytes: 8 * 1024 * 1024,
+        default_durability: "sync".to_string(),
+        max_vectors_per_collection: 0,
+        embed_cache_size: 256,
+        sse_idle_timeout_secs: 0,
+        value_compress_min_bytes: 0,
+        wal_truncate_after_snapshot: false,
+        max_list_limit: 1000,
+        strict_query_dim: true,
+        sqlite_busy_retries: 3,
+        vector_segment_max_items: 8192,
+        vector_merge_tombstone_ratio: 0.3,
+        ttl_grace_ms: 0,
+        max_index_values_per_field: 0,
+        response_compress_min_bytes: 0,
+        wal_fsync_policy: "data".to_string(),
+        max_doc_indexed_fields: 0,
+        lock_wait_warn_ms: 100,
+        sqlite_readonly: false,
+        sqlite_migrations_dir: None,
+        embed_url: None,
+        embed_model: "default".to_string(),
+        embed_timeout_ms: 10_000,
     };
 
-    let engine = Engine::new(config.clone()).unwrap();
+    let engine = Engine::new(config.clone(), CancellationToken::new()).unwrap();
 
     for i in 0..200u32 {
         engine
@@ -68,7 +168,7 @@ async fn snapshot_and_wal_replay_no_loss() {
 
     drop(engine);
 
-    let engine2 = Engine::new(config).unwrap();
+    let engine2 = Engine::new(config, CancellationToken::new()).unwrap();
     for i in 0..400u32 {
         let item = engine2.get_state(&format!("k:{i}")).unwrap();
         assert_eq!(item.value["i"], i);
@@ -122,9 +222,33 @@ async fn state_survives_restart_without_wal() {
         run_retention: 4,
         compaction_trigger_tombstone_ratio: 0.2,
         compaction_max_bytes_per_pass: 64 * 1024 * 1024,
+        verify_on_start: false,
+        max_response_bytes: 8 * 1024 * 1024,
+        default_durability: "sync".to_string(),
+        max_vectors_per_collection: 0,
+        embed_cache_size: 256,
+        sse_idle_timeout_secs: 0,
+        value_compress_min_bytes: 0,
+        wal_truncate_after_snapshot: false,
+        max_list_limit: 1000,
+        strict_query_dim: true,
+        sqlite_busy_retries: 3,
+        vector_segment_max_items: 8192,
+        vector_merge_tombstone_ratio: 0.3,
+        ttl_grace_ms: 0,
+        max_index_values_per_field: 0,
+        response_compress_min_bytes: 0,
+        wal_fsync_policy: "data".to_string(),
+        max_doc_indexed_fields: 0,
+        lock_wait_warn_ms: 100,
+        sqlite_readonly: false,
+        sqlite_migrations_dir: None,
+        embed_url: None,
+        embed_model: "default".to_string(),
+        embed_timeout_ms: 10_000,
     };
 
-    let engine = Engine::new(config.clone()).unwrap();
+    let engine = Engine::new(config.clone(), CancellationToken::new()).unwrap();
     for i in 0..2000u32 {
         engine
             .put_state(
@@ -146,9 +270,1191 @@ async fn state_survives_restart_without_wal() {
         }
     }
 
-    let engine2 = Engine::new(config).unwrap();
+    let engine2 = Engine::new(config, CancellationToken::new()).unwrap();
     for i in 0..2000u32 {
         let item = engine2.get_state(&format!("big:{i}")).unwrap();
         assert_eq!(item.value["i"], i);
     }
 }
+
+#[tokio::test]
+async fn wal_truncation_after_snapshot_drops_dead_segments() {
+    let dir = tempfile::tempdir().unwrap();
+    let data_dir = dir.path().to_string_lossy().to_string();
+    let mut config = test_config(Some(data_dir.clone()));
+    config.wal_truncate_after_snapshot = true;
+
+    let segments = || {
+        let mut names: Vec<String> = std::fs::read_dir(&data_dir)
+            .unwrap()
+            .flatten()
+            .filter_map(|e| e.file_name().to_str().map(str::to_string))
+            .filter(|name| name.starts_with("events-"))
+            .collect();
+        names.sort();
+        names
+    };
+
+    let engine = Engine::new(config.clone(), CancellationToken::new()).unwrap();
+    for round in 0..3u32 {
+        for i in 0..100u32 {
+            let n = round * 100 + i;
+            engine
+                .put_state(format!("k:{n}"), serde_json::json!({ "n": n }), None, None)
+                .unwrap();
+        }
+        if round < 2 {
+            engine.force_snapshot().unwrap();
+        }
+    }
+    assert_eq!(segments(), vec!["events-000003.log".to_string()]);
+    drop(engine);
+
+    let engine2 = Engine::new(config, CancellationToken::new()).unwrap();
+    for n in 0..300u32 {
+        let item = engine2.get_state(&format!("k:{n}")).unwrap();
+        assert_eq!(item.value["n"], n);
+    }
+}
+
+#[test]
+fn compact_wal_keeps_data_and_checks_it_on_restart() {
+    let dir = tempfile::tempdir().unwrap();
+    let data_dir = dir.path().to_string_lossy().to_string();
+    let config = test_config(Some(data_dir.clone()));
+    let segments = || {
+        std::fs::read_dir(&data_dir)
+            .unwrap()
+            .flatten()
+            .filter(|e| e.file_name().to_string_lossy().starts_with("events-"))
+            .count()
+    };
+
+    let engine = Engine::new(config.clone(), CancellationToken::new()).unwrap();
+    engine
+        .create_vector_collection("docs", 2, Metric::Cosine)
+        .unwrap();
+    for n in 0..50u32 {
+        engine
+            .put_state(format!("k:{n}"), serde_json::json!(n), None, None)
+            .unwrap();
+        engine
+            .vector_upsert(
+                "docs",
+                &format!("v{n}"),
+                VectorItem {
+                    vector: vec![1.0, n as f32],
+                    meta: serde_json::json!({}),
+                    revision: 0,
+                    expires_at_ms: None,
+                },
+                None,
+            )
+            .unwrap();
+        if n % 20 == 0 {
+            engine.force_snapshot().unwrap();
+        }
+    }
+    assert!(segments() > 1);
+
+    let compaction = engine.compact_wal().unwrap().unwrap();
+    assert_eq!(
+        compaction.last_offset,
+        engine.events().last_published_offset()
+    );
+    assert!(compaction.removed_segments > 0);
+    assert_eq!(segments(), 1);
+    let snapshot: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(dir.path().join("snapshot.json")).unwrap()).unwrap();
+    assert_eq!(
+        snapshot["checkpoint"]["state_keys"],
+        engine.summary().state_keys
+    );
+    assert_eq!(snapshot["checkpoint"]["vectors"][0]["live_count"], 50);
+    engine
+        .put_state("after".into(), serde_json::json!(1), None, None)
+        .unwrap();
+    drop(engine);
+
+    let engine = Engine::new(config.clone(), CancellationToken::new()).unwrap();
+    assert_eq!(
+        engine.get_state("k:49").unwrap().value,
+        serde_json::json!(49)
+    );
+    assert!(engine.get_state("after").is_some());
+    assert_eq!(
+        engine.vector_collection_stats("docs").unwrap().live_count,
+        50
+    );
+    drop(engine);
+
+    // The compacted WAL can no longer rebuild a collection lost from disk.
+    std::fs::remove_dir_all(dir.path().join("vectors").join("docs")).unwrap();
+    let err = Engine::new(config, CancellationToken::new()).err().unwrap();
+    assert!(format!("{err:#}").contains("vector collection docs missing"));
+
+    let memory = Engine::new(test_config(None), CancellationToken::new()).unwrap();
+    assert!(memory.compact_wal().unwrap().is_none());
+}
+
+#[tokio::test]
+async fn ttl_entries_from_state_db_are_ordered_by_expiry() {
+    let dir = tempfile::tempdir().unwrap();
+    let data_dir = dir.path().to_string_lossy().to_string();
+
+    let config = test_config(Some(data_dir.clone()));
+
+    let engine = Engine::new(config, CancellationToken::new()).unwrap();
+    engine
+        .put_state("b".into(), serde_json::json!(1), Some(90_000), None)
+        .unwrap();
+    engine
+        .put_state("a".into(), serde_json::json!(1), Some(10_000), None)
+        .unwrap();
+    engine
+        .put_state("c".into(), serde_json::json!(1), None, None)
+        .unwrap();
+
+    let entries = engine.list_ttl(10);
+    let keys: Vec<&str> = entries.iter().map(|e| e.key.as_str()).collect();
+    assert_eq!(keys, vec!["a", "b"]);
+    assert!(entries[0].expires_at_ms < entries[1].expires_at_ms);
+    assert!(entries[0].ttl_remaining_ms <= 10_000 && entries[0].ttl_remaining_ms > 5_000);
+    assert!(entries[1].ttl_remaining_ms <= 90_000 && entries[1].ttl_remaining_ms > 85_000);
+}
+
+#[test]
+fn async_durability_puts_are_buffered_until_flush() {
+    let dir = tempfile::tempdir().unwrap();
+    let data_dir = dir.path().to_string_lossy().to_string();
+
+    let config = test_config(Some(data_dir.clone()));
+
+    // No tokio runtime here, so the background flush task is not running and the
+    // buffer only drains on an explicit flush.
+    let engine = Engine::new(config.clone(), CancellationToken::new()).unwrap();
+    let put = |prefix: &str, i: u32, durability: Durability| {
+        engine
+            .put_state_with_durability(
+                format!("{prefix}:{i}"),
+                serde_json::json!({ "i": i }),
+                None,
+                None,
+                durability,
+            )
+            .unwrap();
+    };
+
+    let started = std::time::Instant::now();
+    for i in 0..200u32 {
+        put("sync", i, Durability::Sync);
+    }
+    let sync_elapsed = started.elapsed();
+    let started = std::time::Instant::now();
+    for i in 0..200u32 {
+        put("async", i, Durability::Async);
+    }
+    let async_elapsed = started.elapsed();
+    // Async puts skip both the WAL fsync and the durable state db commit.
+    assert!(
+        async_elapsed < sync_elapsed,
+        "async {async_elapsed:?} vs sync {sync_elapsed:?}"
+    );
+
+    let wal_contains = |needle: &str| {
+        std::fs::read_dir(&data_dir)
+            .unwrap()
+            .flatten()
+            .filter(|e| e.file_name().to_string_lossy().starts_with("events-"))
+            .any(|e| std::fs::read_to_string(e.path()).unwrap().contains(needle))
+    };
+    assert_eq!(engine.get_state("async:199").unwrap().value["i"], 199);
+    assert!(wal_contains("\"sync:199\""));
+    assert!(!wal_contains("\"async:0\""));
+
+    assert_eq!(engine.flush_wal().unwrap(), 200);
+    assert!(wal_contains("\"async:199\""));
+    assert_eq!(engine.flush_wal().unwrap(), 0);
+
+    let mut offsets = Vec::new();
+    engine
+        .persist()
+        .unwrap()
+        .for_each_event_since(0, |ev| {
+            offsets.push(ev.offset);
+            true
+        })
+        .unwrap();
+    assert_eq!(offsets.len(), 400);
+    assert!(offsets.windows(2).all(|w| w[0] < w[1]));
+
+    drop(engine);
+
+    let engine = Engine::new(config, CancellationToken::new()).unwrap();
+    assert_eq!(engine.get_state("async:199").unwrap().value["i"], 199);
+    assert_eq!(engine.get_state("sync:0").unwrap().value["i"], 0);
+}
+
+#[test]
+fn unknown_default_durability_is_rejected() {
+    let mut config = test_config(None);
+    config.default_durability = "eventually".to_string();
+    let err = Engine::new(config, CancellationToken::new()).err().unwrap();
+    assert!(err.to_string().contains("DEFAULT_DURABILITY"), "{err}");
+}
+
+#[test]
+fn drain_makes_buffered_writes_durable_and_refuses_new_ones() {
+    let dir = tempfile::tempdir().unwrap();
+    let data_dir = dir.path().to_string_lossy().to_string();
+    let config = test_config(Some(data_dir.clone()));
+
+    let engine = Engine::new(config.clone(), CancellationToken::new()).unwrap();
+    engine
+        .put_state_with_durability(
+            "inflight".to_string(),
+            serde_json::json!({ "v": 1 }),
+            None,
+            None,
+            Durability::Async,
+        )
+        .unwrap();
+    let wal_contains = |needle: &str| {
+        std::fs::read_dir(&data_dir)
+            .unwrap()
+            .flatten()
+            .filter(|e| e.file_name().to_string_lossy().starts_with("events-"))
+            .any(|e| std::fs::read_to_string(e.path()).unwrap().contains(needle))
+    };
+    assert!(!wal_contains("\"inflight\""));
+
+    engine.drain().unwrap();
+    assert!(engine.is_draining());
+    assert!(wal_contains("\"inflight\""));
+    let refused = engine.put_state("late".to_string(), serde_json::json!(2), None, None);
+    assert!(matches!(refused, Err(EngineError::ShuttingDown)));
+    drop(engine);
+
+    let engine2 = Engine::new(config, CancellationToken::new()).unwrap();
+    assert_eq!(engine2.get_state("inflight").unwrap().value["v"], 1);
+    assert!(engine2.get_state("late").is_none());
+}
+
+#[test]
+fn drain_cancels_background_tasks_when_the_flush_fails() {
+    let dir = tempfile::tempdir().unwrap();
+    let data_dir = dir.path().to_string_lossy().to_string();
+    let shutdown = CancellationToken::new();
+    let engine = Engine::new(test_config(Some(data_dir.clone())), shutdown.clone()).unwrap();
+    engine
+        .put_state("durable".to_string(), serde_json::json!(0), None, None)
+        .unwrap();
+    engine
+        .put_state_with_durability(
+            "inflight".to_string(),
+            serde_json::json!(1),
+            None,
+            None,
+            Durability::Async,
+        )
+        .unwrap();
+    // A directory where the active segment should be makes the flush fail.
+    for entry in std::fs::read_dir(&data_dir).unwrap().flatten() {
+        if entry.file_name().to_string_lossy().starts_with("events-") {
+            std::fs::remove_file(entry.path()).unwrap();
+            std::fs::create_dir(entry.path()).unwrap();
+        }
+    }
+
+    assert!(engine.drain().is_err());
+    assert!(shutdown.is_cancelled());
+    assert!(engine.is_draining());
+}
+
+#[tokio::test]
+async fn rename_state_moves_value_to_fresh_revision() {
+    let dir = tempfile::tempdir().unwrap();
+    let data_dir = dir.path().to_string_lossy().to_string();
+
+    let config = test_config(Some(data_dir.clone()));
+
+    let engine = Engine::new(config.clone(), CancellationToken::new()).unwrap();
+    for v in 1..=3 {
+        engine
+            .put_state("old".into(), serde_json::json!({ "v": v }), None, None)
+            .unwrap();
+    }
+    engine
+        .put_state("taken".into(), serde_json::json!(0), None, None)
+        .unwrap();
+
+    assert!(matches!(
+        engine.rename_state("missing", "new", false),
+        Err(EngineError::State(StateError::NotFound))
+    ));
+    assert!(matches!(
+        engine.rename_state("old", "taken", false),
+        Err(EngineError::State(StateError::AlreadyExists))
+    ));
+    assert!(matches!(
+        engine.rename_state("old", "old", true),
+        Err(EngineError::State(StateError::AlreadyExists))
+    ));
+
+    let mut rx = engine.events().subscribe();
+    let item = engine.rename_state("old", "new", false).unwrap();
+    assert_eq!(item.key, "new");
+    assert_eq!(item.value["v"], 3);
+    assert_eq!(item.revision, 1);
+    assert!(engine.get_state("old").is_none());
+    let put = rx.recv().await.unwrap();
+    let del = rx.recv().await.unwrap();
+    assert_eq!(put.event_type, "state_updated");
+    assert_eq!(del.event_type, "state_deleted");
+    assert_eq!(del.data["reason"], "rename");
+
+    let item = engine.rename_state("new", "taken", true).unwrap();
+    assert_eq!(item.value["v"], 3);
+    assert_eq!(item.revision, 2);
+    drop(rx);
+    drop(engine);
+
+    let engine2 = Engine::new(config, CancellationToken::new()).unwrap();
+    assert!(engine2.get_state("new").is_none());
+    assert_eq!(engine2.get_state("taken").unwrap().value["v"], 3);
+}
+
+#[test]
+fn rename_state_cut_after_the_put_replays_as_no_rename() {
+    let newest_segment = |data_dir: &str| {
+        std::fs::read_dir(data_dir)
+            .unwrap()
+            .flatten()
+            .map(|e| e.path())
+            .filter(|p| {
+                p.file_name()
+                    .unwrap()
+                    .to_string_lossy()
+                    .starts_with("events-")
+            })
+            .max()
+            .unwrap()
+    };
+    let seed = |data_dir: &str| {
+        let engine = Engine::new(
+            test_config(Some(data_dir.to_string())),
+            CancellationToken::new(),
+        )
+        .unwrap();
+        engine
+            .put_state("old".into(), serde_json::json!(1), None, None)
+            .unwrap();
+        engine
+    };
+
+    // Capture the WAL lines of a real rename.
+    let renamed = tempfile::tempdir().unwrap();
+    let renamed_dir = renamed.path().to_string_lossy().to_string();
+    let engine = seed(&renamed_dir);
+    engine.rename_state("old", "new", false).unwrap();
+    drop(engine);
+    let wal = std::fs::read_to_string(newest_segment(&renamed_dir)).unwrap();
+    let lines: Vec<&str> = wal.lines().collect();
+    let put = lines[lines.len() - 2];
+    assert!(put.contains("\"state_updated\""), "{put}");
+
+    // The same history, crashed right after the put line reached the WAL.
+    let cut = tempfile::tempdir().unwrap();
+    let cut_dir = cut.path().to_string_lossy().to_string();
+    drop(seed(&cut_dir));
+    let mut segment = std::fs::OpenOptions::new()
+        .append(true)
+        .open(newest_segment(&cut_dir))
+        .unwrap();
+    std::io::Write::write_all(&mut segment, format!("{put}\n").as_bytes()).unwrap();
+    drop(segment);
+
+    let engine = Engine::new(test_config(Some(cut_dir)), CancellationToken::new()).unwrap();
+    assert_eq!(engine.get_state("old").unwrap().value, 1);
+    assert!(engine.get_state("new").is_none());
+}
+
+#[test]
+fn delete_state_if_revision_only_deletes_the_expected_revision() {
+    let dir = tempfile::tempdir().unwrap();
+    let data_dir = dir.path().to_string_lossy().to_string();
+    for config in [test_config(None), test_config(Some(data_dir))] {
+        let engine = Engine::new(config, CancellationToken::new()).unwrap();
+        engine
+            .put_state("k".into(), serde_json::json!(1), None, None)
+            .unwrap();
+        engine
+            .put_state("k".into(), serde_json::json!(2), None, Some(1))
+            .unwrap();
+
+        assert!(matches!(
+            engine.delete_state_with_reason("k", "explicit", Some(1)),
+            Err(EngineError::State(StateError::RevisionMismatch))
+        ));
+        assert_eq!(engine.get_state("k").unwrap().revision, 2);
+        assert!(engine
+            .delete_state_with_reason("k", "explicit", Some(2))
+            .unwrap());
+        assert!(engine.get_state("k").is_none());
+        assert!(matches!(
+            engine.delete_state_with_reason("k", "explicit", Some(2)),
+            Err(EngineError::State(StateError::RevisionMismatch))
+        ));
+    }
+}
+
+#[test]
+fn increment_state_counts_the_same_in_memory_and_on_disk() {
+    let dir = tempfile::tempdir().unwrap();
+    let data_dir = dir.path().to_string_lossy().to_string();
+    let mut runs = Vec::new();
+    for config in [test_config(None), test_config(Some(data_dir.clone()))] {
+        let engine = Engine::new(config, CancellationToken::new()).unwrap();
+        engine
+            .put_state("label".into(), serde_json::json!("x"), None, None)
+            .unwrap();
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                let engine = engine.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..25 {
+                        engine.increment_state("hits", 2, None).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for t in threads {
+            t.join().unwrap();
+        }
+        let hits = engine.increment_state("hits", -1, Some(60_000)).unwrap();
+        let label = engine.increment_state("label", 5, None).unwrap();
+        runs.push((
+            hits.value.clone(),
+            hits.revision,
+            label.value,
+            label.revision,
+        ));
+        assert!(hits.expires_at_ms.is_some());
+        assert_eq!(engine.get_state("hits").unwrap().value, hits.value);
+    }
+    assert_eq!(
+        runs[0],
+        (serde_json::json!(199), 101, serde_json::json!(5), 2)
+    );
+    assert_eq!(runs[0], runs[1]);
+
+    let engine = Engine::new(test_config(Some(data_dir)), CancellationToken::new()).unwrap();
+    let hits = engine.get_state("hits").unwrap();
+    assert_eq!((hits.value, hits.revision), (serde_json::json!(199), 101));
+}
+
+#[test]
+fn touch_state_keeps_the_value_and_survives_restart() {
+    let dir = tempfile::tempdir().unwrap();
+    let data_dir = dir.path().to_string_lossy().to_string();
+    let mut touched_expiry = None;
+    for config in [test_config(None), test_config(Some(data_dir.clone()))] {
+        let engine = Engine::new(config, CancellationToken::new()).unwrap();
+        let put = engine
+            .put_state("lease".into(), serde_json::json!("w1"), Some(5_000), None)
+            .unwrap();
+        let touched = engine.touch_state("lease", 600_000).unwrap().unwrap();
+        assert_eq!(touched.value, serde_json::json!("w1"));
+        assert_eq!(touched.revision, 2);
+        assert!(touched.expires_at_ms > put.expires_at_ms);
+        assert!(engine.touch_state("missing", 1_000).unwrap().is_none());
+        touched_expiry = touched.expires_at_ms;
+    }
+
+    let engine = Engine::new(test_config(Some(data_dir)), CancellationToken::new()).unwrap();
+    let lease = engine.get_state("lease").unwrap();
+    assert_eq!(lease.revision, 2);
+    assert_eq!(lease.expires_at_ms, touched_expiry);
+}
+
+#[test]
+fn delete_prefix_removes_matching_keys_durably() {
+    let dir = tempfile::tempdir().unwrap();
+    let data_dir = dir.path().to_string_lossy().to_string();
+    for config in [test_config(None), test_config(Some(data_dir.clone()))] {
+        let engine = Engine::new(config, CancellationToken::new()).unwrap();
+        for i in 0..5 {
+            engine
+                .put_state(format!("job:{i}"), serde_json::json!(i), None, None)
+                .unwrap();
+        }
+        engine
+            .put_state("jobs".into(), serde_json::json!("keep"), None, None)
+            .unwrap();
+        assert_eq!(engine.delete_prefix("job:", 2).unwrap(), (2, true));
+        assert_eq!(engine.delete_prefix("job:", 10).unwrap(), (3, false));
+        assert_eq!(engine.delete_prefix("job:", 10).unwrap(), (0, false));
+        assert!(engine.get_state("jobs").is_some());
+    }
+
+    let engine = Engine::new(test_config(Some(data_dir)), CancellationToken::new()).unwrap();
+    let keys: Vec<String> = engine
+        .list_state(None, 10)
+        .into_iter()
+        .map(|item| item.key)
+        .collect();
+    assert_eq!(keys, vec!["jobs".to_string()]);
+}
+
+#[test]
+fn every_wal_fsync_policy_writes_and_replays() {
+    assert_eq!(WalFsyncPolicy::from_config("FULL"), WalFsyncPolicy::Full);
+    assert_eq!(WalFsyncPolicy::from_config("bogus"), WalFsyncPolicy::Data);
+    for (policy, expected) in [
+        ("data", WalFsyncPolicy::Data),
+        ("full", WalFsyncPolicy::Full),
+        ("none", WalFsyncPolicy::None),
+    ] {
+        assert_eq!(WalFsyncPolicy::from_config(policy), expected);
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = test_config(Some(dir.path().to_string_lossy().to_string()));
+        config.wal_fsync_policy = policy.to_string();
+
+        let engine = Engine::new(config.clone(), CancellationToken::new()).unwrap();
+        engine
+            .put_state("before".into(), serde_json::json!(policy), None, None)
+            .unwrap();
+        engine.force_snapshot().unwrap();
+        engine
+            .put_state("after".into(), serde_json::json!(policy), None, None)
+            .unwrap();
+        drop(engine);
+
+        let engine = Engine::new(config, CancellationToken::new()).unwrap();
+        for key in ["before", "after"] {
+            assert_eq!(
+                engine.get_state(key).unwrap().value,
+                serde_json::json!(policy)
+            );
+        }
+    }
+}
+
+#[test]
+fn transact_state_applies_all_ops_or_none() {
+    let dir = tempfile::tempdir().unwrap();
+    let data_dir = dir.path().to_string_lossy().to_string();
+    for config in [test_config(None), test_config(Some(data_dir.clone()))] {
+        let engine = Engine::new(config, CancellationToken::new()).unwrap();
+        engine
+            .put_state("acct:a".into(), serde_json::json!(100), None, None)
+            .unwrap();
+        engine
+            .put_state("acct:b".into(), serde_json::json!(0), None, None)
+            .unwrap();
+        engine
+            .put_state("acct:b".into(), serde_json::json!(5), None, Some(1))
+            .unwrap();
+        let offset_before = engine.events().next_offset();
+
+        let stale = engine.transact_state(vec![
+            StateOp::Put {
+                key: "acct:a".into(),
+                value: serde_json::json!(60),
+                ttl_ms: None,
+                if_revision: Some(1),
+                if_not_exists: false,
+            },
+            StateOp::Put {
+                key: "acct:b".into(),
+                value: serde_json::json!(45),
+                ttl_ms: None,
+                if_revision: Some(1),
+                if_not_exists: false,
+            },
+            StateOp::Delete {
+                key: "pending".into(),
+                if_revision: None,
+            },
+        ]);
+        assert!(matches!(
+            stale,
+            Err(EngineError::State(StateError::RevisionMismatch))
+        ));
+        assert_eq!(engine.get_state("acct:a").unwrap().value, 100);
+        assert_eq!(engine.get_state("acct:b").unwrap().value, 5);
+        assert_eq!(engine.events().next_offset(), offset_before);
+
+        let nx = engine.transact_state(vec![StateOp::Put {
+            key: "acct:a".into(),
+            value: serde_json::json!(1),
+            ttl_ms: None,
+            if_revision: None,
+            if_not_exists: true,
+        }]);
+        assert!(matches!(
+            nx,
+            Err(EngineError::State(StateError::AlreadyExists))
+        ));
+
+        let items = engine
+            .transact_state(vec![
+                StateOp::Put {
+                    key: "acct:a".into(),
+                    value: serde_json::json!(60),
+                    ttl_ms: None,
+                    if_revision: Some(1),
+                    if_not_exists: false,
+                },
+                StateOp::Put {
+                    key: "acct:b".into(),
+                    value: serde_json::json!(45),
+                    ttl_ms: None,
+                    if_revision: Some(2),
+                    if_not_exists: false,
+                },
+                StateOp::Delete {
+                    key: "acct:a".into(),
+                    if_revision: Some(2),
+                },
+            ])
+            .unwrap();
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[1].revision, 3);
+        assert!(engine.get_state("acct:a").is_none());
+        assert_eq!(engine.get_state("acct:b").unwrap().value, 45);
+        assert_eq!(engine.events().next_offset(), offset_before + 3);
+    }
+
+    let engine = Engine::new(test_config(Some(data_dir)), CancellationToken::new()).unwrap();
+    assert!(engine.get_state("acct:a").is_none());
+    assert_eq!(engine.get_state("acct:b").unwrap().revision, 3);
+}
+
+#[test]
+fn replay_drops_a_transaction_torn_mid_write() {
+    let dir = tempfile::tempdir().unwrap();
+    let data_dir = dir.path().to_string_lossy().to_string();
+    let mut config = test_config(Some(data_dir.clone()));
+    config.verify_on_start = true;
+
+    let engine = Engine::new(config.clone(), CancellationToken::new()).unwrap();
+    engine
+        .put_state("acct:a".into(), serde_json::json!(100), None, None)
+        .unwrap();
+    engine
+        .put_state("acct:b".into(), serde_json::json!(0), None, None)
+        .unwrap();
+    let base = engine.events().next_offset();
+    drop(engine);
+
+    // A crash while appending a three-op transaction: two whole lines and the
+    // start of the third reached the WAL, the state db saw none of them.
+    let txn_event = |seq: u64, key: &str, value: i64| EventRecord {
+        offset: base + seq,
+        ts_ms: 0,
+        event_type: "state_updated".to_string(),
+        data: serde_json::json!({
+            "key": key,
+            "revision": 2,
+            "value": value,
+            "expires_at_ms": null,
+            "txn_seq": seq,
+            "txn_len": 3,
+        }),
+    };
+    let mut torn = String::new();
+    for (seq, key, value) in [(0, "acct:a", 60), (1, "acct:b", 40)] {
+        torn.push_str(&serde_json::to_string(&txn_event(seq, key, value)).unwrap());
+        torn.push('\n');
+    }
+    let third = serde_json::to_string(&txn_event(2, "acct:c", 0)).unwrap();
+    torn.push_str(&third[..third.len() / 2]);
+    let segment = std::fs::read_dir(&data_dir)
+        .unwrap()
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| {
+            p.file_name()
+                .unwrap()
+                .to_string_lossy()
+                .starts_with("events-")
+        })
+        .max()
+        .unwrap();
+    let mut wal = std::fs::OpenOptions::new()
+        .append(true)
+        .open(&segment)
+        .unwrap();
+    std::io::Write::write_all(&mut wal, torn.as_bytes()).unwrap();
+    drop(wal);
+
+    let engine = Engine::new(config.clone(), CancellationToken::new()).unwrap();
+    assert_eq!(engine.get_state("acct:a").unwrap().value, 100);
+    assert_eq!(engine.get_state("acct:b").unwrap().value, 0);
+    assert!(engine.get_state("acct:c").is_none());
+    // The dropped offsets are not handed out again.
+    assert!(engine.events().next_offset() > base + 1);
+    engine
+        .put_state("acct:c".into(), serde_json::json!(7), None, None)
+        .unwrap();
+    drop(engine);
+
+    let engine = Engine::new(config, CancellationToken::new()).unwrap();
+    assert_eq!(engine.get_state("acct:a").unwrap().value, 100);
+    assert_eq!(engine.get_state("acct:c").unwrap().value, 7);
+}
+
+#[tokio::test]
+async fn metrics_snapshot_counts_events() {
+    let config = test_config(None);
+
+    let engine = Engine::new(config, CancellationToken::new()).unwrap();
+    assert_eq!(engine.metrics_snapshot(), MetricsSnapshot::default());
+
+    for i in 0..5u32 {
+        engine
+            .put_state(format!("k{i}"), serde_json::json!(i), None, None)
+            .unwrap();
+    }
+    assert!(engine.delete_state("k0").unwrap());
+    assert!(!engine.delete_state("k0").unwrap());
+    engine.rename_state("k1", "k9", false).unwrap();
+
+    let snap = engine.metrics_snapshot();
+    assert_eq!(snap.events_total, 8);
+    assert_eq!(snap.state_put_total, 6);
+    assert_eq!(snap.state_delete_total, 2);
+    assert_eq!(snap.sse_clients, 0);
+    assert!(engine
+        .metrics_text()
+        .contains(&format!("events_total {}", snap.events_total)));
+}
+
+#[tokio::test]
+async fn doc_upserted_replay_restores_doc_and_indexes() {
+    let dir = tempfile::tempdir().unwrap();
+    let data_dir = dir.path().to_string_lossy().to_string();
+    let config = test_config(Some(data_dir.clone()));
+
+    let engine = Engine::new(config.clone(), CancellationToken::new()).unwrap();
+    docstore::put_doc(&engine, "users", "u1", serde_json::json!({"role": "admin"})).unwrap();
+    docstore::put_doc(&engine, "users", "u2", serde_json::json!({"role": "admin"})).unwrap();
+    docstore::put_doc(
+        &engine,
+        "users",
+        "u1",
+        serde_json::json!({"role": "viewer"}),
+    )
+    .unwrap();
+
+    let mut types = Vec::new();
+    engine
+        .persist()
+        .unwrap()
+        .for_each_event_since(0, |ev| {
+            types.push(ev.event_type);
+            true
+        })
+        .unwrap();
+    assert_eq!(types, vec!["doc_upserted"; 3]);
+    drop(engine);
+
+    // Without the state db every key has to come back from the WAL.
+    std::fs::remove_file(dir.path().join("state.redb")).unwrap();
+    let engine2 = Engine::new(config, CancellationToken::new()).unwrap();
+    let doc = docstore::get_doc(&engine2, "users", "u1").unwrap().unwrap();
+    assert_eq!(doc.doc["role"], "viewer");
+    assert_eq!(doc.revision, 2);
+    let admin = engine2.get_state("docidx:users:role:admin").unwrap();
+    assert_eq!(admin.value, serde_json::json!({"ids": ["u2"]}));
+    let viewer = engine2.get_state("docidx:users:role:viewer").unwrap();
+    assert_eq!(viewer.value, serde_json::json!({"ids": ["u1"]}));
+
+    let found = docstore::find_docs(
+        &engine2,
+        "users",
+        Some(&serde_json::json!({"role": "viewer"})),
+        10,
+    )
+    .unwrap();
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].id, "u1");
+}
+
+#[test]
+fn dump_events_writes_wal_as_ndjson_in_offset_order() {
+    let dir = tempfile::tempdir().unwrap();
+    let data_dir = dir.path().to_string_lossy().to_string();
+    let config = test_config(Some(data_dir.clone()));
+
+    let engine = Engine::new(config, CancellationToken::new()).unwrap();
+    for i in 0..5u32 {
+        engine
+            .put_state(format!("k{i}"), serde_json::json!(i), None, None)
+            .unwrap();
+    }
+    assert!(engine.delete_state("k0").unwrap());
+    drop(engine);
+
+    let out_path = dir.path().join("events.ndjson");
+    let mut out = std::fs::File::create(&out_path).unwrap();
+    let written = rust_kiss_vdb::engine::dump_events(&data_dir, 0, &mut out).unwrap();
+    assert_eq!(written, 6);
+
+    let events: Vec<EventRecord> = std::fs::read_to_string(&out_path)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    let offsets: Vec<u64> = events.iter().map(|ev| ev.offset).collect();
+    assert_eq!(offsets, (1..=6).collect::<Vec<_>>());
+    assert_eq!(events[0].event_type, "state_updated");
+    assert_eq!(events[5].event_type, "state_deleted");
+
+    let mut tail = Vec::new();
+    assert_eq!(
+        rust_kiss_vdb::engine::dump_events(&data_dir, 4, &mut tail).unwrap(),
+        2
+    );
+    let tail_offsets: Vec<u64> = String::from_utf8(tail)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str::<EventRecord>(line).unwrap().offset)
+        .collect();
+    assert_eq!(tail_offsets, vec![5, 6]);
+
+    // A server mid-append leaves a partial line; the dump must not trim it.
+    let segment = std::fs::read_dir(&data_dir)
+        .unwrap()
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| {
+            p.file_name()
+                .unwrap()
+                .to_string_lossy()
+                .starts_with("events-")
+        })
+        .max()
+        .unwrap();
+    let mut wal = std::fs::OpenOptions::new()
+        .append(true)
+        .open(&segment)
+        .unwrap();
+    std::io::Write::write_all(&mut wal, b"{\"offset\":7,").unwrap();
+    drop(wal);
+    let len = std::fs::metadata(&segment).unwrap().len();
+    assert_eq!(
+        rust_kiss_vdb::engine::dump_events(&data_dir, 0, &mut Vec::new()).unwrap(),
+        6
+    );
+    assert_eq!(std::fs::metadata(&segment).unwrap().len(), len);
+
+    let missing = rust_kiss_vdb::engine::dump_events(dir.path().join("nope"), 0, &mut Vec::new());
+    assert!(missing.is_err());
+}
+
+#[test]
+fn doc_index_fan_out_is_capped_and_the_rest_is_scanned() {
+    let mut config = test_config(None);
+    config.max_doc_indexed_fields = 3;
+    let engine = Engine::new(config, CancellationToken::new()).unwrap();
+    let doc = serde_json::json!({
+        "f": "6", "e": "5", "d": "4", "c": "3", "b": "2", "a": "1", "n": 7
+    });
+    assert_eq!(
+        docstore::unindexed_fields(&doc, 3)
+            .into_iter()
+            .collect::<Vec<_>>(),
+        vec!["d", "e", "f"]
+    );
+    docstore::put_doc(&engine, "wide", "w1", doc).unwrap();
+    docstore::put_doc(
+        &engine,
+        "wide",
+        "w2",
+        serde_json::json!({"a": "1", "e": "other"}),
+    )
+    .unwrap();
+
+    let index_keys: Vec<String> = engine
+        .list_state(Some("docidx:wide:"), 100)
+        .into_iter()
+        .map(|item| item.key)
+        .collect();
+    // `w2` is under the cap, so its `e` is indexed while `w1`'s is not.
+    assert_eq!(
+        index_keys,
+        vec![
+            "docidx:wide:a:1",
+            "docidx:wide:b:2",
+            "docidx:wide:c:3",
+            "docidx:wide:e:other"
+        ]
+    );
+
+    let find = |filter: serde_json::Value| -> Vec<String> {
+        docstore::find_docs(&engine, "wide", Some(&filter), 10)
+            .unwrap()
+            .into_iter()
+            .map(|d| d.id)
+            .collect()
+    };
+    assert_eq!(find(serde_json::json!({"e": "5"})), vec!["w1"]);
+    assert_eq!(find(serde_json::json!({"e": "other"})), vec!["w2"]);
+    assert_eq!(find(serde_json::json!({"a": "1", "f": "6"})), vec!["w1"]);
+
+    let report = docstore::reindex(&engine, "wide").unwrap();
+    assert_eq!(report.index_keys, 4);
+    assert_eq!(report.repaired, 0);
+}
+
+#[tokio::test]
+async fn doc_reindex_writes_its_repairs_as_one_transaction() {
+    let engine = Engine::new(test_config(None), CancellationToken::new()).unwrap();
+    for (id, role) in [("u1", "admin"), ("u2", "viewer")] {
+        docstore::put_doc(&engine, "users", id, serde_json::json!({ "role": role })).unwrap();
+    }
+    for (key, ids) in [
+        ("docidx:users:role:admin", vec!["ghost"]),
+        ("docidx:users:role:owner", vec!["u2"]),
+    ] {
+        engine
+            .put_state(key.into(), serde_json::json!({ "ids": ids }), None, None)
+            .unwrap();
+    }
+
+    let mut rx = engine.events().subscribe();
+    let report = docstore::reindex(&engine, "users").unwrap();
+    assert_eq!(report.repaired, 2);
+    let mut events = Vec::new();
+    while let Ok(ev) = rx.try_recv() {
+        events.push(ev);
+    }
+    assert_eq!(events.len(), 2);
+    for (seq, ev) in events.iter().enumerate() {
+        assert_eq!(ev.data["txn_seq"], seq);
+        assert_eq!(ev.data["txn_len"], 2);
+    }
+    let admin = engine.get_state("docidx:users:role:admin").unwrap();
+    assert_eq!(admin.value, serde_json::json!({"ids": ["u1"]}));
+    assert!(engine.get_state("docidx:users:role:owner").is_none());
+}
+
+#[test]
+fn doc_find_scan_reaches_matches_past_the_first_page() {
+    let mut config = test_config(None);
+    config.max_doc_indexed_fields = 1;
+    let engine = Engine::new(config, CancellationToken::new()).unwrap();
+    for i in 0..50u32 {
+        let z = if i >= 45 { "hit" } else { "miss" };
+        docstore::put_doc(
+            &engine,
+            "wide",
+            &format!("d{i:02}"),
+            serde_json::json!({"a": "1", "z": z}),
+        )
+        .unwrap();
+    }
+
+    // `z` is past the cap, so this scans; the matches are beyond 4 * limit docs.
+    let filter = serde_json::json!({"z": "hit"});
+    let ids: Vec<String> = docstore::find_docs(&engine, "wide", Some(&filter), 2)
+        .unwrap()
+        .into_iter()
+        .map(|d| d.id)
+        .collect();
+    assert_eq!(ids, vec!["d45", "d46"]);
+    let all = docstore::find_docs(&engine, "wide", Some(&filter), 10).unwrap();
+    assert_eq!(all.len(), 5);
+}
+
+#[tokio::test]
+async fn doc_find_tags_filter_supports_all_and_any() {
+    let engine = Engine::new(test_config(None), CancellationToken::new()).unwrap();
+    for (id, tags) in [
+        ("d1", serde_json::json!(["rust", "db"])),
+        ("d2", serde_json::json!(["rust", "web"])),
+        ("d3", serde_json::json!(["db", "ops"])),
+        ("d4", serde_json::json!(["rust", "db", "web"])),
+    ] {
+        docstore::put_doc(
+            &engine,
+            "posts",
+            id,
+            serde_json::json!({"tags": tags, "n": 1}),
+        )
+        .unwrap();
+    }
+    let tagged = engine.get_state("docidx:posts:tags:rust").unwrap();
+    assert_eq!(tagged.value, serde_json::json!({"ids": ["d1", "d2", "d4"]}));
+
+    let find = |tags: serde_json::Value| {
+        // With `n` the filter is not fully indexed, so the scan path is checked too.
+        let mut results = Vec::new();
+        for filter in [
+            serde_json::json!({ "tags": tags }),
+            serde_json::json!({ "tags": tags, "n": 1 }),
+        ] {
+            let mut ids: Vec<String> = docstore::find_docs(&engine, "posts", Some(&filter), 10)
+                .unwrap()
+                .into_iter()
+                .map(|d| d.id)
+                .collect();
+            ids.sort();
+            results.push(ids);
+        }
+        assert_eq!(results[0], results[1], "{tags}");
+        results.remove(0)
+    };
+    assert_eq!(
+        find(serde_json::json!({"all": ["rust", "db"]})),
+        vec!["d1", "d4"]
+    );
+    assert_eq!(
+        find(serde_json::json!({"any": ["web", "ops"]})),
+        vec!["d2", "d3", "d4"]
+    );
+    assert_eq!(
+        find(serde_json::json!({"all": ["db"], "any": ["web", "ops"]})),
+        vec!["d3", "d4"]
+    );
+    assert!(find(serde_json::json!({"all": ["missing"]})).is_empty());
+
+    // Dropping a tag removes the doc from that tag's postings.
+    docstore::put_doc(
+        &engine,
+        "posts",
+        "d4",
+        serde_json::json!({"tags": ["db"], "n": 1}),
+    )
+    .unwrap();
+    assert_eq!(find(serde_json::json!({"all": ["rust", "db"]})), vec!["d1"]);
+}
+
+#[tokio::test]
+async fn doc_find_tags_scans_until_reindex_backfills_postings() {
+    let engine = Engine::new(test_config(None), CancellationToken::new()).unwrap();
+    for (id, tags) in [("d1", ["rust", "db"]), ("d2", ["rust", "web"])] {
+        docstore::put_doc(&engine, "posts", id, serde_json::json!({ "tags": tags })).unwrap();
+    }
+    // Stored before tags were indexed: no tag postings and no marker.
+    for key in [
+        "docidx:posts:tags:rust",
+        "docidx:posts:tags:db",
+        "docidx:posts:tags:web",
+        "doctagsindexed:posts",
+    ] {
+        assert!(engine.delete_state(key).unwrap(), "{key}");
+    }
+    docstore::put_doc(
+        &engine,
+        "posts",
+        "d3",
+        serde_json::json!({"tags": ["rust"]}),
+    )
+    .unwrap();
+
+    let find = || {
+        let filter = serde_json::json!({"tags": {"all": ["rust"]}});
+        let mut ids: Vec<String> = docstore::find_docs(&engine, "posts", Some(&filter), 10)
+            .unwrap()
+            .into_iter()
+            .map(|d| d.id)
+            .collect();
+        ids.sort();
+        ids
+    };
+    assert_eq!(find(), vec!["d1", "d2", "d3"]);
+
+    docstore::reindex(&engine, "posts").unwrap();
+    assert!(engine.get_state("doctagsindexed:posts").is_some());
+    let tagged = engine.get_state("docidx:posts:tags:rust").unwrap();
+    assert_eq!(tagged.value, serde_json::json!({"ids": ["d1", "d2", "d3"]}));
+    assert_eq!(find(), vec!["d1", "d2", "d3"]);
+}
+
+#[test]
+fn doc_find_tags_before_reindex_reaches_matches_past_the_first_page() {
+    let engine = Engine::new(test_config(None), CancellationToken::new()).unwrap();
+    for i in 0..30u32 {
+        let tags = if i >= 25 {
+            vec!["rare"]
+        } else {
+            vec!["common"]
+        };
+        docstore::put_doc(
+            &engine,
+            "posts",
+            &format!("d{i:02}"),
+            serde_json::json!({ "tags": tags }),
+        )
+        .unwrap();
+    }
+    // Stored before tags were indexed.
+    let stale: Vec<String> = engine
+        .list_state(Some("docidx:posts:tags:"), 100)
+        .into_iter()
+        .map(|item| item.key)
+        .chain(["doctagsindexed:posts".to_string()])
+        .collect();
+    for key in stale {
+        assert!(engine.delete_state(&key).unwrap(), "{key}");
+    }
+
+    let filter = serde_json::json!({"tags": {"any": ["rare"]}});
+    let ids: Vec<String> = docstore::find_docs(&engine, "posts", Some(&filter), 2)
+        .unwrap()
+        .into_iter()
+        .map(|d| d.id)
+        .collect();
+    assert_eq!(ids, vec!["d25", "d26"]);
+}
+
+#[test]
+fn ttl_grace_keeps_expired_keys_readable_until_it_runs_out() {
+    let dir = tempfile::tempdir().unwrap();
+    let data_dir = dir.path().to_string_lossy().to_string();
+
+    for data_dir in [None, Some(data_dir)] {
+        let mut config = test_config(data_dir);
+        config.ttl_grace_ms = 400;
+        let engine = Engine::new(config, CancellationToken::new()).unwrap();
+        engine
+            .put_state("hot".into(), serde_json::json!(1), Some(50), None)
+            .unwrap();
+
+        std::thread::sleep(std::time::Duration::from_millis(150));
+        let item = engine
+            .get_state("hot")
+            .expect("served within the grace window");
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+        assert!(item.expires_at_ms.unwrap() <= now);
+
+        std::thread::sleep(std::time::Duration::from_millis(400));
+        assert!(engine.get_state("hot").is_none());
+    }
+}
+
+#[test]
+fn ttl_sweep_deletes_at_expiry_regardless_of_grace() {
+    let dir = tempfile::tempdir().unwrap();
+    let data_dir = dir.path().to_string_lossy().to_string();
+    let mut config = test_config(Some(data_dir));
+    config.ttl_grace_ms = 60_000;
+
+    let engine = Engine::new(config.clone(), CancellationToken::new()).unwrap();
+    engine
+        .put_state("hot".into(), serde_json::json!(1), Some(50), None)
+        .unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(150));
+    assert!(engine.get_state("hot").is_some());
+    drop(engine);
+
+    // Startup runs the expiry sweep, which ignores the grace window.
+    let engine = Engine::new(config, CancellationToken::new()).unwrap();
+    assert!(engine.get_state("hot").is_none());
+}
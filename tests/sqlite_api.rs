@@ -1,4 +1,5 @@
 use rust_kiss_vdb::api;
+use rust_kiss_vdb::api::auth_store::AuthStore;
 use rust_kiss_vdb::config::Config;
 use rust_kiss_vdb::engine::Engine;
 use rust_kiss_vdb::search::engine::SearchEngine;
@@ -6,9 +7,30 @@ use std::net::SocketAddr;
 use std::path::PathBuf;
 use std::sync::Arc;
 use tokio::sync::oneshot;
+use tokio_util::sync::CancellationToken;
 
 async fn start_with_sqlite(data_dir: String) -> (String, oneshot::Sender<()>) {
-    let config = Config {
+    let (base, shutdown, _) = start_with_sqlite_auth(data_dir, false).await;
+    (base, shutdown)
+}
+
+/// Like `start_with_sqlite`, optionally backing API keys with the sqlite
+/// `AuthStore` (the static "test" key stays valid either way).
+async fn start_with_sqlite_auth(
+    data_dir: String,
+    with_auth_store: bool,
+) -> (String, oneshot::Sender<()>, Option<Arc<AuthStore>>) {
+    start_with_sqlite_options(data_dir, with_auth_store, false).await
+}
+
+/// `read_only` sets `sqlite_readonly` and serves `/v1/sql/*` from a
+/// read-only connection, as the server does with `SQLITE_READONLY`.
+async fn start_with_sqlite_options(
+    data_dir: String,
+    with_auth_store: bool,
+    read_only: bool,
+) -> (String, oneshot::Sender<()>, Option<Arc<AuthStore>>) {
+    let mut config = Config {
         port: 0,
         bind_addr: "127.0.0.1".parse().unwrap(),
         api_key: "test".to_string(),
@@ -50,17 +72,51 @@ async fn start_with_sqlite(data_dir: String) -> (String, oneshot::Sender<()>) {
         run_retention: 4,
         compaction_trigger_tombstone_ratio: 0.2,
         compaction_max_bytes_per_pass: 64 * 1024 * 1024,
+        verify_on_start: false,
+        max_response_bytes: 8 * 1024 * 1024,
+        default_durability: "sync".to_string(),
+        max_vectors_per_collection: 0,
+        embed_cache_size: 256,
+        sse_idle_timeout_secs: 0,
+        value_compress_min_bytes: 0,
+        wal_truncate_after_snapshot: false,
+        max_list_limit: 1000,
+        strict_query_dim: true,
+        sqlite_busy_retries: 3,
+        vector_segment_max_items: 8192,
+        vector_merge_tombstone_ratio: 0.3,
+        ttl_grace_ms: 0,
+        max_index_values_per_field: 0,
+        response_compress_min_bytes: 0,
+        wal_fsync_policy: "data".to_string(),
+        max_doc_indexed_fields: 0,
+        lock_wait_warn_ms: 100,
+        sqlite_readonly: false,
+        sqlite_migrations_dir: None,
+        embed_url: None,
+        embed_model: "default".to_string(),
+        embed_timeout_ms: 10_000,
+    };
+    config.sqlite_readonly = read_only;
+    let engine = Engine::new(config.clone(), CancellationToken::new()).unwrap();
+    let db_path = config.data_dir.as_ref().unwrap().to_string() + "/sqlite/rustkiss.db";
+    let sqlite = Some(rust_kiss_vdb::sqlite::SqliteService::new(&db_path).unwrap());
+    let sqlite = if read_only {
+        Some(rust_kiss_vdb::sqlite::SqliteService::open(&db_path, true).unwrap())
+    } else {
+        sqlite
     };
-    let engine = Engine::new(config.clone()).unwrap();
-    let sqlite = Some(
-        rust_kiss_vdb::sqlite::SqliteService::new(
-            config.data_dir.as_ref().unwrap().to_string() + "/sqlite/rustkiss.db",
-        )
-        .unwrap(),
-    );
     let search_dir = PathBuf::from(&data_dir);
     let search_engine = Arc::new(SearchEngine::new(search_dir).unwrap());
-    let app = api::router(engine, config, sqlite, search_engine);
+    let auth_store = match (&sqlite, with_auth_store) {
+        (Some(svc), true) => {
+            let store = Arc::new(AuthStore::new(Arc::new(svc.clone())));
+            store.init().await.unwrap();
+            Some(store)
+        }
+        _ => None,
+    };
+    let app = api::router(engine, config, sqlite, search_engine, auth_store.clone());
 
     let listener = tokio::net::TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0)))
         .await
@@ -76,7 +132,19 @@ async fn start_with_sqlite(data_dir: String) -> (String, oneshot::Sender<()>) {
             .await;
     });
 
-    (format!("http://{}", addr), tx)
+    (format!("http://{}", addr), tx, auth_store)
+}
+
+fn client() -> reqwest::Client {
+    let mut headers = reqwest::header::HeaderMap::new();
+    headers.insert(
+        reqwest::header::AUTHORIZATION,
+        reqwest::header::HeaderValue::from_static("Bearer test"),
+    );
+    reqwest::Client::builder()
+        .default_headers(headers)
+        .build()
+        .unwrap()
 }
 
 #[tokio::test]
@@ -84,7 +152,7 @@ async fn sqlite_exec_and_query() {
     let dir = tempfile::tempdir().unwrap();
     let data_dir = dir.path().to_string_lossy().to_string();
     let (base, shutdown) = start_with_sqlite(data_dir).await;
-    let client = reqwest::Client::new();
+    let client = client();
 
     let create = client
         .post(format!("{}/v1/sql/exec", base))
@@ -114,3 +182,497 @@ async fn sqlite_exec_and_query() {
 
     let _ = shutdown.send(());
 }
+
+#[tokio::test]
+async fn sqlite_tx_commits_all_statements_or_none() {
+    let dir = tempfile::tempdir().unwrap();
+    let data_dir = dir.path().to_string_lossy().to_string();
+    let (base, shutdown) = start_with_sqlite(data_dir).await;
+    let client = client();
+
+    let setup = client
+        .post(format!("{}/v1/sql/tx", base))
+        .json(&serde_json::json!({"statements": [
+            {"sql": "CREATE TABLE accounts(id TEXT PRIMARY KEY, balance INTEGER)"},
+            {"sql": "INSERT INTO accounts VALUES (?, ?), (?, ?)", "params": ["a", 100, "b", 0]},
+        ]}))
+        .send()
+        .await
+        .unwrap();
+    assert!(setup.status().is_success());
+    let transfer: serde_json::Value = client
+        .post(format!("{}/v1/sql/tx", base))
+        .json(&serde_json::json!({"statements": [
+            {"sql": "UPDATE accounts SET balance = balance - ? WHERE id = ?", "params": [30, "a"]},
+            {"sql": "UPDATE accounts SET balance = balance + ? WHERE id = ?", "params": [30, "b"]},
+            {"sql": "UPDATE accounts SET balance = 0 WHERE id = 'nobody'"},
+        ]}))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(transfer["rows_affected"], serde_json::json!([1, 1, 0]));
+
+    let failed = client
+        .post(format!("{}/v1/sql/tx", base))
+        .json(&serde_json::json!({"statements": [
+            {"sql": "UPDATE accounts SET balance = balance - 50 WHERE id = 'a'"},
+            {"sql": "INSERT INTO missing_table VALUES (1)"},
+        ]}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(failed.status(), reqwest::StatusCode::BAD_REQUEST);
+    let err: serde_json::Value = failed.json().await.unwrap();
+    let message = err["message"].as_str().unwrap_or_default();
+    assert_eq!(err["error"], "sqlite_error");
+    assert!(message.contains("statement 1"), "{err}");
+    assert!(message.contains("missing_table"), "{err}");
+
+    let rows: serde_json::Value = client
+        .post(format!("{}/v1/sql/query", base))
+        .json(&serde_json::json!({"sql": "SELECT id, balance FROM accounts ORDER BY id"}))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(
+        rows["rows"],
+        serde_json::json!([{"id": "a", "balance": 70}, {"id": "b", "balance": 30}])
+    );
+
+    let empty = client
+        .post(format!("{}/v1/sql/tx", base))
+        .json(&serde_json::json!({"statements": []}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(empty.status(), reqwest::StatusCode::BAD_REQUEST);
+
+    let _ = shutdown.send(());
+}
+
+#[tokio::test]
+async fn sqlite_named_params_bind_by_name_and_cannot_mix_with_positional() {
+    let dir = tempfile::tempdir().unwrap();
+    let data_dir = dir.path().to_string_lossy().to_string();
+    let (base, shutdown) = start_with_sqlite(data_dir).await;
+    let client = client();
+    let exec = |body: serde_json::Value| {
+        client
+            .post(format!("{}/v1/sql/exec", base))
+            .json(&body)
+            .send()
+    };
+
+    let create = exec(serde_json::json!({
+        "sql": "CREATE TABLE users(id INTEGER PRIMARY KEY, name TEXT, active INTEGER)"
+    }))
+    .await
+    .unwrap();
+    assert!(create.status().is_success());
+    let insert = exec(serde_json::json!({
+        "sql": "INSERT INTO users VALUES (:id, :name, :active)",
+        "named_params": {"name": "ana", "active": true, ":id": 5},
+    }))
+    .await
+    .unwrap();
+    assert!(insert.status().is_success());
+
+    let rows: serde_json::Value = client
+        .post(format!("{}/v1/sql/query", base))
+        .json(&serde_json::json!({
+            "sql": "SELECT name FROM users WHERE id = :id AND active = :active",
+            "named_params": {"id": 5, "active": 1},
+        }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(rows["rows"], serde_json::json!([{"name": "ana"}]));
+
+    let tx: serde_json::Value = client
+        .post(format!("{}/v1/sql/tx", base))
+        .json(&serde_json::json!({"statements": [
+            {"sql": "UPDATE users SET name = :name WHERE id = :id", "named_params": {"id": 5, "name": "bea"}},
+        ]}))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(tx["rows_affected"], serde_json::json!([1]));
+
+    let mixed = exec(serde_json::json!({
+        "sql": "UPDATE users SET name = ? WHERE id = :id",
+        "params": ["eva"],
+        "named_params": {"id": 5},
+    }))
+    .await
+    .unwrap();
+    assert_eq!(mixed.status(), reqwest::StatusCode::BAD_REQUEST);
+    let err: serde_json::Value = mixed.json().await.unwrap();
+    assert_eq!(err["error"], "invalid_argument");
+
+    let unknown = exec(serde_json::json!({
+        "sql": "UPDATE users SET name = 'x' WHERE id = :id",
+        "named_params": {"nope": 5},
+    }))
+    .await
+    .unwrap();
+    assert_eq!(unknown.status(), reqwest::StatusCode::BAD_REQUEST);
+
+    let _ = shutdown.send(());
+}
+
+#[tokio::test]
+async fn sqlite_blob_and_json_params_round_trip() {
+    let dir = tempfile::tempdir().unwrap();
+    let data_dir = dir.path().to_string_lossy().to_string();
+    let (base, shutdown) = start_with_sqlite(data_dir).await;
+    let client = client();
+    let exec = |body: serde_json::Value| {
+        client
+            .post(format!("{}/v1/sql/exec", base))
+            .json(&body)
+            .send()
+    };
+    let query = |sql: &'static str| {
+        let (client, base) = (&client, &base);
+        async move {
+            let body: serde_json::Value = client
+                .post(format!("{}/v1/sql/query", base))
+                .json(&serde_json::json!({ "sql": sql }))
+                .send()
+                .await
+                .unwrap()
+                .json()
+                .await
+                .unwrap();
+            body["rows"].clone()
+        }
+    };
+
+    exec(serde_json::json!({
+        "sql": "CREATE TABLE files(id INTEGER PRIMARY KEY, data BLOB, meta TEXT)"
+    }))
+    .await
+    .unwrap();
+    let insert = exec(serde_json::json!({
+        "sql": "INSERT INTO files VALUES (1, ?, ?)",
+        "params": [{"$blob": "AAEC//4="}, {"$json": {"tags": ["a", "b"], "size": 5}}],
+    }))
+    .await
+    .unwrap();
+    assert!(insert.status().is_success());
+
+    let rows =
+        query("SELECT typeof(data) AS kind, length(data) AS len, data, meta FROM files").await;
+    assert_eq!(rows[0]["kind"], "blob");
+    assert_eq!(rows[0]["len"], 5);
+    assert_eq!(rows[0]["data"], "AAEC//4");
+    let meta: serde_json::Value = serde_json::from_str(rows[0]["meta"].as_str().unwrap()).unwrap();
+    assert_eq!(meta, serde_json::json!({"tags": ["a", "b"], "size": 5}));
+
+    // The unpadded base64 read back from a row can be written again as is.
+    let copy = exec(serde_json::json!({
+        "sql": "INSERT INTO files VALUES (2, :data, NULL)",
+        "named_params": {"data": {"$blob": rows[0]["data"]}},
+    }))
+    .await
+    .unwrap();
+    assert!(copy.status().is_success());
+    let same =
+        query("SELECT count(*) AS n FROM files a JOIN files b ON a.data = b.data AND a.id < b.id")
+            .await;
+    assert_eq!(same[0]["n"], 1);
+
+    for bad in [
+        serde_json::json!([1, 2]),
+        serde_json::json!({"$blob": "not base64!"}),
+        serde_json::json!({"$blob": 5}),
+        serde_json::json!({"plain": "object"}),
+    ] {
+        let resp = exec(serde_json::json!({
+            "sql": "INSERT INTO files(data) VALUES (?)",
+            "params": [bad],
+        }))
+        .await
+        .unwrap();
+        assert_eq!(resp.status(), reqwest::StatusCode::BAD_REQUEST, "{bad}");
+    }
+
+    let _ = shutdown.send(());
+}
+
+#[tokio::test]
+async fn sqlite_readonly_refuses_writes_but_serves_queries() {
+    let dir = tempfile::tempdir().unwrap();
+    let data_dir = dir.path().to_string_lossy().to_string();
+    let seed =
+        rust_kiss_vdb::sqlite::SqliteService::new(dir.path().join("sqlite/rustkiss.db")).unwrap();
+    seed.execute(
+        "CREATE TABLE notes(id INTEGER PRIMARY KEY, body TEXT)".to_string(),
+        Vec::new(),
+    )
+    .await
+    .unwrap();
+    seed.execute(
+        "INSERT INTO notes(body) VALUES ('hola')".to_string(),
+        Vec::new(),
+    )
+    .await
+    .unwrap();
+
+    let (base, shutdown, _) = start_with_sqlite_options(data_dir, false, true).await;
+    let client = client();
+    let rows: serde_json::Value = client
+        .post(format!("{}/v1/sql/query", base))
+        .json(&serde_json::json!({"sql": "SELECT body FROM notes"}))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(rows["rows"], serde_json::json!([{"body": "hola"}]));
+
+    let exec = client
+        .post(format!("{}/v1/sql/exec", base))
+        .json(&serde_json::json!({"sql": "DROP TABLE notes"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(exec.status(), reqwest::StatusCode::FORBIDDEN);
+    let err: serde_json::Value = exec.json().await.unwrap();
+    assert_eq!(err["error"], "forbidden");
+    let tx = client
+        .post(format!("{}/v1/sql/tx", base))
+        .json(&serde_json::json!({"statements": [{"sql": "DELETE FROM notes"}]}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(tx.status(), reqwest::StatusCode::FORBIDDEN);
+
+    // The connection itself refuses writes, whatever the endpoint lets through.
+    let path = dir.path().join("sqlite/rustkiss.db");
+    let read_only = rust_kiss_vdb::sqlite::SqliteService::open(&path, true).unwrap();
+    assert!(read_only
+        .execute("DELETE FROM notes".to_string(), Vec::new())
+        .await
+        .is_err());
+    let left = read_only
+        .query("SELECT count(*) AS n FROM notes".to_string(), Vec::new())
+        .await
+        .unwrap();
+    assert_eq!(left[0]["n"], 1);
+
+    let _ = shutdown.send(());
+}
+
+#[tokio::test]
+async fn sqlite_migrations_apply_in_file_order_once() {
+    let dir = tempfile::tempdir().unwrap();
+    let migrations_dir = dir.path().join("migrations");
+    std::fs::create_dir_all(&migrations_dir).unwrap();
+    std::fs::write(
+        migrations_dir.join("002_seed.sql"),
+        "INSERT INTO notes(body) VALUES ('a'); INSERT INTO notes(body) VALUES ('b');",
+    )
+    .unwrap();
+    std::fs::write(
+        migrations_dir.join("001_init.sql"),
+        "CREATE TABLE notes(id INTEGER PRIMARY KEY, body TEXT);",
+    )
+    .unwrap();
+    std::fs::write(migrations_dir.join("README.md"), "not a migration").unwrap();
+
+    let service =
+        rust_kiss_vdb::sqlite::SqliteService::new(dir.path().join("sqlite/rustkiss.db")).unwrap();
+    let migrations = rust_kiss_vdb::sqlite::read_migrations_dir(&migrations_dir).unwrap();
+    assert_eq!(migrations.len(), 2);
+    let mut statements: Vec<&str> = migrations.iter().map(String::as_str).collect();
+    assert_eq!(service.run_migrations(&statements).await.unwrap(), 2);
+    assert_eq!(service.run_migrations(&statements).await.unwrap(), 0);
+
+    // A failing migration rolls back the whole batch, earlier ones included.
+    statements.push("ALTER TABLE notes ADD COLUMN tag TEXT");
+    statements.push("INSERT INTO missing_table VALUES (1)");
+    let err = service.run_migrations(&statements).await.unwrap_err();
+    assert!(format!("{err:#}").contains("migration 3"), "{err:#}");
+    let applied = service
+        .query(
+            "SELECT id FROM _migrations ORDER BY id".to_string(),
+            Vec::new(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(applied.len(), 2);
+
+    statements.pop();
+    assert_eq!(service.run_migrations(&statements).await.unwrap(), 1);
+    let rows = service
+        .query(
+            "SELECT body, tag FROM notes ORDER BY id".to_string(),
+            Vec::new(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        rows,
+        vec![
+            serde_json::json!({"body": "a", "tag": null}),
+            serde_json::json!({"body": "b", "tag": null}),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn ready_probes_sqlite() {
+    let dir = tempfile::tempdir().unwrap();
+    let data_dir = dir.path().to_string_lossy().to_string();
+    let (base, shutdown) = start_with_sqlite(data_dir.clone()).await;
+
+    let ready = reqwest::get(format!("{}/v1/ready", base)).await.unwrap();
+    assert_eq!(ready.status(), reqwest::StatusCode::OK);
+    let body: serde_json::Value = ready.json().await.unwrap();
+    assert_eq!(body["ready"], true);
+    assert_eq!(body["sqlite_ok"], true);
+
+    let _ = shutdown.send(());
+}
+
+#[tokio::test]
+async fn ready_reports_corrupt_sqlite_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let data_dir = dir.path().to_string_lossy().to_string();
+    let (base, shutdown) = start_with_sqlite(data_dir.clone()).await;
+    let client = client();
+    client
+        .post(format!("{}/v1/sql/exec", base))
+        .json(&serde_json::json!({"sql":"CREATE TABLE notes(id INTEGER PRIMARY KEY)","params":[]}))
+        .send()
+        .await
+        .unwrap();
+
+    // Overwrite the files in place under the open connection; the clobbered
+    // WAL index forces SQLite to re-read the (now garbage) database header.
+    let db_path = PathBuf::from(&data_dir).join("sqlite").join("rustkiss.db");
+    for path in [
+        db_path.clone(),
+        PathBuf::from(format!("{}-wal", db_path.display())),
+        PathBuf::from(format!("{}-shm", db_path.display())),
+    ] {
+        let len = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+        if len > 0 {
+            let mut file = std::fs::OpenOptions::new().write(true).open(&path).unwrap();
+            std::io::Write::write_all(&mut file, &vec![0xAB; len as usize]).unwrap();
+        }
+    }
+
+    let ready = reqwest::get(format!("{}/v1/ready", base)).await.unwrap();
+    assert_eq!(ready.status(), reqwest::StatusCode::SERVICE_UNAVAILABLE);
+    let body: serde_json::Value = ready.json().await.unwrap();
+    assert_eq!(body["ready"], false);
+    assert_eq!(body["sqlite_ok"], false);
+
+    let _ = shutdown.send(());
+}
+
+#[tokio::test]
+async fn admin_config_requires_an_admin_key() {
+    let dir = tempfile::tempdir().unwrap();
+    let (base, shutdown, store) =
+        start_with_sqlite_auth(dir.path().to_string_lossy().to_string(), true).await;
+    let store = store.unwrap();
+    let user_key = store.generate_api_key();
+    store
+        .create_key("reader", "user", &user_key, serde_json::json!({}))
+        .await
+        .unwrap();
+    let admin_key = store.generate_api_key();
+    store
+        .create_key("ops", "admin", &admin_key, serde_json::json!({}))
+        .await
+        .unwrap();
+
+    let plain = reqwest::Client::new();
+    let get = |key: String| {
+        plain
+            .get(format!("{}/v1/admin/config", base))
+            .bearer_auth(key)
+            .send()
+    };
+    let user = get(user_key).await.unwrap();
+    assert_eq!(user.status(), reqwest::StatusCode::FORBIDDEN);
+    let admin = get(admin_key).await.unwrap();
+    assert_eq!(admin.status(), reqwest::StatusCode::OK);
+    let body: serde_json::Value = admin.json().await.unwrap();
+    assert_eq!(body["api_key"], "***");
+    assert_eq!(body["sqlite_enabled"], true);
+
+    let _ = shutdown.send(());
+}
+
+#[tokio::test]
+async fn sqlite_busy_writes_are_retried_until_the_lock_clears() {
+    use rust_kiss_vdb::sqlite::{self, SqliteService};
+    use std::time::Duration;
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("contended.db");
+    let open = |retries: u32| {
+        SqliteService::new(&path)
+            .unwrap()
+            .with_busy_timeout(Duration::from_millis(10))
+            .unwrap()
+            .with_busy_retries(retries)
+    };
+    let holder = open(0);
+    holder
+        .execute("CREATE TABLE t(v INTEGER)".to_string(), vec![])
+        .await
+        .unwrap();
+    holder
+        .execute("BEGIN IMMEDIATE".to_string(), vec![])
+        .await
+        .unwrap();
+    holder
+        .execute("INSERT INTO t(v) VALUES (1)".to_string(), vec![])
+        .await
+        .unwrap();
+
+    let impatient = open(0);
+    let err = impatient
+        .execute("INSERT INTO t(v) VALUES (2)".to_string(), vec![])
+        .await
+        .unwrap_err();
+    assert!(sqlite::is_busy(&err), "{err}");
+
+    let patient = open(10);
+    let write = tokio::spawn(async move {
+        patient
+            .execute("INSERT INTO t(v) VALUES (3)".to_string(), vec![])
+            .await
+    });
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(!write.is_finished());
+    holder.execute("COMMIT".to_string(), vec![]).await.unwrap();
+    assert_eq!(write.await.unwrap().unwrap(), 1);
+
+    let rows = holder
+        .query("SELECT v FROM t ORDER BY v".to_string(), vec![])
+        .await
+        .unwrap();
+    assert_eq!(
+        rows,
+        vec![serde_json::json!({"v": 1}), serde_json::json!({"v": 3})]
+    );
+}
@@ -4,12 +4,12 @@ use rust_kiss_vdb::config::Config;
 use rust_kiss_vdb::engine::Engine;
 use rust_kiss_vdb::search::engine::SearchEngine;
 use std::net::SocketAddr;
-use std::path::PathBuf;
 use std::sync::Arc;
 use tokio::sync::oneshot;
+use tokio_util::sync::CancellationToken;
 
-async fn start() -> (String, oneshot::Sender<()>) {
-    let config = Config {
+fn test_config() -> Config {
+    Config {
         port: 0,
         bind_addr: "127.0.0.1".parse().unwrap(),
         api_key: "test".to_string(),
@@ -51,13 +51,44 @@ async fn start() -> (String, oneshot::Sender<()>) {
         run_retention: 4,
         compaction_trigger_tombstone_ratio: 0.2,
         compaction_max_bytes_per_pass: 64 * 1024 * 1024,
-    };
-    let engine = Engine::new(config.clone()).unwrap();
+        verify_on_start: false,
+        max_response_bytes: 8 * 1024 * 1024,
+        default_durability: "sync".to_string(),
+        max_vectors_per_collection: 0,
+        embed_cache_size: 256,
+        sse_idle_timeout_secs: 0,
+        value_compress_min_bytes: 0,
+        wal_truncate_after_snapshot: false,
+        max_list_limit: 1000,
+        strict_query_dim: true,
+        sqlite_busy_retries: 3,
+        vector_segment_max_items: 8192,
+        vector_merge_tombstone_ratio: 0.3,
+        ttl_grace_ms: 0,
+        max_index_values_per_field: 0,
+        response_compress_min_bytes: 0,
+        wal_fsync_policy: "data".to_string(),
+        max_doc_indexed_fields: 0,
+        lock_wait_warn_ms: 100,
+        sqlite_readonly: false,
+        sqlite_migrations_dir: None,
+        embed_url: None,
+        embed_model: "default".to_string(),
+        embed_timeout_ms: 10_000,
+    }
+}
+
+async fn start() -> (String, oneshot::Sender<()>) {
+    start_with_config(test_config()).await
+}
+
+async fn start_with_config(config: Config) -> (String, oneshot::Sender<()>) {
+    let engine = Engine::new(config.clone(), CancellationToken::new()).unwrap();
     
     let temp_dir = tempfile::tempdir().unwrap(); 
     let search_engine = Arc::new(SearchEngine::new(temp_dir.path().to_path_buf()).unwrap());
     
-    let app = api::router(engine, config, None, search_engine);
+    let app = api::router(engine, config, None, search_engine, None);
 
     let listener = tokio::net::TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0)))
         .await
@@ -76,10 +107,22 @@ async fn start() -> (String, oneshot::Sender<()>) {
     (format!("http://{}", addr), tx)
 }
 
+fn client() -> reqwest::Client {
+    let mut headers = reqwest::header::HeaderMap::new();
+    headers.insert(
+        reqwest::header::AUTHORIZATION,
+        reqwest::header::HeaderValue::from_static("Bearer test"),
+    );
+    reqwest::Client::builder()
+        .default_headers(headers)
+        .build()
+        .unwrap()
+}
+
 #[tokio::test]
 async fn sse_receives_state_updated() {
     let (base, shutdown) = start().await;
-    let client = reqwest::Client::new();
+    let client = client();
 
     let resp = client
         .get(format!("{}/v1/events?types=state_updated&since=0", base))
@@ -114,3 +157,128 @@ async fn sse_receives_state_updated() {
 
     let _ = shutdown.send(());
 }
+
+#[tokio::test]
+async fn sse_idle_stream_closes_after_timeout() {
+    let mut config = test_config();
+    config.sse_idle_timeout_secs = 1;
+    let (base, shutdown) = start_with_config(config).await;
+    let client = client();
+
+    let resp = client
+        .get(format!(
+            "{}/v1/stream?types=state_updated&key_prefix=never:",
+            base
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert!(resp.status().is_success());
+    let put = client
+        .put(format!("{}/v1/state/other", base))
+        .json(&serde_json::json!({"value": 1}))
+        .send()
+        .await
+        .unwrap();
+    assert!(put.status().is_success());
+
+    let mut stream = resp.bytes_stream();
+    let mut buf = String::new();
+    let deadline = tokio::time::Instant::now() + std::time::Duration::from_secs(5);
+    loop {
+        tokio::select! {
+            _ = tokio::time::sleep_until(deadline) => panic!("stream was not closed"),
+            chunk = stream.next() => {
+                let Some(chunk) = chunk else { break };
+                buf.push_str(&String::from_utf8_lossy(&chunk.unwrap()));
+            }
+        }
+    }
+    assert!(buf.contains("event: idle_timeout") || buf.contains("event:idle_timeout"));
+    assert!(!buf.contains("state_updated"));
+
+    let _ = shutdown.send(());
+}
+
+#[tokio::test]
+async fn events_poll_pages_filtered_events_from_wal_and_buffer() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut persisted = test_config();
+    persisted.data_dir = Some(dir.path().to_string_lossy().to_string());
+
+    for config in [test_config(), persisted] {
+        let (base, shutdown) = start_with_config(config).await;
+        let client = client();
+        for key in ["poll:a", "other", "poll:b", "poll:c"] {
+            let put = client
+                .put(format!("{}/v1/state/{}", base, key))
+                .json(&serde_json::json!({"value": 1}))
+                .send()
+                .await
+                .unwrap();
+            assert!(put.status().is_success());
+        }
+
+        let poll = |since: u64| {
+            let client = client.clone();
+            let base = base.clone();
+            async move {
+                let resp = client
+                    .get(format!(
+                        "{}/v1/events/poll?since={}&limit=2&types=state_updated&key_prefix=poll:",
+                        base, since
+                    ))
+                    .send()
+                    .await
+                    .unwrap();
+                assert_eq!(resp.status(), reqwest::StatusCode::OK);
+                resp.json::<serde_json::Value>().await.unwrap()
+            }
+        };
+        let keys = |page: &serde_json::Value| -> Vec<String> {
+            page["events"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|ev| ev["data"]["key"].as_str().unwrap().to_string())
+                .collect()
+        };
+
+        let first = poll(0).await;
+        assert_eq!(keys(&first), vec!["poll:a", "poll:b"]);
+        let cursor = first["last_offset"].as_u64().unwrap();
+        assert_eq!(cursor, first["events"][1]["offset"].as_u64().unwrap());
+
+        let second = poll(cursor).await;
+        assert_eq!(keys(&second), vec!["poll:c"]);
+        let cursor = second["last_offset"].as_u64().unwrap();
+
+        let empty = poll(cursor).await;
+        assert!(keys(&empty).is_empty());
+        assert_eq!(empty["last_offset"].as_u64(), Some(cursor));
+
+        // A filter that matches nothing still moves the cursor past what it read.
+        let none = client
+            .get(format!(
+                "{}/v1/events/poll?since=0&limit=2&key_prefix=nothing:",
+                base
+            ))
+            .send()
+            .await
+            .unwrap()
+            .json::<serde_json::Value>()
+            .await
+            .unwrap();
+        assert!(keys(&none).is_empty());
+        assert_eq!(none["last_offset"].as_u64(), Some(4));
+
+        let too_large = client
+            .get(format!("{}/v1/events/poll?limit=1000000", base))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(too_large.status(), reqwest::StatusCode::BAD_REQUEST);
+
+        let _ = shutdown.send(());
+    }
+}
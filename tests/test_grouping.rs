@@ -1,5 +1,9 @@
+use axum::response::IntoResponse;
+use rust_kiss_vdb::search::embedder::{Embedder, HashEmbedder, HttpEmbedder};
 use rust_kiss_vdb::search::engine::SearchEngine;
-use rust_kiss_vdb::search::types::{Document, DocumentMetadata, SearchRequest};
+use rust_kiss_vdb::search::types::{Document, DocumentMetadata, SearchFilters, SearchRequest};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 use tempfile::tempdir;
 
 fn create_engine(dir: &tempfile::TempDir) -> SearchEngine {
@@ -62,6 +66,7 @@ fn test_grouping_basic_collapse() {
         filters: None,
         group_by: Some("document_id".to_string()),
         group_limit: 1,
+        mode: None,
     };
 
     let res = engine.search(req).unwrap();
@@ -104,6 +109,7 @@ fn test_grouping_multiple_documents() {
         filters: None,
         group_by: Some("document_id".to_string()),
         group_limit: 1,
+        mode: None,
     };
 
     let res = engine.search(req).unwrap();
@@ -139,6 +145,7 @@ fn test_no_grouping() {
         filters: None,
         group_by: None, // No grouping
         group_limit: 1,
+        mode: None,
     };
 
     let res = engine.search(req).unwrap();
@@ -167,6 +174,7 @@ fn test_determinism() {
         filters: None,
         group_by: Some("document_id".to_string()),
         group_limit: 5,
+        mode: None,
     };
 
     let res1 = engine.search(req.clone()).unwrap();
@@ -178,3 +186,415 @@ fn test_determinism() {
         assert_eq!(r1.score, r2.score);
     }
 }
+
+struct CountingEmbedder {
+    calls: AtomicUsize,
+}
+
+impl Embedder for CountingEmbedder {
+    fn model(&self) -> &str {
+        "counting"
+    }
+
+    fn embed(&self, text: &str, dim: usize) -> Vec<f32> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        HashEmbedder.embed(text, dim)
+    }
+}
+
+#[test]
+fn test_embed_cache_reuses_query_vectors() {
+    let dir = tempdir().unwrap();
+    let embedder = Arc::new(CountingEmbedder {
+        calls: AtomicUsize::new(0),
+    });
+    let engine =
+        SearchEngine::with_embedder(dir.path().to_path_buf(), embedder.clone(), 4).unwrap();
+    engine
+        .ingest(create_doc_with_vec(1, &[1.0, 0.0], None, Some("doc1")))
+        .unwrap();
+
+    let req = SearchRequest {
+        query: "TEST_VEC:1.0,0.0".to_string(),
+        top_k: 10,
+        filters: None,
+        group_by: None,
+        group_limit: 1,
+        mode: None,
+    };
+    let res1 = engine.search(req.clone()).unwrap();
+    let res2 = engine.search(req).unwrap();
+
+    assert_eq!(embedder.calls.load(Ordering::SeqCst), 1);
+    assert_eq!(engine.embed_cache_hits(), 1);
+    assert_eq!(res1.results[0].score, res2.results[0].score);
+    assert!(engine.render_metrics().contains("embed_cache_hits_total 1"));
+
+    // A disabled cache embeds every query.
+    let uncached =
+        SearchEngine::with_embedder(dir.path().to_path_buf(), embedder.clone(), 0).unwrap();
+    let req = SearchRequest {
+        query: "other".to_string(),
+        top_k: 1,
+        filters: None,
+        group_by: None,
+        group_limit: 1,
+        mode: None,
+    };
+    uncached.search(req.clone()).unwrap();
+    uncached.search(req).unwrap();
+    assert_eq!(embedder.calls.load(Ordering::SeqCst), 3);
+    assert_eq!(uncached.embed_cache_hits(), 0);
+}
+
+/// Deterministic two-dimensional model: points texts matching `rule` at
+/// the first axis and everything else at the second.
+struct RuleEmbedder {
+    model: &'static str,
+    rule: fn(&str) -> bool,
+}
+
+impl Embedder for RuleEmbedder {
+    fn model(&self) -> &str {
+        self.model
+    }
+
+    fn embed(&self, text: &str, _dim: usize) -> Vec<f32> {
+        if (self.rule)(text) {
+            vec![1.0, 0.0]
+        } else {
+            vec![0.0, 1.0]
+        }
+    }
+}
+
+#[test]
+fn test_reembed_rewrites_vectors_and_switches_query_model() {
+    let dir = tempdir().unwrap();
+    let by_initial: Arc<dyn Embedder> = Arc::new(RuleEmbedder {
+        model: "initial",
+        rule: |text| text.starts_with('a'),
+    });
+    let by_length: Arc<dyn Embedder> = Arc::new(RuleEmbedder {
+        model: "length",
+        rule: |text| text.len() > 8,
+    });
+    let engine =
+        SearchEngine::with_embedder(dir.path().to_path_buf(), by_initial.clone(), 16).unwrap();
+
+    for (id, content, category) in [(1, "ant", "short"), (2, "zebra crossing", "long")] {
+        let mut doc = create_doc_with_vec(id, &by_initial.embed(content, 2), None, None);
+        doc.content = content.to_string();
+        doc.metadata.category = Some(category.to_string());
+        engine.ingest(doc).unwrap();
+    }
+    let top = |engine: &SearchEngine| {
+        let resp = engine
+            .search(SearchRequest {
+                query: "aardvark crossing".to_string(),
+                top_k: 2,
+                filters: None,
+                group_by: None,
+                group_limit: 1,
+                mode: None,
+            })
+            .unwrap();
+        resp.results
+            .into_iter()
+            .map(|r| {
+                let doc = r.document;
+                (doc.id, doc.content, doc.metadata.category)
+            })
+            .collect::<Vec<_>>()
+    };
+    let before = top(&engine);
+    assert_eq!(before[0].0, 1);
+
+    assert_eq!(engine.reembed(by_length.clone()).unwrap(), 2);
+    let after = top(&engine);
+    assert_eq!(
+        after,
+        vec![
+            (2, "zebra crossing".to_string(), Some("long".to_string())),
+            (1, "ant".to_string(), Some("short".to_string())),
+        ]
+    );
+
+    // The rewritten log is what a restart reads.
+    drop(engine);
+    let reopened = SearchEngine::with_embedder(dir.path().to_path_buf(), by_length, 16).unwrap();
+    assert_eq!(top(&reopened), after);
+}
+
+#[test]
+fn test_http_embedder_embeds_queries_through_the_service() {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let listener = runtime
+        .block_on(tokio::net::TcpListener::bind("127.0.0.1:0"))
+        .unwrap();
+    let addr = listener.local_addr().unwrap();
+    let calls = Arc::new(AtomicUsize::new(0));
+    let seen = calls.clone();
+    let app = axum::Router::new().route(
+        "/embed",
+        axum::routing::post(move |axum::Json(body): axum::Json<serde_json::Value>| {
+            let seen = seen.clone();
+            async move {
+                seen.fetch_add(1, Ordering::SeqCst);
+                assert_eq!(body["model"], "mini");
+                assert_eq!(body["dim"], 384);
+                match body["input"].as_str().unwrap() {
+                    "north" => axum::Json(serde_json::json!({"data": [{"embedding": [1.0, 0.0]}]}))
+                        .into_response(),
+                    "east" => axum::Json(serde_json::json!([0.0, 1.0])).into_response(),
+                    _ => axum::http::StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+                }
+            }
+        }),
+    );
+    runtime.spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+    let embedder = Arc::new(
+        HttpEmbedder::new(
+            format!("http://{addr}/embed"),
+            "mini".to_string(),
+            std::time::Duration::from_secs(5),
+        )
+        .unwrap(),
+    );
+    let dir = tempdir().unwrap();
+    let engine = SearchEngine::with_embedder(dir.path().to_path_buf(), embedder, 16).unwrap();
+    engine
+        .ingest(create_doc_with_vec(1, &[1.0, 0.0], None, Some("north")))
+        .unwrap();
+    engine
+        .ingest(create_doc_with_vec(2, &[0.0, 1.0], None, Some("east")))
+        .unwrap();
+    let search = |query: &str| {
+        engine.search(SearchRequest {
+            query: query.to_string(),
+            top_k: 1,
+            filters: None,
+            group_by: None,
+            group_limit: 1,
+            mode: None,
+        })
+    };
+
+    assert_eq!(search("north").unwrap().results[0].document.id, 1);
+    assert_eq!(search("east").unwrap().results[0].document.id, 2);
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+    // Failed embeddings surface as errors and are not cached.
+    assert!(search("unknown").is_err());
+    assert!(search("unknown").is_err());
+    assert_eq!(calls.load(Ordering::SeqCst), 4);
+
+    // TEST_VEC never reaches the service.
+    assert_eq!(search("TEST_VEC:0,1").unwrap().results[0].document.id, 2);
+    assert_eq!(calls.load(Ordering::SeqCst), 4);
+}
+
+#[test]
+fn test_ann_index_applies_filters_and_versions_after_retrieval() {
+    let dir = tempdir().unwrap();
+    let engine = create_engine(&dir);
+    // 400 docs on a half circle, farther from the query as the id grows;
+    // every 80th is "rare", and ids 1000/1001 are two
+    // versions of group 7 sitting right on the query.
+    for i in 0..400u32 {
+        let angle = i as f32 * 0.0075;
+        let mut doc = create_doc_with_vec(i, &[angle.cos(), angle.sin()], None, None);
+        doc.metadata.category = Some(if i % 80 == 0 { "rare" } else { "common" }.to_string());
+        engine.ingest(doc).unwrap();
+    }
+    for (id, processed_at) in [(1000, 1), (1001, 2)] {
+        let mut doc = create_doc_with_vec(id, &[1.0, 0.0], Some(7), None);
+        doc.metadata.category = Some("common".to_string());
+        doc.metadata.processed_at = Some(processed_at);
+        engine.ingest(doc).unwrap();
+    }
+
+    let search = |engine: &SearchEngine, category: Option<&str>, policy: &str, top_k| {
+        engine
+            .search(SearchRequest {
+                query: "TEST_VEC:1.0,0.0".to_string(),
+                top_k,
+                filters: Some(SearchFilters {
+                    category: category.map(str::to_string),
+                    language: None,
+                    status: None,
+                    version_policy: Some(policy.to_string()),
+                }),
+                group_by: None,
+                group_limit: 1,
+                mode: None,
+            })
+            .unwrap()
+            .results
+            .into_iter()
+            .map(|r| (r.document.id, r.score))
+            .collect::<Vec<_>>()
+    };
+
+    let all = search(&engine, None, "all", 3);
+    // All three tie on score, so compare them as a set.
+    let sorted_ids = |hits: &[(u32, f32)]| {
+        let mut ids: Vec<u32> = hits.iter().map(|(id, _)| *id).collect();
+        ids.sort();
+        ids
+    };
+    assert_eq!(sorted_ids(&all), vec![0, 1000, 1001]);
+
+    // Only the newest version of group 7 is eligible.
+    let latest = search(&engine, None, "latest", 2);
+    assert_eq!(sorted_ids(&latest), vec![0, 1001]);
+
+    // A filter matching 5 of 402 docs still fills the response, far past
+    // the first batch of candidates.
+    let rare = search(&engine, Some("rare"), "all", 10);
+    assert_eq!(
+        rare.iter().map(|(id, _)| *id).collect::<Vec<_>>(),
+        vec![0, 80, 160, 240, 320]
+    );
+
+    // The index is rebuilt from the log on open.
+    drop(engine);
+    let reopened = create_engine(&dir);
+    assert_eq!(search(&reopened, Some("rare"), "latest", 10), rare);
+}
+
+#[test]
+fn test_delete_and_update_supersede_documents_across_reopen() {
+    let dir = tempdir().unwrap();
+    let engine = create_engine(&dir);
+    for (id, vec) in [(1, [1.0, 0.0]), (2, [0.9, 0.1]), (3, [0.8, 0.2])] {
+        engine
+            .ingest(create_doc_with_vec(id, &vec, None, None))
+            .unwrap();
+    }
+    let ids = |engine: &SearchEngine| {
+        engine
+            .search(SearchRequest {
+                query: "TEST_VEC:1.0,0.0".to_string(),
+                top_k: 10,
+                filters: None,
+                group_by: None,
+                group_limit: 1,
+                mode: None,
+            })
+            .unwrap()
+            .results
+            .into_iter()
+            .map(|r| (r.document.id, r.document.content))
+            .collect::<Vec<_>>()
+    };
+
+    assert!(engine.delete(1).unwrap());
+    assert!(!engine.delete(1).unwrap());
+    assert!(!engine.delete(42).unwrap());
+
+    // The new version of 3 moves it ahead of 2; the old one is gone.
+    let mut doc = create_doc_with_vec(3, &[1.0, 0.0], None, None);
+    doc.content = "third, revised".to_string();
+    engine.update(doc).unwrap();
+    let expected = vec![
+        (3, "third, revised".to_string()),
+        (2, "TEST_VEC:0.9,0.1".to_string()),
+    ];
+    assert_eq!(ids(&engine), expected);
+
+    // Tombstones are in the log, so a restart sees the same documents.
+    drop(engine);
+    let reopened = create_engine(&dir);
+    assert_eq!(ids(&reopened), expected);
+
+    // A document ingested again after its delete is live once more.
+    reopened
+        .ingest(create_doc_with_vec(1, &[0.0, 1.0], None, None))
+        .unwrap();
+    drop(reopened);
+    assert_eq!(ids(&create_engine(&dir)).len(), 3);
+}
+
+/// Embeds every text as `[1, 0]`, so hybrid queries can carry keywords.
+struct FixedEmbedder;
+
+impl Embedder for FixedEmbedder {
+    fn model(&self) -> &str {
+        "fixed"
+    }
+
+    fn embed(&self, _text: &str, _dim: usize) -> Vec<f32> {
+        vec![1.0, 0.0]
+    }
+}
+
+#[test]
+fn test_keyword_and_hybrid_modes_rank_by_bm25() {
+    let dir = tempdir().unwrap();
+    let open = || {
+        SearchEngine::with_embedder(dir.path().to_path_buf(), Arc::new(FixedEmbedder), 16).unwrap()
+    };
+    let engine = open();
+    let docs = [
+        (1, [1.0, 0.0], "Rust borrow checker explained"),
+        (2, [0.0, 1.0], "borrow checker errors, borrow checker fixes"),
+        (3, [0.9, 0.1], "gardening in spring"),
+        (4, [0.5, 0.5], "the checker of borrowed books"),
+    ];
+    for (id, vec, content) in docs {
+        let mut doc = create_doc_with_vec(id, &vec, None, None);
+        doc.content = content.to_string();
+        engine.ingest(doc).unwrap();
+    }
+    let search = |engine: &SearchEngine, query: &str, mode: &str| {
+        engine
+            .search(SearchRequest {
+                query: query.to_string(),
+                top_k: 10,
+                filters: None,
+                group_by: None,
+                group_limit: 1,
+                mode: Some(mode.to_string()),
+            })
+            .map(|res| {
+                res.results
+                    .into_iter()
+                    .map(|r| (r.document.id, r.score))
+                    .collect::<Vec<_>>()
+            })
+    };
+    let ids = |hits: &[(u32, f32)]| hits.iter().map(|(id, _)| *id).collect::<Vec<_>>();
+
+    // Repeated terms rank 2 first; 4 only shares "checker"; 3 shares nothing.
+    let keyword = search(&engine, "Borrow checker", "keyword").unwrap();
+    assert_eq!(ids(&keyword), vec![2, 1, 4]);
+    assert!(keyword.windows(2).all(|w| w[0].1 > w[1].1));
+    assert!(search(&engine, "nothing matches", "keyword")
+        .unwrap()
+        .is_empty());
+
+    // Hybrid: 1 is both close to the query vector and a strong keyword
+    // match, so it beats 2 (keywords only) and 3 (vector only).
+    let hybrid = search(&engine, "borrow checker", "hybrid").unwrap();
+    assert_eq!(ids(&hybrid)[0], 1);
+    let rank = |id| ids(&hybrid).iter().position(|x| *x == id).unwrap();
+    assert!(rank(2) < rank(4));
+    assert!(hybrid.iter().all(|(_, score)| (0.0..=1.0).contains(score)));
+
+    assert!(search(&engine, "borrow", "fuzzy").is_err());
+
+    // Deleted documents leave the keyword index too, also after a reopen.
+    engine.delete(2).unwrap();
+    assert_eq!(
+        ids(&search(&engine, "borrow checker", "keyword").unwrap()),
+        vec![1, 4]
+    );
+    drop(engine);
+    let reopened = open();
+    assert_eq!(
+        ids(&search(&reopened, "borrow checker", "keyword").unwrap()),
+        vec![1, 4]
+    );
+}
@@ -1,10 +1,14 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 use rust_kiss_vdb::config::Config;
-use rust_kiss_vdb::engine::Engine;
+use rust_kiss_vdb::engine::{Engine, EventRecord};
 use rust_kiss_vdb::vector::{
-    IndexKind, Metric, SearchRequest, VectorItem, VectorSettings, VectorStore,
+    IndexKind, Metric, Order, SearchRequest, TieBreak, VectorError, VectorItem, VectorSettings,
+    VectorStore,
 };
 use serde_json::json;
 use std::fs;
+use tokio_util::sync::CancellationToken;
 
 fn config_with_dir(dir: &str) -> Config {
     Config {
@@ -49,6 +53,30 @@ fn config_with_dir(dir: &str) -> Config {
         run_retention: 4,
         compaction_trigger_tombstone_ratio: 0.2,
         compaction_max_bytes_per_pass: 64 * 1024 * 1024,
+        verify_on_start: false,
+        max_response_bytes: 8 * 1024 * 1024,
+        default_durability: "sync".to_string(),
+        max_vectors_per_collection: 0,
+        embed_cache_size: 256,
+        sse_idle_timeout_secs: 0,
+        value_compress_min_bytes: 0,
+        wal_truncate_after_snapshot: false,
+        max_list_limit: 1000,
+        strict_query_dim: true,
+        sqlite_busy_retries: 3,
+        vector_segment_max_items: 8192,
+        vector_merge_tombstone_ratio: 0.3,
+        ttl_grace_ms: 0,
+        max_index_values_per_field: 0,
+        response_compress_min_bytes: 0,
+        wal_fsync_policy: "data".to_string(),
+        max_doc_indexed_fields: 0,
+        lock_wait_warn_ms: 100,
+        sqlite_readonly: false,
+        sqlite_migrations_dir: None,
+        embed_url: None,
+        embed_model: "default".to_string(),
+        embed_timeout_ms: 10_000,
     }
 }
 
@@ -58,7 +86,7 @@ async fn ivf_centroids_persist_and_filter() {
     let data_dir = dir.path().to_string_lossy().to_string();
     let config = config_with_dir(&data_dir);
 
-    let engine = Engine::new(config.clone()).unwrap();
+    let engine = Engine::new(config.clone(), CancellationToken::new()).unwrap();
     engine
         .create_vector_collection("docs", 2, Metric::Cosine)
         .unwrap();
@@ -75,7 +103,10 @@ async fn ivf_centroids_persist_and_filter() {
                 VectorItem {
                     vector: vec,
                     meta: json!({ "cluster": if i % 2 == 0 { "a" } else { "b" } }),
+                    revision: 0,
+                    expires_at_ms: None,
                 },
+                None,
             )
             .unwrap();
     }
@@ -89,7 +120,7 @@ async fn ivf_centroids_persist_and_filter() {
         .join("vectors/docs/centroids.json")
         .exists());
 
-    let engine2 = Engine::new(config).unwrap();
+    let engine2 = Engine::new(config, CancellationToken::new()).unwrap();
     let hits_a = engine2
         .vector_search(
             "docs",
@@ -98,6 +129,12 @@ async fn ivf_centroids_persist_and_filter() {
                 k: 4,
                 filters: None,
                 include_meta: Some(true),
+                tie_break: None,
+                include_vector: None,
+                min_score: None,
+                offset: None,
+                exact: None,
+                with_total: None,
             },
         )
         .unwrap();
@@ -114,6 +151,12 @@ async fn ivf_centroids_persist_and_filter() {
                 k: 4,
                 filters: None,
                 include_meta: Some(true),
+                tie_break: None,
+                include_vector: None,
+                min_score: None,
+                offset: None,
+                exact: None,
+                with_total: None,
             },
         )
         .unwrap();
@@ -134,7 +177,7 @@ async fn ivf_config_clamps_values() {
     config.ivf_min_train_vectors = 0;
     config.ivf_retrain_min_deltas = 0;
 
-    let engine = Engine::new(config.clone()).unwrap();
+    let engine = Engine::new(config.clone(), CancellationToken::new()).unwrap();
     engine
         .create_vector_collection("docs", 3, Metric::Cosine)
         .unwrap();
@@ -146,7 +189,10 @@ async fn ivf_config_clamps_values() {
                 VectorItem {
                     vector: vec![idx as f32, 0.0, 1.0],
                     meta: json!({ "idx": idx }),
+                    revision: 0,
+                    expires_at_ms: None,
                 },
+                None,
             )
             .unwrap();
     }
@@ -172,7 +218,7 @@ async fn ivf_retrain_updates_manifest_and_results() {
     config.ivf_clusters = 2;
     config.ivf_nprobe = 1;
 
-    let engine = Engine::new(config.clone()).unwrap();
+    let engine = Engine::new(config.clone(), CancellationToken::new()).unwrap();
     engine
         .create_vector_collection("docs", 3, Metric::Cosine)
         .unwrap();
@@ -184,7 +230,10 @@ async fn ivf_retrain_updates_manifest_and_results() {
                 VectorItem {
                     vector: vec![idx as f32, 1.0, 0.0],
                     meta: json!({ "idx": idx }),
+                    revision: 0,
+                    expires_at_ms: None,
                 },
+                None,
             )
             .unwrap();
     }
@@ -196,6 +245,12 @@ async fn ivf_retrain_updates_manifest_and_results() {
                 k: 2,
                 filters: None,
                 include_meta: Some(false),
+                tie_break: None,
+                include_vector: None,
+                min_score: None,
+                offset: None,
+                exact: None,
+                with_total: None,
             },
         )
         .unwrap();
@@ -208,6 +263,12 @@ async fn ivf_retrain_updates_manifest_and_results() {
                 k: 2,
                 filters: None,
                 include_meta: Some(false),
+                tie_break: None,
+                include_vector: None,
+                min_score: None,
+                offset: None,
+                exact: None,
+                with_total: None,
             },
         )
         .unwrap();
@@ -219,7 +280,7 @@ async fn ivf_retrain_updates_manifest_and_results() {
         serde_json::from_slice(&fs::read(&manifest_path).unwrap()).unwrap();
     let trained_at = manifest["centroids_trained_at_ms"].as_u64().unwrap();
 
-    let engine2 = Engine::new(config).unwrap();
+    let engine2 = Engine::new(config, CancellationToken::new()).unwrap();
     for idx in 16..24 {
         engine2
             .vector_upsert(
@@ -228,7 +289,10 @@ async fn ivf_retrain_updates_manifest_and_results() {
                 VectorItem {
                     vector: vec![idx as f32, 1.0, 0.0],
                     meta: json!({ "idx": idx }),
+                    revision: 0,
+                    expires_at_ms: None,
                 },
+                None,
             )
             .unwrap();
     }
@@ -269,6 +333,8 @@ fn ivf_large_dataset_retrain_consistent() {
                 VectorItem {
                     vector: vec![v0, v1],
                     meta: serde_json::Value::Null,
+                    revision: 0,
+                    expires_at_ms: None,
                 },
             )
             .unwrap();
@@ -278,6 +344,12 @@ fn ivf_large_dataset_retrain_consistent() {
         k: 5,
         filters: None,
         include_meta: Some(false),
+        tie_break: None,
+        include_vector: None,
+        min_score: None,
+        offset: None,
+        exact: None,
+        with_total: None,
     };
     let before = store.search("big", query.clone()).unwrap();
     assert!(!before.is_empty());
@@ -285,3 +357,382 @@ fn ivf_large_dataset_retrain_consistent() {
     let after = store.search("big", query).unwrap();
     assert_eq!(before.first().map(|h| &h.id), after.first().map(|h| &h.id));
 }
+
+#[test]
+fn zero_dim_collections_are_rejected() {
+    let store = VectorStore::with_settings(VectorSettings::default());
+    assert!(matches!(
+        store.create_collection("empty", 0, Metric::Cosine),
+        Err(VectorError::DimMismatch)
+    ));
+    assert!(store.get_collection("empty").is_none());
+
+    let ev = EventRecord {
+        offset: 1,
+        ts_ms: 0,
+        event_type: "vector_collection_created".to_string(),
+        data: json!({"collection": "replayed", "metric": "cosine"}),
+    };
+    assert!(matches!(
+        store.apply_event(&ev),
+        Err(VectorError::InvalidManifest)
+    ));
+    let ev = EventRecord {
+        data: json!({"collection": "replayed", "dim": 0, "metric": "cosine"}),
+        ..ev
+    };
+    assert!(matches!(
+        store.apply_event(&ev),
+        Err(VectorError::InvalidManifest)
+    ));
+    assert!(store.get_collection("replayed").is_none());
+}
+
+#[test]
+fn tie_break_orders_equal_scores_by_meta_field() {
+    let store = VectorStore::with_settings(VectorSettings::default());
+    store.create_collection("ties", 2, Metric::Cosine).unwrap();
+    for (id, meta) in [
+        ("a", json!({"ts_ms": 10})),
+        ("b", json!({})),
+        ("c", json!({"ts_ms": 30})),
+        ("d", json!({"ts_ms": 20})),
+        ("e", json!({"ts_ms": 5})),
+    ] {
+        store
+            .upsert(
+                "ties",
+                id,
+                VectorItem {
+                    vector: vec![1.0, 0.0],
+                    meta,
+                    revision: 0,
+                    expires_at_ms: None,
+                },
+            )
+            .unwrap();
+    }
+    store
+        .upsert(
+            "ties",
+            "far",
+            VectorItem {
+                vector: vec![0.0, 1.0],
+                meta: json!({"ts_ms": 99}),
+                revision: 0,
+                expires_at_ms: None,
+            },
+        )
+        .unwrap();
+
+    let search = |tie_break: Option<TieBreak>| -> Vec<String> {
+        store
+            .search(
+                "ties",
+                SearchRequest {
+                    vector: vec![1.0, 0.0],
+                    k: 6,
+                    filters: None,
+                    include_meta: None,
+                    tie_break,
+                    include_vector: None,
+                    min_score: None,
+                    offset: None,
+                    exact: None,
+                    with_total: None,
+                },
+            )
+            .unwrap()
+            .into_iter()
+            .map(|h| h.id)
+            .collect()
+    };
+
+    assert_eq!(search(None), vec!["a", "b", "c", "d", "e", "far"]);
+    assert_eq!(
+        search(Some(TieBreak {
+            field: "ts_ms".into(),
+            order: Order::Desc,
+        })),
+        vec!["c", "d", "a", "e", "b", "far"]
+    );
+    assert_eq!(
+        search(Some(TieBreak {
+            field: "ts_ms".into(),
+            order: Order::Asc,
+        })),
+        vec!["e", "a", "d", "c", "b", "far"]
+    );
+}
+
+#[test]
+fn bounded_search_pool_keeps_results_correct() {
+    let mut rng = StdRng::seed_from_u64(11);
+    // Two full segments plus a partial one, so the probe fans out.
+    let vectors: Vec<Vec<f32>> = (0..8_192 * 2 + 100)
+        .map(|_| (0..8).map(|_| rng.gen::<f32>() - 0.5).collect())
+        .collect();
+
+    for threads in [1, 4] {
+        let mut settings = VectorSettings::default();
+        settings.search_threads = Some(threads);
+        settings.parallel_segment_min = 2;
+        let store = VectorStore::with_settings(settings);
+        store.create_collection("docs", 8, Metric::Cosine).unwrap();
+        for (i, vector) in vectors.iter().enumerate() {
+            store
+                .upsert(
+                    "docs",
+                    &format!("v{i}"),
+                    VectorItem {
+                        vector: vector.clone(),
+                        meta: serde_json::Value::Null,
+                        revision: 0,
+                        expires_at_ms: None,
+                    },
+                )
+                .unwrap();
+        }
+        let stats = store.collection_stats("docs").unwrap();
+        assert_eq!(stats.segment_count, 3);
+
+        for target in [10, 8_500, vectors.len() - 5] {
+            let hits = store
+                .search(
+                    "docs",
+                    SearchRequest {
+                        vector: vectors[target].clone(),
+                        k: 5,
+                        filters: None,
+                        include_meta: None,
+                        tie_break: None,
+                        include_vector: None,
+                        min_score: None,
+                        offset: None,
+                        exact: None,
+                        with_total: None,
+                    },
+                )
+                .unwrap();
+            assert_eq!(hits.len(), 5, "threads={threads}");
+            assert_eq!(hits[0].id, format!("v{target}"), "threads={threads}");
+            assert!(hits.windows(2).all(|w| w[0].score >= w[1].score));
+        }
+    }
+}
+
+#[test]
+fn exact_flag_returns_true_top_k_with_filters() {
+    let mut rng = StdRng::seed_from_u64(23);
+    let vectors: Vec<Vec<f32>> = (0..2_000)
+        .map(|_| (0..8).map(|_| rng.gen::<f32>() - 0.5).collect())
+        .collect();
+
+    let mut settings = VectorSettings::default();
+    settings.index_kind = IndexKind::IvfFlatQ8;
+    settings.ivf.clusters = 64;
+    settings.ivf.nprobe = 1;
+    settings.ivf.min_train_vectors = 256;
+    let store = VectorStore::with_settings(settings);
+    store.create_collection("docs", 8, Metric::Cosine).unwrap();
+    for (i, vector) in vectors.iter().enumerate() {
+        store
+            .upsert(
+                "docs",
+                &format!("v{i}"),
+                VectorItem {
+                    vector: vector.clone(),
+                    meta: json!({"parity": i % 2}),
+                    revision: 0,
+                    expires_at_ms: None,
+                },
+            )
+            .unwrap();
+    }
+
+    let query: Vec<f32> = (0..8).map(|_| rng.gen::<f32>() - 0.5).collect();
+    let norm = |v: &[f32]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let mut expected: Vec<(f32, usize)> = vectors
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| i % 2 == 0)
+        .map(|(i, v)| {
+            let dot: f32 = v.iter().zip(&query).map(|(a, b)| a * b).sum();
+            (dot / (norm(v) * norm(&query)), i)
+        })
+        .collect();
+    expected.sort_by(|a, b| b.0.total_cmp(&a.0));
+    let expected: Vec<String> = expected[..10]
+        .iter()
+        .map(|(_, i)| format!("v{i}"))
+        .collect();
+
+    let outcome = store
+        .search_detailed(
+            "docs",
+            SearchRequest {
+                vector: query,
+                k: 10,
+                filters: Some(json!({"parity": 0})),
+                include_meta: None,
+                tie_break: None,
+                include_vector: None,
+                min_score: None,
+                offset: None,
+                exact: Some(true),
+                with_total: None,
+            },
+        )
+        .unwrap();
+    assert!(outcome.exact);
+    let ids: Vec<String> = outcome.hits.into_iter().map(|h| h.id).collect();
+    assert_eq!(ids, expected);
+}
+
+#[test]
+fn keyword_index_caps_values_per_field_and_filters_by_scan() {
+    let mut settings = VectorSettings::default();
+    settings.max_index_values_per_field = 16;
+    let store = VectorStore::with_settings(settings);
+    store.create_collection("docs", 4, Metric::Cosine).unwrap();
+    for i in 0..100 {
+        store
+            .upsert(
+                "docs",
+                &format!("v{i}"),
+                VectorItem {
+                    vector: vec![1.0, i as f32 / 100.0, 0.5, 0.25],
+                    meta: json!({"tag": format!("t{i}"), "group": format!("g{}", i % 4)}),
+                    revision: 0,
+                    expires_at_ms: None,
+                },
+            )
+            .unwrap();
+    }
+
+    let stats = store.collection_stats("docs").unwrap();
+    assert_eq!(stats.keyword_index_values.get("group"), Some(&4));
+    assert!(!stats.keyword_index_values.contains_key("tag"));
+    assert_eq!(stats.unindexed_fields, vec!["tag".to_string()]);
+
+    let search = |filters: serde_json::Value| {
+        let mut ids: Vec<String> = store
+            .search(
+                "docs",
+                SearchRequest {
+                    vector: vec![1.0, 0.5, 0.5, 0.25],
+                    k: 10,
+                    filters: Some(filters),
+                    include_meta: None,
+                    tie_break: None,
+                    include_vector: None,
+                    min_score: None,
+                    offset: None,
+                    exact: None,
+                    with_total: None,
+                },
+            )
+            .unwrap()
+            .into_iter()
+            .map(|h| h.id)
+            .collect();
+        ids.sort();
+        ids
+    };
+    assert_eq!(search(json!({"tag": "t57"})), vec!["v57".to_string()]);
+    assert_eq!(
+        search(json!({"tag": {"$in": ["t3", "t98"]}})),
+        vec!["v3".to_string(), "v98".to_string()]
+    );
+    assert_eq!(
+        search(json!({"group": "g1", "tag": "t57"})),
+        vec!["v57".to_string()]
+    );
+    assert!(search(json!({"group": "g1", "tag": "t58"})).is_empty());
+
+    // Later writes keep the field out of the index.
+    store
+        .upsert(
+            "docs",
+            "extra",
+            VectorItem {
+                vector: vec![1.0, 0.5, 0.5, 0.25],
+                meta: json!({"tag": "t57"}),
+                revision: 0,
+                expires_at_ms: None,
+            },
+        )
+        .unwrap();
+    let stats = store.collection_stats("docs").unwrap();
+    assert!(!stats.keyword_index_values.contains_key("tag"));
+    assert_eq!(
+        search(json!({"tag": "t57"})),
+        vec!["extra".to_string(), "v57".to_string()]
+    );
+}
+
+#[test]
+fn with_total_counts_every_vector_matching_an_indexed_filter() {
+    let store = VectorStore::new();
+    store.create_collection("docs", 4, Metric::Cosine).unwrap();
+    let mut rng = StdRng::seed_from_u64(11);
+    for i in 0..900 {
+        store
+            .upsert(
+                "docs",
+                &format!("v{i}"),
+                VectorItem {
+                    vector: (0..4).map(|_| rng.gen::<f32>()).collect(),
+                    meta: json!({"group": format!("g{}", i % 3), "rank": i}),
+                    revision: 0,
+                    expires_at_ms: None,
+                },
+            )
+            .unwrap();
+    }
+    // One expired and one live TTL item: only the live one is counted.
+    for (id, expires_at_ms) in [("expired", 1), ("later", u64::MAX)] {
+        store
+            .upsert(
+                "docs",
+                id,
+                VectorItem {
+                    vector: vec![0.1, 0.2, 0.3, 0.4],
+                    meta: json!({"group": "ttl"}),
+                    revision: 0,
+                    expires_at_ms: Some(expires_at_ms),
+                },
+            )
+            .unwrap();
+    }
+
+    let page = |filters: Option<serde_json::Value>, with_total: Option<bool>| {
+        store
+            .search_detailed(
+                "docs",
+                SearchRequest {
+                    vector: vec![0.5, 0.5, 0.5, 0.5],
+                    k: 5,
+                    filters,
+                    include_meta: None,
+                    tie_break: None,
+                    include_vector: None,
+                    min_score: None,
+                    offset: Some(10),
+                    exact: None,
+                    with_total,
+                },
+            )
+            .unwrap()
+    };
+    let filtered = page(Some(json!({"group": "g1"})), Some(true));
+    assert_eq!(filtered.hits.len(), 5);
+    assert_eq!(filtered.total, Some(300));
+    let narrowed = page(
+        Some(json!({"$and": [{"group": "g1"}, {"rank": {"$lt": 30}}]})),
+        Some(true),
+    );
+    assert_eq!(narrowed.total, Some(10));
+    assert_eq!(page(None, Some(true)).total, Some(901));
+    assert_eq!(page(Some(json!({"group": "g1"})), None).total, None);
+}
@@ -1,12 +1,20 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 use rust_kiss_vdb::config::Config;
-use rust_kiss_vdb::engine::Engine;
+use rust_kiss_vdb::engine::{Engine, EngineError, EventRecord};
 use rust_kiss_vdb::vector::index::DiskAnnBuildParams;
-use rust_kiss_vdb::vector::{Metric, SearchRequest, VectorItem};
+use rust_kiss_vdb::vector::{
+    ExportQuantize, HnswParams, Metric, SearchHit, SearchRequest, VectorError, VectorItem,
+    VectorSettings, VectorStore,
+};
 use serde_json::json;
 use std::collections::HashSet;
 use std::fs::{self, OpenOptions};
 use std::io::{Read, Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering as AtomicOrdering};
+use std::sync::Arc;
+use tokio_util::sync::CancellationToken;
 
 fn config_with_dir(dir: &str) -> Config {
     Config {
@@ -51,6 +59,30 @@ fn config_with_dir(dir: &str) -> Config {
         run_retention: 4,
         compaction_trigger_tombstone_ratio: 0.2,
         compaction_max_bytes_per_pass: 64 * 1024 * 1024,
+        verify_on_start: false,
+        max_response_bytes: 8 * 1024 * 1024,
+        default_durability: "sync".to_string(),
+        max_vectors_per_collection: 0,
+        embed_cache_size: 256,
+        sse_idle_timeout_secs: 0,
+        value_compress_min_bytes: 0,
+        wal_truncate_after_snapshot: false,
+        max_list_limit: 1000,
+        strict_query_dim: true,
+        sqlite_busy_retries: 3,
+        vector_segment_max_items: 8192,
+        vector_merge_tombstone_ratio: 0.3,
+        ttl_grace_ms: 0,
+        max_index_values_per_field: 0,
+        response_compress_min_bytes: 0,
+        wal_fsync_policy: "data".to_string(),
+        max_doc_indexed_fields: 0,
+        lock_wait_warn_ms: 100,
+        sqlite_readonly: false,
+        sqlite_migrations_dir: None,
+        embed_url: None,
+        embed_model: "default".to_string(),
+        embed_timeout_ms: 10_000,
     }
 }
 
@@ -110,7 +142,7 @@ async fn vector_persistence_restart_search() {
     let data_dir = dir.path().to_string_lossy().to_string();
     let config = config_with_dir(&data_dir);
 
-    let engine = Engine::new(config.clone()).unwrap();
+    let engine = Engine::new(config.clone(), CancellationToken::new()).unwrap();
     engine
         .create_vector_collection("docs", 3, Metric::Cosine)
         .unwrap();
@@ -121,12 +153,15 @@ async fn vector_persistence_restart_search() {
             VectorItem {
                 vector: vec![1.0, 0.0, 0.0],
                 meta: json!({"tag": "persist"}),
+                revision: 0,
+                expires_at_ms: None,
             },
+            None,
         )
         .unwrap();
     drop(engine);
 
-    let engine2 = Engine::new(config).unwrap();
+    let engine2 = Engine::new(config, CancellationToken::new()).unwrap();
     let hits = engine2
         .vector_search(
             "docs",
@@ -135,6 +170,12 @@ async fn vector_persistence_restart_search() {
                 k: 1,
                 filters: None,
                 include_meta: Some(true),
+                tie_break: None,
+                include_vector: None,
+                min_score: None,
+                offset: None,
+                exact: None,
+                with_total: None,
             },
         )
         .unwrap();
@@ -149,7 +190,7 @@ async fn vector_rebuild_handles_many_vectors() {
     let data_dir = dir.path().to_string_lossy().to_string();
     let config = config_with_dir(&data_dir);
 
-    let engine = Engine::new(config.clone()).unwrap();
+    let engine = Engine::new(config.clone(), CancellationToken::new()).unwrap();
     engine
         .create_vector_collection("docs", 2, Metric::Cosine)
         .unwrap();
@@ -162,13 +203,16 @@ async fn vector_rebuild_handles_many_vectors() {
                 VectorItem {
                     vector: vec![weight, 1.0 - weight],
                     meta: json!({ "i": i }),
+                    revision: 0,
+                    expires_at_ms: None,
                 },
+                None,
             )
             .unwrap();
     }
     drop(engine);
 
-    let engine2 = Engine::new(config).unwrap();
+    let engine2 = Engine::new(config, CancellationToken::new()).unwrap();
     let hits = engine2
         .vector_search(
             "docs",
@@ -177,6 +221,12 @@ async fn vector_rebuild_handles_many_vectors() {
                 k: 3,
                 filters: None,
                 include_meta: Some(false),
+                tie_break: None,
+                include_vector: None,
+                min_score: None,
+                offset: None,
+                exact: None,
+                with_total: None,
             },
         )
         .unwrap();
@@ -190,7 +240,7 @@ async fn vector_delete_update_persisted() {
     let data_dir = dir.path().to_string_lossy().to_string();
     let config = config_with_dir(&data_dir);
 
-    let engine = Engine::new(config.clone()).unwrap();
+    let engine = Engine::new(config.clone(), CancellationToken::new()).unwrap();
     engine
         .create_vector_collection("docs", 3, Metric::Cosine)
         .unwrap();
@@ -201,7 +251,10 @@ async fn vector_delete_update_persisted() {
             VectorItem {
                 vector: vec![0.0, 1.0, 0.0],
                 meta: json!({"state": "keep"}),
+                revision: 0,
+                expires_at_ms: None,
             },
+            None,
         )
         .unwrap();
     engine
@@ -211,16 +264,19 @@ async fn vector_delete_update_persisted() {
             VectorItem {
                 vector: vec![1.0, 0.0, 0.0],
                 meta: json!({"state": "gone"}),
+                revision: 0,
+                expires_at_ms: None,
             },
+            None,
         )
         .unwrap();
     engine.vector_delete("docs", "gone").unwrap();
     engine
-        .vector_update("docs", "keep", Some(vec![0.0, 0.0, 1.0]), None)
+        .vector_update("docs", "keep", Some(vec![0.0, 0.0, 1.0]), None, None)
         .unwrap();
     drop(engine);
 
-    let engine2 = Engine::new(config).unwrap();
+    let engine2 = Engine::new(config, CancellationToken::new()).unwrap();
     assert!(engine2.vector_get("docs", "gone").unwrap().is_none());
     let hits = engine2
         .vector_search(
@@ -230,19 +286,284 @@ async fn vector_delete_update_persisted() {
                 k: 1,
                 filters: None,
                 include_meta: Some(false),
+                tie_break: None,
+                include_vector: None,
+                min_score: None,
+                offset: None,
+                exact: None,
+                with_total: None,
             },
         )
         .unwrap();
     assert_eq!(hits.first().map(|h| h.id.as_str()), Some("keep"));
 }
 
+#[tokio::test]
+async fn dropped_collection_is_removed_from_disk_and_replay() {
+    let dir = tempfile::tempdir().unwrap();
+    let data_dir = dir.path().to_string_lossy().to_string();
+    let mut config = config_with_dir(&data_dir);
+    config.verify_on_start = true;
+
+    let engine = Engine::new(config.clone(), CancellationToken::new()).unwrap();
+    for name in ["docs", "keep"] {
+        engine
+            .create_vector_collection(name, 2, Metric::Cosine)
+            .unwrap();
+        engine
+            .vector_upsert(
+                name,
+                "a",
+                VectorItem {
+                    vector: vec![1.0, 0.0],
+                    meta: json!({}),
+                    revision: 0,
+                    expires_at_ms: None,
+                },
+                None,
+            )
+            .unwrap();
+    }
+    engine.drop_vector_collection("docs").unwrap();
+    assert!(!Path::new(&data_dir).join("vectors").join("docs").exists());
+    assert!(matches!(
+        engine.drop_vector_collection("docs"),
+        Err(EngineError::Vector(VectorError::CollectionNotFound))
+    ));
+    drop(engine);
+
+    let engine2 = Engine::new(config, CancellationToken::new()).unwrap();
+    let names: Vec<String> = engine2
+        .list_vector_collections()
+        .into_iter()
+        .map(|info| info.collection)
+        .collect();
+    assert_eq!(names, vec!["keep".to_string()]);
+    assert!(engine2.vector_get("keep", "a").unwrap().is_some());
+    engine2
+        .create_vector_collection("docs", 3, Metric::Dot)
+        .unwrap();
+    assert!(engine2.vector_get("docs", "a").unwrap().is_none());
+}
+
+#[test]
+fn replayed_collection_drop_is_idempotent() {
+    let dir = tempfile::tempdir().unwrap();
+    let store = VectorStore::open(dir.path()).unwrap();
+    store.create_collection("docs", 2, Metric::Cosine).unwrap();
+    let event = EventRecord {
+        offset: 10,
+        ts_ms: 0,
+        event_type: "vector_collection_deleted".to_string(),
+        data: json!({"collection": "docs"}),
+    };
+    store.apply_event(&event).unwrap();
+    store.apply_event(&event).unwrap();
+    assert!(store.get_collection("docs").is_none());
+    assert!(!dir.path().join("vectors").join("docs").exists());
+}
+
+#[test]
+fn hnsw_params_reject_beam_widths_past_the_cap() {
+    let with = |f: fn(&mut HnswParams)| {
+        let mut params = HnswParams::default();
+        f(&mut params);
+        params.validate()
+    };
+    assert!(with(|p| p.ef_construction = 10_000).is_ok());
+    assert!(with(|p| p.ef_search = Some(10_000)).is_ok());
+    let err = with(|p| p.ef_construction = 10_001).unwrap_err();
+    assert!(err.contains("ef_construction"), "{err}");
+    let err = with(|p| p.ef_search = Some(10_001)).unwrap_err();
+    assert!(err.contains("ef_search"), "{err}");
+    assert!(with(|p| p.ef_search_floor = 10_001).is_err());
+}
+
+#[tokio::test]
+async fn hnsw_params_persist_in_manifest() {
+    let dir = tempfile::tempdir().unwrap();
+    let data_dir = dir.path().to_string_lossy().to_string();
+    let config = config_with_dir(&data_dir);
+    let tuned = HnswParams {
+        max_nb_conn: 32,
+        ef_construction: 400,
+        ef_search: Some(128),
+        ..HnswParams::default()
+    };
+
+    let engine = Engine::new(config.clone(), CancellationToken::new()).unwrap();
+    engine
+        .create_vector_collection_with_hnsw("tuned", 2, Metric::Cosine, Some(tuned))
+        .unwrap();
+    engine
+        .create_vector_collection("plain", 2, Metric::Cosine)
+        .unwrap();
+    for i in 0..20 {
+        engine
+            .vector_upsert(
+                "tuned",
+                &format!("v{i}"),
+                VectorItem {
+                    vector: vec![1.0, i as f32 / 10.0],
+                    meta: json!({}),
+                    revision: 0,
+                    expires_at_ms: None,
+                },
+                None,
+            )
+            .unwrap();
+    }
+    drop(engine);
+
+    let manifest = read_manifest_json(&data_dir, "tuned");
+    assert_eq!(
+        manifest["hnsw"],
+        json!({
+            "max_nb_conn": 32,
+            "ef_construction": 400,
+            "ef_search": 128,
+            "ef_search_floor": 50
+        })
+    );
+    let plain = read_manifest_json(&data_dir, "plain");
+    assert_eq!(
+        plain["hnsw"],
+        json!({"max_nb_conn": 16, "ef_construction": 200, "ef_search_floor": 50})
+    );
+
+    let engine2 = Engine::new(config, CancellationToken::new()).unwrap();
+    let hits = engine2
+        .vector_search(
+            "tuned",
+            SearchRequest {
+                vector: vec![1.0, 0.0],
+                k: 1,
+                filters: None,
+                include_meta: Some(false),
+                tie_break: None,
+                include_vector: None,
+                min_score: None,
+                offset: None,
+                exact: None,
+                with_total: None,
+            },
+        )
+        .unwrap();
+    assert_eq!(hits.first().map(|h| h.id.as_str()), Some("v0"));
+}
+
+#[test]
+fn ef_search_floor_keeps_top1_close_to_exact() {
+    let mut rng = StdRng::seed_from_u64(7);
+    let dim = 32;
+    let vectors: Vec<Vec<f32>> = (0..3000)
+        .map(|_| (0..dim).map(|_| rng.gen_range(-1.0..1.0)).collect())
+        .collect();
+    let queries: Vec<Vec<f32>> = (0..50)
+        .map(|_| (0..dim).map(|_| rng.gen_range(-1.0..1.0)).collect())
+        .collect();
+
+    let store = VectorStore::new();
+    for (name, floor) in [("low", 1), ("high", 400)] {
+        let params = HnswParams {
+            max_nb_conn: 4,
+            ef_search_floor: floor,
+            ..HnswParams::default()
+        };
+        store
+            .create_collection_with_hnsw(name, dim, Metric::Cosine, params)
+            .unwrap();
+        for (i, v) in vectors.iter().enumerate() {
+            store
+                .upsert(
+                    name,
+                    &format!("v{i}"),
+                    VectorItem {
+                        vector: v.clone(),
+                        meta: json!({}),
+                        revision: 0,
+                        expires_at_ms: None,
+                    },
+                )
+                .unwrap();
+        }
+    }
+
+    let request = |vector: &Vec<f32>| SearchRequest {
+        vector: vector.clone(),
+        k: 1,
+        filters: None,
+        include_meta: None,
+        tie_break: None,
+        include_vector: None,
+        min_score: None,
+        offset: None,
+        exact: None,
+        with_total: None,
+    };
+    let top1_matches = |collection: &str| {
+        queries
+            .iter()
+            .filter(|q| {
+                let approx = store.search(collection, request(q)).unwrap();
+                let exact = store.search_exact(collection, request(q)).unwrap();
+                approx.first().map(|h| &h.id) == exact.first().map(|h| &h.id)
+            })
+            .count()
+    };
+    let low = top1_matches("low");
+    let high = top1_matches("high");
+    assert!(
+        high >= low,
+        "floor 400 matched {high}, floor 1 matched {low}"
+    );
+    assert!(high >= 45, "floor 400 matched only {high}/50");
+}
+
+#[test]
+fn direct_store_writes_survive_reopen() {
+    let dir = tempfile::tempdir().unwrap();
+    {
+        let store = VectorStore::open(dir.path()).unwrap();
+        store.create_collection("docs", 2, Metric::Cosine).unwrap();
+        for id in ["a", "b", "c"] {
+            store
+                .upsert(
+                    "docs",
+                    id,
+                    VectorItem {
+                        vector: vec![1.0, 0.0],
+                        meta: json!({"id": id}),
+                        revision: 0,
+                        expires_at_ms: None,
+                    },
+                )
+                .unwrap();
+        }
+        store
+            .update("docs", "b", Some(vec![0.0, 1.0]), None)
+            .unwrap();
+        store.delete("docs", "c").unwrap();
+    }
+
+    let store = VectorStore::open(dir.path()).unwrap();
+    let a = store.get("docs", "a").unwrap().unwrap();
+    assert_eq!(a.vector, vec![1.0, 0.0]);
+    assert_eq!(a.meta, json!({"id": "a"}));
+    assert_eq!(
+        store.get("docs", "b").unwrap().unwrap().vector,
+        vec![0.0, 1.0]
+    );
+    assert!(store.get("docs", "c").unwrap().is_none());
+}
+
 #[tokio::test]
 async fn vector_runs_tail_truncation_safe() {
     let dir = tempfile::tempdir().unwrap();
     let data_dir = dir.path().to_string_lossy().to_string();
     let config = config_with_dir(&data_dir);
 
-    let engine = Engine::new(config.clone()).unwrap();
+    let engine = Engine::new(config.clone(), CancellationToken::new()).unwrap();
     engine
         .create_vector_collection("docs", 4, Metric::Cosine)
         .unwrap();
@@ -254,7 +575,10 @@ async fn vector_runs_tail_truncation_safe() {
                 VectorItem {
                     vector: vec![idx as f32, 1.0, 0.0, 0.0],
                     meta: json!({ "idx": idx }),
+                    revision: 0,
+                    expires_at_ms: None,
                 },
+                None,
             )
             .unwrap();
     }
@@ -269,7 +593,7 @@ async fn vector_runs_tail_truncation_safe() {
     file.flush().unwrap();
     drop(file);
 
-    let reopened = Engine::new(config).unwrap();
+    let reopened = Engine::new(config, CancellationToken::new()).unwrap();
     assert!(reopened.vector_get("docs", "id-0").unwrap().is_some());
     let hits = reopened
         .vector_search(
@@ -279,6 +603,12 @@ async fn vector_runs_tail_truncation_safe() {
                 k: 5,
                 filters: None,
                 include_meta: Some(false),
+                tie_break: None,
+                include_vector: None,
+                min_score: None,
+                offset: None,
+                exact: None,
+                with_total: None,
             },
         )
         .unwrap();
@@ -294,7 +624,7 @@ async fn vector_runs_checksum_detection() {
     let data_dir = dir.path().to_string_lossy().to_string();
     let config = config_with_dir(&data_dir);
 
-    let engine = Engine::new(config.clone()).unwrap();
+    let engine = Engine::new(config.clone(), CancellationToken::new()).unwrap();
     engine
         .create_vector_collection("docs", 4, Metric::Cosine)
         .unwrap();
@@ -306,7 +636,10 @@ async fn vector_runs_checksum_detection() {
                 VectorItem {
                     vector: vec![idx as f32, 0.0, 1.0, 0.0],
                     meta: json!({ "idx": idx }),
+                    revision: 0,
+                    expires_at_ms: None,
                 },
+                None,
             )
             .unwrap();
     }
@@ -329,7 +662,7 @@ async fn vector_runs_checksum_detection() {
     file.flush().unwrap();
     drop(file);
 
-    let reopened = Engine::new(config).unwrap();
+    let reopened = Engine::new(config, CancellationToken::new()).unwrap();
     assert!(
         reopened.vector_get("docs", "crc-0").unwrap().is_some(),
         "earlier records must still load"
@@ -348,7 +681,7 @@ async fn vector_q8_run_roundtrip() {
     config.ivf_clusters = 2;
     config.ivf_nprobe = 1;
 
-    let engine = Engine::new(config.clone()).unwrap();
+    let engine = Engine::new(config.clone(), CancellationToken::new()).unwrap();
     engine
         .create_vector_collection("docs", 3, Metric::Cosine)
         .unwrap();
@@ -359,7 +692,10 @@ async fn vector_q8_run_roundtrip() {
             VectorItem {
                 vector: vec![1.0, 0.0, 0.0],
                 meta: json!({"dir": "north"}),
+                revision: 0,
+                expires_at_ms: None,
             },
+            None,
         )
         .unwrap();
     engine
@@ -369,7 +705,10 @@ async fn vector_q8_run_roundtrip() {
             VectorItem {
                 vector: vec![0.0, 1.0, 0.0],
                 meta: json!({"dir": "east"}),
+                revision: 0,
+                expires_at_ms: None,
             },
+            None,
         )
         .unwrap();
     engine
@@ -379,7 +718,10 @@ async fn vector_q8_run_roundtrip() {
             VectorItem {
                 vector: vec![0.0, -1.0, 0.0],
                 meta: json!({"dir": "west"}),
+                revision: 0,
+                expires_at_ms: None,
             },
+            None,
         )
         .unwrap();
     drop(engine);
@@ -394,7 +736,7 @@ async fn vector_q8_run_roundtrip() {
         fs::remove_file(&legacy).unwrap();
     }
 
-    let reopened = Engine::new(config).unwrap();
+    let reopened = Engine::new(config, CancellationToken::new()).unwrap();
     let hits = reopened
         .vector_search(
             "docs",
@@ -403,6 +745,12 @@ async fn vector_q8_run_roundtrip() {
                 k: 1,
                 filters: None,
                 include_meta: Some(true),
+                tie_break: None,
+                include_vector: None,
+                min_score: None,
+                offset: None,
+                exact: None,
+                with_total: None,
             },
         )
         .unwrap();
@@ -419,7 +767,7 @@ async fn vector_run_retention_compacts_old_runs() {
     config.run_target_bytes = 512;
     config.run_retention = 1;
 
-    let engine = Engine::new(config.clone()).unwrap();
+    let engine = Engine::new(config.clone(), CancellationToken::new()).unwrap();
     engine
         .create_vector_collection("docs", 6, Metric::Cosine)
         .unwrap();
@@ -438,7 +786,10 @@ async fn vector_run_retention_compacts_old_runs() {
                         1.0 - (idx as f32 / 200.0),
                     ],
                     meta: json!({ "idx": idx }),
+                    revision: 0,
+                    expires_at_ms: None,
                 },
+                None,
             )
             .unwrap();
     }
@@ -457,7 +808,7 @@ async fn vector_run_retention_compacts_old_runs() {
     let removed = before_set.difference(&after_set).count();
     assert!(removed > 0, "old run files should be removed");
     drop(engine);
-    let reopened = Engine::new(config).unwrap();
+    let reopened = Engine::new(config, CancellationToken::new()).unwrap();
     assert!(
         reopened.vector_get("docs", "doc-0").unwrap().is_some(),
         "data must survive retention compaction"
@@ -473,7 +824,7 @@ async fn vector_compaction_triggers_on_tombstones() {
     config.run_retention = 8;
     config.compaction_trigger_tombstone_ratio = 0.01;
 
-    let engine = Engine::new(config.clone()).unwrap();
+    let engine = Engine::new(config.clone(), CancellationToken::new()).unwrap();
     engine
         .create_vector_collection("docs", 3, Metric::Cosine)
         .unwrap();
@@ -485,7 +836,10 @@ async fn vector_compaction_triggers_on_tombstones() {
                 VectorItem {
                     vector: vec![idx as f32, 1.0, 0.0],
                     meta: json!({ "idx": idx }),
+                    revision: 0,
+                    expires_at_ms: None,
                 },
+                None,
             )
             .unwrap();
     }
@@ -508,7 +862,7 @@ async fn vector_compaction_triggers_on_tombstones() {
         "tombstone ratio trigger should rewrite runs"
     );
 
-    let reopened = Engine::new(config).unwrap();
+    let reopened = Engine::new(config, CancellationToken::new()).unwrap();
     for idx in 0..16usize {
         assert!(
             reopened
@@ -534,7 +888,7 @@ async fn vector_manifest_settings_persisted() {
     config.compaction_trigger_tombstone_ratio = 0.35;
     config.compaction_max_bytes_per_pass = 16 * 1024;
 
-    let engine = Engine::new(config.clone()).unwrap();
+    let engine = Engine::new(config.clone(), CancellationToken::new()).unwrap();
     engine
         .create_vector_collection("docs", 4, Metric::Cosine)
         .unwrap();
@@ -546,7 +900,10 @@ async fn vector_manifest_settings_persisted() {
                 VectorItem {
                     vector: vec![idx as f32, 0.0, 1.0, 0.5],
                     meta: json!({ "idx": idx }),
+                    revision: 0,
+                    expires_at_ms: None,
                 },
+                None,
             )
             .unwrap();
     }
@@ -570,7 +927,7 @@ async fn vector_manifest_settings_persisted() {
 
     let mut config2 = config.clone();
     config2.run_target_bytes = 1024;
-    let engine2 = Engine::new(config2.clone()).unwrap();
+    let engine2 = Engine::new(config2.clone(), CancellationToken::new()).unwrap();
     engine2
         .vector_upsert(
             "docs",
@@ -578,7 +935,10 @@ async fn vector_manifest_settings_persisted() {
             VectorItem {
                 vector: vec![1.0, 1.0, 1.0, 1.0],
                 meta: json!({ "state": "extra" }),
+                revision: 0,
+                expires_at_ms: None,
             },
+            None,
         )
         .unwrap();
     drop(engine2);
@@ -601,7 +961,7 @@ async fn vector_compaction_budget_multiple_passes() {
     config.compaction_trigger_tombstone_ratio = 0.1;
     config.compaction_max_bytes_per_pass = 600;
 
-    let engine = Engine::new(config.clone()).unwrap();
+    let engine = Engine::new(config.clone(), CancellationToken::new()).unwrap();
     engine
         .create_vector_collection("docs", 3, Metric::Cosine)
         .unwrap();
@@ -613,7 +973,10 @@ async fn vector_compaction_budget_multiple_passes() {
                 VectorItem {
                     vector: vec![idx as f32, 0.0, 1.0],
                     meta: json!({ "idx": idx }),
+                    revision: 0,
+                    expires_at_ms: None,
                 },
+                None,
             )
             .unwrap();
     }
@@ -654,6 +1017,12 @@ async fn vector_compaction_budget_multiple_passes() {
                 k: 1,
                 filters: None,
                 include_meta: Some(false),
+                tie_break: None,
+                include_vector: None,
+                min_score: None,
+                offset: None,
+                exact: None,
+                with_total: None,
             },
         )
         .unwrap();
@@ -669,7 +1038,7 @@ async fn vector_disk_index_manifest_roundtrip() {
     let data_dir = dir.path().to_string_lossy().to_string();
     let config = config_with_dir(&data_dir);
 
-    let engine = Engine::new(config.clone()).unwrap();
+    let engine = Engine::new(config.clone(), CancellationToken::new()).unwrap();
     engine
         .create_vector_collection("docs", 3, Metric::Cosine)
         .unwrap();
@@ -680,7 +1049,10 @@ async fn vector_disk_index_manifest_roundtrip() {
             VectorItem {
                 vector: vec![1.0, 0.0, 0.0],
                 meta: json!({"k": "a"}),
+                revision: 0,
+                expires_at_ms: None,
             },
+            None,
         )
         .unwrap();
     engine
@@ -690,7 +1062,10 @@ async fn vector_disk_index_manifest_roundtrip() {
             VectorItem {
                 vector: vec![0.0, 1.0, 0.0],
                 meta: json!({"k": "b"}),
+                revision: 0,
+                expires_at_ms: None,
             },
+            None,
         )
         .unwrap();
     let params = DiskAnnBuildParams {
@@ -727,7 +1102,7 @@ async fn vector_disk_index_manifest_roundtrip() {
         .join(graph_path);
     assert!(graph_full.exists(), "disk graph file should exist");
 
-    let reopened = Engine::new(config).unwrap();
+    let reopened = Engine::new(config, CancellationToken::new()).unwrap();
     reopened.vector_drop_disk_index("docs").unwrap();
     let manifest2 = read_manifest_json(&data_dir, "docs");
     assert!(
@@ -746,7 +1121,7 @@ async fn vector_diskann_search_roundtrip() {
     let mut config = config_with_dir(&data_dir);
     config.index_kind = "DISKANN".to_string();
 
-    let engine = Engine::new(config.clone()).unwrap();
+    let engine = Engine::new(config.clone(), CancellationToken::new()).unwrap();
     engine
         .create_vector_collection("docs", 3, Metric::Cosine)
         .unwrap();
@@ -757,7 +1132,10 @@ async fn vector_diskann_search_roundtrip() {
             VectorItem {
                 vector: vec![1.0, 0.0, 0.0],
                 meta: json!({"dir": "north"}),
+                revision: 0,
+                expires_at_ms: None,
             },
+            None,
         )
         .unwrap();
     engine
@@ -767,7 +1145,10 @@ async fn vector_diskann_search_roundtrip() {
             VectorItem {
                 vector: vec![0.0, 1.0, 0.0],
                 meta: json!({"dir": "east"}),
+                revision: 0,
+                expires_at_ms: None,
             },
+            None,
         )
         .unwrap();
     engine
@@ -777,7 +1158,10 @@ async fn vector_diskann_search_roundtrip() {
             VectorItem {
                 vector: vec![0.0, -1.0, 0.0],
                 meta: json!({"dir": "west"}),
+                revision: 0,
+                expires_at_ms: None,
             },
+            None,
         )
         .unwrap();
     let params = DiskAnnBuildParams {
@@ -790,7 +1174,7 @@ async fn vector_diskann_search_roundtrip() {
         .expect("build disk index");
     drop(engine);
 
-    let engine2 = Engine::new(config).unwrap();
+    let engine2 = Engine::new(config, CancellationToken::new()).unwrap();
     let hits = engine2
         .vector_search(
             "docs",
@@ -799,6 +1183,12 @@ async fn vector_diskann_search_roundtrip() {
                 k: 1,
                 filters: None,
                 include_meta: Some(true),
+                tie_break: None,
+                include_vector: None,
+                min_score: None,
+                offset: None,
+                exact: None,
+                with_total: None,
             },
         )
         .unwrap();
@@ -806,3 +1196,1082 @@ async fn vector_diskann_search_roundtrip() {
     assert_eq!(hits[0].id, "north");
     assert_eq!(hits[0].meta.as_ref().unwrap()["dir"], "north");
 }
+
+#[tokio::test]
+async fn verify_on_start_accepts_clean_replay() {
+    let dir = tempfile::tempdir().unwrap();
+    let data_dir = dir.path().to_string_lossy().to_string();
+    let mut config = config_with_dir(&data_dir);
+    config.verify_on_start = true;
+
+    let engine = Engine::new(config.clone(), CancellationToken::new()).unwrap();
+    engine
+        .create_vector_collection("docs", 2, Metric::Cosine)
+        .unwrap();
+    for i in 0..8 {
+        engine
+            .vector_upsert(
+                "docs",
+                &format!("id{i}"),
+                VectorItem {
+                    vector: vec![1.0, i as f32],
+                    meta: json!({ "i": i }),
+                    revision: 0,
+                    expires_at_ms: None,
+                },
+                None,
+            )
+            .unwrap();
+    }
+    engine
+        .put_state("k".to_string(), json!({"v": 1}), None, None)
+        .unwrap();
+    drop(engine);
+
+    let engine2 = Engine::new(config, CancellationToken::new()).unwrap();
+    assert!(engine2.vector_get("docs", "id7").unwrap().is_some());
+}
+
+#[tokio::test]
+async fn verify_on_start_flags_tampered_applied_offset() {
+    let dir = tempfile::tempdir().unwrap();
+    let data_dir = dir.path().to_string_lossy().to_string();
+    let mut config = config_with_dir(&data_dir);
+    config.verify_on_start = true;
+
+    let engine = Engine::new(config.clone(), CancellationToken::new()).unwrap();
+    engine
+        .create_vector_collection("docs", 2, Metric::Cosine)
+        .unwrap();
+    engine
+        .vector_upsert(
+            "docs",
+            "a",
+            VectorItem {
+                vector: vec![1.0, 0.0],
+                meta: json!({}),
+                revision: 0,
+                expires_at_ms: None,
+            },
+            None,
+        )
+        .unwrap();
+    drop(engine);
+
+    let manifest_path = Path::new(&data_dir)
+        .join("vectors")
+        .join("docs")
+        .join("manifest.json");
+    let mut manifest = read_manifest_json(&data_dir, "docs");
+    manifest["applied_offset"] = json!(1_000_000);
+    fs::write(&manifest_path, serde_json::to_vec(&manifest).unwrap()).unwrap();
+
+    let err = match Engine::new(config.clone(), CancellationToken::new()) {
+        Ok(_) => panic!("tampered applied offset should fail verification"),
+        Err(err) => format!("{err:#}"),
+    };
+    assert!(err.contains("replay consistency check failed"), "{err}");
+    assert!(err.contains("docs"), "{err}");
+
+    config.verify_on_start = false;
+    assert!(Engine::new(config, CancellationToken::new()).is_ok());
+}
+
+#[tokio::test]
+async fn vector_norm_stats_track_raw_magnitudes() {
+    let dir = tempfile::tempdir().unwrap();
+    let data_dir = dir.path().to_string_lossy().to_string();
+    let config = config_with_dir(&data_dir);
+
+    let engine = Engine::new(config.clone(), CancellationToken::new()).unwrap();
+    engine
+        .create_vector_collection("docs", 2, Metric::Cosine)
+        .unwrap();
+    for (i, vector) in [vec![3.0, 4.0], vec![0.6, 0.8], vec![0.0, 10.0]]
+        .into_iter()
+        .enumerate()
+    {
+        engine
+            .vector_upsert(
+                "docs",
+                &format!("id{i}"),
+                VectorItem {
+                    vector,
+                    meta: json!({}),
+                    revision: 0,
+                    expires_at_ms: None,
+                },
+                None,
+            )
+            .unwrap();
+    }
+
+    let stats = engine
+        .vector_collection_info("docs")
+        .unwrap()
+        .norm_stats
+        .unwrap();
+    assert_eq!(stats.count, 3);
+    assert!((stats.min - 1.0).abs() < 1e-5);
+    assert!((stats.max - 10.0).abs() < 1e-5);
+    assert!((stats.mean - 16.0 / 3.0).abs() < 1e-4);
+    drop(engine);
+
+    let engine2 = Engine::new(config, CancellationToken::new()).unwrap();
+    let reopened = engine2
+        .vector_collection_info("docs")
+        .unwrap()
+        .norm_stats
+        .unwrap();
+    assert_eq!(reopened, stats);
+}
+
+#[tokio::test]
+async fn dot_collections_return_raw_vectors() {
+    let dir = tempfile::tempdir().unwrap();
+    let data_dir = dir.path().to_string_lossy().to_string();
+    let config = config_with_dir(&data_dir);
+
+    let engine = Engine::new(config.clone(), CancellationToken::new()).unwrap();
+    engine
+        .create_vector_collection("docs", 2, Metric::Dot)
+        .unwrap();
+    for (id, vector) in [("a", vec![3.0, 4.0]), ("b", vec![0.0, -2.0])] {
+        engine
+            .vector_upsert(
+                "docs",
+                id,
+                VectorItem {
+                    vector,
+                    meta: json!({}),
+                    revision: 0,
+                    expires_at_ms: None,
+                },
+                None,
+            )
+            .unwrap();
+    }
+    assert_eq!(
+        engine.vector_get("docs", "a").unwrap().unwrap().vector,
+        vec![3.0, 4.0]
+    );
+
+    // Scores are still computed on the normalized vectors.
+    let search = |engine: &Engine| {
+        engine
+            .vector_search(
+                "docs",
+                SearchRequest {
+                    vector: vec![6.0, 8.0],
+                    k: 2,
+                    filters: None,
+                    include_meta: None,
+                    tie_break: None,
+                    include_vector: None,
+                    min_score: None,
+                    offset: None,
+                    exact: None,
+                    with_total: None,
+                },
+            )
+            .unwrap()
+    };
+    let hits = search(&engine);
+    assert_eq!(hits[0].id, "a");
+    assert!((hits[0].score - 1.0).abs() < 1e-5);
+    assert!((hits[1].score + 0.8).abs() < 1e-5);
+    drop(engine);
+
+    let engine2 = Engine::new(config, CancellationToken::new()).unwrap();
+    assert_eq!(
+        engine2.vector_get("docs", "a").unwrap().unwrap().vector,
+        vec![3.0, 4.0]
+    );
+    assert_eq!(
+        engine2.vector_get("docs", "b").unwrap().unwrap().vector,
+        vec![0.0, -2.0]
+    );
+    let hits = search(&engine2);
+    assert_eq!(hits[0].id, "a");
+    assert!((hits[0].score - 1.0).abs() < 1e-5);
+}
+
+#[tokio::test]
+async fn vector_revision_guards_updates_and_survives_restart() {
+    let dir = tempfile::tempdir().unwrap();
+    let data_dir = dir.path().to_string_lossy().to_string();
+    let config = config_with_dir(&data_dir);
+
+    let engine = Engine::new(config.clone(), CancellationToken::new()).unwrap();
+    engine
+        .create_vector_collection("docs", 2, Metric::Cosine)
+        .unwrap();
+    engine
+        .vector_upsert(
+            "docs",
+            "a",
+            VectorItem {
+                vector: vec![1.0, 0.0],
+                meta: json!({"v": 1}),
+                revision: 0,
+                expires_at_ms: None,
+            },
+            None,
+        )
+        .unwrap();
+    assert_eq!(engine.vector_get("docs", "a").unwrap().unwrap().revision, 1);
+
+    let stale = engine.vector_update("docs", "a", None, Some(json!({"v": 2})), Some(0));
+    assert!(matches!(
+        stale,
+        Err(EngineError::Vector(VectorError::RevisionMismatch))
+    ));
+    engine
+        .vector_update("docs", "a", None, Some(json!({"v": 2})), Some(1))
+        .unwrap();
+    let missing = engine.vector_upsert(
+        "docs",
+        "b",
+        VectorItem {
+            vector: vec![0.0, 1.0],
+            meta: json!({}),
+            revision: 0,
+            expires_at_ms: None,
+        },
+        Some(1),
+    );
+    assert!(matches!(
+        missing,
+        Err(EngineError::Vector(VectorError::RevisionMismatch))
+    ));
+    drop(engine);
+
+    let engine2 = Engine::new(config, CancellationToken::new()).unwrap();
+    let item = engine2.vector_get("docs", "a").unwrap().unwrap();
+    assert_eq!(item.revision, 2);
+    assert_eq!(item.meta["v"], 2);
+    engine2.vector_force_compact_collection("docs").unwrap();
+    assert_eq!(
+        engine2.vector_get("docs", "a").unwrap().unwrap().revision,
+        2
+    );
+}
+
+#[test]
+fn searches_stay_consistent_during_vacuum() {
+    let dir = tempfile::tempdir().unwrap();
+    let store =
+        Arc::new(VectorStore::open_with_settings(dir.path(), VectorSettings::default()).unwrap());
+    store.create_collection("big", 8, Metric::Cosine).unwrap();
+    let vector_for =
+        |i: usize| -> Vec<f32> { (0..8).map(|d| ((i * 8 + d) as f32 * 0.37).sin()).collect() };
+    for i in 0..4000 {
+        store
+            .upsert(
+                "big",
+                &format!("v{i}"),
+                VectorItem {
+                    vector: vector_for(i),
+                    meta: json!({"i": i}),
+                    revision: 0,
+                    expires_at_ms: None,
+                },
+            )
+            .unwrap();
+    }
+    for i in 0..1000 {
+        store.delete("big", &format!("v{i}")).unwrap();
+    }
+
+    let done = Arc::new(AtomicBool::new(false));
+    let vacuum = {
+        let store = store.clone();
+        let done = done.clone();
+        std::thread::spawn(move || {
+            let res = store.vacuum_collection("big");
+            done.store(true, AtomicOrdering::SeqCst);
+            res
+        })
+    };
+
+    let mut searches = 0usize;
+    loop {
+        let finished = done.load(AtomicOrdering::SeqCst);
+        let i = 1000 + (searches * 37) % 3000;
+        let hits = store
+            .search(
+                "big",
+                SearchRequest {
+                    vector: vector_for(i),
+                    k: 1,
+                    filters: None,
+                    include_meta: Some(true),
+                    tie_break: None,
+                    include_vector: None,
+                    min_score: None,
+                    offset: None,
+                    exact: None,
+                    with_total: None,
+                },
+            )
+            .unwrap();
+        assert_eq!(hits.len(), 1, "search {searches} for v{i}");
+        assert_eq!(hits[0].id, format!("v{i}"));
+        searches += 1;
+        if finished {
+            break;
+        }
+    }
+    vacuum.join().unwrap().unwrap();
+    assert!(searches > 1);
+
+    let info = store.get_collection_info("big").unwrap();
+    assert_eq!(info.live_count, 3000);
+    assert_eq!(info.deleted_count, Some(0));
+}
+
+#[tokio::test]
+async fn hnsw_search_counts_tombstone_skips() {
+    let dir = tempfile::tempdir().unwrap();
+    let data_dir = dir.path().to_string_lossy().to_string();
+    let mut config = config_with_dir(&data_dir);
+    config.index_kind = "HNSW".to_string();
+
+    let engine = Engine::new(config, CancellationToken::new()).unwrap();
+    engine
+        .create_vector_collection("docs", 2, Metric::Cosine)
+        .unwrap();
+    for i in 0..200 {
+        let angle = i as f32 * 0.005;
+        engine
+            .vector_upsert(
+                "docs",
+                &format!("v{i}"),
+                VectorItem {
+                    vector: vec![angle.cos(), angle.sin()],
+                    meta: json!({}),
+                    revision: 0,
+                    expires_at_ms: None,
+                },
+                None,
+            )
+            .unwrap();
+    }
+    // The vectors closest to the query are gone, so the index keeps
+    // surfacing their tombstones first.
+    for i in 0..30 {
+        engine.vector_delete("docs", &format!("v{i}")).unwrap();
+    }
+    assert_eq!(engine.metrics_snapshot().vector_tombstone_skips_total, 0);
+
+    let outcome = engine
+        .vector_search_detailed(
+            "docs",
+            SearchRequest {
+                vector: vec![1.0, 0.0],
+                k: 5,
+                filters: None,
+                include_meta: None,
+                tie_break: None,
+                include_vector: None,
+                min_score: None,
+                offset: None,
+                exact: None,
+                with_total: None,
+            },
+        )
+        .unwrap();
+    assert_eq!(outcome.hits.len(), 5);
+    assert_eq!(outcome.hits[0].id, "v30");
+    assert!(outcome.tombstone_skips > 0);
+    let snap = engine.metrics_snapshot();
+    assert_eq!(snap.vector_tombstone_skips_total, outcome.tombstone_skips);
+    assert!(engine.metrics_text().contains(&format!(
+        "vector_tombstone_skips_total {}",
+        outcome.tombstone_skips
+    )));
+}
+
+#[tokio::test]
+async fn default_filter_scopes_every_search_and_survives_restart() {
+    let dir = tempfile::tempdir().unwrap();
+    let data_dir = dir.path().to_string_lossy().to_string();
+    let config = config_with_dir(&data_dir);
+
+    let engine = Engine::new(config.clone(), CancellationToken::new()).unwrap();
+    engine
+        .create_vector_collection("shared", 2, Metric::Cosine)
+        .unwrap();
+    for (id, tenant, vector) in [
+        ("a1", "a", vec![1.0, 0.0]),
+        ("a2", "a", vec![0.0, 1.0]),
+        ("b1", "b", vec![1.0, 0.1]),
+        ("b2", "b", vec![0.9, 0.0]),
+    ] {
+        engine
+            .vector_upsert(
+                "shared",
+                id,
+                VectorItem {
+                    vector,
+                    meta: json!({"tenant": tenant, "rank": id.len()}),
+                    revision: 0,
+                    expires_at_ms: None,
+                },
+                None,
+            )
+            .unwrap();
+    }
+    engine
+        .set_vector_default_filter("shared", Some(json!({"tenant": "a"})))
+        .unwrap();
+
+    let search = |engine: &Engine, filters: Option<serde_json::Value>| {
+        let mut ids: Vec<String> = engine
+            .vector_search(
+                "shared",
+                SearchRequest {
+                    vector: vec![1.0, 0.0],
+                    k: 10,
+                    filters,
+                    include_meta: None,
+                    tie_break: None,
+                    include_vector: None,
+                    min_score: None,
+                    offset: None,
+                    exact: None,
+                    with_total: None,
+                },
+            )
+            .unwrap()
+            .into_iter()
+            .map(|h| h.id)
+            .collect();
+        ids.sort();
+        ids
+    };
+    assert_eq!(search(&engine, None), vec!["a1", "a2"]);
+    // A client filter narrows the default but cannot override it.
+    assert!(search(&engine, Some(json!({"tenant": "b"}))).is_empty());
+    assert_eq!(
+        search(&engine, Some(json!({"rank": {"$gte": 2}}))),
+        vec!["a1", "a2"]
+    );
+    drop(engine);
+
+    let engine2 = Engine::new(config.clone(), CancellationToken::new()).unwrap();
+    assert_eq!(
+        engine2.vector_default_filter("shared").unwrap(),
+        Some(json!({"tenant": "a"}))
+    );
+    assert_eq!(search(&engine2, None), vec!["a1", "a2"]);
+    engine2.set_vector_default_filter("shared", None).unwrap();
+    assert_eq!(search(&engine2, None), vec!["a1", "a2", "b1", "b2"]);
+    drop(engine2);
+
+    let engine3 = Engine::new(config, CancellationToken::new()).unwrap();
+    assert_eq!(engine3.vector_default_filter("shared").unwrap(), None);
+}
+
+#[tokio::test]
+async fn default_filter_replays_from_wal_over_a_stale_manifest() {
+    let dir = tempfile::tempdir().unwrap();
+    let data_dir = dir.path().to_string_lossy().to_string();
+    let config = config_with_dir(&data_dir);
+
+    let engine = Engine::new(config.clone(), CancellationToken::new()).unwrap();
+    engine
+        .create_vector_collection("shared", 2, Metric::Cosine)
+        .unwrap();
+    engine
+        .vector_upsert(
+            "shared",
+            "a1",
+            VectorItem {
+                vector: vec![1.0, 0.0],
+                meta: json!({"tenant": "a"}),
+                revision: 0,
+                expires_at_ms: None,
+            },
+            None,
+        )
+        .unwrap();
+    let manifest_path = Path::new(&data_dir)
+        .join("vectors")
+        .join("shared")
+        .join("manifest.json");
+    let stale = fs::read(&manifest_path).unwrap();
+    engine
+        .set_vector_default_filter("shared", Some(json!({"tenant": "a"})))
+        .unwrap();
+    drop(engine);
+
+    // Roll the manifest back to before the filter: only the WAL has it now.
+    fs::write(&manifest_path, stale).unwrap();
+    assert!(read_manifest_json(&data_dir, "shared")["default_filter"].is_null());
+
+    let engine2 = Engine::new(config, CancellationToken::new()).unwrap();
+    assert_eq!(
+        engine2.vector_default_filter("shared").unwrap(),
+        Some(json!({"tenant": "a"}))
+    );
+    assert_eq!(
+        read_manifest_json(&data_dir, "shared")["default_filter"],
+        json!({"tenant": "a"})
+    );
+}
+
+#[tokio::test]
+async fn hnsw_graphs_reload_from_dump_instead_of_rebuilding() {
+    let dir = tempfile::tempdir().unwrap();
+    let data_dir = dir.path().to_string_lossy().to_string();
+    let mut config = config_with_dir(&data_dir);
+    config.index_kind = "HNSW".to_string();
+
+    let request = |vector: Vec<f32>| SearchRequest {
+        vector,
+        k: 10,
+        filters: None,
+        include_meta: None,
+        tie_break: None,
+        include_vector: None,
+        min_score: None,
+        offset: None,
+        exact: None,
+        with_total: None,
+    };
+    let mut rng = StdRng::seed_from_u64(7);
+    let query: Vec<f32> = (0..8).map(|_| rng.gen::<f32>()).collect();
+
+    let engine = Engine::new(config.clone(), CancellationToken::new()).unwrap();
+    engine
+        .create_vector_collection("docs", 8, Metric::Cosine)
+        .unwrap();
+    // Enough vectors to spill into a second segment.
+    for i in 0..9_000 {
+        engine
+            .vector_upsert(
+                "docs",
+                &format!("v{i}"),
+                VectorItem {
+                    vector: (0..8).map(|_| rng.gen::<f32>()).collect(),
+                    meta: json!({}),
+                    revision: 0,
+                    expires_at_ms: None,
+                },
+                None,
+            )
+            .unwrap();
+    }
+    for i in 0..100 {
+        engine.vector_delete("docs", &format!("v{i}")).unwrap();
+    }
+    let before = engine
+        .vector_search("docs", request(query.clone()))
+        .unwrap();
+    let stats_before = engine.vector_collection_stats("docs").unwrap();
+    assert!(stats_before.segment_count >= 2);
+    assert!(!stats_before.hnsw_reloaded);
+    engine.drain().unwrap();
+    drop(engine);
+    assert!(dir.path().join("vectors/docs/hnsw/segments.bin").exists());
+
+    let engine = Engine::new(config.clone(), CancellationToken::new()).unwrap();
+    let stats = engine.vector_collection_stats("docs").unwrap();
+    assert!(stats.hnsw_reloaded);
+    assert_eq!(stats.segments, stats_before.segments);
+    let after = engine
+        .vector_search("docs", request(query.clone()))
+        .unwrap();
+    let ids = |hits: &[SearchHit]| hits.iter().map(|h| h.id.clone()).collect::<Vec<_>>();
+    assert_eq!(ids(&after), ids(&before));
+
+    // A write after the dump makes it stale, so the next open rebuilds.
+    engine
+        .vector_upsert(
+            "docs",
+            "late",
+            VectorItem {
+                vector: query.clone(),
+                meta: json!({}),
+                revision: 0,
+                expires_at_ms: None,
+            },
+            None,
+        )
+        .unwrap();
+    engine.shutdown();
+    drop(engine);
+
+    let engine = Engine::new(config, CancellationToken::new()).unwrap();
+    let stats = engine.vector_collection_stats("docs").unwrap();
+    assert!(!stats.hnsw_reloaded);
+    let hits = engine.vector_search("docs", request(query)).unwrap();
+    assert_eq!(hits[0].id, "late");
+}
+
+#[tokio::test]
+async fn segment_size_comes_from_config_and_sticks_to_the_collection() {
+    let dir = tempfile::tempdir().unwrap();
+    let data_dir = dir.path().to_string_lossy().to_string();
+    let mut config = config_with_dir(&data_dir);
+    config.index_kind = "HNSW".to_string();
+    config.vector_segment_max_items = 500;
+
+    let engine = Engine::new(config.clone(), CancellationToken::new()).unwrap();
+    engine
+        .create_vector_collection("small", 2, Metric::Cosine)
+        .unwrap();
+    for i in 0..1_200 {
+        let angle = i as f32 * 0.001;
+        engine
+            .vector_upsert(
+                "small",
+                &format!("v{i}"),
+                VectorItem {
+                    vector: vec![angle.cos(), angle.sin()],
+                    meta: json!({}),
+                    revision: 0,
+                    expires_at_ms: None,
+                },
+                None,
+            )
+            .unwrap();
+    }
+    let stats = engine.vector_collection_stats("small").unwrap();
+    assert_eq!(stats.segment_count, 3);
+    assert!(stats.segments.iter().all(|s| s.capacity == 500));
+    engine.shutdown();
+    drop(engine);
+
+    config.vector_segment_max_items = 8192;
+    let engine = Engine::new(config, CancellationToken::new()).unwrap();
+    let stats = engine.vector_collection_stats("small").unwrap();
+    assert_eq!(stats.segment_count, 3);
+    assert_eq!(stats.live_count, 1_200);
+    engine
+        .create_vector_collection("large", 2, Metric::Cosine)
+        .unwrap();
+    let stats = engine.vector_collection_stats("large").unwrap();
+    assert_eq!(stats.segments[0].capacity, 8192);
+}
+
+#[tokio::test]
+async fn sparse_segments_are_merged_manually_and_after_heavy_deletes() {
+    let dir = tempfile::tempdir().unwrap();
+    let data_dir = dir.path().to_string_lossy().to_string();
+    let mut config = config_with_dir(&data_dir);
+    config.index_kind = "HNSW".to_string();
+    config.vector_segment_max_items = 100;
+    config.vector_merge_tombstone_ratio = 0.0;
+
+    let upsert_all = |engine: &Engine, collection: &str| {
+        engine
+            .create_vector_collection(collection, 2, Metric::Cosine)
+            .unwrap();
+        for i in 0..300 {
+            let angle = i as f32 * 0.005;
+            engine
+                .vector_upsert(
+                    collection,
+                    &format!("v{i}"),
+                    VectorItem {
+                        vector: vec![angle.cos(), angle.sin()],
+                        meta: json!({}),
+                        revision: 0,
+                        expires_at_ms: None,
+                    },
+                    None,
+                )
+                .unwrap();
+        }
+    };
+    let tombstones = |engine: &Engine, collection: &str| -> usize {
+        let stats = engine.vector_collection_stats(collection).unwrap();
+        stats.segments.iter().map(|s| s.deleted).sum()
+    };
+    let top_hit = |engine: &Engine, collection: &str, i: usize| {
+        let angle = i as f32 * 0.005;
+        engine
+            .vector_search(
+                collection,
+                SearchRequest {
+                    vector: vec![angle.cos(), angle.sin()],
+                    k: 1,
+                    filters: None,
+                    include_meta: None,
+                    tie_break: None,
+                    include_vector: None,
+                    min_score: None,
+                    offset: None,
+                    exact: None,
+                    with_total: None,
+                },
+            )
+            .unwrap()[0]
+            .id
+            .clone()
+    };
+
+    let engine = Engine::new(config.clone(), CancellationToken::new()).unwrap();
+    upsert_all(&engine, "manual");
+    for i in 0..60 {
+        engine.vector_delete("manual", &format!("v{i}")).unwrap();
+    }
+    assert_eq!(tombstones(&engine, "manual"), 60);
+    assert_eq!(engine.vector_merge_segments("manual").unwrap(), 1);
+    let stats = engine.vector_collection_stats("manual").unwrap();
+    assert_eq!(tombstones(&engine, "manual"), 0);
+    assert_eq!(stats.segment_count, 3);
+    assert_eq!(
+        stats.segments.iter().map(|s| s.live).sum::<usize>(),
+        stats.live_count
+    );
+    assert_eq!(stats.live_count, 240);
+    assert_eq!(top_hit(&engine, "manual", 70), "v70");
+    assert_eq!(top_hit(&engine, "manual", 10), "v60");
+    assert_eq!(engine.vector_merge_segments("manual").unwrap(), 0);
+    engine.shutdown();
+    drop(engine);
+
+    config.vector_merge_tombstone_ratio = 0.3;
+    let engine = Engine::new(config, CancellationToken::new()).unwrap();
+    upsert_all(&engine, "auto");
+    for i in 0..100 {
+        engine.vector_delete("auto", &format!("v{i}")).unwrap();
+    }
+    // The merge kicked in at 90 tombstones out of 300 nodes; only the
+    // deletes after it are left as tombstones.
+    assert!(tombstones(&engine, "auto") < 30);
+    assert_eq!(
+        engine.vector_collection_stats("auto").unwrap().live_count,
+        200
+    );
+    assert_eq!(top_hit(&engine, "auto", 150), "v150");
+}
+
+#[test]
+fn int8_export_roundtrip_keeps_recall_and_shrinks_the_file() {
+    let mut rng = StdRng::seed_from_u64(7);
+    let source = VectorStore::with_settings(VectorSettings::default());
+    source
+        .create_collection("docs", 32, Metric::Cosine)
+        .unwrap();
+    for i in 0..500 {
+        let vector: Vec<f32> = (0..32).map(|_| rng.gen::<f32>() - 0.5).collect();
+        source
+            .upsert(
+                "docs",
+                &format!("v{i:03}"),
+                VectorItem {
+                    vector,
+                    meta: json!({"n": i}),
+                    revision: 0,
+                    expires_at_ms: None,
+                },
+            )
+            .unwrap();
+    }
+
+    let mut lossless = Vec::new();
+    let mut quantized = Vec::new();
+    assert_eq!(
+        source
+            .export_collection("docs", ExportQuantize::F32, &mut lossless)
+            .unwrap(),
+        500
+    );
+    assert_eq!(
+        source
+            .export_collection("docs", ExportQuantize::Int8, &mut quantized)
+            .unwrap(),
+        500
+    );
+    assert!(quantized.len() * 2 < lossless.len());
+
+    let exact = VectorStore::with_settings(VectorSettings::default());
+    exact.import_collection(lossless.as_slice(), None).unwrap();
+    let original = source.get("docs", "v042").unwrap().unwrap();
+    let restored = exact.get("docs", "v042").unwrap().unwrap();
+    assert_eq!(restored.vector, original.vector);
+    assert_eq!(restored.meta, json!({"n": 42}));
+
+    let dir = tempfile::tempdir().unwrap();
+    {
+        let store = VectorStore::open(dir.path()).unwrap();
+        let (name, imported) = store
+            .import_collection(quantized.as_slice(), Some("docs_q8"))
+            .unwrap();
+        assert_eq!((name.as_str(), imported), ("docs_q8", 500));
+    }
+    let restored = VectorStore::open(dir.path()).unwrap();
+    assert_eq!(
+        restored.get_collection("docs_q8"),
+        Some((32, Metric::Cosine))
+    );
+    assert_eq!(
+        restored.collection_stats("docs_q8").unwrap().live_count,
+        500
+    );
+
+    let request = |vector: Vec<f32>| SearchRequest {
+        vector,
+        k: 10,
+        filters: None,
+        include_meta: None,
+        tie_break: None,
+        include_vector: None,
+        min_score: None,
+        offset: None,
+        exact: None,
+        with_total: None,
+    };
+    let mut found = 0;
+    for _ in 0..20 {
+        let query: Vec<f32> = (0..32).map(|_| rng.gen::<f32>() - 0.5).collect();
+        let truth: HashSet<String> = source
+            .search_exact("docs", request(query.clone()))
+            .unwrap()
+            .into_iter()
+            .map(|h| h.id)
+            .collect();
+        found += restored
+            .search_exact("docs_q8", request(query))
+            .unwrap()
+            .iter()
+            .filter(|h| truth.contains(&h.id))
+            .count();
+    }
+    let recall = found as f32 / 200.0;
+    assert!(recall >= 0.9, "recall@10 = {recall}");
+
+    // Losing the last line is reported instead of importing a partial backup.
+    let last_line = quantized[..quantized.len() - 1]
+        .iter()
+        .rposition(|b| *b == b'\n')
+        .unwrap();
+    let err = VectorStore::with_settings(VectorSettings::default())
+        .import_collection(&quantized[..=last_line], None)
+        .unwrap_err();
+    assert!(err.to_string().contains("truncated"), "{err}");
+}
+
+#[test]
+fn vector_ttl_hides_expired_items_and_sweeps_them_after_restart() {
+    let dir = tempfile::tempdir().unwrap();
+    let data_dir = dir.path().to_string_lossy().to_string();
+    let config = config_with_dir(&data_dir);
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64;
+    let expires_at = now + 300;
+
+    {
+        let engine = Engine::new(config.clone(), CancellationToken::new()).unwrap();
+        engine
+            .create_vector_collection("sessions", 2, Metric::Cosine)
+            .unwrap();
+        for (id, expires_at_ms) in [("eph", Some(expires_at)), ("keep", None)] {
+            engine
+                .vector_upsert(
+                    "sessions",
+                    id,
+                    VectorItem {
+                        vector: vec![1.0, 0.0],
+                        meta: json!({}),
+                        revision: 0,
+                        expires_at_ms,
+                    },
+                    None,
+                )
+                .unwrap();
+        }
+    }
+
+    let search = |engine: &Engine| -> Vec<String> {
+        let mut ids: Vec<String> = engine
+            .vector_search(
+                "sessions",
+                SearchRequest {
+                    vector: vec![1.0, 0.0],
+                    k: 5,
+                    filters: None,
+                    include_meta: None,
+                    tie_break: None,
+                    include_vector: None,
+                    min_score: None,
+                    offset: None,
+                    exact: None,
+                    with_total: None,
+                },
+            )
+            .unwrap()
+            .into_iter()
+            .map(|h| h.id)
+            .collect();
+        ids.sort();
+        ids
+    };
+
+    let engine = Engine::new(config.clone(), CancellationToken::new()).unwrap();
+    let item = engine.vector_get("sessions", "eph").unwrap().unwrap();
+    assert_eq!(item.expires_at_ms, Some(expires_at));
+    assert_eq!(search(&engine), vec!["eph", "keep"]);
+
+    std::thread::sleep(std::time::Duration::from_millis(350));
+    // Past its expiry the item drops out of searches before any sweep runs.
+    assert_eq!(search(&engine), vec!["keep"]);
+    assert!(engine.vector_get("sessions", "eph").unwrap().is_some());
+
+    let before = engine.events().last_published_offset();
+    engine.force_snapshot().unwrap();
+    assert!(engine.vector_get("sessions", "eph").unwrap().is_none());
+    let deleted: Vec<EventRecord> = engine
+        .events()
+        .replay_since(before)
+        .into_iter()
+        .filter(|ev| ev.event_type == "vector_deleted")
+        .collect();
+    assert_eq!(deleted.len(), 1);
+    assert_eq!(deleted[0].data["id"], "eph");
+    assert_eq!(deleted[0].data["reason"], "ttl");
+    drop(engine);
+
+    let engine = Engine::new(config, CancellationToken::new()).unwrap();
+    assert!(engine.vector_get("sessions", "eph").unwrap().is_none());
+    assert_eq!(search(&engine), vec!["keep"]);
+}
+
+#[test]
+fn only_if_changed_skips_identical_upserts() {
+    let dir = tempfile::tempdir().unwrap();
+    let data_dir = dir.path().to_string_lossy().to_string();
+    let engine = Engine::new(config_with_dir(&data_dir), CancellationToken::new()).unwrap();
+    engine
+        .create_vector_collection("docs", 3, Metric::Cosine)
+        .unwrap();
+    let item = |tag: &str| VectorItem {
+        vector: vec![0.0, 1.0, 0.0],
+        meta: json!({"tag": tag}),
+        revision: 0,
+        expires_at_ms: None,
+    };
+    let upserted = || {
+        engine
+            .events()
+            .replay_since(0)
+            .iter()
+            .filter(|ev| ev.event_type == "vector_upserted")
+            .count()
+    };
+
+    let first = engine
+        .vector_upsert_with_options("docs", "a", item("x"), None, true)
+        .unwrap();
+    assert!(first.created && first.changed);
+    let segments = engine.vector_collection_stats("docs").unwrap().segments;
+
+    let again = engine
+        .vector_upsert_with_options("docs", "a", item("x"), None, true)
+        .unwrap();
+    assert!(!again.created && !again.changed);
+    assert_eq!(upserted(), 1);
+    assert_eq!(
+        engine.vector_collection_stats("docs").unwrap().segments,
+        segments
+    );
+    assert_eq!(engine.vector_get("docs", "a").unwrap().unwrap().revision, 1);
+
+    let retagged = engine
+        .vector_upsert_with_options("docs", "a", item("y"), None, true)
+        .unwrap();
+    assert!(!retagged.created && retagged.changed);
+    assert_eq!(upserted(), 2);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn search_lock_wait_is_measured_while_vacuum_swaps_the_index() {
+    let dir = tempfile::tempdir().unwrap();
+    let data_dir = dir.path().to_string_lossy().to_string();
+    let mut config = config_with_dir(&data_dir);
+    config.wal_fsync_policy = "none".to_string();
+    config.index_kind = "HNSW".to_string();
+    let engine = Engine::new(config, CancellationToken::new()).unwrap();
+    engine
+        .create_vector_collection("busy", 16, Metric::Cosine)
+        .unwrap();
+    let vector_for = |i: usize| -> Vec<f32> {
+        (0..16)
+            .map(|d| ((i * 16 + d) as f32 * 0.37).sin())
+            .collect()
+    };
+    for i in 0..2000 {
+        engine
+            .vector_upsert(
+                "busy",
+                &format!("v{i}"),
+                VectorItem {
+                    vector: vector_for(i),
+                    meta: json!({}),
+                    revision: 0,
+                    expires_at_ms: None,
+                },
+                None,
+            )
+            .unwrap();
+    }
+    let lock_wait_sum = |engine: &Engine| -> f64 {
+        engine
+            .metrics_text()
+            .lines()
+            .find_map(|line| line.strip_prefix("vector_search_lock_wait_seconds_sum "))
+            .unwrap()
+            .parse()
+            .unwrap()
+    };
+
+    // Searches keep the read lock busy so the vacuum's upgrade to the write
+    // lock has to queue, and searches arriving meanwhile wait behind it.
+    let stop = Arc::new(AtomicBool::new(false));
+    let searchers: Vec<_> = (0..3)
+        .map(|t| {
+            let engine = engine.clone();
+            let stop = stop.clone();
+            std::thread::spawn(move || {
+                let mut n = t;
+                while !stop.load(AtomicOrdering::SeqCst) {
+                    let req = SearchRequest {
+                        vector: vector_for(n % 2000),
+                        k: 10,
+                        filters: None,
+                        include_meta: Some(false),
+                        tie_break: None,
+                        include_vector: None,
+                        min_score: None,
+                        offset: None,
+                        exact: Some(true),
+                        with_total: None,
+                    };
+                    engine.vector_search_detailed("busy", req).unwrap();
+                    n += 3;
+                }
+            })
+        })
+        .collect();
+    for _ in 0..50 {
+        engine.vector_vacuum_collection("busy").unwrap();
+        if lock_wait_sum(&engine) > 0.0 {
+            break;
+        }
+    }
+    stop.store(true, AtomicOrdering::SeqCst);
+    for searcher in searchers {
+        searcher.join().unwrap();
+    }
+
+    assert!(lock_wait_sum(&engine) > 0.0);
+    let text = engine.metrics_text();
+    assert!(text.contains("# TYPE vector_search_lock_wait_seconds histogram"));
+    assert!(text.contains("vector_search_lock_wait_seconds_bucket{le=\"0.00001\"}"));
+}
@@ -1,6 +1,7 @@
 use rust_kiss_vdb::config::Config;
 use rust_kiss_vdb::engine::Engine;
 use rust_kiss_vdb::vector::{Metric, SearchRequest, VectorItem};
+use tokio_util::sync::CancellationToken;
 
 fn config_for(dir: &str) -> Config {
     Config {
@@ -45,6 +46,30 @@ fn config_for(dir: &str) -> Config {
         run_retention: 4,
         compaction_trigger_tombstone_ratio: 0.2,
         compaction_max_bytes_per_pass: 64 * 1024 * 1024,
+        verify_on_start: false,
+        max_response_bytes: 8 * 1024 * 1024,
+        default_durability: "sync".to_string(),
+        max_vectors_per_collection: 0,
+        embed_cache_size: 256,
+        sse_idle_timeout_secs: 0,
+        value_compress_min_bytes: 0,
+        wal_truncate_after_snapshot: false,
+        max_list_limit: 1000,
+        strict_query_dim: true,
+        sqlite_busy_retries: 3,
+        vector_segment_max_items: 8192,
+        vector_merge_tombstone_ratio: 0.3,
+        ttl_grace_ms: 0,
+        max_index_values_per_field: 0,
+        response_compress_min_bytes: 0,
+        wal_fsync_policy: "data".to_string(),
+        max_doc_indexed_fields: 0,
+        lock_wait_warn_ms: 100,
+        sqlite_readonly: false,
+        sqlite_migrations_dir: None,
+        embed_url: None,
+        embed_model: "default".to_string(),
+        embed_timeout_ms: 10_000,
     }
 }
 
@@ -53,7 +78,7 @@ async fn vector_roundtrip_matrix_dims() {
     let dir = tempfile::tempdir().unwrap();
     let data_dir = dir.path().to_string_lossy().to_string();
     let config = config_for(&data_dir);
-    let engine = Engine::new(config.clone()).unwrap();
+    let engine = Engine::new(config.clone(), CancellationToken::new()).unwrap();
 
     let dims = [8usize, 128, 384, 768];
     for &dim in &dims {
@@ -71,14 +96,17 @@ async fn vector_roundtrip_matrix_dims() {
                     VectorItem {
                         vector,
                         meta: serde_json::json!({ "dim": dim, "idx": idx }),
+                        revision: 0,
+                        expires_at_ms: None,
                     },
+                    None,
                 )
                 .unwrap();
         }
     }
     drop(engine);
 
-    let reopened = Engine::new(config).unwrap();
+    let reopened = Engine::new(config, CancellationToken::new()).unwrap();
     for &dim in &dims {
         let collection = format!("roundtrip_{dim}");
         let hits = reopened
@@ -89,6 +117,12 @@ async fn vector_roundtrip_matrix_dims() {
                     k: 5,
                     filters: None,
                     include_meta: Some(true),
+                    tie_break: None,
+                    include_vector: None,
+                    min_score: None,
+                    offset: None,
+                    exact: None,
+                    with_total: None,
                 },
             )
             .unwrap();